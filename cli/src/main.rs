@@ -4,7 +4,7 @@ use rand::rngs::OsRng;
 use std::path::PathBuf;
 
 use trv1_genesis::builder::GenesisBuilder;
-use trv1_genesis::{GenesisConfig, GenesisValidator};
+use trv1_genesis::{GenesisAccount, GenesisConfig, GenesisValidator};
 use trv1_staking::LockTier;
 
 /// TRv1 Blockchain CLI
@@ -191,6 +191,12 @@ fn cmd_genesis_add_validator(genesis_path: PathBuf, pubkey_hex: &str, stake: u64
         initial_stake: stake,
         commission_rate: commission,
     });
+    // The stake is drawn from the validator's genesis account, so a
+    // validator without one is given an account holding just the stake.
+    let funded = !config.accounts.iter().any(|a| a.pubkey == pubkey);
+    if funded {
+        config.accounts.push(GenesisAccount { pubkey, balance: stake });
+    }
 
     config.genesis_hash = config.compute_genesis_hash();
 
@@ -208,6 +214,9 @@ fn cmd_genesis_add_validator(genesis_path: PathBuf, pubkey_hex: &str, stake: u64
     println!("  Pubkey: {pubkey_hex}");
     println!("  Stake: {stake}");
     println!("  Commission: {} bps", commission);
+    if funded {
+        println!("  Funded with a genesis account of {stake}");
+    }
     println!("  Total validators: {}", config.validators.len());
     println!("  New genesis hash: {}", hex::encode(config.genesis_hash));
 }
@@ -1,7 +1,8 @@
-use ed25519_dalek::{Signature, SigningKey, Verifier, VerifyingKey};
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::encoding::{self, domain};
 use crate::types::{BlockHash, Height, ValidatorId};
 
 /// Block header containing metadata.
@@ -24,19 +25,24 @@ pub struct Transaction {
     pub nonce: u64,
     pub signature: Vec<u8>,
     pub data: Vec<u8>,
+    /// Maximum gas this transaction may consume.
+    pub gas_limit: u64,
+    /// Maximum total price per gas unit (base fee plus tip) the sender
+    /// will pay.
+    pub max_fee_per_gas: u64,
+    /// Maximum tip per gas unit offered to the block proposer on top of
+    /// the base fee.
+    pub max_priority_fee_per_gas: u64,
 }
 
 impl Transaction {
-    /// Compute the signing message for this transaction.
-    /// SHA256(from ++ to ++ amount.to_le_bytes() ++ nonce.to_le_bytes() ++ data)
+    /// Compute the signing message for this transaction: the SHA-256 of the
+    /// transaction domain tag followed by the canonical encoding of every
+    /// field except the signature.
     pub fn signing_message(&self) -> [u8; 32] {
-        let mut hasher = Sha256::new();
-        hasher.update(self.from);
-        hasher.update(self.to);
-        hasher.update(self.amount.to_le_bytes());
-        hasher.update(self.nonce.to_le_bytes());
-        hasher.update(&self.data);
-        let digest = hasher.finalize();
+        let mut buf = vec![domain::TRANSACTION];
+        self.encode_unsigned(&mut buf);
+        let digest = Sha256::digest(&buf);
         let mut out = [0u8; 32];
         out.copy_from_slice(&digest);
         out
@@ -52,7 +58,8 @@ impl Transaction {
     }
 
     /// Verify the transaction signature.
-    /// Returns true if the signature is valid for self.from as the public key.
+    /// Returns true if the signature is valid for self.from as the public key
+    /// under `verify_strict`, the same rule `SignatureVerifier` applies.
     pub fn verify_signature(&self) -> bool {
         if self.signature.len() != 64 {
             return false;
@@ -61,21 +68,30 @@ impl Transaction {
             Ok(b) => b,
             Err(_) => return false,
         };
-        let sig = match Signature::from_bytes(&sig_bytes) {
-            sig => sig,
-        };
+        let sig = Signature::from_bytes(&sig_bytes);
         let vk = match VerifyingKey::from_bytes(&self.from) {
             Ok(vk) => vk,
             Err(_) => return false,
         };
         let msg = self.signing_message();
-        vk.verify(&msg, &sig).is_ok()
+        vk.verify_strict(&msg, &sig).is_ok()
+    }
+
+    /// The tip per gas unit this transaction actually pays at `base_fee`:
+    /// the priority fee, capped by whatever headroom `max_fee_per_gas`
+    /// leaves above the base fee.
+    pub fn effective_tip(&self, base_fee: u64) -> u64 {
+        self.max_priority_fee_per_gas
+            .min(self.max_fee_per_gas.saturating_sub(base_fee))
     }
 
-    /// Compute a unique hash for this transaction.
+    /// Compute a unique hash for this transaction: the SHA-256 of its
+    /// versioned canonical encoding, signature included.
+    ///
+    /// This is the only transaction hash: block Merkle leaves, compact
+    /// blocks, gossip, the mempool and receipts all use it.
     pub fn hash(&self) -> [u8; 32] {
-        let encoded = bincode::serialize(self).expect("tx serialization should never fail");
-        let digest = Sha256::digest(&encoded);
+        let digest = Sha256::digest(encoding::to_bytes(self));
         let mut hash = [0u8; 32];
         hash.copy_from_slice(&digest);
         hash
@@ -90,11 +106,10 @@ pub struct Block {
 }
 
 impl Block {
-    /// Compute the SHA-256 hash of the block header.
+    /// Compute the SHA-256 hash of the block header's versioned canonical
+    /// encoding.
     pub fn hash(&self) -> BlockHash {
-        let encoded = bincode::serialize(&self.header)
-            .expect("block header serialization should never fail");
-        let digest = Sha256::digest(&encoded);
+        let digest = Sha256::digest(encoding::to_bytes(&self.header));
         let mut hash = [0u8; 32];
         hash.copy_from_slice(&digest);
         BlockHash(hash)
@@ -103,21 +118,18 @@ impl Block {
     /// Compute a Merkle root from the block's transactions.
     /// Uses a simple binary Merkle tree with SHA-256.
     pub fn compute_tx_merkle_root(transactions: &[Transaction]) -> [u8; 32] {
-        if transactions.is_empty() {
+        let leaves: Vec<[u8; 32]> = transactions.iter().map(Transaction::hash).collect();
+        Self::merkle_root_from_hashes(&leaves)
+    }
+
+    /// Compute the transaction Merkle root from already-hashed leaves.
+    /// Each leaf is a `Transaction::hash()`.
+    pub fn merkle_root_from_hashes(tx_hashes: &[[u8; 32]]) -> [u8; 32] {
+        if tx_hashes.is_empty() {
             return [0u8; 32];
         }
 
-        let mut leaves: Vec<[u8; 32]> = transactions
-            .iter()
-            .map(|tx| {
-                let encoded = bincode::serialize(tx).expect("tx serialization should never fail");
-                let digest = Sha256::digest(&encoded);
-                let mut hash = [0u8; 32];
-                hash.copy_from_slice(&digest);
-                hash
-            })
-            .collect();
-
+        let mut leaves = tx_hashes.to_vec();
         while leaves.len() > 1 {
             let mut next_level = Vec::new();
             for chunk in leaves.chunks(2) {
@@ -141,6 +153,67 @@ impl Block {
     }
 }
 
+/// A block with its body replaced by transaction hashes.
+///
+/// Proposals are gossiped in this form: receivers usually hold most of the
+/// transactions in their mempool already and only need to fetch the rest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactBlock {
+    pub header: BlockHeader,
+    /// `Transaction::hash()` of each transaction, in block order.
+    pub tx_hashes: Vec<[u8; 32]>,
+}
+
+impl CompactBlock {
+    /// Strip a full block down to its header and transaction hashes.
+    pub fn from_block(block: &Block) -> Self {
+        Self {
+            header: block.header.clone(),
+            tx_hashes: block.transactions.iter().map(Transaction::hash).collect(),
+        }
+    }
+
+    /// The hash of the full block this compact block stands for.
+    pub fn hash(&self) -> BlockHash {
+        Block {
+            header: self.header.clone(),
+            transactions: Vec::new(),
+        }
+        .hash()
+    }
+
+    /// Whether the transaction hashes match the header's Merkle root.
+    pub fn verify_tx_root(&self) -> bool {
+        Block::merkle_root_from_hashes(&self.tx_hashes) == self.header.tx_merkle_root
+    }
+
+    /// Rebuild the full block, looking up each transaction by hash.
+    ///
+    /// Transactions returned by `lookup` are re-hashed, so a wrong answer is
+    /// treated the same as a missing one. On failure, returns the hashes that
+    /// could not be resolved, in block order.
+    pub fn reconstruct<F>(&self, mut lookup: F) -> Result<Block, Vec<[u8; 32]>>
+    where
+        F: FnMut(&[u8; 32]) -> Option<Transaction>,
+    {
+        let mut transactions = Vec::with_capacity(self.tx_hashes.len());
+        let mut missing = Vec::new();
+        for hash in &self.tx_hashes {
+            match lookup(hash) {
+                Some(tx) if tx.hash() == *hash => transactions.push(tx),
+                _ => missing.push(*hash),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(missing);
+        }
+        Ok(Block {
+            header: self.header.clone(),
+            transactions,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,6 +233,9 @@ mod tests {
                 nonce: i as u64,
                 signature: vec![0u8; 64],
                 data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
             })
             .collect();
 
@@ -210,6 +286,9 @@ mod tests {
             nonce: 0,
             signature: vec![],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
         let root = Block::compute_tx_merkle_root(&[tx]);
         assert_ne!(root, [0u8; 32]);
@@ -225,6 +304,9 @@ mod tests {
                 nonce: i as u64,
                 signature: vec![],
                 data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
             })
             .collect();
         let r1 = Block::compute_tx_merkle_root(&txs);
@@ -242,12 +324,74 @@ mod tests {
                 nonce: i as u64,
                 signature: vec![],
                 data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
             })
             .collect();
         let root = Block::compute_tx_merkle_root(&txs);
         assert_ne!(root, [0u8; 32]);
     }
 
+    #[test]
+    fn test_merkle_root_matches_leaf_hashes() {
+        let block = make_test_block(5);
+        let hashes: Vec<[u8; 32]> = block.transactions.iter().map(Transaction::hash).collect();
+        assert_eq!(
+            Block::merkle_root_from_hashes(&hashes),
+            block.header.tx_merkle_root
+        );
+    }
+
+    #[test]
+    fn test_compact_block_roundtrip() {
+        let block = make_test_block(3);
+        let compact = CompactBlock::from_block(&block);
+        assert_eq!(compact.hash(), block.hash());
+        assert!(compact.verify_tx_root());
+
+        let by_hash: std::collections::HashMap<[u8; 32], Transaction> = block
+            .transactions
+            .iter()
+            .map(|tx| (tx.hash(), tx.clone()))
+            .collect();
+        let rebuilt = compact.reconstruct(|h| by_hash.get(h).cloned()).unwrap();
+        assert_eq!(rebuilt.hash(), block.hash());
+        assert_eq!(rebuilt.transactions.len(), 3);
+        assert_eq!(rebuilt.transactions[1].amount, block.transactions[1].amount);
+    }
+
+    #[test]
+    fn test_compact_block_reports_missing() {
+        let block = make_test_block(3);
+        let compact = CompactBlock::from_block(&block);
+        let first = block.transactions[0].clone();
+        let wrong = block.transactions[1].clone();
+
+        // Only the first tx is known; a lookup returning the wrong tx for
+        // the third hash must not be accepted.
+        let missing = compact
+            .reconstruct(|h| {
+                if *h == compact.tx_hashes[0] {
+                    Some(first.clone())
+                } else if *h == compact.tx_hashes[2] {
+                    Some(wrong.clone())
+                } else {
+                    None
+                }
+            })
+            .unwrap_err();
+        assert_eq!(missing, vec![compact.tx_hashes[1], compact.tx_hashes[2]]);
+    }
+
+    #[test]
+    fn test_compact_block_tampered_hashes_fail_root() {
+        let block = make_test_block(2);
+        let mut compact = CompactBlock::from_block(&block);
+        compact.tx_hashes.swap(0, 1);
+        assert!(!compact.verify_tx_root());
+    }
+
     #[test]
     fn test_sign_and_verify_roundtrip() {
         let signing_key = SigningKey::generate(&mut OsRng);
@@ -260,6 +404,9 @@ mod tests {
             nonce: 0,
             signature: vec![],
             data: vec![1, 2, 3],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         tx.sign(&signing_key);
@@ -279,6 +426,9 @@ mod tests {
             nonce: 0,
             signature: vec![],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         tx.sign(&signing_key);
@@ -301,6 +451,9 @@ mod tests {
             nonce: 0,
             signature: vec![],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         tx.sign(&signing_key); // signed by different key
@@ -322,11 +475,35 @@ mod tests {
             nonce: 0,
             signature: vec![], // no signature
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         assert!(!tx.verify_signature(), "empty signature must fail");
     }
 
+    #[test]
+    fn test_small_order_key_fails() {
+        // The identity as key and R with S = 0 satisfies the cofactorless
+        // equation for any message; strict verification rejects it.
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        let tx = Transaction {
+            from: identity,
+            to: [2u8; 32],
+            amount: 100,
+            nonce: 0,
+            signature: [identity, [0u8; 32]].concat(),
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+
+        assert!(!tx.verify_signature(), "small-order key must fail");
+    }
+
     #[test]
     fn test_tx_hash_uniqueness() {
         let tx1 = Transaction {
@@ -336,6 +513,9 @@ mod tests {
             nonce: 0,
             signature: vec![0u8; 64],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         let tx2 = Transaction {
@@ -345,6 +525,9 @@ mod tests {
             nonce: 0,
             signature: vec![0u8; 64],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         assert_ne!(tx1.hash(), tx2.hash(), "different txs must have different hashes");
@@ -359,6 +542,9 @@ mod tests {
             nonce: 0,
             signature: vec![0u8; 64],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         assert_eq!(tx.hash(), tx.hash(), "same tx must produce same hash");
@@ -373,6 +559,9 @@ mod tests {
             nonce: 0,
             signature: vec![],
             data: vec![10, 20],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         assert_eq!(
@@ -3,7 +3,10 @@ pub mod state_machine;
 pub mod round;
 pub mod vote;
 pub mod block;
+pub mod encoding;
+pub mod sigverify;
 
 pub use types::*;
 pub use state_machine::BftStateMachine;
-pub use block::{Block, BlockHeader, Transaction};
+pub use block::{Block, BlockHeader, CompactBlock, Transaction};
+pub use sigverify::SignatureVerifier;
@@ -0,0 +1,352 @@
+//! Parallel batch verification of transaction signatures.
+//!
+//! Signatures are checked in chunks with ed25519 batch verification,
+//! spread across rayon's worker pool. A chunk that fails is re-checked one
+//! signature at a time to find the bad ones.
+//!
+//! Batch verification is randomized and cofactorless, so on its own it
+//! accepts some signatures with a small-order component that
+//! `verify_strict` rejects, and nodes must agree exactly on which
+//! transactions are valid. Only signatures whose key and `R` are
+//! torsion-free, whose `R` is canonically encoded and not the identity and
+//! whose `S` is canonical are batched; for those both checks agree. Any
+//! other signature is checked on its own with `verify_strict`.
+//!
+//! Hashes of transactions that verified are remembered, so a transaction
+//! checked when it was gossiped is not checked again when its block is
+//! proposed or committed. `Transaction::hash()` covers the signature, so a
+//! cached hash vouches for that exact signature.
+
+use std::fmt;
+use std::num::NonZeroUsize;
+
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::Scalar;
+use ed25519_dalek::{Signature, VerifyingKey};
+use lru::LruCache;
+use parking_lot::Mutex;
+use rayon::prelude::*;
+
+use crate::block::Transaction;
+
+/// Default number of verified transaction hashes remembered.
+pub const DEFAULT_CACHE_CAPACITY: usize = 65_536;
+
+/// Signatures per batch verification call.
+const BATCH_SIZE: usize = 64;
+
+/// A transaction's parsed key, signature and signing message.
+struct Parsed {
+    key: VerifyingKey,
+    signature: Signature,
+    message: [u8; 32],
+    /// Whether batch verification gives the same answer as
+    /// `verify_strict` for this signature.
+    batchable: bool,
+}
+
+impl Parsed {
+    /// Parse a transaction's signing inputs. Malformed signatures and weak
+    /// (small-order) keys never verify.
+    fn new(tx: &Transaction) -> Option<Self> {
+        let key = VerifyingKey::from_bytes(&tx.from).ok()?;
+        if key.is_weak() {
+            return None;
+        }
+        let sig_bytes: [u8; 64] = tx.signature.as_slice().try_into().ok()?;
+        let signature = Signature::from_bytes(&sig_bytes);
+        let r = CompressedEdwardsY(*signature.r_bytes());
+        let batchable = bool::from(Scalar::from_canonical_bytes(*signature.s_bytes()).is_some())
+            && key.to_edwards().is_torsion_free()
+            && r.decompress().is_some_and(|point| {
+                point.compress() == r && !point.is_small_order() && point.is_torsion_free()
+            });
+        Some(Self {
+            key,
+            signature,
+            message: tx.signing_message(),
+            batchable,
+        })
+    }
+
+    fn verify(&self) -> bool {
+        self.key.verify_strict(&self.message, &self.signature).is_ok()
+    }
+}
+
+/// Batch-verify a chunk of batchable signatures, falling back to
+/// one-by-one checks to pinpoint failures.
+fn verify_chunk(chunk: &[&Parsed]) -> Vec<bool> {
+    let messages: Vec<&[u8]> = chunk.iter().map(|p| p.message.as_slice()).collect();
+    let signatures: Vec<Signature> = chunk.iter().map(|p| p.signature).collect();
+    let keys: Vec<VerifyingKey> = chunk.iter().map(|p| p.key).collect();
+    if ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok() {
+        vec![true; chunk.len()]
+    } else {
+        chunk.iter().map(|p| p.verify()).collect()
+    }
+}
+
+/// Verifies transaction signatures in parallel batches and caches the
+/// hashes of those that verified.
+///
+/// Shared between the network, the mempool and block execution so each
+/// transaction is verified once however it reaches the node.
+pub struct SignatureVerifier {
+    verified: Mutex<LruCache<[u8; 32], ()>>,
+}
+
+impl SignatureVerifier {
+    /// Create a verifier remembering up to `cache_capacity` verified
+    /// transactions.
+    pub fn new(cache_capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(cache_capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            verified: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Whether the transaction with this hash has already verified.
+    pub fn is_verified(&self, tx_hash: &[u8; 32]) -> bool {
+        self.verified.lock().get(tx_hash).is_some()
+    }
+
+    /// Verify a single transaction's signature, consulting the cache.
+    pub fn verify(&self, tx: &Transaction) -> bool {
+        self.verify_all(std::slice::from_ref(tx))[0]
+    }
+
+    /// Verify every transaction's signature, returning one result per
+    /// transaction in order.
+    ///
+    /// Cached transactions are not re-verified. The rest are batch
+    /// verified across the worker pool, and those that pass are cached.
+    pub fn verify_all(&self, txs: &[Transaction]) -> Vec<bool> {
+        let hashes: Vec<[u8; 32]> = txs.iter().map(Transaction::hash).collect();
+        let mut results: Vec<bool> = {
+            let mut verified = self.verified.lock();
+            hashes.iter().map(|h| verified.get(h).is_some()).collect()
+        };
+
+        let unchecked: Vec<usize> = (0..txs.len()).filter(|&i| !results[i]).collect();
+        if unchecked.is_empty() {
+            return results;
+        }
+        let parsed: Vec<Option<Parsed>> = unchecked
+            .par_iter()
+            .map(|&i| Parsed::new(&txs[i]))
+            .collect();
+        let (batched, single): (Vec<_>, Vec<_>) = unchecked
+            .iter()
+            .zip(&parsed)
+            .filter_map(|(&i, p)| p.as_ref().map(|p| (i, p)))
+            .partition(|(_, p)| p.batchable);
+
+        let batch_inputs: Vec<&Parsed> = batched.iter().map(|&(_, p)| p).collect();
+        let batch_outcomes: Vec<bool> = batch_inputs
+            .par_chunks(BATCH_SIZE)
+            .flat_map_iter(verify_chunk)
+            .collect();
+        let single_outcomes: Vec<bool> = single.par_iter().map(|(_, p)| p.verify()).collect();
+
+        let mut verified = self.verified.lock();
+        let outcomes = batch_outcomes.into_iter().chain(single_outcomes);
+        for (&(i, _), ok) in batched.iter().chain(&single).zip(outcomes) {
+            if ok {
+                results[i] = true;
+                verified.put(hashes[i], ());
+            }
+        }
+        results
+    }
+
+    /// Number of verified transactions currently cached.
+    pub fn cached(&self) -> usize {
+        self.verified.lock().len()
+    }
+}
+
+impl Default for SignatureVerifier {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+impl fmt::Debug for SignatureVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verified = self.verified.lock();
+        f.debug_struct("SignatureVerifier")
+            .field("capacity", &verified.cap())
+            .field("cached", &verified.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+
+    fn signed_tx(sk: &SigningKey, nonce: u64) -> Transaction {
+        let mut tx = Transaction {
+            from: sk.verifying_key().to_bytes(),
+            to: [2u8; 32],
+            amount: 100,
+            nonce,
+            signature: vec![],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+        tx.sign(sk);
+        tx
+    }
+
+    #[test]
+    fn test_batch_all_valid() {
+        let sk = SigningKey::generate(&mut OsRng);
+        let txs: Vec<Transaction> = (0..200).map(|n| signed_tx(&sk, n)).collect();
+        let verifier = SignatureVerifier::default();
+        assert!(verifier.verify_all(&txs).into_iter().all(|ok| ok));
+        assert_eq!(verifier.cached(), 200);
+    }
+
+    #[test]
+    fn test_batch_pinpoints_invalid() {
+        let sk = SigningKey::generate(&mut OsRng);
+        let mut txs: Vec<Transaction> = (0..150).map(|n| signed_tx(&sk, n)).collect();
+        txs[3].amount += 1;
+        txs[70].signature[0] ^= 1;
+        txs[149].signature.truncate(10);
+
+        let verifier = SignatureVerifier::default();
+        let results = verifier.verify_all(&txs);
+        for (i, ok) in results.iter().enumerate() {
+            assert_eq!(*ok, ![3, 70, 149].contains(&i), "tx {i}");
+        }
+        assert_eq!(verifier.cached(), 147);
+        assert!(!verifier.is_verified(&txs[70].hash()));
+    }
+
+    #[test]
+    fn test_cached_transactions_skip_verification() {
+        let sk = SigningKey::generate(&mut OsRng);
+        let tx = signed_tx(&sk, 0);
+        let verifier = SignatureVerifier::default();
+        assert!(!verifier.is_verified(&tx.hash()));
+        assert!(verifier.verify(&tx));
+        assert!(verifier.is_verified(&tx.hash()));
+
+        // A different signature over the same transfer is a different hash.
+        let mut forged = tx.clone();
+        forged.signature = vec![0u8; 64];
+        assert!(!verifier.verify(&forged));
+    }
+
+    /// A signature with the identity point, which has small order, as R.
+    /// It satisfies the cofactorless equation `S·B = R + k·A`, but strict
+    /// verification rejects it.
+    fn small_order_r_tx(sk: &SigningKey) -> Transaction {
+        use curve25519_dalek::Scalar;
+        use sha2::{Digest, Sha512};
+
+        let mut tx = signed_tx(sk, 0);
+        let mut r = [0u8; 32];
+        r[0] = 1; // compressed identity
+        let k = Scalar::from_bytes_mod_order_wide(
+            &Sha512::new()
+                .chain_update(r)
+                .chain_update(tx.from)
+                .chain_update(tx.signing_message())
+                .finalize()
+                .into(),
+        );
+        let s = k * sk.to_scalar();
+        tx.signature = [r, s.to_bytes()].concat();
+        tx
+    }
+
+    #[test]
+    fn test_small_order_r_rejected() {
+        let sk = SigningKey::generate(&mut OsRng);
+        let bad = small_order_r_tx(&sk);
+        let mut txs: Vec<Transaction> = (1..10).map(|n| signed_tx(&sk, n)).collect();
+        txs.push(bad.clone());
+
+        let verifier = SignatureVerifier::default();
+        let results = verifier.verify_all(&txs);
+        assert_eq!(results.iter().filter(|ok| **ok).count(), 9);
+        assert!(!results[9]);
+        assert!(!verifier.is_verified(&bad.hash()));
+        assert!(!verifier.verify(&bad));
+    }
+
+    #[test]
+    fn test_mixed_order_r_rejected() {
+        use curve25519_dalek::constants::{ED25519_BASEPOINT_POINT, EIGHT_TORSION};
+        use curve25519_dalek::Scalar;
+        use sha2::{Digest, Sha512};
+
+        // R = r·B + T for an order-8 point T. S·B - k·A - R = -T, which
+        // batch verification misses whenever its random weight is a
+        // multiple of 8; strict verification always rejects it.
+        let sk = SigningKey::generate(&mut OsRng);
+        let mut bad = signed_tx(&sk, 0);
+        let nonce = Scalar::from(7u64);
+        let r = (nonce * ED25519_BASEPOINT_POINT + EIGHT_TORSION[1]).compress();
+        let k = Scalar::from_bytes_mod_order_wide(
+            &Sha512::new()
+                .chain_update(r.as_bytes())
+                .chain_update(bad.from)
+                .chain_update(bad.signing_message())
+                .finalize()
+                .into(),
+        );
+        let s = nonce + k * sk.to_scalar();
+        bad.signature = [r.to_bytes(), s.to_bytes()].concat();
+
+        let mut txs: Vec<Transaction> = (1..100).map(|n| signed_tx(&sk, n)).collect();
+        txs.push(bad.clone());
+        let verifier = SignatureVerifier::default();
+        let results = verifier.verify_all(&txs);
+        assert!(results[..99].iter().all(|ok| *ok));
+        assert!(!results[99]);
+        assert!(!verifier.is_verified(&bad.hash()));
+    }
+
+    #[test]
+    fn test_non_canonical_s_rejected() {
+        let sk = SigningKey::generate(&mut OsRng);
+        let mut tx = signed_tx(&sk, 0);
+        // S + ℓ is the same scalar mod ℓ, but not its canonical encoding.
+        let mut s = [0u8; 32];
+        s.copy_from_slice(&tx.signature[32..]);
+        let order = hex::decode("edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010")
+            .unwrap();
+        let mut carry = 0u16;
+        for (byte, l) in s.iter_mut().zip(&order) {
+            let sum = u16::from(*byte) + u16::from(*l) + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        tx.signature[32..].copy_from_slice(&s);
+
+        let txs = vec![signed_tx(&sk, 1), tx.clone()];
+        let verifier = SignatureVerifier::default();
+        assert_eq!(verifier.verify_all(&txs), vec![true, false]);
+        assert!(!verifier.is_verified(&tx.hash()));
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        let sk = SigningKey::generate(&mut OsRng);
+        let txs: Vec<Transaction> = (0..10).map(|n| signed_tx(&sk, n)).collect();
+        let verifier = SignatureVerifier::new(4);
+        verifier.verify_all(&txs);
+        assert_eq!(verifier.cached(), 4);
+        assert!(verifier.is_verified(&txs[9].hash()));
+        assert!(!verifier.is_verified(&txs[0].hash()));
+    }
+}
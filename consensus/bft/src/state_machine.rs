@@ -64,16 +64,12 @@ impl BftStateMachine {
         let total = self.validators.len();
         self.round_state = RoundState::new(round, self.height, total);
 
-        let mut out = Vec::new();
-
         // Schedule propose timeout
-        out.push(ConsensusMessage::ScheduleTimeout(TimeoutEvent {
+        vec![ConsensusMessage::ScheduleTimeout(TimeoutEvent {
             height: self.height,
             round: self.round,
             step: TimeoutStep::Propose,
-        }));
-
-        out
+        })]
     }
 
     /// Determine the proposer for a given height and round via simple rotation.
@@ -171,6 +167,11 @@ impl BftStateMachine {
             return out;
         }
 
+        // Report equivocation before the VoteSet drops the conflicting vote
+        if let Some(evidence) = self.round_state.prevotes.find_conflict(vote) {
+            out.push(ConsensusMessage::SubmitEvidence(evidence));
+        }
+
         // Add vote to the set (the VoteSet handles dedup and verification)
         self.round_state.prevotes.add_vote(vote.clone());
 
@@ -240,6 +241,10 @@ impl BftStateMachine {
             return out;
         }
 
+        if let Some(evidence) = self.round_state.precommits.find_conflict(vote) {
+            out.push(ConsensusMessage::SubmitEvidence(evidence));
+        }
+
         self.round_state.precommits.add_vote(vote.clone());
 
         // Check for commit
@@ -317,6 +322,14 @@ impl BftStateMachine {
         self.proposed_blocks.get(hash)
     }
 
+    /// Replace the validator set, with our index in the new one. Called
+    /// between a commit and `advance_height`, so the new set takes over
+    /// from the next height.
+    pub fn set_validators(&mut self, validators: Vec<ValidatorId>, validator_index: Option<usize>) {
+        self.validators = validators;
+        self.validator_index = validator_index;
+    }
+
     /// Advance to the next height after a commit.
     pub fn advance_height(&mut self, new_height: Height) -> Vec<ConsensusMessage> {
         self.height = new_height;
@@ -380,6 +393,27 @@ mod tests {
         assert_eq!(sm.proposer_index(Height(3), Round(3)), 2); // (3+3)%4=2
     }
 
+    #[test]
+    fn test_set_validators_applies_from_next_height() {
+        let (keys, ids) = make_validators(4);
+        let mut sm = BftStateMachine::new(Height(3), ids.clone(), Some(0), TimeoutConfig::default());
+        sm.start_round(Round(0));
+
+        // Validator 0 leaves; the rest are reordered.
+        let next = vec![ids[3].clone(), ids[1].clone(), ids[2].clone()];
+        sm.set_validators(next, None);
+        sm.advance_height(Height(4));
+        assert!(!sm.is_proposer());
+        assert_eq!(sm.validators.len(), 3);
+
+        // (4+0)%3=1 now proposes; the old rotation's pick is ignored.
+        let hash = BlockHash([9; 32]);
+        let stale = Proposal::new(Height(4), Round(0), hash, None, &keys[0]);
+        assert!(sm.on_proposal(&stale, None).is_empty());
+        let proposal = Proposal::new(Height(4), Round(0), hash, None, &keys[1]);
+        assert!(!sm.on_proposal(&proposal, None).is_empty());
+    }
+
     #[test]
     fn test_start_round_schedules_timeout() {
         let (_keys, ids) = make_validators(4);
@@ -746,6 +780,9 @@ mod tests {
             nonce: 0,
             signature: vec![0u8; 64],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         }];
         let block = Block {
             header: BlockHeader {
@@ -827,4 +864,28 @@ mod tests {
         sm.advance_height(Height(1));
         assert!(sm.proposed_blocks.is_empty(), "proposed_blocks should be cleared on height advance");
     }
+
+    #[test]
+    fn test_conflicting_prevote_emits_evidence() {
+        let (keys, ids) = make_validators(4);
+        let mut sm = BftStateMachine::new(Height(0), ids, Some(0), TimeoutConfig::default());
+        sm.start_round(Round(0));
+        sm.step = RoundStep::Prevote;
+
+        let hash_a = BlockHash([0xAA; 32]);
+        let hash_b = BlockHash([0xBB; 32]);
+        let v1 = make_signed_vote(VoteType::Prevote, Height(0), Round(0), Some(hash_a), &keys[2]);
+        let v2 = make_signed_vote(VoteType::Prevote, Height(0), Round(0), Some(hash_b), &keys[2]);
+
+        assert!(sm.on_prevote(&v1).is_empty());
+        let msgs = sm.on_prevote(&v2);
+        match msgs.as_slice() {
+            [ConsensusMessage::SubmitEvidence(ev)] => {
+                assert!(ev.verify());
+                assert_eq!(ev.offender(), &ValidatorId(keys[2].verifying_key()));
+            }
+            other => panic!("expected evidence, got {other:?}"),
+        }
+        assert_eq!(sm.round_state.prevotes.count(), 1);
+    }
 }
@@ -1,7 +1,7 @@
-use ed25519_dalek::{Signature, VerifyingKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use serde::{Deserialize, Serialize};
 
-use crate::block::Block;
+use crate::block::{Block, CompactBlock};
 
 /// Wrapper around an ed25519 public key identifying a validator.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -38,7 +38,7 @@ pub struct Height(pub u64);
 pub struct Round(pub u32);
 
 /// A vote cast by a validator (prevote or precommit).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Vote {
     pub vote_type: VoteType,
     pub height: Height,
@@ -67,18 +67,79 @@ pub struct Proposal {
     pub valid_round: Option<Round>,
 }
 
+impl Proposal {
+    /// Create and sign a proposal.
+    pub fn new(
+        height: Height,
+        round: Round,
+        block_hash: BlockHash,
+        valid_round: Option<Round>,
+        signing_key: &SigningKey,
+    ) -> Self {
+        let proposer = ValidatorId(signing_key.verifying_key());
+        let sign_bytes = Self::sign_bytes(height, round, &block_hash, valid_round);
+        let signature = signing_key.sign(&sign_bytes);
+        Self {
+            height,
+            round,
+            block_hash,
+            proposer,
+            signature,
+            valid_round,
+        }
+    }
+
+    /// Canonical bytes to sign / verify: the encoding of the unsigned fields.
+    ///
+    /// Prefixed with 0x03 so a proposal signature can never be replayed as a
+    /// prevote (0x01) or precommit (0x02).
+    fn sign_bytes(
+        height: Height,
+        round: Round,
+        block_hash: &BlockHash,
+        valid_round: Option<Round>,
+    ) -> Vec<u8> {
+        let mut buf = Vec::new();
+        Self::encode_unsigned(height, round, block_hash, valid_round, &mut buf);
+        buf
+    }
+
+    /// Verify the proposal signature against the proposer's public key.
+    pub fn verify(&self) -> bool {
+        let sign_bytes =
+            Self::sign_bytes(self.height, self.round, &self.block_hash, self.valid_round);
+        self.proposer.0.verify(&sign_bytes, &self.signature).is_ok()
+    }
+}
+
+/// Proof that a validator signed two conflicting votes of the same type for
+/// the same height and round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateVoteEvidence {
+    pub vote_a: Vote,
+    pub vote_b: Vote,
+}
+
 /// Messages produced and consumed by the BFT state machine.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ConsensusMessage {
     ProposeBlock {
         proposal: Proposal,
         block: Option<Block>,
     },
+    /// A proposal carrying only the block header and transaction hashes.
+    /// Receivers rebuild the block from their mempool.
+    ProposeCompact {
+        proposal: Proposal,
+        block: CompactBlock,
+    },
     CastVote(Vote),
     CommitBlock {
         height: Height,
         block_hash: BlockHash,
     },
+    SubmitEvidence(DuplicateVoteEvidence),
     ScheduleTimeout(TimeoutEvent),
 }
 
@@ -129,3 +190,42 @@ impl TimeoutConfig {
         base + self.increment_ms * round.0 as u64
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_proposal_sign_and_verify() {
+        let key = SigningKey::generate(&mut OsRng);
+        let proposal = Proposal::new(Height(3), Round(1), BlockHash([0x11; 32]), None, &key);
+        assert!(proposal.verify(), "valid proposal should verify");
+        assert_eq!(proposal.proposer, ValidatorId(key.verifying_key()));
+    }
+
+    #[test]
+    fn test_proposal_tampered_fails() {
+        let key = SigningKey::generate(&mut OsRng);
+        let mut proposal =
+            Proposal::new(Height(3), Round(1), BlockHash([0x11; 32]), Some(Round(0)), &key);
+        proposal.valid_round = None;
+        assert!(!proposal.verify(), "tampered valid_round should fail verification");
+    }
+
+    #[test]
+    fn test_proposal_signature_not_valid_as_vote() {
+        let key = SigningKey::generate(&mut OsRng);
+        let hash = BlockHash([0x22; 32]);
+        let proposal = Proposal::new(Height(1), Round(0), hash, None, &key);
+        let vote = Vote {
+            vote_type: VoteType::Prevote,
+            height: Height(1),
+            round: Round(0),
+            block_hash: Some(hash),
+            validator: proposal.proposer.clone(),
+            signature: proposal.signature,
+        };
+        assert!(!vote.verify());
+    }
+}
@@ -1,7 +1,9 @@
 use ed25519_dalek::{Signer, SigningKey, Verifier};
 use std::collections::HashMap;
 
-use crate::types::{BlockHash, Height, Round, ValidatorId, Vote, VoteType};
+use crate::types::{
+    BlockHash, DuplicateVoteEvidence, Height, Round, ValidatorId, Vote, VoteType,
+};
 
 impl Vote {
     /// Create and sign a vote.
@@ -33,21 +35,7 @@ impl Vote {
         block_hash: Option<&BlockHash>,
     ) -> Vec<u8> {
         let mut buf = Vec::new();
-        buf.push(match vote_type {
-            VoteType::Prevote => 0x01,
-            VoteType::Precommit => 0x02,
-        });
-        buf.extend_from_slice(&height.0.to_le_bytes());
-        buf.extend_from_slice(&round.0.to_le_bytes());
-        match block_hash {
-            Some(h) => {
-                buf.push(0x01);
-                buf.extend_from_slice(&h.0);
-            }
-            None => {
-                buf.push(0x00);
-            }
-        }
+        Self::encode_unsigned(vote_type, height, round, block_hash, &mut buf);
         buf
     }
 
@@ -63,6 +51,26 @@ impl Vote {
     }
 }
 
+impl DuplicateVoteEvidence {
+    /// The validator that signed both votes.
+    pub fn offender(&self) -> &ValidatorId {
+        &self.vote_a.validator
+    }
+
+    /// Check that both votes are validly signed by the same validator for the
+    /// same height, round and type, but for different values.
+    pub fn verify(&self) -> bool {
+        let (a, b) = (&self.vote_a, &self.vote_b);
+        a.validator == b.validator
+            && a.vote_type == b.vote_type
+            && a.height == b.height
+            && a.round == b.round
+            && a.block_hash != b.block_hash
+            && a.verify()
+            && b.verify()
+    }
+}
+
 /// Collects votes for a specific height/round/type and checks quorum.
 #[derive(Debug, Clone)]
 pub struct VoteSet {
@@ -111,6 +119,19 @@ impl VoteSet {
         true
     }
 
+    /// If `vote` is validly signed and conflicts with a vote already recorded
+    /// from the same validator, return evidence of the equivocation.
+    pub fn find_conflict(&self, vote: &Vote) -> Option<DuplicateVoteEvidence> {
+        let existing = self.votes.get(vote.validator.as_bytes())?;
+        if existing.block_hash == vote.block_hash || !vote.verify() {
+            return None;
+        }
+        Some(DuplicateVoteEvidence {
+            vote_a: existing.clone(),
+            vote_b: vote.clone(),
+        })
+    }
+
     /// Check if there is a 2/3+ quorum for a specific block hash.
     pub fn has_quorum_for(&self, block_hash: &BlockHash) -> bool {
         let count = self
@@ -330,4 +351,39 @@ mod tests {
         // But we have 2/3+ of total having voted
         assert!(vs.has_two_thirds_any());
     }
+
+    #[test]
+    fn test_voteset_find_conflict() {
+        let key = SigningKey::generate(&mut OsRng);
+        let mut vs = VoteSet::new(VoteType::Prevote, Height(1), Round(0), 4);
+
+        let hash = BlockHash([0x01; 32]);
+        let first = Vote::new(VoteType::Prevote, Height(1), Round(0), Some(hash), &key);
+        assert!(vs.find_conflict(&first).is_none());
+        vs.add_vote(first.clone());
+
+        // Same value again is a duplicate, not an equivocation.
+        assert!(vs.find_conflict(&first).is_none());
+
+        let second = Vote::new(VoteType::Prevote, Height(1), Round(0), None, &key);
+        let evidence = vs.find_conflict(&second).expect("conflicting vote should be detected");
+        assert!(evidence.verify());
+        assert_eq!(evidence.offender(), &ValidatorId(key.verifying_key()));
+    }
+
+    #[test]
+    fn test_duplicate_vote_evidence_rejects_different_validators() {
+        let keys = make_signing_keys(2);
+        let evidence = DuplicateVoteEvidence {
+            vote_a: Vote::new(VoteType::Prevote, Height(1), Round(0), None, &keys[0]),
+            vote_b: Vote::new(
+                VoteType::Prevote,
+                Height(1),
+                Round(0),
+                Some(BlockHash([0x02; 32])),
+                &keys[1],
+            ),
+        };
+        assert!(!evidence.verify());
+    }
 }
@@ -1,26 +1,83 @@
+use libp2p::gossipsub::MessageAcceptance;
+use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use trv1_bft::ConsensusMessage;
 use trv1_bft::block::Transaction;
+use trv1_bft::encoding::{self, DecodeError};
+
+use crate::peer::ValidatorRegistry;
+use crate::topics::GossipTopic;
+use crate::tx_gossip::TxAnnouncement;
 
 /// Errors during network message encoding/decoding.
 #[derive(Debug, Error)]
 pub enum CodecError {
     #[error("serialization failed: {0}")]
     Serialize(#[from] bincode::Error),
+    #[error("decoding failed: {0}")]
+    Decode(#[from] DecodeError),
+}
+
+/// Reasons an inbound gossip message fails validation.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("message has no authenticated source")]
+    MissingSource,
+    #[error("local-only message received from the network")]
+    LocalOnly,
+    #[error("message does not belong on the {0} topic")]
+    WrongTopic(GossipTopic),
+    #[error("signer is not a known validator")]
+    UnknownValidator,
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("publisher {publisher} does not match the signing validator")]
+    SourceMismatch { publisher: PeerId },
+    #[error("attached block does not match the proposal's block hash")]
+    BlockHashMismatch,
+    #[error("transaction hashes do not match the block's merkle root")]
+    TxRootMismatch,
+    #[error("evidence does not prove an equivocation")]
+    InvalidEvidence,
+}
+
+impl ValidationError {
+    /// How gossipsub should treat a message that failed with this error.
+    ///
+    /// Messages from validators we do not know are ignored rather than
+    /// rejected: our view of the validator set may simply be behind, and the
+    /// relaying peer should not be penalised for that. Everything else is
+    /// provably bad and is rejected so it is never re-propagated.
+    pub fn acceptance(&self) -> MessageAcceptance {
+        match self {
+            ValidationError::UnknownValidator => MessageAcceptance::Ignore,
+            _ => MessageAcceptance::Reject,
+        }
+    }
 }
 
 /// A network-level wrapper around a consensus message,
-/// including the sender's peer identity.
+/// including the verified peer identities it arrived through.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkMessage {
-    /// The libp2p PeerId of the sender, serialized as bytes.
+    /// The libp2p PeerId of the original publisher, serialized as bytes.
+    /// Taken from the gossipsub message source, which is covered by the
+    /// publisher's signature.
     pub sender: Vec<u8>,
+    /// The libp2p PeerId of the directly connected peer that relayed the
+    /// message to us, serialized as bytes.
+    pub relayer: Vec<u8>,
     /// The inner consensus message.
     pub message: ConsensusMessage,
 }
 
 impl NetworkMessage {
+    /// The original publisher as a PeerId, if `sender` is well-formed.
+    pub fn sender_peer_id(&self) -> Option<PeerId> {
+        PeerId::from_bytes(&self.sender).ok()
+    }
+
     /// Encode a NetworkMessage to bytes using bincode.
     pub fn encode(&self) -> Result<Vec<u8>, CodecError> {
         Ok(bincode::serialize(self)?)
@@ -32,23 +89,117 @@ impl NetworkMessage {
     }
 }
 
-/// Encode a raw ConsensusMessage to bytes (for gossipsub publishing).
+/// Validate an inbound consensus message before it is delivered or forwarded.
+///
+/// The message must belong on the `topic` it arrived on. Votes and proposals
+/// must carry a valid signature from a validator in `validators`, and must
+/// have been published by that validator's own peer. A compact proposal's
+/// transaction hashes must also match its header's Merkle root. Commit notices carry no
+/// signature, so only the publisher is checked. Evidence may be relayed by
+/// anyone, but must prove an equivocation by a known validator.
+pub fn validate_consensus_message(
+    msg: &ConsensusMessage,
+    topic: GossipTopic,
+    source: Option<&PeerId>,
+    validators: &ValidatorRegistry,
+) -> Result<(), ValidationError> {
+    match GossipTopic::for_message(msg) {
+        None => return Err(ValidationError::LocalOnly),
+        Some(expected) if expected != topic => return Err(ValidationError::WrongTopic(topic)),
+        Some(_) => {}
+    }
+    let source = source.ok_or(ValidationError::MissingSource)?;
+
+    let signer = match msg {
+        ConsensusMessage::ProposeBlock { proposal, block } => {
+            if !validators.contains(&proposal.proposer) {
+                return Err(ValidationError::UnknownValidator);
+            }
+            if !proposal.verify() {
+                return Err(ValidationError::InvalidSignature);
+            }
+            if let Some(blk) = block {
+                if blk.hash() != proposal.block_hash {
+                    return Err(ValidationError::BlockHashMismatch);
+                }
+            }
+            &proposal.proposer
+        }
+        ConsensusMessage::ProposeCompact { proposal, block } => {
+            if !validators.contains(&proposal.proposer) {
+                return Err(ValidationError::UnknownValidator);
+            }
+            if !proposal.verify() {
+                return Err(ValidationError::InvalidSignature);
+            }
+            if block.hash() != proposal.block_hash {
+                return Err(ValidationError::BlockHashMismatch);
+            }
+            if !block.verify_tx_root() {
+                return Err(ValidationError::TxRootMismatch);
+            }
+            &proposal.proposer
+        }
+        ConsensusMessage::CastVote(vote) => {
+            if !validators.contains(&vote.validator) {
+                return Err(ValidationError::UnknownValidator);
+            }
+            if !vote.verify() {
+                return Err(ValidationError::InvalidSignature);
+            }
+            &vote.validator
+        }
+        ConsensusMessage::CommitBlock { .. } => {
+            return match validators.validator_of(source) {
+                Some(_) => Ok(()),
+                None => Err(ValidationError::UnknownValidator),
+            };
+        }
+        ConsensusMessage::SubmitEvidence(evidence) => {
+            if !validators.contains(evidence.offender()) {
+                return Err(ValidationError::UnknownValidator);
+            }
+            if !evidence.verify() {
+                return Err(ValidationError::InvalidEvidence);
+            }
+            return Ok(());
+        }
+        ConsensusMessage::ScheduleTimeout(_) => return Err(ValidationError::LocalOnly),
+    };
+
+    if validators.peer_id_of(signer) != Some(*source) {
+        return Err(ValidationError::SourceMismatch { publisher: *source });
+    }
+    Ok(())
+}
+
+/// Encode a raw ConsensusMessage to its canonical bytes (for gossipsub publishing).
 pub fn encode_consensus_message(msg: &ConsensusMessage) -> Result<Vec<u8>, CodecError> {
-    Ok(bincode::serialize(msg)?)
+    Ok(encoding::to_bytes(msg))
 }
 
-/// Decode a raw ConsensusMessage from bytes.
+/// Decode a raw ConsensusMessage from canonical bytes.
 pub fn decode_consensus_message(data: &[u8]) -> Result<ConsensusMessage, CodecError> {
-    Ok(bincode::deserialize(data)?)
+    Ok(encoding::from_bytes(data)?)
 }
 
-/// Encode a Transaction to bytes (for gossipsub publishing on the tx topic).
+/// Encode a Transaction to its canonical bytes.
 pub fn encode_transaction(tx: &Transaction) -> Result<Vec<u8>, CodecError> {
-    Ok(bincode::serialize(tx)?)
+    Ok(encoding::to_bytes(tx))
 }
 
-/// Decode a Transaction from bytes.
+/// Decode a Transaction from canonical bytes.
 pub fn decode_transaction(data: &[u8]) -> Result<Transaction, CodecError> {
+    Ok(encoding::from_bytes(data)?)
+}
+
+/// Encode a transaction hash announcement (for gossipsub publishing on the tx topic).
+pub fn encode_tx_announcement(announcement: &TxAnnouncement) -> Result<Vec<u8>, CodecError> {
+    Ok(bincode::serialize(announcement)?)
+}
+
+/// Decode a transaction hash announcement from bytes.
+pub fn decode_tx_announcement(data: &[u8]) -> Result<TxAnnouncement, CodecError> {
     Ok(bincode::deserialize(data)?)
 }
 
@@ -61,6 +212,7 @@ mod tests {
     fn test_network_message_roundtrip() {
         let msg = NetworkMessage {
             sender: vec![1, 2, 3, 4],
+            relayer: vec![5, 6, 7, 8],
             message: ConsensusMessage::CommitBlock {
                 height: Height(42),
                 block_hash: BlockHash([0xAB; 32]),
@@ -71,6 +223,7 @@ mod tests {
         let decoded = NetworkMessage::decode(&encoded).expect("decode should succeed");
 
         assert_eq!(decoded.sender, msg.sender);
+        assert_eq!(decoded.relayer, msg.relayer);
         match decoded.message {
             ConsensusMessage::CommitBlock {
                 height,
@@ -114,6 +267,7 @@ mod tests {
     fn test_empty_sender() {
         let msg = NetworkMessage {
             sender: vec![],
+            relayer: vec![],
             message: ConsensusMessage::CommitBlock {
                 height: Height(0),
                 block_hash: BlockHash([0; 32]),
@@ -123,6 +277,7 @@ mod tests {
         let encoded = msg.encode().unwrap();
         let decoded = NetworkMessage::decode(&encoded).unwrap();
         assert!(decoded.sender.is_empty());
+        assert!(decoded.sender_peer_id().is_none());
     }
 
     #[test]
@@ -136,6 +291,9 @@ mod tests {
             nonce: 7,
             signature: vec![0xAB; 64],
             data: vec![1, 2, 3, 4],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         let encoded = super::encode_transaction(&tx).expect("encode tx");
@@ -155,6 +313,17 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_tx_announcement_roundtrip() {
+        let announcement = TxAnnouncement {
+            hashes: vec![[0x01; 32], [0x02; 32]],
+        };
+        let encoded = encode_tx_announcement(&announcement).expect("encode announcement");
+        let decoded = decode_tx_announcement(&encoded).expect("decode announcement");
+        assert_eq!(decoded.hashes, announcement.hashes);
+        assert!(decode_tx_announcement(&[0xFF]).is_err());
+    }
+
     #[test]
     fn test_propose_block_with_block_roundtrip() {
         use ed25519_dalek::{Signer, SigningKey};
@@ -172,6 +341,9 @@ mod tests {
             nonce: 0,
             signature: vec![0u8; 64],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         }];
 
         let block = Block {
@@ -258,4 +430,252 @@ mod tests {
             _ => panic!("expected ProposeBlock"),
         }
     }
+
+    fn validator_key() -> (ed25519_dalek::SigningKey, trv1_bft::ValidatorId, PeerId) {
+        use rand::rngs::OsRng;
+
+        let key = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let id = trv1_bft::ValidatorId(key.verifying_key());
+        let peer = crate::peer::validator_peer_id(&id);
+        (key, id, peer)
+    }
+
+    #[test]
+    fn test_validate_signed_vote_from_its_validator() {
+        use trv1_bft::{Vote, VoteType};
+
+        let (key, id, peer) = validator_key();
+        let registry = ValidatorRegistry::new(&[id]);
+        let vote = Vote::new(VoteType::Prevote, Height(1), Round(0), None, &key);
+
+        let msg = ConsensusMessage::CastVote(vote);
+        assert_eq!(
+            validate_consensus_message(&msg, GossipTopic::Votes, Some(&peer), &registry),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_tampered_vote() {
+        use trv1_bft::{Vote, VoteType};
+
+        let (key, id, peer) = validator_key();
+        let registry = ValidatorRegistry::new(&[id]);
+        let mut vote = Vote::new(VoteType::Prevote, Height(1), Round(0), None, &key);
+        vote.block_hash = Some(BlockHash([0x01; 32]));
+
+        let msg = ConsensusMessage::CastVote(vote);
+        let err = validate_consensus_message(&msg, GossipTopic::Votes, Some(&peer), &registry)
+            .unwrap_err();
+        assert_eq!(err, ValidationError::InvalidSignature);
+        assert!(matches!(err.acceptance(), MessageAcceptance::Reject));
+    }
+
+    #[test]
+    fn test_validate_ignores_unknown_validator() {
+        use trv1_bft::{Vote, VoteType};
+
+        let (key, _id, peer) = validator_key();
+        let registry = ValidatorRegistry::default();
+        let vote = Vote::new(VoteType::Precommit, Height(1), Round(0), None, &key);
+
+        let msg = ConsensusMessage::CastVote(vote);
+        let err = validate_consensus_message(&msg, GossipTopic::Votes, Some(&peer), &registry)
+            .unwrap_err();
+        assert_eq!(err, ValidationError::UnknownValidator);
+        assert!(matches!(err.acceptance(), MessageAcceptance::Ignore));
+    }
+
+    #[test]
+    fn test_validate_rejects_vote_published_by_other_peer() {
+        use trv1_bft::{Vote, VoteType};
+
+        let (key, id, _peer) = validator_key();
+        let (_, other_id, other_peer) = validator_key();
+        let registry = ValidatorRegistry::new(&[id, other_id]);
+        let vote = Vote::new(VoteType::Prevote, Height(1), Round(0), None, &key);
+
+        let err = validate_consensus_message(
+            &ConsensusMessage::CastVote(vote),
+            GossipTopic::Votes,
+            Some(&other_peer),
+            &registry,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ValidationError::SourceMismatch { .. }));
+    }
+
+    #[test]
+    fn test_validate_proposal_signature_and_block() {
+        use trv1_bft::block::{Block, BlockHeader};
+        use trv1_bft::Proposal;
+
+        let (key, id, peer) = validator_key();
+        let registry = ValidatorRegistry::new(std::slice::from_ref(&id));
+        let block = Block {
+            header: BlockHeader {
+                height: Height(2),
+                timestamp: 1700000000,
+                parent_hash: BlockHash([0; 32]),
+                proposer: id,
+                state_root: [0u8; 32],
+                tx_merkle_root: [0u8; 32],
+            },
+            transactions: vec![],
+        };
+
+        let proposal = Proposal::new(Height(2), Round(0), block.hash(), None, &key);
+        let msg = ConsensusMessage::ProposeBlock {
+            proposal,
+            block: Some(block.clone()),
+        };
+        assert_eq!(
+            validate_consensus_message(&msg, GossipTopic::Proposals, Some(&peer), &registry),
+            Ok(())
+        );
+        assert_eq!(
+            validate_consensus_message(&msg, GossipTopic::Votes, Some(&peer), &registry),
+            Err(ValidationError::WrongTopic(GossipTopic::Votes))
+        );
+
+        let wrong = Proposal::new(Height(2), Round(0), BlockHash([0xEE; 32]), None, &key);
+        let msg = ConsensusMessage::ProposeBlock {
+            proposal: wrong,
+            block: Some(block),
+        };
+        assert_eq!(
+            validate_consensus_message(&msg, GossipTopic::Proposals, Some(&peer), &registry),
+            Err(ValidationError::BlockHashMismatch)
+        );
+    }
+
+    #[test]
+    fn test_validate_compact_proposal() {
+        use trv1_bft::block::{Block, BlockHeader, Transaction};
+        use trv1_bft::{CompactBlock, Proposal};
+
+        let (key, id, peer) = validator_key();
+        let registry = ValidatorRegistry::new(std::slice::from_ref(&id));
+        let transactions: Vec<Transaction> = (0..2)
+            .map(|i| Transaction {
+                from: [i + 1; 32],
+                to: [0xEE; 32],
+                amount: 5,
+                nonce: 0,
+                signature: vec![0u8; 64],
+                data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
+            })
+            .collect();
+        let block = Block {
+            header: BlockHeader {
+                height: Height(2),
+                timestamp: 1700000000,
+                parent_hash: BlockHash([0; 32]),
+                proposer: id,
+                state_root: [0u8; 32],
+                tx_merkle_root: Block::compute_tx_merkle_root(&transactions),
+            },
+            transactions,
+        };
+        let proposal = Proposal::new(Height(2), Round(0), block.hash(), None, &key);
+        let compact = CompactBlock::from_block(&block);
+
+        let msg = ConsensusMessage::ProposeCompact {
+            proposal: proposal.clone(),
+            block: compact.clone(),
+        };
+        assert_eq!(
+            validate_consensus_message(&msg, GossipTopic::Proposals, Some(&peer), &registry),
+            Ok(())
+        );
+
+        let mut reordered = compact;
+        reordered.tx_hashes.reverse();
+        let msg = ConsensusMessage::ProposeCompact {
+            proposal,
+            block: reordered,
+        };
+        assert_eq!(
+            validate_consensus_message(&msg, GossipTopic::Proposals, Some(&peer), &registry),
+            Err(ValidationError::TxRootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_validate_commit_requires_validator_publisher() {
+        let (_key, id, peer) = validator_key();
+        let registry = ValidatorRegistry::new(&[id]);
+        let msg = ConsensusMessage::CommitBlock {
+            height: Height(1),
+            block_hash: BlockHash([0x01; 32]),
+        };
+
+        let topic = GossipTopic::Commits;
+        assert_eq!(
+            validate_consensus_message(&msg, topic, Some(&peer), &registry),
+            Ok(())
+        );
+        assert_eq!(
+            validate_consensus_message(&msg, topic, Some(&PeerId::random()), &registry),
+            Err(ValidationError::UnknownValidator)
+        );
+        assert_eq!(
+            validate_consensus_message(&msg, topic, None, &registry),
+            Err(ValidationError::MissingSource)
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_timeout_from_network() {
+        use trv1_bft::{TimeoutEvent, TimeoutStep};
+
+        let (_key, id, peer) = validator_key();
+        let registry = ValidatorRegistry::new(&[id]);
+        let msg = ConsensusMessage::ScheduleTimeout(TimeoutEvent {
+            height: Height(1),
+            round: Round(0),
+            step: TimeoutStep::Propose,
+        });
+        assert_eq!(
+            validate_consensus_message(&msg, GossipTopic::Votes, Some(&peer), &registry),
+            Err(ValidationError::LocalOnly)
+        );
+    }
+
+    #[test]
+    fn test_validate_evidence_from_any_relayer() {
+        use trv1_bft::{DuplicateVoteEvidence, Vote, VoteType};
+
+        let (key, id, _peer) = validator_key();
+        let registry = ValidatorRegistry::new(&[id]);
+        let evidence = DuplicateVoteEvidence {
+            vote_a: Vote::new(VoteType::Prevote, Height(4), Round(0), None, &key),
+            vote_b: Vote::new(
+                VoteType::Prevote,
+                Height(4),
+                Round(0),
+                Some(BlockHash([0x04; 32])),
+                &key,
+            ),
+        };
+
+        // Evidence is not bound to its publisher: any peer may relay it.
+        let relayer = PeerId::random();
+        let msg = ConsensusMessage::SubmitEvidence(evidence.clone());
+        assert_eq!(
+            validate_consensus_message(&msg, GossipTopic::Evidence, Some(&relayer), &registry),
+            Ok(())
+        );
+
+        let mut bogus = evidence;
+        bogus.vote_b = bogus.vote_a.clone();
+        let msg = ConsensusMessage::SubmitEvidence(bogus);
+        assert_eq!(
+            validate_consensus_message(&msg, GossipTopic::Evidence, Some(&relayer), &registry),
+            Err(ValidationError::InvalidEvidence)
+        );
+    }
 }
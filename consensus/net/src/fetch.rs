@@ -0,0 +1,193 @@
+use libp2p::{PeerId, StreamProtocol};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::{mpsc, oneshot};
+use trv1_bft::block::{Block, Transaction};
+use trv1_bft::BlockHash;
+
+use crate::network::NetworkError;
+
+/// Request-response protocol for fetching a proposed block's transactions.
+pub const BLOCK_TXS_PROTOCOL: StreamProtocol = StreamProtocol::new("/trv1/block-txs/1");
+
+/// Number of recent blocks kept to serve transaction requests from.
+pub const BLOCK_CACHE_SIZE: usize = 16;
+
+/// Ask a peer for transactions of a block it proposed or relayed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTxsRequest {
+    pub block_hash: BlockHash,
+    pub tx_hashes: Vec<[u8; 32]>,
+}
+
+/// The requested transactions the peer could find, in request order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockTxsResponse {
+    pub transactions: Vec<Transaction>,
+}
+
+/// Recently proposed or reconstructed blocks, indexed for serving
+/// `BlockTxsRequest`s. Holds at most `BLOCK_CACHE_SIZE` blocks.
+#[derive(Debug, Default)]
+pub struct BlockCache {
+    order: VecDeque<BlockHash>,
+    blocks: HashMap<BlockHash, HashMap<[u8; 32], Transaction>>,
+}
+
+impl BlockCache {
+    /// Add a block, evicting the oldest one if the cache is full.
+    pub fn insert(&mut self, block: &Block) {
+        let hash = block.hash();
+        if self.blocks.contains_key(&hash) {
+            return;
+        }
+        if self.order.len() >= BLOCK_CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.blocks.remove(&oldest);
+            }
+        }
+        let txs = block
+            .transactions
+            .iter()
+            .map(|tx| (tx.hash(), tx.clone()))
+            .collect();
+        self.order.push_back(hash);
+        self.blocks.insert(hash, txs);
+    }
+
+    pub fn contains(&self, block_hash: &BlockHash) -> bool {
+        self.blocks.contains_key(block_hash)
+    }
+
+    /// A transaction of a cached block.
+    pub fn get(&self, block_hash: &BlockHash, tx_hash: &[u8; 32]) -> Option<&Transaction> {
+        self.blocks.get(block_hash)?.get(tx_hash)
+    }
+
+    /// Answer a request from the cached block. Unknown blocks or hashes
+    /// yield an empty or partial response.
+    pub fn serve(&self, request: &BlockTxsRequest) -> BlockTxsResponse {
+        BlockTxsResponse {
+            transactions: request
+                .tx_hashes
+                .iter()
+                .filter_map(|h| self.get(&request.block_hash, h).cloned())
+                .collect(),
+        }
+    }
+}
+
+/// An outbound transaction request queued for the runner.
+pub(crate) struct FetchCommand {
+    pub peer: PeerId,
+    pub request: BlockTxsRequest,
+    pub reply: oneshot::Sender<Result<BlockTxsResponse, NetworkError>>,
+}
+
+/// Cloneable handle for fetching block transactions from peers.
+///
+/// Independent of `NetworkHandle` so fetches can run in spawned tasks
+/// without holding up the consensus loop.
+#[derive(Clone)]
+pub struct TxFetcher {
+    pub(crate) fetch_tx: mpsc::Sender<FetchCommand>,
+}
+
+impl TxFetcher {
+    /// Request transactions of `block_hash` from `peer`.
+    ///
+    /// The response may be partial; callers must check that every
+    /// transaction they need was returned.
+    pub async fn fetch(
+        &self,
+        peer: PeerId,
+        block_hash: BlockHash,
+        tx_hashes: Vec<[u8; 32]>,
+    ) -> Result<Vec<Transaction>, NetworkError> {
+        let (reply, rx) = oneshot::channel();
+        let command = FetchCommand {
+            peer,
+            request: BlockTxsRequest {
+                block_hash,
+                tx_hashes,
+            },
+            reply,
+        };
+        self.fetch_tx
+            .send(command)
+            .await
+            .map_err(|_| NetworkError::ChannelClosed)?;
+        let response = rx.await.map_err(|_| NetworkError::ChannelClosed)??;
+        Ok(response.transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trv1_bft::block::BlockHeader;
+    use trv1_bft::{Height, ValidatorId};
+
+    fn make_block(height: u64, tx_count: usize) -> Block {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let transactions: Vec<Transaction> = (0..tx_count)
+            .map(|i| Transaction {
+                from: [i as u8 + 1; 32],
+                to: [0xEE; 32],
+                amount: 10,
+                nonce: height,
+                signature: vec![0u8; 64],
+                data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
+            })
+            .collect();
+        Block {
+            header: BlockHeader {
+                height: Height(height),
+                timestamp: 0,
+                parent_hash: BlockHash::default(),
+                proposer: ValidatorId(SigningKey::generate(&mut OsRng).verifying_key()),
+                state_root: [0u8; 32],
+                tx_merkle_root: Block::compute_tx_merkle_root(&transactions),
+            },
+            transactions,
+        }
+    }
+
+    #[test]
+    fn test_cache_serves_requested_transactions() {
+        let block = make_block(1, 3);
+        let mut cache = BlockCache::default();
+        cache.insert(&block);
+
+        let wanted = block.transactions[2].hash();
+        let response = cache.serve(&BlockTxsRequest {
+            block_hash: block.hash(),
+            tx_hashes: vec![wanted, [0xAB; 32]],
+        });
+        assert_eq!(response.transactions.len(), 1);
+        assert_eq!(response.transactions[0].hash(), wanted);
+
+        let unknown = cache.serve(&BlockTxsRequest {
+            block_hash: BlockHash([0x99; 32]),
+            tx_hashes: vec![wanted],
+        });
+        assert!(unknown.transactions.is_empty());
+    }
+
+    #[test]
+    fn test_cache_evicts_oldest() {
+        let mut cache = BlockCache::default();
+        let first = make_block(0, 1);
+        cache.insert(&first);
+        for h in 1..=BLOCK_CACHE_SIZE as u64 {
+            cache.insert(&make_block(h, 1));
+        }
+        assert!(!cache.contains(&first.hash()));
+        assert_eq!(cache.order.len(), BLOCK_CACHE_SIZE);
+    }
+}
@@ -1,5 +1,12 @@
 pub mod network;
 pub mod codec;
+pub mod fetch;
 pub mod peer;
+pub mod stats;
+pub mod testing;
+pub mod topics;
+pub mod tx_gossip;
 
-pub use network::{ConsensusNetwork, NetworkHandle, NetworkRunner, TRANSACTION_TOPIC};
+pub use network::{ConsensusNetwork, NetworkHandle, NetworkRunner, TransportKind};
+pub use fetch::TxFetcher;
+pub use topics::{GossipTopic, TRANSACTION_TOPIC};
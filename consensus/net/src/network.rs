@@ -1,24 +1,38 @@
 use libp2p::{
     futures::StreamExt,
-    gossipsub::{self, IdentTopic, MessageAuthenticity},
+    gossipsub::{self, MessageAuthenticity, TopicHash},
     identity::Keypair,
-    noise, tcp, yamux, Multiaddr, PeerId, Swarm, SwarmBuilder,
+    multiaddr::Protocol,
+    core::{transport::MemoryTransport, upgrade, Transport as _},
+    noise,
+    request_response::{self, OutboundRequestId, ProtocolSupport},
+    swarm::{
+        dial_opts::{DialOpts, PeerCondition},
+        NetworkBehaviour, SwarmEvent,
+    },
+    tcp, yamux, Multiaddr, PeerId, Swarm, SwarmBuilder,
 };
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::sync::mpsc;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::oneshot;
 use tracing;
-use trv1_bft::ConsensusMessage;
-use trv1_bft::block::Transaction;
+use trv1_bft::{CompactBlock, ConsensusMessage, Proposal, SignatureVerifier, ValidatorId};
+use trv1_bft::block::{Block, Transaction};
 
 use crate::codec::{self, NetworkMessage};
-use crate::peer::PeerManager;
-
-/// The gossipsub topic for consensus messages.
-pub const CONSENSUS_TOPIC: &str = "trv1-consensus";
-
-/// The gossipsub topic for transaction gossip.
-pub const TRANSACTION_TOPIC: &str = "trv1-transactions";
+use crate::fetch::{
+    BlockCache, BlockTxsRequest, BlockTxsResponse, FetchCommand, TxFetcher, BLOCK_TXS_PROTOCOL,
+};
+use crate::peer::{PeerManager, PeerPolicy, ValidatorRegistry};
+use crate::stats::{NetworkStats, TopicStats};
+use crate::topics::GossipTopic;
+use crate::tx_gossip::{
+    PeerRateLimiter, SeenCache, TxAnnouncement, TxGossipConfig, TxRequest, TxResponse, TxStore,
+    TX_PROTOCOL,
+};
 
 #[derive(Debug, Error)]
 pub enum NetworkError {
@@ -28,24 +42,81 @@ pub enum NetworkError {
     Gossipsub(String),
     #[error("codec error: {0}")]
     Codec(#[from] crate::codec::CodecError),
+    #[error("outbound queue for {0} is full")]
+    QueueFull(GossipTopic),
+    #[error("transaction fetch failed: {0}")]
+    Fetch(String),
     #[error("channel closed")]
     ChannelClosed,
 }
 
+/// The libp2p behaviours driven by the runner: gossip for broadcast traffic
+/// and request-response for pulling announced transactions and missing
+/// block transactions.
+#[derive(NetworkBehaviour)]
+struct NodeBehaviour {
+    gossipsub: gossipsub::Behaviour,
+    block_txs: request_response::cbor::Behaviour<BlockTxsRequest, BlockTxsResponse>,
+    txs: request_response::cbor::Behaviour<TxRequest, TxResponse>,
+}
+
+/// The transport the swarm runs over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransportKind {
+    /// TCP with noise and yamux; addresses look like `/ip4/.../tcp/...`.
+    /// With the `quic` feature, QUIC runs alongside it on
+    /// `/ip4/.../udp/.../quic-v1` addresses.
+    #[default]
+    Tcp,
+    /// In-process channels; addresses look like `/memory/<n>`. Only nodes
+    /// in the same process can connect, which makes this useful for tests.
+    Memory,
+}
+
+/// Whether an address uses the QUIC transport.
+pub fn is_quic_address(addr: &Multiaddr) -> bool {
+    addr.iter().any(|p| matches!(p, Protocol::QuicV1))
+}
+
+/// Fail early with a clear error for QUIC addresses when QUIC support was
+/// not compiled in.
+fn check_transport_support(addr: &Multiaddr) -> Result<(), NetworkError> {
+    if is_quic_address(addr) && !cfg!(feature = "quic") {
+        return Err(NetworkError::Transport(format!(
+            "{addr} needs QUIC support; build with the `quic` feature"
+        )));
+    }
+    Ok(())
+}
+
 /// Configuration for the consensus network.
 #[derive(Debug, Clone)]
 pub struct NetworkConfig {
+    pub transport: TransportKind,
     pub listen_address: Multiaddr,
     pub heartbeat_interval: Duration,
     pub peer_ban_threshold: i64,
+    /// Persistent, private and unconditional peers, for sentry setups.
+    pub peer_policy: PeerPolicy,
+    /// How often disconnected persistent peers are redialed.
+    pub persistent_redial_interval: Duration,
+    pub tx_gossip: TxGossipConfig,
+    /// Verifies signatures on fetched transactions. Share it with the
+    /// mempool so they are not verified twice.
+    pub sig_verifier: Arc<SignatureVerifier>,
 }
 
 impl Default for NetworkConfig {
     fn default() -> Self {
         Self {
+            transport: TransportKind::Tcp,
             listen_address: "/ip4/0.0.0.0/tcp/30333".parse().unwrap(),
             heartbeat_interval: Duration::from_secs(1),
             peer_ban_threshold: -100,
+            peer_policy: PeerPolicy::default(),
+            persistent_redial_interval: Duration::from_secs(5),
+            tx_gossip: TxGossipConfig::default(),
+            sig_verifier: Arc::new(SignatureVerifier::default()),
         }
     }
 }
@@ -57,12 +128,24 @@ impl Default for NetworkConfig {
 pub struct NetworkHandle {
     /// Send outbound consensus messages to the swarm runner.
     broadcast_tx: mpsc::Sender<ConsensusMessage>,
-    /// Receive inbound messages from the network.
-    msg_rx: mpsc::Receiver<NetworkMessage>,
+    /// Inbound queues, one per consensus topic.
+    proposal_rx: mpsc::Receiver<NetworkMessage>,
+    commit_rx: mpsc::Receiver<NetworkMessage>,
+    vote_rx: mpsc::Receiver<NetworkMessage>,
+    evidence_rx: mpsc::Receiver<NetworkMessage>,
     /// Send outbound transactions to the swarm runner for gossip publishing.
     tx_broadcast_tx: mpsc::Sender<Transaction>,
+    /// Send hashes of transactions to stop gossiping to the swarm runner.
+    tx_withdraw_tx: mpsc::Sender<[u8; 32]>,
     /// Receive inbound transactions from the network.
     tx_msg_rx: mpsc::Receiver<Transaction>,
+    /// Validators whose consensus messages the runner accepts.
+    validators: Arc<RwLock<ValidatorRegistry>>,
+    /// Blocks whose transactions we serve to peers, shared with the runner.
+    blocks: Arc<RwLock<BlockCache>>,
+    /// Send transaction fetch requests to the swarm runner.
+    fetch_tx: mpsc::Sender<FetchCommand>,
+    stats: Arc<NetworkStats>,
     local_peer_id: PeerId,
 }
 
@@ -72,10 +155,58 @@ impl NetworkHandle {
         self.local_peer_id
     }
 
+    /// Replace the set of validators whose consensus messages are accepted.
+    ///
+    /// Inbound votes and proposals signed by anyone outside this set are
+    /// ignored and not forwarded to other peers.
+    pub fn set_validators(&self, validators: &[ValidatorId]) {
+        *self.validators.write().expect("validator registry lock poisoned") =
+            ValidatorRegistry::new(validators);
+    }
+
+    /// Traffic counters for a gossip topic.
+    pub fn topic_stats(&self, topic: GossipTopic) -> TopicStats {
+        self.stats.topic(topic)
+    }
+
+    /// A cloneable fetcher for pulling block transactions from peers.
+    pub fn tx_fetcher(&self) -> TxFetcher {
+        TxFetcher {
+            fetch_tx: self.fetch_tx.clone(),
+        }
+    }
+
+    /// Make a block's transactions available to peers rebuilding it from a
+    /// compact proposal.
+    pub fn cache_block(&self, block: &Block) {
+        self.blocks
+            .write()
+            .expect("block cache lock poisoned")
+            .insert(block);
+    }
+
+    /// Broadcast a proposal in compact form.
+    ///
+    /// Only the header and transaction hashes are gossiped; the full block
+    /// is cached so peers missing transactions can fetch them from us.
+    pub async fn broadcast_proposal(
+        &self,
+        proposal: &Proposal,
+        block: &Block,
+    ) -> Result<(), NetworkError> {
+        self.cache_block(block);
+        self.broadcast_message(&ConsensusMessage::ProposeCompact {
+            proposal: proposal.clone(),
+            block: CompactBlock::from_block(block),
+        })
+        .await
+    }
+
     /// Broadcast a consensus message to all peers via gossipsub.
     ///
     /// This sends the message to the `NetworkRunner` over a channel;
-    /// the runner publishes it on the gossipsub topic.
+    /// the runner publishes it on the message's topic. Waits for queue
+    /// space rather than dropping, since our own votes must not be lost.
     pub async fn broadcast_message(&self, msg: &ConsensusMessage) -> Result<(), NetworkError> {
         self.broadcast_tx
             .send(msg.clone())
@@ -84,16 +215,46 @@ impl NetworkHandle {
     }
 
     /// Receive the next inbound consensus message (async).
+    ///
+    /// Queues are drained in topic priority order, so a backlog of votes
+    /// never delays a waiting proposal or commit.
     pub async fn next_message(&mut self) -> Option<NetworkMessage> {
-        self.msg_rx.recv().await
+        tokio::select! {
+            biased;
+            Some(msg) = self.proposal_rx.recv() => Some(msg),
+            Some(msg) = self.commit_rx.recv() => Some(msg),
+            Some(msg) = self.vote_rx.recv() => Some(msg),
+            Some(msg) = self.evidence_rx.recv() => Some(msg),
+            else => None,
+        }
     }
 
-    /// Broadcast a transaction to all peers via the transaction gossipsub topic.
+    /// Announce a transaction to peers.
+    ///
+    /// The runner batches the transaction's hash into the next announcement
+    /// on the transaction topic; peers that do not have it pull the full
+    /// transaction from us. Returns `NetworkError::QueueFull` instead of waiting when the runner
+    /// is behind, so transaction gossip can never hold up the caller.
     pub async fn broadcast_transaction(&self, tx: &Transaction) -> Result<(), NetworkError> {
-        self.tx_broadcast_tx
-            .send(tx.clone())
-            .await
-            .map_err(|_| NetworkError::ChannelClosed)
+        self.tx_broadcast_tx.try_send(tx.clone()).map_err(|e| match e {
+            TrySendError::Full(_) => {
+                self.stats.record_dropped(GossipTopic::Transactions);
+                NetworkError::QueueFull(GossipTopic::Transactions)
+            }
+            TrySendError::Closed(_) => NetworkError::ChannelClosed,
+        })
+    }
+
+    /// Stop announcing and serving a transaction, e.g. one replaced by fee
+    /// in the mempool.
+    ///
+    /// The hash stays marked as seen, so the runner will not pull the
+    /// transaction again when other peers announce it.
+    pub async fn withdraw_transaction(&self, hash: [u8; 32]) -> Result<(), NetworkError> {
+        self.tx_withdraw_tx.try_send(hash).map_err(|e| match e {
+            TrySendError::Full(_) => NetworkError::QueueFull(GossipTopic::Transactions),
+            TrySendError::Closed(_) => NetworkError::ChannelClosed,
+        })
     }
 
     /// Receive the next inbound transaction from gossip.
@@ -111,168 +272,656 @@ impl NetworkHandle {
 
 /// Owns and drives the libp2p swarm. Spawned as a background task.
 pub struct NetworkRunner {
-    swarm: Swarm<gossipsub::Behaviour>,
-    topic: IdentTopic,
-    tx_topic: IdentTopic,
+    swarm: Swarm<NodeBehaviour>,
+    /// Maps subscribed topic hashes back to their topic.
+    topics: HashMap<TopicHash, GossipTopic>,
     peer_manager: PeerManager,
+    /// Validators whose consensus messages are accepted, shared with the handle.
+    validators: Arc<RwLock<ValidatorRegistry>>,
+    /// Blocks whose transactions we serve to peers, shared with the handle.
+    blocks: Arc<RwLock<BlockCache>>,
+    stats: Arc<NetworkStats>,
+    /// Receives transaction fetch requests from `TxFetcher`s.
+    fetch_rx: mpsc::Receiver<FetchCommand>,
+    /// Replies owed to fetchers, keyed by outbound request.
+    pending_fetches: HashMap<
+        OutboundRequestId,
+        oneshot::Sender<Result<BlockTxsResponse, NetworkError>>,
+    >,
     /// Receives outbound broadcast requests from `NetworkHandle`s.
     broadcast_rx: mpsc::Receiver<ConsensusMessage>,
-    /// Sends inbound messages to `NetworkHandle`.
-    msg_tx: mpsc::Sender<NetworkMessage>,
+    /// Inbound queues to `NetworkHandle`, one per consensus topic.
+    proposal_tx: mpsc::Sender<NetworkMessage>,
+    commit_tx: mpsc::Sender<NetworkMessage>,
+    vote_tx: mpsc::Sender<NetworkMessage>,
+    evidence_tx: mpsc::Sender<NetworkMessage>,
     /// Receives outbound transaction broadcast requests from `NetworkHandle`s.
     tx_broadcast_rx: mpsc::Receiver<Transaction>,
+    /// Receives hashes of transactions to withdraw from gossip.
+    tx_withdraw_rx: mpsc::Receiver<[u8; 32]>,
     /// Sends inbound transactions to `NetworkHandle`.
     tx_msg_tx: mpsc::Sender<Transaction>,
+    tx_gossip: TxGossipConfig,
+    /// Hashes of transactions we hold or are fetching.
+    seen_txs: SeenCache,
+    /// Recent transactions we can serve to peers.
+    tx_store: TxStore,
+    /// Hashes waiting for the next announcement.
+    pending_announce: Vec<[u8; 32]>,
+    /// Hashes requested per outbound transaction request.
+    pending_tx_requests: HashMap<OutboundRequestId, Vec<[u8; 32]>>,
+    announce_limits: PeerRateLimiter,
+    request_limits: PeerRateLimiter,
+    persistent_redial_interval: Duration,
+    sig_verifier: Arc<SignatureVerifier>,
 }
 
 impl NetworkRunner {
-    /// Start listening on the given address and subscribe to the consensus and transaction topics.
+    /// Start listening on the given address and subscribe to all gossip topics.
     pub fn start(&mut self, listen_addr: Multiaddr) -> Result<(), NetworkError> {
+        self.listen(listen_addr)?;
+        for topic in GossipTopic::ALL {
+            self.swarm
+                .behaviour_mut()
+                .gossipsub
+                .subscribe(&topic.ident_topic())
+                .map_err(|e| NetworkError::Gossipsub(e.to_string()))?;
+        }
+        self.dial_persistent_peers();
+        tracing::info!("consensus network started");
+        Ok(())
+    }
+
+    /// Dial every persistent peer we are neither connected to nor dialing.
+    fn dial_persistent_peers(&mut self) {
+        let peers: Vec<(PeerId, Multiaddr)> = self
+            .peer_manager
+            .persistent_peers()
+            .map(|(peer_id, addr)| (*peer_id, addr.clone()))
+            .collect();
+        for (peer_id, addr) in peers {
+            let opts = DialOpts::peer_id(peer_id)
+                .addresses(vec![addr])
+                .condition(PeerCondition::DisconnectedAndNotDialing)
+                .build();
+            if let Err(e) = self.swarm.dial(opts) {
+                tracing::debug!(peer = %peer_id, error = %e, "failed to dial persistent peer");
+            }
+        }
+    }
+
+    /// Lower a peer's local score, disconnecting it once it is banned.
+    fn penalise(&mut self, peer: &PeerId, delta: i64) {
+        self.peer_manager.adjust_score(peer, delta);
+        if self.peer_manager.is_banned(peer) {
+            tracing::warn!(%peer, "peer banned, disconnecting");
+            let _ = self.swarm.disconnect_peer_id(*peer);
+        }
+    }
+
+    /// Listen on an additional address, e.g. a QUIC address next to the
+    /// TCP one passed to `start`.
+    pub fn listen(&mut self, listen_addr: Multiaddr) -> Result<(), NetworkError> {
+        check_transport_support(&listen_addr)?;
         self.swarm
             .listen_on(listen_addr)
             .map_err(|e| NetworkError::Transport(e.to_string()))?;
-        self.swarm
-            .behaviour_mut()
-            .subscribe(&self.topic)
-            .map_err(|e| NetworkError::Gossipsub(e.to_string()))?;
-        self.swarm
-            .behaviour_mut()
-            .subscribe(&self.tx_topic)
-            .map_err(|e| NetworkError::Gossipsub(e.to_string()))?;
-        tracing::info!("consensus network started");
         Ok(())
     }
 
     /// Dial a remote peer.
     pub fn dial(&mut self, addr: Multiaddr) -> Result<(), NetworkError> {
+        check_transport_support(&addr)?;
         self.swarm
             .dial(addr)
             .map_err(|e| NetworkError::Transport(e.to_string()))?;
         Ok(())
     }
 
-    /// Run the swarm event loop. Consumes self and drives libp2p networking.
+    /// Report a gossipsub validation result for an inbound message.
     ///
-    /// Uses `tokio::select!` to simultaneously:
-    /// - Poll the swarm for incoming events (messages, connections)
-    /// - Receive outbound broadcast requests from `NetworkHandle`s
-    /// - Receive outbound transaction broadcast requests from `NetworkHandle`s
-    pub async fn run(mut self) {
-        use libp2p::swarm::SwarmEvent;
+    /// Only accepted messages are forwarded to the rest of the mesh; rejected
+    /// ones also count against the relaying peer's local score.
+    fn report_validation(
+        &mut self,
+        message_id: &gossipsub::MessageId,
+        propagation_source: &PeerId,
+        acceptance: gossipsub::MessageAcceptance,
+    ) {
+        if matches!(acceptance, gossipsub::MessageAcceptance::Reject) {
+            self.penalise(propagation_source, -10);
+        }
+        if let Err(e) = self.swarm.behaviour_mut().gossipsub.report_message_validation_result(
+            message_id,
+            propagation_source,
+            acceptance,
+        ) {
+            tracing::debug!(error = %e, "failed to report gossipsub validation result");
+        }
+    }
 
-        let consensus_topic_hash = self.topic.hash();
-        let tx_topic_hash = self.tx_topic.hash();
+    /// The inbound queue for a consensus topic.
+    fn consensus_queue(&self, topic: GossipTopic) -> &mpsc::Sender<NetworkMessage> {
+        match topic {
+            GossipTopic::Proposals => &self.proposal_tx,
+            GossipTopic::Commits => &self.commit_tx,
+            GossipTopic::Votes => &self.vote_tx,
+            GossipTopic::Evidence => &self.evidence_tx,
+            GossipTopic::Transactions => unreachable!("transactions have their own queue"),
+        }
+    }
 
-        loop {
-            tokio::select! {
-                // Poll the swarm for events.
-                event = self.swarm.select_next_some() => {
-                    match event {
-                        SwarmEvent::Behaviour(gossipsub::Event::Message {
-                            propagation_source,
-                            message,
-                            ..
-                        }) => {
-                            if message.topic == consensus_topic_hash {
-                                match codec::decode_consensus_message(&message.data) {
-                                    Ok(consensus_msg) => {
-                                        let net_msg = NetworkMessage {
-                                            sender: propagation_source.to_bytes(),
-                                            message: consensus_msg,
-                                        };
-                                        if self.msg_tx.send(net_msg).await.is_err() {
-                                            tracing::warn!("consensus message channel closed, stopping network loop");
-                                            return;
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::warn!(
-                                            peer = %propagation_source,
-                                            error = %e,
-                                            "failed to decode consensus message"
-                                        );
-                                        self.peer_manager
-                                            .adjust_score(&propagation_source, -10);
-                                    }
-                                }
-                            } else if message.topic == tx_topic_hash {
-                                match codec::decode_transaction(&message.data) {
-                                    Ok(tx) => {
-                                        if self.tx_msg_tx.send(tx).await.is_err() {
-                                            tracing::warn!("transaction message channel closed, stopping network loop");
-                                            return;
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::warn!(
-                                            peer = %propagation_source,
-                                            error = %e,
-                                            "failed to decode transaction message"
-                                        );
-                                        self.peer_manager
-                                            .adjust_score(&propagation_source, -10);
-                                    }
-                                }
-                            } else {
-                                tracing::debug!(
-                                    topic = ?message.topic,
-                                    "received message on unknown topic"
-                                );
-                            }
-                        }
-                        SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                            let now = std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_secs();
-                            self.peer_manager.add_peer(peer_id, None, now);
-                            tracing::info!(peer = %peer_id, "peer connected");
-                        }
-                        SwarmEvent::ConnectionClosed { peer_id, .. } => {
-                            self.peer_manager.remove_peer(&peer_id);
-                            tracing::info!(peer = %peer_id, "peer disconnected");
-                        }
-                        SwarmEvent::NewListenAddr { address, .. } => {
-                            tracing::info!(address = %address, "listening on");
+    /// Publish encoded data on a topic, counting the outcome.
+    fn publish(&mut self, topic: GossipTopic, data: Vec<u8>) {
+        match self.swarm.behaviour_mut().gossipsub.publish(topic.ident_topic(), data) {
+            Ok(_) => self.stats.record_published(topic),
+            Err(e) => {
+                self.stats.record_publish_failed(topic);
+                tracing::debug!(%topic, error = %e, "failed to publish gossipsub message");
+            }
+        }
+    }
+
+    /// Validate an inbound gossip message, report the result to gossipsub and
+    /// queue accepted messages for the handle.
+    ///
+    /// A full inbound queue drops the message locally (and counts it) without
+    /// blocking the swarm; the message is still forwarded since it is valid.
+    /// Returns `ChannelClosed` once the handle has gone away.
+    fn handle_gossip_message(
+        &mut self,
+        propagation_source: PeerId,
+        message_id: gossipsub::MessageId,
+        message: gossipsub::Message,
+    ) -> Result<(), NetworkError> {
+        let Some(&topic) = self.topics.get(&message.topic) else {
+            tracing::debug!(
+                topic = ?message.topic,
+                "received message on unknown topic"
+            );
+            self.report_validation(
+                &message_id,
+                &propagation_source,
+                gossipsub::MessageAcceptance::Ignore,
+            );
+            return Ok(());
+        };
+
+        if topic == GossipTopic::Transactions {
+            self.handle_tx_announcement(propagation_source, message_id, &message.data);
+            return Ok(());
+        }
+
+        let consensus_msg = match codec::decode_consensus_message(&message.data) {
+            Ok(consensus_msg) => consensus_msg,
+            Err(e) => {
+                tracing::warn!(
+                    peer = %propagation_source,
+                    error = %e,
+                    "failed to decode consensus message"
+                );
+                self.stats.record_rejected(topic);
+                self.report_validation(
+                    &message_id,
+                    &propagation_source,
+                    gossipsub::MessageAcceptance::Reject,
+                );
+                return Ok(());
+            }
+        };
+
+        let validation = {
+            let validators = self
+                .validators
+                .read()
+                .expect("validator registry lock poisoned");
+            codec::validate_consensus_message(
+                &consensus_msg,
+                topic,
+                message.source.as_ref(),
+                &validators,
+            )
+        };
+        if let Err(e) = validation {
+            tracing::debug!(
+                peer = %propagation_source,
+                %topic,
+                error = %e,
+                "consensus message failed validation"
+            );
+            self.stats.record_rejected(topic);
+            self.report_validation(&message_id, &propagation_source, e.acceptance());
+            return Ok(());
+        }
+        self.report_validation(
+            &message_id,
+            &propagation_source,
+            gossipsub::MessageAcceptance::Accept,
+        );
+
+        let net_msg = NetworkMessage {
+            sender: message.source.map(|p| p.to_bytes()).unwrap_or_default(),
+            relayer: propagation_source.to_bytes(),
+            message: consensus_msg,
+        };
+        match self.consensus_queue(topic).try_send(net_msg) {
+            Ok(()) => {
+                self.stats.record_received(topic);
+                Ok(())
+            }
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!(%topic, "inbound consensus queue full, dropping message");
+                self.stats.record_dropped(topic);
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => Err(NetworkError::ChannelClosed),
+        }
+    }
+
+    /// Queue a transaction we hold for the next announcement.
+    fn announce_transaction(&mut self, hash: [u8; 32], tx: Transaction) {
+        self.seen_txs.insert(hash);
+        self.tx_store.insert(hash, tx);
+        self.pending_announce.push(hash);
+        if self.pending_announce.len() >= self.tx_gossip.max_announce_batch {
+            self.flush_announcements();
+        }
+    }
+
+    /// Drop a transaction from the announcement queue and the serving store,
+    /// keeping its hash seen so it is not fetched again.
+    fn withdraw_transaction(&mut self, hash: [u8; 32]) {
+        self.seen_txs.insert(hash);
+        self.tx_store.remove(&hash);
+        self.pending_announce.retain(|h| *h != hash);
+    }
+
+    /// Publish queued transaction hashes as one announcement.
+    fn flush_announcements(&mut self) {
+        if self.pending_announce.is_empty() {
+            return;
+        }
+        let announcement = TxAnnouncement {
+            hashes: std::mem::take(&mut self.pending_announce),
+        };
+        match codec::encode_tx_announcement(&announcement) {
+            Ok(data) => self.publish(GossipTopic::Transactions, data),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to encode transaction announcement");
+            }
+        }
+    }
+
+    /// Handle a transaction announcement and pull any hashes we have not seen
+    /// from the announcing peer.
+    ///
+    /// Announcements are never forwarded by gossipsub: each node re-announces
+    /// a transaction only once it holds it, so the peer we pull from is
+    /// always one that can serve the request.
+    fn handle_tx_announcement(
+        &mut self,
+        propagation_source: PeerId,
+        message_id: gossipsub::MessageId,
+        data: &[u8],
+    ) {
+        let topic = GossipTopic::Transactions;
+        let announcement = match codec::decode_tx_announcement(data) {
+            Ok(a) if a.hashes.len() <= self.tx_gossip.max_announce_batch => a,
+            Ok(_) | Err(_) => {
+                tracing::warn!(peer = %propagation_source, "invalid transaction announcement");
+                self.stats.record_rejected(topic);
+                self.report_validation(
+                    &message_id,
+                    &propagation_source,
+                    gossipsub::MessageAcceptance::Reject,
+                );
+                return;
+            }
+        };
+        self.report_validation(
+            &message_id,
+            &propagation_source,
+            gossipsub::MessageAcceptance::Ignore,
+        );
+
+        let now = Instant::now();
+        let cost = announcement.hashes.len();
+        if !self.announce_limits.allow(&propagation_source, cost, now) {
+            tracing::debug!(peer = %propagation_source, "transaction announcement rate exceeded");
+            self.penalise(&propagation_source, -1);
+            self.stats.record_dropped(topic);
+            return;
+        }
+
+        let unknown: Vec<[u8; 32]> = announcement
+            .hashes
+            .into_iter()
+            .filter(|h| self.seen_txs.insert(*h))
+            .collect();
+        if unknown.is_empty() {
+            return;
+        }
+        let request_id = self.swarm.behaviour_mut().txs.send_request(
+            &propagation_source,
+            TxRequest {
+                hashes: unknown.clone(),
+            },
+        );
+        self.pending_tx_requests.insert(request_id, unknown);
+    }
+
+    /// Accept transactions pulled in response to our request.
+    ///
+    /// Only hashes we asked for are accepted, and each must carry a valid
+    /// signature; the response's signatures are verified together.
+    /// Hashes the peer did not deliver are forgotten so a later
+    /// announcement can be fetched from someone else.
+    fn handle_tx_response(
+        &mut self,
+        peer: PeerId,
+        requested: Vec<[u8; 32]>,
+        response: TxResponse,
+    ) -> Result<(), NetworkError> {
+        let topic = GossipTopic::Transactions;
+        let mut outstanding: std::collections::HashSet<[u8; 32]> =
+            requested.into_iter().collect();
+        let valid = self.sig_verifier.verify_all(&response.transactions);
+        for (tx, valid) in response.transactions.into_iter().zip(valid) {
+            let hash = tx.hash();
+            if !outstanding.remove(&hash) || !valid {
+                tracing::warn!(%peer, "peer served an unrequested or invalid transaction");
+                self.penalise(&peer, -10);
+                self.stats.record_rejected(topic);
+                continue;
+            }
+            self.announce_transaction(hash, tx.clone());
+            match self.tx_msg_tx.try_send(tx) {
+                Ok(()) => self.stats.record_received(topic),
+                Err(TrySendError::Full(_)) => self.stats.record_dropped(topic),
+                Err(TrySendError::Closed(_)) => return Err(NetworkError::ChannelClosed),
+            }
+        }
+        for hash in &outstanding {
+            self.seen_txs.remove(hash);
+        }
+        Ok(())
+    }
+
+    /// Handle a request-response event for the transaction pull protocol.
+    fn handle_txs_event(
+        &mut self,
+        event: request_response::Event<TxRequest, TxResponse>,
+    ) -> Result<(), NetworkError> {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => {
+                    let now = Instant::now();
+                    let response = if self.request_limits.allow(&peer, request.hashes.len(), now) {
+                        self.tx_store.serve(&request)
+                    } else {
+                        tracing::debug!(%peer, "transaction request rate exceeded");
+                        self.penalise(&peer, -1);
+                        TxResponse::default()
+                    };
+                    if self
+                        .swarm
+                        .behaviour_mut()
+                        .txs
+                        .send_response(channel, response)
+                        .is_err()
+                    {
+                        tracing::debug!(%peer, "transaction response channel closed");
+                    }
+                }
+                request_response::Message::Response {
+                    request_id,
+                    response,
+                } => {
+                    if let Some(requested) = self.pending_tx_requests.remove(&request_id) {
+                        return self.handle_tx_response(peer, requested, response);
+                    }
+                }
+            },
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                tracing::debug!(%peer, error = %error, "transaction request failed");
+                if let Some(requested) = self.pending_tx_requests.remove(&request_id) {
+                    for hash in &requested {
+                        self.seen_txs.remove(hash);
+                    }
+                }
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                tracing::debug!(%peer, error = %error, "failed to serve transactions");
+            }
+            request_response::Event::ResponseSent { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Handle a request-response event for the block transaction protocol.
+    fn handle_block_txs_event(
+        &mut self,
+        event: request_response::Event<BlockTxsRequest, BlockTxsResponse>,
+    ) {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => {
+                    // Fall back to recently gossiped transactions when the
+                    // block is not one we proposed or rebuilt.
+                    let response = {
+                        let blocks = self.blocks.read().expect("block cache lock poisoned");
+                        BlockTxsResponse {
+                            transactions: request
+                                .tx_hashes
+                                .iter()
+                                .filter_map(|h| {
+                                    blocks
+                                        .get(&request.block_hash, h)
+                                        .or_else(|| self.tx_store.get(h))
+                                        .cloned()
+                                })
+                                .collect(),
                         }
-                        _ => {}
+                    };
+                    tracing::debug!(
+                        %peer,
+                        requested = request.tx_hashes.len(),
+                        served = response.transactions.len(),
+                        "serving block transactions"
+                    );
+                    if self
+                        .swarm
+                        .behaviour_mut()
+                        .block_txs
+                        .send_response(channel, response)
+                        .is_err()
+                    {
+                        tracing::debug!(%peer, "block transaction response channel closed");
                     }
                 }
+                request_response::Message::Response {
+                    request_id,
+                    response,
+                } => {
+                    if let Some(reply) = self.pending_fetches.remove(&request_id) {
+                        let _ = reply.send(Ok(response));
+                    }
+                }
+            },
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                tracing::debug!(%peer, error = %error, "block transaction request failed");
+                if let Some(reply) = self.pending_fetches.remove(&request_id) {
+                    let _ = reply.send(Err(NetworkError::Fetch(error.to_string())));
+                }
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                tracing::debug!(%peer, error = %error, "failed to serve block transactions");
+            }
+            request_response::Event::ResponseSent { .. } => {}
+        }
+    }
+
+    /// Handle one swarm event. Returns `ChannelClosed` once the handle has
+    /// gone away.
+    fn handle_swarm_event(
+        &mut self,
+        event: SwarmEvent<NodeBehaviourEvent>,
+    ) -> Result<(), NetworkError> {
+        match event {
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                propagation_source,
+                message_id,
+                message,
+            })) => self.handle_gossip_message(propagation_source, message_id, message)?,
+            SwarmEvent::Behaviour(NodeBehaviourEvent::BlockTxs(event)) => {
+                self.handle_block_txs_event(event);
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Txs(event)) => self.handle_txs_event(event)?,
+            SwarmEvent::ConnectionEstablished {
+                peer_id, endpoint, ..
+            } => {
+                if !self.peer_manager.admits(&peer_id) {
+                    tracing::debug!(peer = %peer_id, "peer not admitted by policy, disconnecting");
+                    let _ = self.swarm.disconnect_peer_id(peer_id);
+                    return Ok(());
+                }
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let addr = endpoint.get_remote_address().to_string();
+                self.peer_manager.add_peer(peer_id, Some(addr), now);
+                tracing::info!(peer = %peer_id, "peer connected");
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+            } => {
+                self.peer_manager.remove_peer(&peer_id);
+                self.announce_limits.forget(&peer_id);
+                self.request_limits.forget(&peer_id);
+                tracing::info!(peer = %peer_id, "peer disconnected");
+            }
+            SwarmEvent::NewListenAddr { address, .. } => {
+                tracing::info!(address = %address, "listening on");
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Number of connected peers subscribed to every gossip topic.
+    fn fully_subscribed_peers(&self) -> usize {
+        self.swarm
+            .behaviour()
+            .gossipsub
+            .all_peers()
+            .filter(|(_, topics)| {
+                GossipTopic::ALL
+                    .iter()
+                    .all(|t| topics.contains(&&t.ident_topic().hash()))
+            })
+            .count()
+    }
+
+    /// Drive the swarm until at least `peers` peers are subscribed to every
+    /// gossip topic. Used to bring test networks up before spawning `run`.
+    pub(crate) async fn wait_for_peers(&mut self, peers: usize) -> Result<(), NetworkError> {
+        while self.fully_subscribed_peers() < peers {
+            let event = self.swarm.select_next_some().await;
+            self.handle_swarm_event(event)?;
+        }
+        Ok(())
+    }
+
+    /// Run the swarm event loop. Consumes self and drives libp2p networking.
+    ///
+    /// Uses a biased `tokio::select!` so that, in order of priority, it:
+    /// - Publishes outbound consensus messages from `NetworkHandle`s
+    /// - Sends transaction fetch requests from `TxFetcher`s
+    /// - Polls the swarm for incoming events (messages, connections)
+    /// - Queues outbound transactions from `NetworkHandle`s for announcement
+    /// - Withdraws transactions the node no longer wants gossiped
+    /// - Publishes queued transaction announcements on a timer
+    /// - Redials disconnected persistent peers on a timer
+    pub async fn run(mut self) {
+        let mut announce_timer = tokio::time::interval(self.tx_gossip.announce_interval);
+        announce_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut redial_timer = tokio::time::interval(self.persistent_redial_interval);
+        redial_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                biased;
 
                 // Receive outbound consensus broadcast requests from handles.
                 Some(msg) = self.broadcast_rx.recv() => {
+                    let Some(topic) = GossipTopic::for_message(&msg) else {
+                        tracing::debug!("not publishing local-only consensus message");
+                        continue;
+                    };
                     match codec::encode_consensus_message(&msg) {
-                        Ok(data) => {
-                            if let Err(e) = self.swarm
-                                .behaviour_mut()
-                                .publish(self.topic.clone(), data)
-                            {
-                                tracing::debug!(error = %e, "failed to publish consensus gossipsub message");
-                            }
-                        }
+                        Ok(data) => self.publish(topic, data),
                         Err(e) => {
                             tracing::warn!(error = %e, "failed to encode consensus message for broadcast");
                         }
                     }
                 }
 
+                // Send transaction fetch requests from fetchers.
+                Some(command) = self.fetch_rx.recv() => {
+                    let request_id = self
+                        .swarm
+                        .behaviour_mut()
+                        .block_txs
+                        .send_request(&command.peer, command.request);
+                    self.pending_fetches.insert(request_id, command.reply);
+                }
+
+                // Poll the swarm for events.
+                event = self.swarm.select_next_some() => {
+                    if let Err(e) = self.handle_swarm_event(event) {
+                        tracing::warn!(error = %e, "inbound channel closed, stopping network loop");
+                        return;
+                    }
+                }
+
                 // Receive outbound transaction broadcast requests from handles.
                 Some(tx) = self.tx_broadcast_rx.recv() => {
-                    match codec::encode_transaction(&tx) {
-                        Ok(data) => {
-                            if let Err(e) = self.swarm
-                                .behaviour_mut()
-                                .publish(self.tx_topic.clone(), data)
-                            {
-                                tracing::debug!(error = %e, "failed to publish transaction gossipsub message");
-                            }
-                        }
-                        Err(e) => {
-                            tracing::warn!(error = %e, "failed to encode transaction for broadcast");
-                        }
+                    let hash = tx.hash();
+                    if !self.seen_txs.contains(&hash) {
+                        self.announce_transaction(hash, tx);
                     }
                 }
 
+                // Withdraw transactions the node no longer wants gossiped.
+                Some(hash) = self.tx_withdraw_rx.recv() => {
+                    self.withdraw_transaction(hash);
+                }
+
+                // Announce queued transaction hashes.
+                _ = announce_timer.tick() => {
+                    self.flush_announcements();
+                }
+
+                // Reconnect to persistent peers we lost.
+                _ = redial_timer.tick() => {
+                    self.dial_persistent_peers();
+                }
+
                 else => {
                     tracing::info!("all channels closed, stopping network runner");
                     return;
@@ -289,6 +938,7 @@ impl NetworkRunner {
 pub struct ConsensusNetwork;
 
 impl ConsensusNetwork {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(
         keypair: Keypair,
         config: NetworkConfig,
@@ -298,58 +948,155 @@ impl ConsensusNetwork {
         let gossipsub_config = gossipsub::ConfigBuilder::default()
             .heartbeat_interval(config.heartbeat_interval)
             .validation_mode(gossipsub::ValidationMode::Strict)
+            .validate_messages()
             .build()
             .map_err(|e| NetworkError::Gossipsub(e.to_string()))?;
 
-        let gossipsub_behaviour = gossipsub::Behaviour::new(
+        let mut gossipsub_behaviour = gossipsub::Behaviour::new(
             MessageAuthenticity::Signed(keypair.clone()),
             gossipsub_config,
         )
         .map_err(|e| NetworkError::Gossipsub(e.to_string()))?;
 
-        let swarm = SwarmBuilder::with_existing_identity(keypair)
-            .with_tokio()
-            .with_tcp(
-                tcp::Config::default(),
-                noise::Config::new,
-                yamux::Config::default,
-            )
-            .map_err(|e| NetworkError::Transport(e.to_string()))?
-            .with_behaviour(|_| Ok(gossipsub_behaviour))
-            .map_err(|e| NetworkError::Transport(e.to_string()))?
-            .with_swarm_config(|cfg| cfg.with_idle_connection_timeout(Duration::from_secs(60)))
-            .build();
-
-        let topic = IdentTopic::new(CONSENSUS_TOPIC);
-        let tx_topic = IdentTopic::new(TRANSACTION_TOPIC);
-        let peer_manager = PeerManager::new(config.peer_ban_threshold);
-
-        // Channel for inbound consensus messages: runner -> handle
-        let (msg_tx, msg_rx) = mpsc::channel(256);
+        // Per-topic score weights so misbehaviour on consensus topics costs
+        // a peer far more than misbehaviour on transaction gossip.
+        let mut topics = HashMap::new();
+        let mut score_params = gossipsub::PeerScoreParams::default();
+        for topic in GossipTopic::ALL {
+            let hash = topic.ident_topic().hash();
+            score_params.topics.insert(hash.clone(), topic.score_params());
+            topics.insert(hash, topic);
+        }
+        gossipsub_behaviour
+            .with_peer_score(score_params, gossipsub::PeerScoreThresholds::default())
+            .map_err(NetworkError::Gossipsub)?;
+
+        let block_txs = request_response::cbor::Behaviour::new(
+            [(BLOCK_TXS_PROTOCOL, ProtocolSupport::Full)],
+            request_response::Config::default().with_request_timeout(Duration::from_secs(2)),
+        );
+        let txs = request_response::cbor::Behaviour::new(
+            [(TX_PROTOCOL, ProtocolSupport::Full)],
+            request_response::Config::default().with_request_timeout(Duration::from_secs(5)),
+        );
+        let behaviour = NodeBehaviour {
+            gossipsub: gossipsub_behaviour,
+            block_txs,
+            txs,
+        };
+
+        let idle_timeout = Duration::from_secs(60);
+        let swarm = match config.transport {
+            TransportKind::Tcp => {
+                let builder = SwarmBuilder::with_existing_identity(keypair)
+                    .with_tokio()
+                    .with_tcp(
+                        tcp::Config::default(),
+                        noise::Config::new,
+                        yamux::Config::default,
+                    )
+                    .map_err(|e| NetworkError::Transport(e.to_string()))?;
+                #[cfg(feature = "quic")]
+                let builder = builder.with_quic();
+                builder
+                    .with_behaviour(|_| Ok(behaviour))
+                    .map_err(|e| NetworkError::Transport(e.to_string()))?
+                    .with_swarm_config(|cfg| cfg.with_idle_connection_timeout(idle_timeout))
+                    .build()
+            }
+            TransportKind::Memory => SwarmBuilder::with_existing_identity(keypair)
+                .with_tokio()
+                .with_other_transport(|key| {
+                    let noise = noise::Config::new(key)?;
+                    Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+                        MemoryTransport::default()
+                            .upgrade(upgrade::Version::V1)
+                            .authenticate(noise)
+                            .multiplex(yamux::Config::default()),
+                    )
+                })
+                .map_err(|e| NetworkError::Transport(e.to_string()))?
+                .with_behaviour(|_| Ok(behaviour))
+                .map_err(|e| NetworkError::Transport(e.to_string()))?
+                .with_swarm_config(|cfg| cfg.with_idle_connection_timeout(idle_timeout))
+                .build(),
+        };
+
+        for addr in &config.peer_policy.persistent_peers {
+            check_transport_support(addr)?;
+        }
+        let peer_manager = PeerManager::with_policy(config.peer_ban_threshold, config.peer_policy)
+            .map_err(|addr| {
+                NetworkError::Transport(format!("persistent peer {addr} has no /p2p/<peer id>"))
+            })?;
+        let validators = Arc::new(RwLock::new(ValidatorRegistry::default()));
+        let blocks = Arc::new(RwLock::new(BlockCache::default()));
+        let stats = Arc::new(NetworkStats::default());
+
+        // Inbound queues: runner -> handle, sized per topic
+        let (proposal_tx, proposal_rx) =
+            mpsc::channel(GossipTopic::Proposals.params().queue_capacity);
+        let (commit_tx, commit_rx) = mpsc::channel(GossipTopic::Commits.params().queue_capacity);
+        let (vote_tx, vote_rx) = mpsc::channel(GossipTopic::Votes.params().queue_capacity);
+        let (evidence_tx, evidence_rx) =
+            mpsc::channel(GossipTopic::Evidence.params().queue_capacity);
+        let (tx_msg_tx, tx_msg_rx) =
+            mpsc::channel(GossipTopic::Transactions.params().queue_capacity);
         // Channel for outbound consensus broadcasts: handle -> runner
         let (broadcast_tx, broadcast_rx) = mpsc::channel(256);
-        // Channel for inbound transactions: runner -> handle
-        let (tx_msg_tx, tx_msg_rx) = mpsc::channel(256);
         // Channel for outbound transaction broadcasts: handle -> runner
         let (tx_broadcast_tx, tx_broadcast_rx) = mpsc::channel(256);
+        // Channel for transaction withdrawals: handle -> runner
+        let (tx_withdraw_tx, tx_withdraw_rx) = mpsc::channel(256);
+        // Channel for transaction fetch requests: fetcher -> runner
+        let (fetch_tx, fetch_rx) = mpsc::channel(64);
 
         let handle = NetworkHandle {
             broadcast_tx,
-            msg_rx,
+            proposal_rx,
+            commit_rx,
+            vote_rx,
+            evidence_rx,
             tx_broadcast_tx,
+            tx_withdraw_tx,
             tx_msg_rx,
+            validators: validators.clone(),
+            blocks: blocks.clone(),
+            fetch_tx,
+            stats: stats.clone(),
             local_peer_id,
         };
 
+        let tx_gossip = config.tx_gossip;
         let runner = NetworkRunner {
             swarm,
-            topic,
-            tx_topic,
+            topics,
             peer_manager,
+            validators,
+            blocks,
+            stats,
+            fetch_rx,
+            pending_fetches: HashMap::new(),
             broadcast_rx,
-            msg_tx,
+            proposal_tx,
+            commit_tx,
+            vote_tx,
+            evidence_tx,
             tx_broadcast_rx,
+            tx_withdraw_rx,
             tx_msg_tx,
+            seen_txs: SeenCache::new(tx_gossip.seen_cache_size),
+            tx_store: TxStore::new(tx_gossip.store_size),
+            pending_announce: Vec::new(),
+            pending_tx_requests: HashMap::new(),
+            announce_limits: PeerRateLimiter::new(
+                tx_gossip.announce_rate,
+                tx_gossip.announce_burst,
+            ),
+            request_limits: PeerRateLimiter::new(tx_gossip.request_rate, tx_gossip.request_burst),
+            tx_gossip,
+            persistent_redial_interval: config.persistent_redial_interval,
+            sig_verifier: config.sig_verifier,
         };
 
         Ok((handle, runner))
@@ -359,6 +1106,7 @@ impl ConsensusNetwork {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use libp2p::futures::future::join_all;
     use trv1_bft::{BlockHash, Height};
 
     #[test]
@@ -379,12 +1127,19 @@ mod tests {
     }
 
     #[test]
-    fn test_consensus_topic_constant() {
-        assert_eq!(CONSENSUS_TOPIC, "trv1-consensus");
+    fn test_consensus_topic_constants() {
+        use crate::topics::{COMMIT_TOPIC, EVIDENCE_TOPIC, PROPOSAL_TOPIC, VOTE_TOPIC};
+
+        assert_eq!(PROPOSAL_TOPIC, "trv1-proposals");
+        assert_eq!(VOTE_TOPIC, "trv1-votes");
+        assert_eq!(COMMIT_TOPIC, "trv1-commits");
+        assert_eq!(EVIDENCE_TOPIC, "trv1-evidence");
     }
 
     #[test]
     fn test_transaction_topic_constant() {
+        use crate::topics::TRANSACTION_TOPIC;
+
         assert_eq!(TRANSACTION_TOPIC, "trv1-transactions");
     }
 
@@ -445,6 +1200,9 @@ mod tests {
             nonce: 42,
             signature: vec![0u8; 64],
             data: vec![10, 20, 30],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         // Send a transaction through the handle
@@ -458,4 +1216,341 @@ mod tests {
         assert_eq!(received.nonce, 42);
         assert_eq!(received.data, vec![10, 20, 30]);
     }
+
+    #[test]
+    fn test_set_validators_shared_with_runner() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let keypair = Keypair::generate_ed25519();
+        let (handle, runner) = ConsensusNetwork::new(keypair, NetworkConfig::default()).unwrap();
+        assert!(runner.validators.read().unwrap().is_empty());
+
+        let validator = ValidatorId(SigningKey::generate(&mut OsRng).verifying_key());
+        handle.set_validators(std::slice::from_ref(&validator));
+
+        let registry = runner.validators.read().unwrap();
+        assert_eq!(registry.len(), 1);
+        assert!(registry.contains(&validator));
+    }
+
+    #[tokio::test]
+    async fn test_next_message_prioritises_proposals_over_votes() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_bft::{Proposal, Round, Vote, VoteType};
+
+        let keypair = Keypair::generate_ed25519();
+        let (mut handle, runner) = ConsensusNetwork::new(keypair, NetworkConfig::default()).unwrap();
+        let key = SigningKey::generate(&mut OsRng);
+
+        let vote = Vote::new(VoteType::Prevote, Height(1), Round(0), None, &key);
+        let proposal = Proposal::new(Height(1), Round(0), BlockHash([0x01; 32]), None, &key);
+        let wrap = |message| NetworkMessage {
+            sender: vec![],
+            relayer: vec![],
+            message,
+        };
+
+        // The vote is queued first, but the proposal must be delivered first.
+        runner.vote_tx.try_send(wrap(ConsensusMessage::CastVote(vote))).unwrap();
+        runner
+            .proposal_tx
+            .try_send(wrap(ConsensusMessage::ProposeBlock { proposal, block: None }))
+            .unwrap();
+
+        let first = handle.next_message().await.unwrap();
+        assert!(matches!(first.message, ConsensusMessage::ProposeBlock { .. }));
+        let second = handle.next_message().await.unwrap();
+        assert!(matches!(second.message, ConsensusMessage::CastVote(_)));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_proposal_sends_compact_and_caches_block() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_bft::block::BlockHeader;
+        use trv1_bft::{Proposal, Round};
+
+        let keypair = Keypair::generate_ed25519();
+        let (handle, mut runner) = ConsensusNetwork::new(keypair, NetworkConfig::default()).unwrap();
+        let key = SigningKey::generate(&mut OsRng);
+
+        let tx = Transaction {
+            from: [1u8; 32],
+            to: [2u8; 32],
+            amount: 7,
+            nonce: 0,
+            signature: vec![0u8; 64],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+        let block = Block {
+            header: BlockHeader {
+                height: Height(4),
+                timestamp: 0,
+                parent_hash: BlockHash::default(),
+                proposer: ValidatorId(key.verifying_key()),
+                state_root: [0u8; 32],
+                tx_merkle_root: Block::compute_tx_merkle_root(std::slice::from_ref(&tx)),
+            },
+            transactions: vec![tx.clone()],
+        };
+        let proposal = Proposal::new(Height(4), Round(0), block.hash(), None, &key);
+        handle.broadcast_proposal(&proposal, &block).await.unwrap();
+
+        match runner.broadcast_rx.recv().await.unwrap() {
+            ConsensusMessage::ProposeCompact { block: compact, .. } => {
+                assert_eq!(compact.hash(), block.hash());
+                assert_eq!(compact.tx_hashes, vec![tx.hash()]);
+            }
+            _ => panic!("expected ProposeCompact"),
+        }
+
+        let response = runner.blocks.read().unwrap().serve(&BlockTxsRequest {
+            block_hash: block.hash(),
+            tx_hashes: vec![tx.hash()],
+        });
+        assert_eq!(response.transactions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tx_response_accepts_only_requested_valid_transactions() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let keypair = Keypair::generate_ed25519();
+        let (mut handle, mut runner) =
+            ConsensusNetwork::new(keypair, NetworkConfig::default()).unwrap();
+        let key = SigningKey::generate(&mut OsRng);
+        let signed = |nonce| {
+            let mut tx = Transaction {
+                from: key.verifying_key().to_bytes(),
+                to: [2u8; 32],
+                amount: 1,
+                nonce,
+                signature: vec![],
+                data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
+            };
+            tx.sign(&key);
+            tx
+        };
+        let wanted = signed(0);
+        let unrequested = signed(1);
+        let never_sent = [0x77; 32];
+
+        for hash in [wanted.hash(), never_sent] {
+            runner.seen_txs.insert(hash);
+        }
+        let peer = PeerId::random();
+        let response = TxResponse {
+            transactions: vec![wanted.clone(), unrequested],
+        };
+        runner
+            .handle_tx_response(peer, vec![wanted.hash(), never_sent], response)
+            .unwrap();
+
+        let delivered = handle.next_transaction().await.unwrap();
+        assert_eq!(delivered.hash(), wanted.hash());
+        assert_eq!(runner.pending_announce, vec![wanted.hash()]);
+        assert!(runner.tx_store.get(&wanted.hash()).is_some());
+        assert!(!runner.seen_txs.contains(&never_sent), "undelivered hash is forgotten");
+
+        let stats = runner.stats.topic(GossipTopic::Transactions);
+        assert_eq!(stats.received, 1);
+        assert_eq!(stats.rejected, 1);
+    }
+
+    #[test]
+    fn test_announcements_flush_at_batch_size() {
+        let keypair = Keypair::generate_ed25519();
+        let mut config = NetworkConfig::default();
+        config.tx_gossip.max_announce_batch = 2;
+        let (_handle, mut runner) = ConsensusNetwork::new(keypair, config).unwrap();
+
+        let tx = |nonce| Transaction {
+            from: [1u8; 32],
+            to: [2u8; 32],
+            amount: 1,
+            nonce,
+            signature: vec![0u8; 64],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+        runner.announce_transaction(tx(0).hash(), tx(0));
+        assert_eq!(runner.pending_announce.len(), 1);
+
+        // The second hash fills the batch and triggers a publish attempt,
+        // which fails without peers but still empties the queue.
+        runner.announce_transaction(tx(1).hash(), tx(1));
+        assert!(runner.pending_announce.is_empty());
+        assert_eq!(runner.stats.topic(GossipTopic::Transactions).publish_failed, 1);
+        assert_eq!(runner.tx_store.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_withdrawn_transaction_is_not_announced_or_served() {
+        let keypair = Keypair::generate_ed25519();
+        let (handle, mut runner) =
+            ConsensusNetwork::new(keypair, NetworkConfig::default()).unwrap();
+
+        let tx = |nonce| Transaction {
+            from: [1u8; 32],
+            to: [2u8; 32],
+            amount: 1,
+            nonce,
+            signature: vec![0u8; 64],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+        runner.announce_transaction(tx(0).hash(), tx(0));
+        runner.announce_transaction(tx(1).hash(), tx(1));
+
+        handle.withdraw_transaction(tx(0).hash()).await.unwrap();
+        let hash = runner.tx_withdraw_rx.recv().await.unwrap();
+        runner.withdraw_transaction(hash);
+
+        assert_eq!(runner.pending_announce, vec![tx(1).hash()]);
+        assert!(runner.tx_store.get(&tx(0).hash()).is_none());
+        assert!(runner.tx_store.get(&tx(1).hash()).is_some());
+        // Still seen, so a peer announcing it again does not trigger a pull.
+        assert!(runner.seen_txs.contains(&tx(0).hash()));
+    }
+
+    #[tokio::test]
+    async fn test_tx_broadcast_backpressure_counts_drops() {
+        let keypair = Keypair::generate_ed25519();
+        let (handle, _runner) = ConsensusNetwork::new(keypair, NetworkConfig::default()).unwrap();
+
+        let tx = Transaction {
+            from: [1u8; 32],
+            to: [2u8; 32],
+            amount: 1,
+            nonce: 0,
+            signature: vec![0u8; 64],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+
+        // Nothing drains the outbound queue, so it eventually fills up.
+        let mut result = Ok(());
+        for _ in 0..=256 {
+            result = handle.broadcast_transaction(&tx).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(
+            result,
+            Err(NetworkError::QueueFull(GossipTopic::Transactions))
+        ));
+        assert_eq!(handle.topic_stats(GossipTopic::Transactions).dropped, 1);
+    }
+
+    #[test]
+    fn test_persistent_peer_without_id_rejected() {
+        let config = NetworkConfig {
+            peer_policy: PeerPolicy {
+                persistent_peers: vec!["/ip4/10.0.0.1/tcp/30333".parse().unwrap()],
+                ..PeerPolicy::default()
+            },
+            ..NetworkConfig::default()
+        };
+        let result = ConsensusNetwork::new(Keypair::generate_ed25519(), config);
+        assert!(matches!(result, Err(NetworkError::Transport(_))));
+    }
+
+    #[tokio::test]
+    async fn test_allow_list_only_dials_sentry_and_refuses_strangers() {
+        use crate::testing::memory_config;
+        use libp2p::multiaddr::Protocol;
+
+        let start = |config: NetworkConfig| {
+            let keypair = Keypair::generate_ed25519();
+            let peer_id = PeerId::from(keypair.public());
+            let address = config.listen_address.clone();
+            let (_handle, mut runner) = ConsensusNetwork::new(keypair, config).unwrap();
+            runner.start(address.clone()).unwrap();
+            (runner, peer_id, address)
+        };
+
+        let (mut sentry, sentry_id, sentry_addr) = start(memory_config());
+        let mut config = memory_config();
+        config.peer_policy = PeerPolicy {
+            persistent_peers: vec![sentry_addr.with(Protocol::P2p(sentry_id))],
+            allow_list_only: true,
+            ..PeerPolicy::default()
+        };
+        let (mut validator, _, validator_addr) = start(config);
+        let (mut stranger, stranger_id, _) = start(memory_config());
+        stranger.dial(validator_addr).unwrap();
+
+        // Drive all three swarms for a while; the target is never reached.
+        let drive = join_all([
+            sentry.wait_for_peers(usize::MAX),
+            validator.wait_for_peers(usize::MAX),
+            stranger.wait_for_peers(usize::MAX),
+        ]);
+        let _ = tokio::time::timeout(Duration::from_secs(1), drive).await;
+
+        assert!(validator.swarm.is_connected(&sentry_id));
+        assert!(!validator.swarm.is_connected(&stranger_id));
+        assert_eq!(validator.peer_manager.peer_count(), 1);
+    }
+
+    #[test]
+    fn test_is_quic_address() {
+        assert!(is_quic_address(&"/ip4/127.0.0.1/udp/30333/quic-v1".parse().unwrap()));
+        assert!(!is_quic_address(&"/ip4/127.0.0.1/tcp/30333".parse().unwrap()));
+    }
+
+    #[cfg(not(feature = "quic"))]
+    #[test]
+    fn test_quic_address_needs_feature() {
+        let (_handle, mut runner) =
+            ConsensusNetwork::new(Keypair::generate_ed25519(), NetworkConfig::default()).unwrap();
+        let addr: Multiaddr = "/ip4/127.0.0.1/udp/0/quic-v1".parse().unwrap();
+        assert!(matches!(runner.listen(addr.clone()), Err(NetworkError::Transport(_))));
+        assert!(matches!(runner.dial(addr), Err(NetworkError::Transport(_))));
+    }
+
+    #[cfg(feature = "quic")]
+    #[tokio::test]
+    async fn test_nodes_connect_over_quic() {
+        let (_h1, mut listener) =
+            ConsensusNetwork::new(Keypair::generate_ed25519(), NetworkConfig::default()).unwrap();
+        listener.start("/ip4/127.0.0.1/tcp/0".parse().unwrap()).unwrap();
+        listener.listen("/ip4/127.0.0.1/udp/0/quic-v1".parse().unwrap()).unwrap();
+
+        // Wait until the OS has assigned the QUIC port.
+        let quic_addr = loop {
+            if let Some(addr) = listener.swarm.listeners().find(|a| is_quic_address(a)) {
+                break addr.clone();
+            }
+            let event = listener.swarm.select_next_some().await;
+            listener.handle_swarm_event(event).unwrap();
+        };
+
+        let (_h2, mut dialer) =
+            ConsensusNetwork::new(Keypair::generate_ed25519(), NetworkConfig::default()).unwrap();
+        dialer.start("/ip4/127.0.0.1/tcp/0".parse().unwrap()).unwrap();
+        dialer.dial(quic_addr).unwrap();
+
+        let connected = join_all([listener.wait_for_peers(1), dialer.wait_for_peers(1)]);
+        let results = tokio::time::timeout(Duration::from_secs(10), connected)
+            .await
+            .expect("nodes should connect over QUIC");
+        assert!(results.iter().all(|r| r.is_ok()));
+    }
 }
@@ -1,5 +1,6 @@
-use libp2p::PeerId;
-use std::collections::HashMap;
+use libp2p::{identity, multiaddr::Protocol, Multiaddr, PeerId};
+use std::collections::{HashMap, HashSet};
+use trv1_bft::ValidatorId;
 
 /// Information about a connected peer.
 #[derive(Debug, Clone)]
@@ -11,19 +12,137 @@ pub struct PeerInfo {
     pub score: i64,
 }
 
+/// Which peers a node connects to and shares, for sentry-node setups.
+///
+/// A validator hiding behind sentries typically sets its sentries as
+/// persistent peers and enables `allow_list_only`. Each sentry lists the
+/// validator as persistent, private and unconditional.
+#[derive(Debug, Clone)]
+pub struct PeerPolicy {
+    /// Peers we always stay connected to, redialing when the connection
+    /// drops. Each address must end in `/p2p/<peer id>`.
+    pub persistent_peers: Vec<Multiaddr>,
+    /// Peers whose identity is never shared with other peers.
+    pub private_peer_ids: HashSet<PeerId>,
+    /// Peers exempt from the `max_peers` limit and from score bans.
+    pub unconditional_peer_ids: HashSet<PeerId>,
+    /// Only accept connections from persistent and unconditional peers.
+    pub allow_list_only: bool,
+    /// Maximum number of connected peers, not counting unconditional ones.
+    pub max_peers: usize,
+}
+
+impl Default for PeerPolicy {
+    fn default() -> Self {
+        Self {
+            persistent_peers: Vec::new(),
+            private_peer_ids: HashSet::new(),
+            unconditional_peer_ids: HashSet::new(),
+            allow_list_only: false,
+            max_peers: 50,
+        }
+    }
+}
+
+/// The PeerId in a multiaddr's trailing `/p2p/<peer id>` component.
+pub fn peer_id_of_addr(addr: &Multiaddr) -> Option<PeerId> {
+    match addr.iter().last() {
+        Some(Protocol::P2p(peer_id)) => Some(peer_id),
+        _ => None,
+    }
+}
+
 /// Tracks connected peers and their reputation.
 pub struct PeerManager {
     peers: HashMap<PeerId, PeerInfo>,
     /// Score below which a peer is considered banned.
     ban_threshold: i64,
+    /// Persistent peers and the address to redial them on.
+    persistent: HashMap<PeerId, Multiaddr>,
+    private: HashSet<PeerId>,
+    unconditional: HashSet<PeerId>,
+    allow_list_only: bool,
+    max_peers: usize,
 }
 
 impl PeerManager {
     pub fn new(ban_threshold: i64) -> Self {
-        Self {
+        Self::with_policy(ban_threshold, PeerPolicy::default())
+            .expect("the default policy has no persistent peers")
+    }
+
+    /// Create a peer manager enforcing `policy`.
+    ///
+    /// Returns the offending address if a persistent peer has no
+    /// `/p2p/<peer id>` suffix.
+    pub fn with_policy(ban_threshold: i64, policy: PeerPolicy) -> Result<Self, Multiaddr> {
+        let mut persistent = HashMap::new();
+        for addr in policy.persistent_peers {
+            let peer_id = peer_id_of_addr(&addr).ok_or_else(|| addr.clone())?;
+            persistent.insert(peer_id, addr);
+        }
+        Ok(Self {
             peers: HashMap::new(),
             ban_threshold,
+            persistent,
+            private: policy.private_peer_ids,
+            unconditional: policy.unconditional_peer_ids,
+            allow_list_only: policy.allow_list_only,
+            max_peers: policy.max_peers,
+        })
+    }
+
+    /// Whether a newly connected peer may stay connected.
+    ///
+    /// Unconditional peers are always admitted. Otherwise the peer must be
+    /// on the allow-list when `allow_list_only` is set, must not be banned,
+    /// and must fit under `max_peers`.
+    pub fn admits(&self, peer_id: &PeerId) -> bool {
+        if self.is_unconditional(peer_id) {
+            return true;
+        }
+        if self.allow_list_only && !self.is_persistent(peer_id) {
+            return false;
+        }
+        if self.is_banned(peer_id) {
+            return false;
+        }
+        if self.peers.contains_key(peer_id) {
+            return true;
         }
+        let limited = self
+            .peers
+            .keys()
+            .filter(|p| !self.is_unconditional(p))
+            .count();
+        limited < self.max_peers
+    }
+
+    pub fn is_persistent(&self, peer_id: &PeerId) -> bool {
+        self.persistent.contains_key(peer_id)
+    }
+
+    pub fn is_private(&self, peer_id: &PeerId) -> bool {
+        self.private.contains(peer_id)
+    }
+
+    pub fn is_unconditional(&self, peer_id: &PeerId) -> bool {
+        self.unconditional.contains(peer_id)
+    }
+
+    /// Persistent peers and their dial addresses.
+    pub fn persistent_peers(&self) -> impl Iterator<Item = (&PeerId, &Multiaddr)> {
+        self.persistent.iter()
+    }
+
+    /// Connected peers that may be shared with other peers, i.e. all
+    /// connected peers except private ones.
+    pub fn advertisable_peers(&self) -> Vec<PeerId> {
+        self.peers
+            .keys()
+            .filter(|p| !self.is_private(p))
+            .copied()
+            .collect()
     }
 
     /// Register a new peer or update an existing one's last_seen.
@@ -58,10 +177,13 @@ impl PeerManager {
     }
 
     /// Check if a peer is banned (score below threshold).
+    /// Unconditional peers are never banned.
     pub fn is_banned(&self, peer_id: &PeerId) -> bool {
-        self.peers
-            .get(peer_id)
-            .map_or(false, |info| info.score < self.ban_threshold)
+        !self.is_unconditional(peer_id)
+            && self
+                .peers
+                .get(peer_id)
+                .is_some_and(|info| info.score < self.ban_threshold)
     }
 
     /// Get info for a specific peer.
@@ -94,6 +216,60 @@ impl PeerManager {
     }
 }
 
+/// Derive the libp2p PeerId a validator uses on the network.
+///
+/// Validators build their libp2p identity from their ed25519 consensus key,
+/// so the PeerId is fully determined by the validator's public key.
+pub fn validator_peer_id(validator: &ValidatorId) -> PeerId {
+    let public = identity::ed25519::PublicKey::try_from_bytes(validator.as_bytes())
+        .expect("a valid ed25519 verifying key is a valid libp2p ed25519 key");
+    identity::PublicKey::from(public).to_peer_id()
+}
+
+/// The set of validators whose consensus messages are accepted from gossip,
+/// indexed both by public key and by their derived PeerId.
+#[derive(Debug, Clone, Default)]
+pub struct ValidatorRegistry {
+    by_key: HashMap<[u8; 32], PeerId>,
+    by_peer: HashMap<PeerId, ValidatorId>,
+}
+
+impl ValidatorRegistry {
+    pub fn new(validators: &[ValidatorId]) -> Self {
+        let mut registry = Self::default();
+        for validator in validators {
+            let peer_id = validator_peer_id(validator);
+            registry.by_key.insert(*validator.as_bytes(), peer_id);
+            registry.by_peer.insert(peer_id, validator.clone());
+        }
+        registry
+    }
+
+    /// Whether the given validator is part of the registered set.
+    pub fn contains(&self, validator: &ValidatorId) -> bool {
+        self.by_key.contains_key(validator.as_bytes())
+    }
+
+    /// The PeerId a registered validator publishes from.
+    pub fn peer_id_of(&self, validator: &ValidatorId) -> Option<PeerId> {
+        self.by_key.get(validator.as_bytes()).copied()
+    }
+
+    /// The validator behind a PeerId, if that peer is a registered validator.
+    pub fn validator_of(&self, peer_id: &PeerId) -> Option<&ValidatorId> {
+        self.by_peer.get(peer_id)
+    }
+
+    /// Number of registered validators.
+    pub fn len(&self) -> usize {
+        self.by_key.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_key.is_empty()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,4 +370,115 @@ mod tests {
         assert!(peers.contains(&p1));
         assert!(peers.contains(&p2));
     }
+
+    fn addr_of(peer: PeerId) -> Multiaddr {
+        format!("/ip4/10.0.0.1/tcp/30333/p2p/{peer}").parse().unwrap()
+    }
+
+    #[test]
+    fn test_policy_rejects_persistent_peer_without_id() {
+        let addr: Multiaddr = "/ip4/10.0.0.1/tcp/30333".parse().unwrap();
+        let policy = PeerPolicy {
+            persistent_peers: vec![addr.clone()],
+            ..PeerPolicy::default()
+        };
+        assert_eq!(PeerManager::with_policy(-100, policy).err(), Some(addr));
+    }
+
+    #[test]
+    fn test_allow_list_only_admits_persistent_and_unconditional() {
+        let sentry = random_peer_id();
+        let friend = random_peer_id();
+        let stranger = random_peer_id();
+        let policy = PeerPolicy {
+            persistent_peers: vec![addr_of(sentry)],
+            unconditional_peer_ids: HashSet::from([friend]),
+            allow_list_only: true,
+            ..PeerPolicy::default()
+        };
+        let pm = PeerManager::with_policy(-100, policy).unwrap();
+        assert!(pm.admits(&sentry));
+        assert!(pm.admits(&friend));
+        assert!(!pm.admits(&stranger));
+        assert_eq!(pm.persistent_peers().count(), 1);
+    }
+
+    #[test]
+    fn test_max_peers_exempts_unconditional() {
+        let vip = random_peer_id();
+        let policy = PeerPolicy {
+            unconditional_peer_ids: HashSet::from([vip]),
+            max_peers: 1,
+            ..PeerPolicy::default()
+        };
+        let mut pm = PeerManager::with_policy(-100, policy).unwrap();
+        let first = random_peer_id();
+        assert!(pm.admits(&first));
+        pm.add_peer(first, None, 1);
+        pm.add_peer(vip, None, 1);
+
+        assert!(!pm.admits(&random_peer_id()), "limit reached");
+        assert!(pm.admits(&first), "already connected peers stay admitted");
+        assert!(pm.admits(&vip));
+    }
+
+    #[test]
+    fn test_unconditional_peer_never_banned() {
+        let vip = random_peer_id();
+        let policy = PeerPolicy {
+            unconditional_peer_ids: HashSet::from([vip]),
+            ..PeerPolicy::default()
+        };
+        let mut pm = PeerManager::with_policy(-50, policy).unwrap();
+        pm.add_peer(vip, None, 1);
+        pm.adjust_score(&vip, -1000);
+        assert!(!pm.is_banned(&vip));
+        assert!(pm.admits(&vip));
+    }
+
+    #[test]
+    fn test_private_peers_not_advertised() {
+        let validator = random_peer_id();
+        let public = random_peer_id();
+        let policy = PeerPolicy {
+            private_peer_ids: HashSet::from([validator]),
+            ..PeerPolicy::default()
+        };
+        let mut pm = PeerManager::with_policy(-100, policy).unwrap();
+        pm.add_peer(validator, None, 1);
+        pm.add_peer(public, None, 1);
+        assert_eq!(pm.advertisable_peers(), vec![public]);
+    }
+
+    #[test]
+    fn test_validator_peer_id_matches_libp2p_identity() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let key = SigningKey::generate(&mut OsRng);
+        let mut secret = key.to_bytes().to_vec();
+        let keypair = identity::Keypair::ed25519_from_bytes(&mut secret).unwrap();
+
+        let validator = ValidatorId(key.verifying_key());
+        assert_eq!(validator_peer_id(&validator), PeerId::from(keypair.public()));
+    }
+
+    #[test]
+    fn test_validator_registry_lookups() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let v1 = ValidatorId(SigningKey::generate(&mut OsRng).verifying_key());
+        let v2 = ValidatorId(SigningKey::generate(&mut OsRng).verifying_key());
+        let outsider = ValidatorId(SigningKey::generate(&mut OsRng).verifying_key());
+
+        let registry = ValidatorRegistry::new(&[v1.clone(), v2.clone()]);
+        assert_eq!(registry.len(), 2);
+        assert!(registry.contains(&v1));
+        assert!(!registry.contains(&outsider));
+
+        let peer = registry.peer_id_of(&v2).unwrap();
+        assert_eq!(registry.validator_of(&peer), Some(&v2));
+        assert!(registry.validator_of(&validator_peer_id(&outsider)).is_none());
+    }
 }
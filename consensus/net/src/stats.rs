@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::topics::GossipTopic;
+
+/// Live counters for a single gossip topic.
+#[derive(Debug, Default)]
+struct TopicCounters {
+    received: AtomicU64,
+    dropped: AtomicU64,
+    rejected: AtomicU64,
+    published: AtomicU64,
+    publish_failed: AtomicU64,
+}
+
+/// Point-in-time copy of a topic's traffic counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TopicStats {
+    /// Valid messages delivered to the local node.
+    pub received: u64,
+    /// Messages discarded because a local queue was full.
+    pub dropped: u64,
+    /// Inbound messages that failed decoding or validation.
+    pub rejected: u64,
+    /// Messages successfully handed to gossipsub for publishing.
+    pub published: u64,
+    /// Publish attempts gossipsub refused (e.g. no peers on the topic).
+    pub publish_failed: u64,
+}
+
+/// Per-topic traffic counters, shared between the runner and its handle.
+#[derive(Debug, Default)]
+pub struct NetworkStats {
+    topics: [TopicCounters; GossipTopic::ALL.len()],
+}
+
+impl NetworkStats {
+    fn counters(&self, topic: GossipTopic) -> &TopicCounters {
+        &self.topics[topic as usize]
+    }
+
+    pub(crate) fn record_received(&self, topic: GossipTopic) {
+        self.counters(topic).received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dropped(&self, topic: GossipTopic) {
+        self.counters(topic).dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_rejected(&self, topic: GossipTopic) {
+        self.counters(topic).rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_published(&self, topic: GossipTopic) {
+        self.counters(topic).published.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_publish_failed(&self, topic: GossipTopic) {
+        self.counters(topic).publish_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Snapshot the counters for one topic.
+    pub fn topic(&self, topic: GossipTopic) -> TopicStats {
+        let c = self.counters(topic);
+        TopicStats {
+            received: c.received.load(Ordering::Relaxed),
+            dropped: c.dropped.load(Ordering::Relaxed),
+            rejected: c.rejected.load(Ordering::Relaxed),
+            published: c.published.load(Ordering::Relaxed),
+            publish_failed: c.publish_failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_are_per_topic() {
+        let stats = NetworkStats::default();
+        stats.record_received(GossipTopic::Votes);
+        stats.record_received(GossipTopic::Votes);
+        stats.record_dropped(GossipTopic::Transactions);
+        stats.record_publish_failed(GossipTopic::Proposals);
+
+        assert_eq!(stats.topic(GossipTopic::Votes).received, 2);
+        assert_eq!(stats.topic(GossipTopic::Transactions).dropped, 1);
+        assert_eq!(stats.topic(GossipTopic::Proposals).publish_failed, 1);
+        assert_eq!(stats.topic(GossipTopic::Evidence), TopicStats::default());
+    }
+}
@@ -0,0 +1,133 @@
+//! Helpers for running several network nodes inside one process.
+//!
+//! Nodes use the in-memory transport, so tests need no free ports and are
+//! not affected by the host's network.
+
+use libp2p::futures::future::join_all;
+use libp2p::identity::Keypair;
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::network::{ConsensusNetwork, NetworkConfig, NetworkError, NetworkHandle, TransportKind};
+
+/// How long `connected_nodes` waits for every node to see all the others.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static NEXT_MEMORY_PORT: AtomicU64 = AtomicU64::new(1);
+
+/// A fresh `/memory/<n>` address, unique within the process.
+pub fn memory_address() -> Multiaddr {
+    Multiaddr::empty().with(Protocol::Memory(NEXT_MEMORY_PORT.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// A network config for the in-memory transport with a fast heartbeat.
+pub fn memory_config() -> NetworkConfig {
+    NetworkConfig {
+        transport: TransportKind::Memory,
+        listen_address: memory_address(),
+        heartbeat_interval: Duration::from_millis(100),
+        ..NetworkConfig::default()
+    }
+}
+
+/// A running node started by `connected_nodes`.
+pub struct TestNode {
+    pub handle: NetworkHandle,
+    pub peer_id: PeerId,
+    pub address: Multiaddr,
+}
+
+/// Start one node per keypair on the in-memory transport and connect every
+/// pair of nodes.
+///
+/// Returns once each node sees all the others subscribed to every gossip
+/// topic, so messages published afterwards reach every node. The runners
+/// are spawned on the current tokio runtime.
+pub async fn connected_nodes(keypairs: Vec<Keypair>) -> Result<Vec<TestNode>, NetworkError> {
+    let mut handles = Vec::new();
+    let mut runners = Vec::new();
+    let mut addresses: Vec<Multiaddr> = Vec::new();
+
+    for keypair in keypairs {
+        let config = memory_config();
+        let address = config.listen_address.clone();
+        let (handle, mut runner) = ConsensusNetwork::new(keypair, config)?;
+        runner.start(address.clone())?;
+        for earlier in &addresses {
+            runner.dial(earlier.clone())?;
+        }
+        handles.push(handle);
+        runners.push(runner);
+        addresses.push(address);
+    }
+
+    let peers = runners.len().saturating_sub(1);
+    let ready = join_all(runners.iter_mut().map(|r| r.wait_for_peers(peers)));
+    let results = tokio::time::timeout(CONNECT_TIMEOUT, ready)
+        .await
+        .map_err(|_| NetworkError::Transport("timed out connecting test nodes".into()))?;
+    results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    for runner in runners {
+        tokio::spawn(runner.run());
+    }
+
+    Ok(handles
+        .into_iter()
+        .zip(addresses)
+        .map(|(handle, address)| TestNode {
+            peer_id: handle.local_peer_id(),
+            handle,
+            address,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+    use trv1_bft::{BlockHash, ConsensusMessage, Height, ValidatorId};
+
+    #[test]
+    fn test_memory_addresses_unique() {
+        assert_ne!(memory_address(), memory_address());
+    }
+
+    #[tokio::test]
+    async fn test_connected_nodes_exchange_gossip() {
+        let keys: Vec<SigningKey> = (0..3).map(|_| SigningKey::generate(&mut OsRng)).collect();
+        let validators: Vec<ValidatorId> =
+            keys.iter().map(|k| ValidatorId(k.verifying_key())).collect();
+        let keypairs = keys
+            .iter()
+            .map(|k| Keypair::ed25519_from_bytes(k.to_bytes()).unwrap())
+            .collect();
+
+        let mut nodes = connected_nodes(keypairs).await.unwrap();
+        for node in &nodes {
+            node.handle.set_validators(&validators);
+        }
+
+        let msg = ConsensusMessage::CommitBlock {
+            height: Height(1),
+            block_hash: BlockHash([0x01; 32]),
+        };
+        nodes[0].handle.broadcast_message(&msg).await.unwrap();
+
+        for node in nodes.iter_mut().skip(1) {
+            let received = tokio::time::timeout(Duration::from_secs(5), node.handle.next_message())
+                .await
+                .expect("commit should arrive")
+                .unwrap();
+            assert!(matches!(received.message, ConsensusMessage::CommitBlock { .. }));
+            assert_eq!(
+                received.sender_peer_id(),
+                Some(crate::peer::validator_peer_id(&validators[0]))
+            );
+        }
+    }
+}
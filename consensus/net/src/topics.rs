@@ -0,0 +1,208 @@
+use libp2p::gossipsub::{IdentTopic, TopicScoreParams};
+use std::time::Duration;
+use trv1_bft::ConsensusMessage;
+
+/// The gossipsub topic for block proposals.
+pub const PROPOSAL_TOPIC: &str = "trv1-proposals";
+
+/// The gossipsub topic for prevotes and precommits.
+pub const VOTE_TOPIC: &str = "trv1-votes";
+
+/// The gossipsub topic for commit notices.
+pub const COMMIT_TOPIC: &str = "trv1-commits";
+
+/// The gossipsub topic for equivocation evidence.
+pub const EVIDENCE_TOPIC: &str = "trv1-evidence";
+
+/// The gossipsub topic for transaction gossip.
+pub const TRANSACTION_TOPIC: &str = "trv1-transactions";
+
+/// The gossip topics the node subscribes to.
+///
+/// Variants are declared in priority order: when several inbound queues
+/// have messages waiting, the earlier topic is drained first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GossipTopic {
+    Proposals,
+    Commits,
+    Votes,
+    Evidence,
+    Transactions,
+}
+
+/// Per-topic queueing and peer-scoring parameters.
+///
+/// libp2p-gossipsub applies mesh degree settings globally, so topics are
+/// differentiated through their score weights: a peer that relays invalid
+/// proposals or votes loses far more standing than one relaying bad
+/// transactions.
+#[derive(Debug, Clone, Copy)]
+pub struct TopicParams {
+    /// Capacity of the inbound queue between the runner and the handle.
+    pub queue_capacity: usize,
+    /// Weight of this topic in the overall peer score.
+    pub topic_weight: f64,
+    /// Reward for being first to deliver a valid message on this topic.
+    pub first_delivery_weight: f64,
+    /// Penalty per (squared) invalid message delivered on this topic.
+    pub invalid_message_weight: f64,
+}
+
+impl GossipTopic {
+    /// All topics, in priority order.
+    pub const ALL: [GossipTopic; 5] = [
+        GossipTopic::Proposals,
+        GossipTopic::Commits,
+        GossipTopic::Votes,
+        GossipTopic::Evidence,
+        GossipTopic::Transactions,
+    ];
+
+    /// The gossipsub topic name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GossipTopic::Proposals => PROPOSAL_TOPIC,
+            GossipTopic::Commits => COMMIT_TOPIC,
+            GossipTopic::Votes => VOTE_TOPIC,
+            GossipTopic::Evidence => EVIDENCE_TOPIC,
+            GossipTopic::Transactions => TRANSACTION_TOPIC,
+        }
+    }
+
+    pub fn ident_topic(&self) -> IdentTopic {
+        IdentTopic::new(self.name())
+    }
+
+    /// Whether the topic carries consensus-critical traffic.
+    pub fn is_consensus(&self) -> bool {
+        !matches!(self, GossipTopic::Transactions)
+    }
+
+    /// The topic a consensus message is published on.
+    /// Returns `None` for local-only messages such as timeouts.
+    pub fn for_message(msg: &ConsensusMessage) -> Option<GossipTopic> {
+        match msg {
+            ConsensusMessage::ProposeBlock { .. } | ConsensusMessage::ProposeCompact { .. } => {
+                Some(GossipTopic::Proposals)
+            }
+            ConsensusMessage::CastVote(_) => Some(GossipTopic::Votes),
+            ConsensusMessage::CommitBlock { .. } => Some(GossipTopic::Commits),
+            ConsensusMessage::SubmitEvidence(_) => Some(GossipTopic::Evidence),
+            ConsensusMessage::ScheduleTimeout(_) => None,
+        }
+    }
+
+    /// Default queueing and scoring parameters for the topic.
+    pub fn params(&self) -> TopicParams {
+        match self {
+            GossipTopic::Proposals => TopicParams {
+                queue_capacity: 64,
+                topic_weight: 1.0,
+                first_delivery_weight: 1.0,
+                invalid_message_weight: -100.0,
+            },
+            GossipTopic::Commits => TopicParams {
+                queue_capacity: 64,
+                topic_weight: 0.5,
+                first_delivery_weight: 0.5,
+                invalid_message_weight: -100.0,
+            },
+            GossipTopic::Votes => TopicParams {
+                queue_capacity: 1024,
+                topic_weight: 1.0,
+                first_delivery_weight: 0.5,
+                invalid_message_weight: -50.0,
+            },
+            GossipTopic::Evidence => TopicParams {
+                queue_capacity: 64,
+                topic_weight: 0.5,
+                first_delivery_weight: 0.5,
+                invalid_message_weight: -50.0,
+            },
+            GossipTopic::Transactions => TopicParams {
+                queue_capacity: 4096,
+                topic_weight: 0.1,
+                first_delivery_weight: 0.1,
+                invalid_message_weight: -10.0,
+            },
+        }
+    }
+
+    /// Gossipsub peer-score parameters for the topic.
+    pub fn score_params(&self) -> TopicScoreParams {
+        let params = self.params();
+        TopicScoreParams {
+            topic_weight: params.topic_weight,
+            time_in_mesh_weight: 0.01,
+            time_in_mesh_quantum: Duration::from_secs(1),
+            time_in_mesh_cap: 3600.0,
+            first_message_deliveries_weight: params.first_delivery_weight,
+            first_message_deliveries_decay: 0.9,
+            first_message_deliveries_cap: 100.0,
+            // Mesh delivery-rate penalties assume steady traffic, which a
+            // small validator set does not produce; leave them disabled.
+            mesh_message_deliveries_weight: 0.0,
+            mesh_failure_penalty_weight: 0.0,
+            invalid_message_deliveries_weight: params.invalid_message_weight,
+            invalid_message_deliveries_decay: 0.5,
+            ..TopicScoreParams::default()
+        }
+    }
+}
+
+impl std::fmt::Display for GossipTopic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trv1_bft::{BlockHash, Height, Round, TimeoutEvent, TimeoutStep};
+
+    #[test]
+    fn test_topic_names_unique() {
+        let mut names: Vec<&str> = GossipTopic::ALL.iter().map(|t| t.name()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), GossipTopic::ALL.len());
+    }
+
+    #[test]
+    fn test_priority_order() {
+        assert!(GossipTopic::Proposals < GossipTopic::Votes);
+        assert!(GossipTopic::Votes < GossipTopic::Transactions);
+        assert!(!GossipTopic::Transactions.is_consensus());
+        assert!(GossipTopic::Evidence.is_consensus());
+    }
+
+    #[test]
+    fn test_topic_for_message() {
+        let commit = ConsensusMessage::CommitBlock {
+            height: Height(1),
+            block_hash: BlockHash([0; 32]),
+        };
+        assert_eq!(GossipTopic::for_message(&commit), Some(GossipTopic::Commits));
+
+        let timeout = ConsensusMessage::ScheduleTimeout(TimeoutEvent {
+            height: Height(1),
+            round: Round(0),
+            step: TimeoutStep::Propose,
+        });
+        assert_eq!(GossipTopic::for_message(&timeout), None);
+    }
+
+    #[test]
+    fn test_score_params_penalise_invalid_messages() {
+        for topic in GossipTopic::ALL {
+            let params = topic.score_params();
+            assert!(params.invalid_message_deliveries_weight < 0.0);
+            assert!(params.topic_weight > 0.0);
+        }
+        assert!(
+            GossipTopic::Proposals.params().invalid_message_weight
+                < GossipTopic::Transactions.params().invalid_message_weight
+        );
+    }
+}
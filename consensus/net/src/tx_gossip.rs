@@ -0,0 +1,324 @@
+use libp2p::{PeerId, StreamProtocol};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use trv1_bft::block::Transaction;
+
+/// Request-response protocol for pulling announced transactions.
+pub const TX_PROTOCOL: StreamProtocol = StreamProtocol::new("/trv1/txs/1");
+
+/// Tuning for announce/pull transaction gossip.
+#[derive(Debug, Clone)]
+pub struct TxGossipConfig {
+    /// How often queued transaction hashes are announced.
+    pub announce_interval: Duration,
+    /// Maximum hashes per announcement; a full batch is announced immediately.
+    pub max_announce_batch: usize,
+    /// Sustained hashes per second a peer may announce to us.
+    pub announce_rate: f64,
+    /// Hashes a peer may announce in a burst above `announce_rate`.
+    pub announce_burst: f64,
+    /// Sustained transactions per second a peer may request from us.
+    pub request_rate: f64,
+    /// Transactions a peer may request in a burst above `request_rate`.
+    pub request_burst: f64,
+    /// Number of transaction hashes remembered as already seen.
+    pub seen_cache_size: usize,
+    /// Number of recent transactions kept to serve peer requests.
+    pub store_size: usize,
+}
+
+impl Default for TxGossipConfig {
+    fn default() -> Self {
+        Self {
+            announce_interval: Duration::from_millis(100),
+            max_announce_batch: 256,
+            announce_rate: 1000.0,
+            announce_burst: 2000.0,
+            request_rate: 1000.0,
+            request_burst: 2000.0,
+            seen_cache_size: 65_536,
+            store_size: 16_384,
+        }
+    }
+}
+
+/// A batch of transaction hashes the publisher can serve.
+///
+/// Hashes are `Transaction::hash()`, the same hash used for block Merkle
+/// leaves and compact blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxAnnouncement {
+    pub hashes: Vec<[u8; 32]>,
+}
+
+/// Ask a peer for transactions it announced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxRequest {
+    pub hashes: Vec<[u8; 32]>,
+}
+
+/// The requested transactions the peer still had.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TxResponse {
+    pub transactions: Vec<Transaction>,
+}
+
+/// Bounded set of transaction hashes already received or being fetched.
+///
+/// Oldest entries are evicted first once `capacity` is reached.
+#[derive(Debug)]
+pub struct SeenCache {
+    capacity: usize,
+    next_seq: u64,
+    order: VecDeque<([u8; 32], u64)>,
+    entries: HashMap<[u8; 32], u64>,
+}
+
+impl SeenCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            next_seq: 0,
+            order: VecDeque::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Mark a hash as seen. Returns `true` if it was not already seen.
+    pub fn insert(&mut self, hash: [u8; 32]) -> bool {
+        if self.entries.contains_key(&hash) {
+            return false;
+        }
+        while self.entries.len() >= self.capacity {
+            let Some((oldest, seq)) = self.order.pop_front() else {
+                break;
+            };
+            // Skip stale queue entries for hashes removed and re-inserted.
+            if self.entries.get(&oldest) == Some(&seq) {
+                self.entries.remove(&oldest);
+            }
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.order.push_back((hash, seq));
+        self.entries.insert(hash, seq);
+        true
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    /// Forget a hash, e.g. after a failed fetch, so a later announcement
+    /// can be acted on.
+    pub fn remove(&mut self, hash: &[u8; 32]) {
+        self.entries.remove(hash);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Bounded store of recent transactions, served to peers that request
+/// hashes we announced.
+#[derive(Debug)]
+pub struct TxStore {
+    capacity: usize,
+    order: VecDeque<[u8; 32]>,
+    txs: HashMap<[u8; 32], Transaction>,
+}
+
+impl TxStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::new(),
+            txs: HashMap::new(),
+        }
+    }
+
+    /// Store a transaction under its hash, evicting the oldest if full.
+    pub fn insert(&mut self, hash: [u8; 32], tx: Transaction) {
+        if self.txs.contains_key(&hash) {
+            return;
+        }
+        while self.txs.len() >= self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => {
+                    self.txs.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+        self.order.push_back(hash);
+        self.txs.insert(hash, tx);
+    }
+
+    pub fn get(&self, hash: &[u8; 32]) -> Option<&Transaction> {
+        self.txs.get(hash)
+    }
+
+    /// Stop serving a transaction, e.g. one replaced in the mempool.
+    pub fn remove(&mut self, hash: &[u8; 32]) {
+        if self.txs.remove(hash).is_some() {
+            self.order.retain(|h| h != hash);
+        }
+    }
+
+    /// Answer a request with the transactions still held, in request order.
+    pub fn serve(&self, request: &TxRequest) -> TxResponse {
+        TxResponse {
+            transactions: request
+                .hashes
+                .iter()
+                .filter_map(|h| self.txs.get(h).cloned())
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+}
+
+/// Per-peer token-bucket rate limiter.
+#[derive(Debug)]
+pub struct PeerRateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: HashMap<PeerId, (f64, Instant)>,
+}
+
+impl PeerRateLimiter {
+    /// Allow `rate` units per second per peer, with bursts up to `burst`.
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Spend `cost` units of `peer`'s budget. Returns `false`, spending
+    /// nothing, if the peer does not have enough left.
+    pub fn allow(&mut self, peer: &PeerId, cost: usize, now: Instant) -> bool {
+        let (tokens, last) = self.buckets.entry(*peer).or_insert((self.burst, now));
+        let elapsed = now.saturating_duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.rate).min(self.burst);
+        *last = now;
+        let cost = cost as f64;
+        if *tokens < cost {
+            return false;
+        }
+        *tokens -= cost;
+        true
+    }
+
+    /// Drop a disconnected peer's bucket.
+    pub fn forget(&mut self, peer: &PeerId) {
+        self.buckets.remove(peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seen_cache_dedup_and_eviction() {
+        let mut seen = SeenCache::new(2);
+        assert!(seen.insert([1; 32]));
+        assert!(!seen.insert([1; 32]));
+        assert!(seen.insert([2; 32]));
+        assert!(seen.insert([3; 32]));
+        assert_eq!(seen.len(), 2);
+        assert!(!seen.contains(&[1; 32]), "oldest hash should be evicted");
+        assert!(seen.contains(&[3; 32]));
+    }
+
+    #[test]
+    fn test_seen_cache_reinsert_after_remove() {
+        let mut seen = SeenCache::new(2);
+        seen.insert([1; 32]);
+        seen.insert([2; 32]);
+        seen.remove(&[1; 32]);
+        assert!(seen.insert([1; 32]));
+        // The stale entry for [1] must not evict the fresh one.
+        assert!(seen.insert([3; 32]));
+        assert!(seen.contains(&[1; 32]));
+        assert!(!seen.contains(&[2; 32]));
+    }
+
+    #[test]
+    fn test_tx_store_remove_stops_serving() {
+        let tx = Transaction {
+            from: [1; 32],
+            to: [2; 32],
+            amount: 1,
+            nonce: 0,
+            signature: vec![0u8; 64],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+        let mut store = TxStore::new(2);
+        store.insert([1; 32], tx);
+        store.remove(&[1; 32]);
+        assert!(store.get(&[1; 32]).is_none());
+        assert!(store.is_empty());
+        let response = store.serve(&TxRequest { hashes: vec![[1; 32]] });
+        assert!(response.transactions.is_empty());
+    }
+
+    #[test]
+    fn test_tx_store_serves_known_hashes() {
+        let tx = Transaction {
+            from: [1; 32],
+            to: [2; 32],
+            amount: 3,
+            nonce: 0,
+            signature: vec![0u8; 64],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+        let mut store = TxStore::new(1);
+        store.insert(tx.hash(), tx.clone());
+        let response = store.serve(&TxRequest {
+            hashes: vec![[9; 32], tx.hash()],
+        });
+        assert_eq!(response.transactions.len(), 1);
+
+        let mut other = tx;
+        other.nonce = 1;
+        store.insert(other.hash(), other.clone());
+        assert_eq!(store.len(), 1);
+        assert!(store.get(&other.hash()).is_some());
+    }
+
+    #[test]
+    fn test_rate_limiter_refills() {
+        let peer = PeerId::random();
+        let mut limiter = PeerRateLimiter::new(10.0, 20.0);
+        let start = Instant::now();
+        assert!(limiter.allow(&peer, 20, start));
+        assert!(!limiter.allow(&peer, 1, start), "burst exhausted");
+        assert!(limiter.allow(&peer, 5, start + Duration::from_millis(500)));
+        assert!(!limiter.allow(&peer, 1, start + Duration::from_millis(500)));
+
+        // Other peers have their own budget.
+        assert!(limiter.allow(&PeerId::random(), 20, start));
+    }
+}
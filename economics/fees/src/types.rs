@@ -1,5 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+/// Gas consumed by a plain value transfer, and therefore the smallest
+/// `gas_limit` a transaction may declare.
+pub const TRANSFER_GAS: u64 = 21_000;
+
 /// Configuration for the EIP-1559 fee market.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FeeConfig {
@@ -45,6 +45,11 @@ impl StakingPool {
             return Err(StakingError::ZeroAmount);
         }
 
+        let total_staked = self
+            .state
+            .total_staked
+            .checked_add(amount)
+            .ok_or(StakingError::Overflow)?;
         let unlock_epoch = tier
             .lock_duration_epochs()
             .map(|d| self.state.current_epoch + d);
@@ -62,12 +67,7 @@ impl StakingPool {
             .entry(staker)
             .or_default()
             .push(entry);
-
-        self.state.total_staked = self
-            .state
-            .total_staked
-            .checked_add(amount)
-            .ok_or(StakingError::Overflow)?;
+        self.state.total_staked = total_staked;
 
         Ok(())
     }
@@ -127,6 +127,32 @@ impl StakingPool {
         Ok(())
     }
 
+    /// Remove up to `amount` of `staker`'s direct stake, locked or not,
+    /// from the earliest entries first. Returns the amount removed, which
+    /// is less than `amount` only if `staker` has less staked.
+    pub fn slash(&mut self, staker: &PublicKey, mut amount: u64) -> u64 {
+        let Some(entries) = self.state.entries.get_mut(staker) else {
+            return 0;
+        };
+        let mut removed_total = 0u64;
+        while amount > 0 && !entries.is_empty() {
+            let taken = entries[0].amount.min(amount);
+            entries[0].amount -= taken;
+            if entries[0].amount == 0 {
+                entries.remove(0);
+            }
+            amount -= taken;
+            removed_total += taken;
+        }
+        self.state.total_staked -= removed_total;
+
+        if entries.is_empty() {
+            self.state.entries.remove(staker);
+        }
+
+        removed_total
+    }
+
     /// Delegate tokens to a validator.
     pub fn delegate(
         &mut self,
@@ -139,6 +165,11 @@ impl StakingPool {
             return Err(StakingError::ZeroAmount);
         }
 
+        let total_staked = self
+            .state
+            .total_staked
+            .checked_add(amount)
+            .ok_or(StakingError::Overflow)?;
         let unlock_epoch = tier
             .lock_duration_epochs()
             .map(|d| self.state.current_epoch + d);
@@ -158,12 +189,7 @@ impl StakingPool {
             .entry(key)
             .or_default()
             .push(entry);
-
-        self.state.total_staked = self
-            .state
-            .total_staked
-            .checked_add(amount)
-            .ok_or(StakingError::Overflow)?;
+        self.state.total_staked = total_staked;
 
         Ok(())
     }
@@ -287,6 +313,14 @@ impl StakingPool {
         power
     }
 
+    /// Total amount `staker` has staked directly, locked or not.
+    pub fn stake_of(&self, staker: &PublicKey) -> u64 {
+        self.state
+            .entries
+            .get(staker)
+            .map_or(0, |entries| entries.iter().map(|e| e.amount).sum())
+    }
+
     /// Get the total amount staked across the entire pool.
     pub fn total_staked(&self) -> u64 {
         self.state.total_staked
@@ -320,6 +354,19 @@ mod tests {
         assert_eq!(result, Err(StakingError::ZeroAmount));
     }
 
+    #[test]
+    fn test_stake_overflow_changes_nothing() {
+        let mut pool = StakingPool::new();
+        pool.stake(key(1), u64::MAX, LockTier::NoLock).unwrap();
+        assert_eq!(pool.stake(key(2), 1, LockTier::NoLock), Err(StakingError::Overflow));
+        assert_eq!(
+            pool.delegate(key(2), key(1), 1, LockTier::NoLock),
+            Err(StakingError::Overflow)
+        );
+        assert_eq!(pool.stake_of(&key(2)), 0);
+        assert_eq!(pool.total_staked(), u64::MAX);
+    }
+
     #[test]
     fn test_unstake_no_lock() {
         let mut pool = StakingPool::new();
@@ -486,9 +533,30 @@ mod tests {
         // Can only unstake the NoLock portion.
         pool.unstake(key(1), 500_000).unwrap();
         assert_eq!(pool.total_staked(), 500_000);
+        assert_eq!(pool.stake_of(&key(1)), 500_000);
+        assert_eq!(pool.stake_of(&key(2)), 0);
 
         // Trying to unstake more fails since the rest is locked.
         let result = pool.unstake(key(1), 100);
         assert!(matches!(result, Err(StakingError::InsufficientBalance { .. })));
     }
+
+    #[test]
+    fn test_slash_takes_locked_stake() {
+        let mut pool = StakingPool::new();
+        pool.stake(key(1), 300_000, LockTier::Permanent).unwrap();
+        pool.stake(key(1), 500_000, LockTier::NoLock).unwrap();
+        pool.stake(key(2), 100_000, LockTier::NoLock).unwrap();
+
+        assert_eq!(pool.slash(&key(1), 400_000), 400_000);
+        assert_eq!(pool.stake_of(&key(1)), 400_000);
+        assert_eq!(pool.total_staked(), 500_000);
+        // The permanent entry went first, so what is left can be unstaked.
+        pool.unstake(key(1), 400_000).unwrap();
+
+        // A slash takes no more than is staked.
+        assert_eq!(pool.slash(&key(2), 250_000), 100_000);
+        assert_eq!(pool.slash(&key(3), 1_000), 0);
+        assert_eq!(pool.total_staked(), 0);
+    }
 }
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::tiers::LockTier;
 
@@ -42,14 +42,17 @@ pub struct DelegationEntry {
 pub type DelegationKey = (PublicKey, PublicKey);
 
 /// The aggregate staking state for the whole chain.
+///
+/// Ordered maps keep iteration, and so reward lists and serialized state,
+/// identical on every node.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct StakingState {
     /// Total amount staked across all entries.
     pub total_staked: u64,
     /// All stake entries keyed by staker pubkey.
-    pub entries: HashMap<PublicKey, Vec<StakeEntry>>,
+    pub entries: BTreeMap<PublicKey, Vec<StakeEntry>>,
     /// All delegation entries keyed by (delegator, validator).
-    pub delegations: HashMap<DelegationKey, Vec<DelegationEntry>>,
+    pub delegations: BTreeMap<DelegationKey, Vec<DelegationEntry>>,
     /// Current epoch for time tracking.
     pub current_epoch: u64,
 }
@@ -109,6 +109,9 @@ mod tests {
             .with_validator(test_pubkey(1), 5_000_000, 300)
             .with_validator(test_pubkey(2), 10_000_000, 500)
             .with_validator(test_pubkey(3), 15_000_000, 700)
+            .with_account(test_pubkey(1), 5_000_000)
+            .with_account(test_pubkey(2), 10_000_000)
+            .with_account(test_pubkey(3), 15_000_000)
             .build()
             .unwrap();
 
@@ -135,6 +138,7 @@ mod tests {
         let config = GenesisBuilder::new("custom")
             .with_params(params)
             .with_validator(test_pubkey(1), 10_000_000, 500)
+            .with_account(test_pubkey(1), 10_000_000)
             .build()
             .unwrap();
 
@@ -152,6 +156,7 @@ mod tests {
         let config = GenesisBuilder::new("timed")
             .with_genesis_time(fixed_time)
             .with_validator(test_pubkey(1), 10_000_000, 500)
+            .with_account(test_pubkey(1), 10_000_000)
             .build()
             .unwrap();
 
@@ -171,6 +176,7 @@ mod tests {
     fn builder_hash_computed_on_build() {
         let config = GenesisBuilder::new("hashed")
             .with_validator(test_pubkey(1), 10_000_000, 500)
+            .with_account(test_pubkey(1), 10_000_000)
             .build()
             .unwrap();
 
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
 
 use chrono::{DateTime, Utc};
@@ -49,6 +49,7 @@ impl GenesisConfig {
 
         // Check for duplicate validator pubkeys.
         let mut seen = HashSet::new();
+        let balances = self.allocations();
         for (i, v) in self.validators.iter().enumerate() {
             if !seen.insert(v.pubkey) {
                 return Err(GenesisError::DuplicateValidator(i));
@@ -59,6 +60,9 @@ impl GenesisConfig {
             if v.commission_rate > 10_000 {
                 return Err(GenesisError::InvalidCommission { index: i });
             }
+            if balances.get(&v.pubkey).copied().unwrap_or(0) < v.initial_stake {
+                return Err(GenesisError::UnfundedStake { index: i });
+            }
         }
 
         // Validate chain params.
@@ -90,6 +94,25 @@ impl GenesisConfig {
         Ok(())
     }
 
+    /// The balance each genesis account starts with. Each validator's
+    /// initial stake is drawn from its own account, so the allocations
+    /// alone make up the total supply.
+    pub fn account_balances(&self) -> BTreeMap<[u8; 32], u64> {
+        let mut balances = self.allocations();
+        for v in &self.validators {
+            if let Some(balance) = balances.get_mut(&v.pubkey) {
+                *balance = balance.saturating_sub(v.initial_stake);
+            }
+        }
+        balances
+    }
+
+    /// Each account's allocation. A later entry for the same account
+    /// replaces an earlier one.
+    fn allocations(&self) -> BTreeMap<[u8; 32], u64> {
+        self.accounts.iter().map(|a| (a.pubkey, a.balance)).collect()
+    }
+
     /// Compute a SHA-256 hash of the canonical JSON representation.
     /// This provides a unique fingerprint for the genesis state.
     pub fn compute_genesis_hash(&self) -> [u8; 32] {
@@ -292,6 +315,97 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn validate_unfunded_stake_fails() {
+        let mut config = GenesisConfig::default_testnet();
+        config.validators[2].initial_stake = config.accounts[2].balance + 1;
+        assert!(matches!(
+            config.validate(),
+            Err(GenesisError::UnfundedStake { index: 2 })
+        ));
+        config.accounts.remove(2);
+        config.validators[2].initial_stake = 1;
+        assert!(matches!(
+            config.validate(),
+            Err(GenesisError::UnfundedStake { index: 2 })
+        ));
+    }
+
+    #[test]
+    fn pre_funding_genesis_is_rejected() {
+        // A genesis written before stakes were drawn from accounts: the
+        // validator's stake was minted separately and it has no account.
+        let json = r#"{
+            "chain_id": "trv1-testnet-1",
+            "genesis_time": "2025-01-01T00:00:00Z",
+            "chain_params": {
+                "chain_id": "trv1-testnet-1",
+                "epoch_length": 100,
+                "block_time_ms": 2000,
+                "max_validators": 200,
+                "base_fee_floor": 1,
+                "fee_launch_burn_bps": 1000,
+                "fee_launch_validator_bps": 0,
+                "fee_launch_treasury_bps": 4500,
+                "fee_launch_developer_bps": 4500,
+                "fee_maturity_burn_bps": 2500,
+                "fee_maturity_validator_bps": 2500,
+                "fee_maturity_treasury_bps": 2500,
+                "fee_maturity_developer_bps": 2500,
+                "fee_transition_epochs": 1825,
+                "slash_double_sign_bps": 500,
+                "slash_downtime_bps": 100,
+                "staking_base_apy": 500
+            },
+            "validators": [{
+                "pubkey": "0100000000000000000000000000000000000000000000000000000000000000",
+                "initial_stake": 10000000,
+                "commission_rate": 500
+            }],
+            "accounts": [{
+                "pubkey": "0200000000000000000000000000000000000000000000000000000000000000",
+                "balance": 100000000
+            }],
+            "genesis_hash": "0000000000000000000000000000000000000000000000000000000000000000"
+        }"#;
+        let dir = env::temp_dir().join(format!(
+            "trv1_genesis_legacy_test_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::create_dir_all(&dir);
+        let path = dir.join("genesis.json");
+        std::fs::write(&path, json).unwrap();
+
+        let mut config = GenesisConfig::from_file(&path).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(matches!(err, GenesisError::UnfundedStake { index: 0 }));
+        assert_eq!(
+            err.to_string(),
+            "validator at index 0 stakes more than its genesis account holds"
+        );
+
+        // The documented migration: give the validator an account holding
+        // its stake.
+        config.accounts.push(GenesisAccount {
+            pubkey: config.validators[0].pubkey,
+            balance: 10_000_000,
+        });
+        config.validate().unwrap();
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn account_balances_exclude_stakes() {
+        let config = GenesisConfig::default_testnet();
+        let balances = config.account_balances();
+        assert_eq!(balances.len(), 4);
+        assert!(balances.values().all(|&b| b == 90_000_000));
+        let staked: u64 = config.validators.iter().map(|v| v.initial_stake).sum();
+        let allocated: u64 = config.accounts.iter().map(|a| a.balance).sum();
+        assert_eq!(balances.values().sum::<u64>() + staked, allocated);
+    }
+
     #[test]
     fn serde_roundtrip() {
         let config = GenesisConfig::default_testnet();
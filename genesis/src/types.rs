@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use trv1_fees::{SplitConfig, SplitRatios};
 
 /// A validator entry in the genesis configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,6 +99,25 @@ impl ChainParams {
             + self.fee_maturity_developer_bps;
         launch_sum == 10_000 && maturity_sum == 10_000
     }
+
+    /// The fee split schedule these parameters describe.
+    pub fn fee_split_config(&self) -> SplitConfig {
+        SplitConfig {
+            launch: SplitRatios {
+                burn_bps: self.fee_launch_burn_bps,
+                validator_bps: self.fee_launch_validator_bps,
+                treasury_bps: self.fee_launch_treasury_bps,
+                developer_bps: self.fee_launch_developer_bps,
+            },
+            maturity: SplitRatios {
+                burn_bps: self.fee_maturity_burn_bps,
+                validator_bps: self.fee_maturity_validator_bps,
+                treasury_bps: self.fee_maturity_treasury_bps,
+                developer_bps: self.fee_maturity_developer_bps,
+            },
+            transition_epochs: self.fee_transition_epochs,
+        }
+    }
 }
 
 /// Genesis configuration error.
@@ -124,6 +144,9 @@ pub enum GenesisError {
     #[error("max validators must be > 0")]
     ZeroMaxValidators,
 
+    #[error("validator at index {index} stakes more than its genesis account holds")]
+    UnfundedStake { index: usize },
+
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -190,6 +213,18 @@ mod tests {
         assert!(!params.validate_fee_split());
     }
 
+    #[test]
+    fn fee_split_config_from_params() {
+        let params = ChainParams {
+            fee_transition_epochs: 10,
+            ..ChainParams::default()
+        };
+        let config = params.fee_split_config();
+        assert!(config.validate());
+        assert_eq!(config.launch.treasury_bps, 4500);
+        assert_eq!(config.split_at_epoch(10).validator_bps, 2500);
+    }
+
     #[test]
     fn genesis_validator_serde_roundtrip() {
         let val = GenesisValidator {
@@ -0,0 +1,226 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use tracing::warn;
+use trv1_bft::block::Transaction;
+use trv1_bft::encoding;
+
+/// Errors reading or writing the mempool journal.
+#[derive(Debug, Error)]
+pub enum JournalError {
+    #[error("journal I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Append-only log of locally submitted transactions, so they survive a
+/// node restart.
+///
+/// Each record is a little-endian `u32` length followed by the
+/// transaction's canonical encoding. A record cut short by a crash is
+/// dropped when the journal is opened. Transactions that have since left
+/// the pool are removed by rewriting the journal (`rewrite`).
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    /// Hashes of the journaled transactions, in file order.
+    hashes: Vec<[u8; 32]>,
+}
+
+impl Journal {
+    /// Open (or create) the journal at `path`, returning it together with
+    /// the transactions it holds, oldest first.
+    ///
+    /// Records that no longer decode, e.g. after an encoding version
+    /// change, are skipped.
+    pub fn open(path: impl AsRef<Path>) -> Result<(Self, Vec<Transaction>), JournalError> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let mut txs = Vec::new();
+        let mut offset = 0;
+        while let Some(len_bytes) = data.get(offset..offset + 4) {
+            let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+            let Some(record) = data.get(offset + 4..offset + 4 + len) else {
+                break;
+            };
+            offset += 4 + len;
+            match encoding::from_bytes::<Transaction>(record) {
+                Ok(tx) => txs.push(tx),
+                Err(e) => warn!(error = %e, "skipping undecodable mempool journal record"),
+            }
+        }
+        if offset < data.len() {
+            warn!(
+                path = %path.display(),
+                bytes = data.len() - offset,
+                "dropping truncated mempool journal tail"
+            );
+            file.set_len(offset as u64)?;
+        }
+
+        let hashes = txs.iter().map(Transaction::hash).collect();
+        Ok((Self { path, file, hashes }, txs))
+    }
+
+    /// Append a transaction and flush it to disk.
+    pub fn append(&mut self, tx: &Transaction) -> Result<(), JournalError> {
+        self.file.write_all(&record(tx))?;
+        self.file.sync_data()?;
+        self.hashes.push(tx.hash());
+        Ok(())
+    }
+
+    /// Replace the journal's contents with `txs`.
+    ///
+    /// The new journal is written beside the old one and renamed over it,
+    /// so a crash midway leaves one or the other intact.
+    pub fn rewrite(&mut self, txs: &[Transaction]) -> Result<(), JournalError> {
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let mut out = BufWriter::new(File::create(&tmp_path)?);
+            for tx in txs {
+                out.write_all(&record(tx))?;
+            }
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.hashes = txs.iter().map(Transaction::hash).collect();
+        Ok(())
+    }
+
+    /// Hashes of the journaled transactions, oldest first.
+    pub fn hashes(&self) -> &[[u8; 32]] {
+        &self.hashes
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
+
+/// A transaction's length-prefixed journal record.
+fn record(tx: &Transaction) -> Vec<u8> {
+    let bytes = encoding::to_bytes(tx);
+    let mut out = Vec::with_capacity(4 + bytes.len());
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(&bytes);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir()
+            .join(format!("trv1_journal_test_{name}_{}", std::process::id()))
+            .join("mempool.journal")
+    }
+
+    fn cleanup(path: &Path) {
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    fn make_tx(nonce: u64) -> Transaction {
+        Transaction {
+            from: [1u8; 32],
+            to: [2u8; 32],
+            amount: 100,
+            nonce,
+            signature: vec![0u8; 64],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        }
+    }
+
+    #[test]
+    fn test_append_and_reopen() {
+        let path = temp_path("reopen");
+        cleanup(&path);
+
+        let (mut journal, loaded) = Journal::open(&path).unwrap();
+        assert!(loaded.is_empty());
+        journal.append(&make_tx(0)).unwrap();
+        journal.append(&make_tx(1)).unwrap();
+        drop(journal);
+
+        let (journal, loaded) = Journal::open(&path).unwrap();
+        let nonces: Vec<u64> = loaded.iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![0, 1]);
+        assert_eq!(journal.hashes(), &[make_tx(0).hash(), make_tx(1).hash()]);
+
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_truncated_tail_is_dropped() {
+        let path = temp_path("truncated");
+        cleanup(&path);
+
+        let (mut journal, _) = Journal::open(&path).unwrap();
+        journal.append(&make_tx(0)).unwrap();
+        journal.append(&make_tx(1)).unwrap();
+        drop(journal);
+
+        // Simulate a crash halfway through writing the second record.
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 10).unwrap();
+
+        let (mut journal, loaded) = Journal::open(&path).unwrap();
+        assert_eq!(loaded.len(), 1);
+        journal.append(&make_tx(2)).unwrap();
+        drop(journal);
+
+        let (_, loaded) = Journal::open(&path).unwrap();
+        let nonces: Vec<u64> = loaded.iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![0, 2]);
+
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_rewrite_replaces_contents() {
+        let path = temp_path("rewrite");
+        cleanup(&path);
+
+        let (mut journal, _) = Journal::open(&path).unwrap();
+        for nonce in 0..3 {
+            journal.append(&make_tx(nonce)).unwrap();
+        }
+        journal.rewrite(&[make_tx(2)]).unwrap();
+        assert_eq!(journal.len(), 1);
+        journal.append(&make_tx(3)).unwrap();
+        drop(journal);
+
+        let (_, loaded) = Journal::open(&path).unwrap();
+        let nonces: Vec<u64> = loaded.iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![2, 3]);
+
+        cleanup(&path);
+    }
+}
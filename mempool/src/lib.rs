@@ -1,6 +1,8 @@
+pub mod journal;
 pub mod pool;
 pub mod validation;
 pub mod types;
 
+pub use journal::{Journal, JournalError};
 pub use pool::TransactionPool;
 pub use types::*;
@@ -1,22 +1,240 @@
-use std::collections::{HashMap, HashSet};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{btree_map, BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use sha2::{Digest, Sha256};
-use tracing::debug;
+use tracing::{debug, warn};
 use trv1_bft::block::Transaction;
+use trv1_bft::SignatureVerifier;
+use trv1_state::StateDB;
 
-use crate::types::{MempoolConfig, MempoolError, PendingTransaction};
-use crate::validation::{validate_transaction, verify_signature};
+use crate::journal::{Journal, JournalError};
+use crate::types::{
+    bumped_fee, EvictedTransaction, EvictionReason, MempoolConfig, MempoolError,
+    PendingTransaction,
+};
+use crate::validation::{max_cost, validate_transaction};
+
+/// Current wall-clock time in milliseconds since the Unix epoch.
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// One sender's pooled transactions, keyed by nonce.
+///
+/// `pending` always holds a contiguous run of nonces starting at
+/// `state_nonce`, so every pending transaction can execute once those
+/// before it have. Transactions behind a nonce gap wait in `queued`.
+#[derive(Debug)]
+struct SenderTxs {
+    /// The sender's account nonce in `StateDB`, i.e. the nonce its next
+    /// executed transaction must carry.
+    state_nonce: u64,
+    pending: BTreeMap<u64, PendingTransaction>,
+    queued: BTreeMap<u64, PendingTransaction>,
+}
+
+impl SenderTxs {
+    fn new(state_nonce: u64) -> Self {
+        Self {
+            state_nonce,
+            pending: BTreeMap::new(),
+            queued: BTreeMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.pending.len() + self.queued.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_empty() && self.queued.is_empty()
+    }
+
+    /// The nonce that would extend the pending run.
+    fn next_nonce(&self) -> u64 {
+        self.state_nonce + self.pending.len() as u64
+    }
+
+    /// The highest-nonce transaction and whether it is pending. Dropping
+    /// it never opens a gap in front of another transaction.
+    fn tail(&self) -> Option<(&PendingTransaction, bool)> {
+        if let Some((_, ptx)) = self.queued.last_key_value() {
+            return Some((ptx, false));
+        }
+        self.pending.last_key_value().map(|(_, ptx)| (ptx, true))
+    }
+
+    fn get(&self, nonce: u64) -> Option<&PendingTransaction> {
+        self.pending.get(&nonce).or_else(|| self.queued.get(&nonce))
+    }
+
+    /// Swap in a transaction for the one already held at its nonce, keeping
+    /// it pending or queued as before.
+    fn replace(&mut self, ptx: PendingTransaction) -> Option<PendingTransaction> {
+        let nonce = ptx.tx.nonce;
+        if let Some(slot) = self.pending.get_mut(&nonce) {
+            return Some(std::mem::replace(slot, ptx));
+        }
+        let slot = self.queued.get_mut(&nonce)?;
+        Some(std::mem::replace(slot, ptx))
+    }
+
+    fn insert(&mut self, ptx: PendingTransaction) {
+        let nonce = ptx.tx.nonce;
+        if nonce == self.next_nonce() {
+            self.pending.insert(nonce, ptx);
+            self.promote();
+        } else {
+            self.queued.insert(nonce, ptx);
+        }
+    }
+
+    /// Move queued transactions whose gap has been filled into `pending`.
+    fn promote(&mut self) {
+        loop {
+            let next = self.next_nonce();
+            match self.queued.remove(&next) {
+                Some(ptx) => {
+                    self.pending.insert(next, ptx);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Remove the transaction with `nonce`. Removing a pending transaction
+    /// opens a gap, so every pending transaction after it is queued again.
+    fn remove(&mut self, nonce: u64) -> Option<PendingTransaction> {
+        if let Some(ptx) = self.queued.remove(&nonce) {
+            return Some(ptx);
+        }
+        let ptx = self.pending.remove(&nonce)?;
+        let demoted = self.pending.split_off(&nonce);
+        self.queued.extend(demoted);
+        Some(ptx)
+    }
+
+    /// Total `max_cost` of every pooled transaction except the one at
+    /// `nonce`.
+    fn cost_excluding(&self, nonce: u64) -> u64 {
+        self.pending
+            .values()
+            .chain(self.queued.values())
+            .filter(|ptx| ptx.tx.nonce != nonce)
+            .map(|ptx| max_cost(&ptx.tx).unwrap_or(u64::MAX))
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Walk the transactions in nonce order against `balance` and remove
+    /// each one that no longer fits on top of those kept before it.
+    fn evict_unaffordable(&mut self, balance: u64) -> Vec<PendingTransaction> {
+        let mut nonces: Vec<u64> = self.pending.keys().chain(self.queued.keys()).copied().collect();
+        nonces.sort_unstable();
+        let mut spent = 0u64;
+        let mut unaffordable = Vec::new();
+        for nonce in nonces {
+            let Some(ptx) = self.get(nonce) else {
+                continue;
+            };
+            let cost = max_cost(&ptx.tx).unwrap_or(u64::MAX);
+            match spent.checked_add(cost) {
+                Some(total) if total <= balance => spent = total,
+                _ => unaffordable.push(nonce),
+            }
+        }
+        unaffordable
+            .into_iter()
+            .filter_map(|nonce| self.remove(nonce))
+            .collect()
+    }
+
+    /// Re-base on the account nonce now in `StateDB`. Transactions with a
+    /// lower nonce can never execute and are returned for removal.
+    fn set_state_nonce(&mut self, state_nonce: u64) -> Vec<PendingTransaction> {
+        if state_nonce == self.state_nonce {
+            return Vec::new();
+        }
+        self.state_nonce = state_nonce;
+        let mut all = std::mem::take(&mut self.pending);
+        all.append(&mut self.queued);
+        let keep = all.split_off(&state_nonce);
+        self.queued = keep;
+        self.promote();
+        all.into_values().collect()
+    }
+}
+
+/// A sender's lowest not-yet-selected pending transaction, competing with
+/// other senders' for the next block slot.
+struct Candidate<'a> {
+    ptx: &'a PendingTransaction,
+    sender: [u8; 32],
+}
+
+impl Candidate<'_> {
+    /// Higher effective tip first, then earlier arrival.
+    fn key(&self) -> (u64, Reverse<u64>) {
+        (self.ptx.fee_priority, Reverse(self.ptx.seq))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
 
 /// Transaction pool storing pending transactions awaiting inclusion in a block.
+///
+/// Each sender's transactions are split by nonce into executable "pending"
+/// ones and nonce-gapped "queued" ones, tracked against the sender's nonce
+/// in `StateDB`. Queued transactions are promoted as gaps fill.
+///
+/// Pending transactions compete on the tip they pay at the current base
+/// fee, which the node feeds in from its `FeeMarket` after every commit.
+/// When the pool is full, a new transaction takes the place of the
+/// lowest-priority one if it pays more, and transactions older than the
+/// configured time-to-live are dropped by `expire`.
 #[derive(Debug)]
 pub struct TransactionPool {
     config: MempoolConfig,
     /// Transactions grouped by sender pubkey.
-    by_sender: HashMap<[u8; 32], Vec<PendingTransaction>>,
-    /// Set of known tx hashes for dedup.
-    known_hashes: HashSet<[u8; 32]>,
-    /// Total number of pending transactions.
+    by_sender: HashMap<[u8; 32], SenderTxs>,
+    /// Sender and nonce of every pooled transaction, by tx hash.
+    known_hashes: HashMap<[u8; 32], ([u8; 32], u64)>,
+    /// Total number of pooled transactions, pending and queued.
     total_count: usize,
+    /// Arrival counter used to order equal-fee transactions.
+    next_seq: u64,
+    /// Base fee per gas of the next block.
+    base_fee: u64,
+    /// Hashes of transactions displaced by replace-by-fee since the last
+    /// `take_replaced`, so the node can stop gossiping them.
+    replaced: Vec<[u8; 32]>,
+    /// On-disk log of locally submitted transactions, if persistence is
+    /// enabled.
+    journal: Option<Journal>,
+    /// Signature verifier, shared with the rest of the node so a
+    /// transaction verified here is not verified again at commit.
+    verifier: Arc<SignatureVerifier>,
 }
 
 impl TransactionPool {
@@ -25,42 +243,239 @@ impl TransactionPool {
         Self {
             config,
             by_sender: HashMap::new(),
-            known_hashes: HashSet::new(),
+            known_hashes: HashMap::new(),
             total_count: 0,
+            next_seq: 0,
+            base_fee: 0,
+            replaced: Vec::new(),
+            journal: None,
+            verifier: Arc::new(SignatureVerifier::default()),
+        }
+    }
+
+    /// Verify signatures with `verifier` instead of a private one.
+    pub fn with_signature_verifier(mut self, verifier: Arc<SignatureVerifier>) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    /// The verifier used for admission, and its cache of verified hashes.
+    pub fn signature_verifier(&self) -> &Arc<SignatureVerifier> {
+        &self.verifier
+    }
+
+    /// Record locally submitted transactions in `journal` from now on.
+    ///
+    /// Any transactions already in the journal should be replayed through
+    /// `add_transaction` first; `compact_journal` then drops those that
+    /// were not readmitted.
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    /// The base fee per gas the pool is currently pricing against.
+    pub fn base_fee(&self) -> u64 {
+        self.base_fee
+    }
+
+    /// Update the base fee for the next block and re-prioritise every
+    /// pooled transaction by the tip it now pays.
+    ///
+    /// Transactions whose fee cap falls below the new base fee stay pooled
+    /// but are not selected until the base fee drops back.
+    pub fn set_base_fee(&mut self, base_fee: u64) {
+        if base_fee == self.base_fee {
+            return;
+        }
+        self.base_fee = base_fee;
+        for sender_txs in self.by_sender.values_mut() {
+            for ptx in sender_txs
+                .pending
+                .values_mut()
+                .chain(sender_txs.queued.values_mut())
+            {
+                ptx.reprioritise(base_fee);
+            }
+        }
+        debug!(base_fee, "mempool re-prioritised for new base fee");
+    }
+
+    /// Add a group of transactions, e.g. a burst of gossip, returning one
+    /// result per transaction in order.
+    ///
+    /// Signatures are verified up front across the verifier's worker
+    /// pool; admission then proceeds as for `add_transaction`.
+    pub fn add_transactions(
+        &mut self,
+        txs: Vec<Transaction>,
+        state: &StateDB,
+    ) -> Vec<Result<(), MempoolError>> {
+        let verified = self.verifier.verify_all(&txs);
+        txs.into_iter()
+            .zip(verified)
+            .map(|(tx, ok)| {
+                if ok {
+                    self.add_transaction(tx, state)
+                } else {
+                    validate_transaction(&tx).and(Err(MempoolError::InvalidSignature))
+                }
+            })
+            .collect()
+    }
+
+    /// Add a transaction submitted to this node, journaling it on success
+    /// so it is restored after a restart.
+    ///
+    /// A journal write failure is logged rather than returned: the
+    /// transaction is already pooled and will still be gossiped.
+    pub fn add_local_transaction(
+        &mut self,
+        tx: Transaction,
+        state: &StateDB,
+    ) -> Result<(), MempoolError> {
+        let journaled = self.journal.is_some().then(|| tx.clone());
+        self.add_transaction(tx, state)?;
+        if let (Some(journal), Some(tx)) = (self.journal.as_mut(), journaled) {
+            if let Err(e) = journal.append(&tx) {
+                warn!(error = %e, "failed to journal local transaction");
+            }
         }
+        Ok(())
+    }
+
+    /// Rewrite the journal to hold only the journaled transactions still
+    /// in the pool, dropping those committed, replaced, evicted or
+    /// expired since. Returns the number of transactions kept.
+    ///
+    /// Does nothing if no journal is set or none of its entries is stale.
+    pub fn compact_journal(&mut self) -> Result<usize, JournalError> {
+        let Some(journal) = self.journal.as_ref() else {
+            return Ok(0);
+        };
+        let live: Vec<Transaction> = journal
+            .hashes()
+            .iter()
+            .filter_map(|hash| self.get(hash).cloned())
+            .collect();
+        if live.len() == journal.len() {
+            return Ok(live.len());
+        }
+        let journal = self.journal.as_mut().expect("checked above");
+        journal.rewrite(&live)?;
+        Ok(live.len())
     }
 
     /// Add a transaction to the pool after validation.
-    pub fn add_transaction(&mut self, tx: Transaction) -> Result<(), MempoolError> {
+    ///
+    /// The sender's expected nonce is read from `state`. A transaction
+    /// continuing the sender's pending run becomes pending; one beyond a
+    /// nonce gap is queued until the gap fills. Transactions that cannot
+    /// pay the current base fee are rejected, as are those whose maximum
+    /// cost the sender's balance cannot cover on top of its other pooled
+    /// transactions.
+    ///
+    /// A transaction for a (sender, nonce) already in the pool replaces the
+    /// pooled one if it raises both its max fee and its priority fee by
+    /// `replace_bump_percent`, and is rejected as underpriced otherwise.
+    pub fn add_transaction(
+        &mut self,
+        tx: Transaction,
+        state: &StateDB,
+    ) -> Result<(), MempoolError> {
         // Basic structural validation
         validate_transaction(&tx)?;
 
-        // Cryptographic signature verification
-        verify_signature(&tx)?;
+        // Cryptographic signature verification, skipped if already cached
+        if !self.verifier.verify(&tx) {
+            return Err(MempoolError::InvalidSignature);
+        }
 
-        // Check pool capacity
-        if self.total_count >= self.config.max_size {
-            return Err(MempoolError::PoolFull);
+        if tx.max_fee_per_gas < self.base_fee {
+            return Err(MempoolError::FeeTooLow {
+                max_fee: tx.max_fee_per_gas,
+                base_fee: self.base_fee,
+            });
         }
 
         // Compute hash for dedup
-        let tx_hash = compute_tx_hash(&tx);
-        if self.known_hashes.contains(&tx_hash) {
+        let tx_hash = tx.hash();
+        if self.known_hashes.contains_key(&tx_hash) {
             return Err(MempoolError::DuplicateTransaction);
         }
 
-        // Check per-account limit
-        let sender_txs = self.by_sender.entry(tx.from).or_default();
-        if sender_txs.len() >= self.config.max_tx_per_account {
-            return Err(MempoolError::PoolFull);
+        let state_nonce = state.get_account(&tx.from).map_or(0, |acct| acct.nonce);
+        if tx.nonce < state_nonce {
+            return Err(MempoolError::NonceTooLow {
+                expected: state_nonce,
+                got: tx.nonce,
+            });
         }
+        self.sync_sender(&tx.from, state_nonce);
 
-        // Use a simple incrementing timestamp (in production, use real time)
-        let added_at = self.total_count as u64;
-        let pending = PendingTransaction::new(tx, added_at);
+        let sender_txs = self.by_sender.get(&tx.from);
+        let existing = sender_txs.and_then(|txs| txs.get(tx.nonce));
+        if let Some(existing) = existing {
+            let percent = self.config.replace_bump_percent;
+            let min_max_fee = bumped_fee(existing.tx.max_fee_per_gas, percent);
+            let min_priority_fee = bumped_fee(existing.tx.max_priority_fee_per_gas, percent);
+            // A fee already at u64::MAX cannot be outbid, so nothing replaces it.
+            let outbids = |fee: u64, min: Option<u64>| min.is_some_and(|min| fee >= min);
+            if !outbids(tx.max_fee_per_gas, min_max_fee)
+                || !outbids(tx.max_priority_fee_per_gas, min_priority_fee)
+            {
+                return Err(MempoolError::ReplacementUnderpriced {
+                    nonce: tx.nonce,
+                    min_max_fee: min_max_fee.unwrap_or(u64::MAX),
+                    min_priority_fee: min_priority_fee.unwrap_or(u64::MAX),
+                });
+            }
+        }
 
-        sender_txs.push(pending);
-        self.known_hashes.insert(tx_hash);
+        // The sender must be able to pay for this and everything else it
+        // has pooled; validation guarantees max_cost does not overflow.
+        let balance = state.get_account(&tx.from).map_or(0, |acct| acct.balance);
+        let pooled_cost = sender_txs.map_or(0, |txs| txs.cost_excluding(tx.nonce));
+        let cost = max_cost(&tx).unwrap_or(u64::MAX);
+        if pooled_cost.saturating_add(cost) > balance {
+            return Err(MempoolError::InsufficientBalance);
+        }
+
+        if existing.is_some() {
+            self.replace_transaction(tx, tx_hash);
+            return Ok(());
+        }
+
+        // Per-account limits, so no sender can take over the pool
+        let next_nonce = sender_txs.map_or(state_nonce, |txs| txs.next_nonce());
+        let is_pending = tx.nonce == next_nonce;
+        if let Some(txs) = sender_txs {
+            if txs.len() >= self.config.max_tx_per_account
+                || (!is_pending && txs.queued.len() >= self.config.max_queued_per_account)
+            {
+                return Err(MempoolError::PoolFull);
+            }
+        }
+
+        // When full, make room by dropping a lower-priority transaction
+        if self.total_count >= self.config.max_size {
+            let rank = (is_pending, tx.effective_tip(self.base_fee));
+            match self.eviction_victim(&tx.from) {
+                Some((sender, nonce, victim_rank)) if victim_rank < rank => {
+                    self.evict_outbid(&sender, nonce);
+                }
+                _ => return Err(MempoolError::PoolFull),
+            }
+        }
+
+        let ptx = PendingTransaction::new(tx, now_millis(), self.next_seq, self.base_fee);
+        self.next_seq += 1;
+        let sender = ptx.tx.from;
+        let nonce = ptx.tx.nonce;
+        self.by_sender
+            .entry(sender)
+            .or_insert_with(|| SenderTxs::new(state_nonce))
+            .insert(ptx);
+        self.known_hashes.insert(tx_hash, (sender, nonce));
         self.total_count += 1;
 
         debug!(tx_hash = ?tx_hash, total = self.total_count, "transaction added to mempool");
@@ -68,77 +483,300 @@ impl TransactionPool {
         Ok(())
     }
 
-    /// Remove transactions that have been committed in a block.
-    pub fn remove_committed(&mut self, tx_hashes: &[[u8; 32]]) {
-        let remove_set: HashSet<[u8; 32]> = tx_hashes.iter().copied().collect();
+    /// Put an already fee-checked transaction in place of the pooled one
+    /// with the same (sender, nonce). The displaced transaction is forgotten,
+    /// so it is no longer deduplicated against, and queued for
+    /// `take_replaced`.
+    fn replace_transaction(&mut self, tx: Transaction, tx_hash: [u8; 32]) {
+        let ptx = PendingTransaction::new(tx, now_millis(), self.next_seq, self.base_fee);
+        self.next_seq += 1;
+        let sender = ptx.tx.from;
+        let nonce = ptx.tx.nonce;
+        let Some(old) = self.by_sender.get_mut(&sender).and_then(|txs| txs.replace(ptx)) else {
+            return;
+        };
+        let old_hash = old.tx.hash();
+        self.known_hashes.remove(&old_hash);
+        self.known_hashes.insert(tx_hash, (sender, nonce));
+        self.replaced.push(old_hash);
 
-        for sender_txs in self.by_sender.values_mut() {
-            let before = sender_txs.len();
-            sender_txs.retain(|ptx| {
-                let hash = compute_tx_hash(&ptx.tx);
-                !remove_set.contains(&hash)
-            });
-            self.total_count -= before - sender_txs.len();
+        debug!(
+            tx_hash = ?tx_hash,
+            replaced = ?old_hash,
+            nonce,
+            "transaction replaced by fee in mempool"
+        );
+    }
+
+    /// Pick the transaction to drop for a newcomer from `exclude` when the
+    /// pool is full.
+    ///
+    /// Only each other sender's highest-nonce transaction is considered,
+    /// so no sender is left with a nonce gap. Queued transactions go before
+    /// pending ones, then the lowest tip, and on a tie the sender holding
+    /// the most transactions gives one up. Returns the victim's sender,
+    /// nonce and rank as (is pending, effective tip).
+    fn eviction_victim(&self, exclude: &[u8; 32]) -> Option<([u8; 32], u64, (bool, u64))> {
+        self.by_sender
+            .iter()
+            .filter(|(sender, _)| *sender != exclude)
+            .filter_map(|(sender, txs)| {
+                let (ptx, is_pending) = txs.tail()?;
+                let rank = (is_pending, ptx.fee_priority);
+                Some((rank, Reverse(txs.len()), *sender, ptx.tx.nonce))
+            })
+            .min()
+            .map(|(rank, _, sender, nonce)| (sender, nonce, rank))
+    }
+
+    /// Drop a sender's tail transaction to make room for a better one.
+    fn evict_outbid(&mut self, sender: &[u8; 32], nonce: u64) {
+        let Some(sender_txs) = self.by_sender.get_mut(sender) else {
+            return;
+        };
+        let Some(ptx) = sender_txs.remove(nonce) else {
+            return;
+        };
+        if sender_txs.is_empty() {
+            self.by_sender.remove(sender);
         }
+        let tx_hash = ptx.tx.hash();
+        self.known_hashes.remove(&tx_hash);
+        self.total_count -= 1;
+        debug!(
+            tx_hash = ?tx_hash,
+            nonce,
+            reason = %EvictionReason::Outbid,
+            "evicted transaction from full mempool"
+        );
+    }
 
-        // Clean up empty sender entries
+    /// Drop every transaction that has been pooled for longer than the
+    /// configured time-to-live as of `now_ms` (milliseconds since the Unix
+    /// epoch). Later transactions from the same sender stay pooled, queued
+    /// behind the gap.
+    pub fn expire(&mut self, now_ms: u64) -> Vec<EvictedTransaction> {
+        let ttl_ms = self.config.tx_ttl.as_millis() as u64;
+        let mut evicted = Vec::new();
+        for (sender, sender_txs) in self.by_sender.iter_mut() {
+            let expired: Vec<u64> = sender_txs
+                .pending
+                .values()
+                .chain(sender_txs.queued.values())
+                .filter(|ptx| ptx.added_at.saturating_add(ttl_ms) <= now_ms)
+                .map(|ptx| ptx.tx.nonce)
+                .collect();
+            for nonce in expired {
+                if let Some(ptx) = sender_txs.remove(nonce) {
+                    evicted.push(EvictedTransaction {
+                        tx_hash: ptx.tx.hash(),
+                        sender: *sender,
+                        nonce,
+                        reason: EvictionReason::Expired,
+                    });
+                }
+            }
+        }
+
+        for eviction in &evicted {
+            self.known_hashes.remove(&eviction.tx_hash);
+            self.total_count -= 1;
+        }
         self.by_sender.retain(|_, txs| !txs.is_empty());
 
-        // Remove from known hashes
+        if !evicted.is_empty() {
+            debug!(expired = evicted.len(), total = self.total_count, "mempool expired transactions");
+        }
+        evicted
+    }
+
+    /// Drain the hashes of transactions displaced by replace-by-fee since
+    /// the last call. The node withdraws these from gossip.
+    pub fn take_replaced(&mut self) -> Vec<[u8; 32]> {
+        std::mem::take(&mut self.replaced)
+    }
+
+    /// Remove transactions that have been committed in a block, then
+    /// revalidate every sender against `state`.
+    ///
+    /// Transactions made stale by the block are evicted, as are those the
+    /// sender can no longer afford, and queued ones whose gap closed are
+    /// promoted. Returns what was evicted and why; committed transactions
+    /// are not included.
+    pub fn remove_committed(
+        &mut self,
+        tx_hashes: &[[u8; 32]],
+        state: &StateDB,
+    ) -> Vec<EvictedTransaction> {
         for hash in tx_hashes {
-            self.known_hashes.remove(hash);
+            let Some((sender, nonce)) = self.known_hashes.remove(hash) else {
+                continue;
+            };
+            if let Some(sender_txs) = self.by_sender.get_mut(&sender) {
+                if sender_txs.remove(nonce).is_some() {
+                    self.total_count -= 1;
+                }
+            }
+        }
+
+        let mut evicted = Vec::new();
+        let senders: Vec<[u8; 32]> = self.by_sender.keys().copied().collect();
+        for sender in senders {
+            evicted.extend(self.revalidate_sender(&sender, state));
         }
+
+        // Clean up empty sender entries
+        self.by_sender.retain(|_, txs| !txs.is_empty());
+
+        if !evicted.is_empty() {
+            debug!(evicted = evicted.len(), total = self.total_count, "mempool revalidated");
+        }
+        evicted
     }
 
-    /// Get pending transactions ordered by fee priority (highest first), up to `max_count`.
-    pub fn get_pending_ordered(&self, max_count: usize) -> Vec<Transaction> {
-        let mut all_pending: Vec<&PendingTransaction> = self
-            .by_sender
-            .values()
-            .flat_map(|txs| txs.iter())
+    /// Check a sender's pooled transactions against its account in
+    /// `state`, evicting stale nonces and anything it can no longer pay for.
+    fn revalidate_sender(&mut self, sender: &[u8; 32], state: &StateDB) -> Vec<EvictedTransaction> {
+        let Some(sender_txs) = self.by_sender.get_mut(sender) else {
+            return Vec::new();
+        };
+        let (state_nonce, balance) = state
+            .get_account(sender)
+            .map_or((0, 0), |acct| (acct.nonce, acct.balance));
+
+        let stale = sender_txs
+            .set_state_nonce(state_nonce)
+            .into_iter()
+            .map(|ptx| (ptx, EvictionReason::NonceTooLow));
+        let unaffordable = sender_txs
+            .evict_unaffordable(balance)
+            .into_iter()
+            .map(|ptx| (ptx, EvictionReason::InsufficientBalance));
+        let evicted: Vec<EvictedTransaction> = stale
+            .chain(unaffordable)
+            .map(|(ptx, reason)| EvictedTransaction {
+                tx_hash: ptx.tx.hash(),
+                sender: *sender,
+                nonce: ptx.tx.nonce,
+                reason,
+            })
             .collect();
 
-        // Sort by fee_priority descending, then by added_at ascending for tie-breaking
-        all_pending.sort_by(|a, b| {
-            b.fee_priority
-                .cmp(&a.fee_priority)
-                .then(a.added_at.cmp(&b.added_at))
-        });
+        for eviction in &evicted {
+            self.known_hashes.remove(&eviction.tx_hash);
+            self.total_count -= 1;
+        }
+        evicted
+    }
 
-        all_pending
+    /// Re-base a sender on its state nonce, forgetting transactions it has
+    /// made stale.
+    fn sync_sender(&mut self, sender: &[u8; 32], state_nonce: u64) {
+        let Some(sender_txs) = self.by_sender.get_mut(sender) else {
+            return;
+        };
+        for stale in sender_txs.set_state_nonce(state_nonce) {
+            self.known_hashes.remove(&stale.tx.hash());
+            self.total_count -= 1;
+        }
+    }
+
+    /// Select up to `max_count` pending transactions for a block.
+    ///
+    /// Each sender's transactions come out in nonce order. Across senders,
+    /// the next transaction with the highest effective tip is taken first,
+    /// with earlier arrivals winning ties. Queued transactions are never
+    /// selected, and a sender stops at its first transaction whose fee cap
+    /// is below the base fee.
+    pub fn get_pending_ordered(&self, max_count: usize) -> Vec<Transaction> {
+        self.get_pending_for_block(max_count, u64::MAX)
+    }
+
+    /// Like `get_pending_ordered`, but the selected transactions' gas
+    /// limits add up to at most `max_gas`. A sender whose next transaction
+    /// does not fit contributes nothing more.
+    pub fn get_pending_for_block(&self, max_count: usize, max_gas: u64) -> Vec<Transaction> {
+        let base_fee = self.base_fee;
+        let affordable = |ptx: &&PendingTransaction| ptx.tx.max_fee_per_gas >= base_fee;
+        let mut heads: HashMap<[u8; 32], btree_map::Values<'_, u64, PendingTransaction>> =
+            HashMap::new();
+        let mut heap = BinaryHeap::new();
+        for (sender, sender_txs) in &self.by_sender {
+            let mut iter = sender_txs.pending.values();
+            if let Some(ptx) = iter.next().filter(affordable) {
+                heap.push(Candidate {
+                    ptx,
+                    sender: *sender,
+                });
+                heads.insert(*sender, iter);
+            }
+        }
+
+        let mut selected = Vec::with_capacity(max_count.min(self.total_count));
+        let mut gas_left = max_gas;
+        while selected.len() < max_count {
+            let Some(best) = heap.pop() else {
+                break;
+            };
+            let Some(left) = gas_left.checked_sub(best.ptx.tx.gas_limit) else {
+                continue;
+            };
+            gas_left = left;
+            selected.push(best.ptx.tx.clone());
+            if let Some(ptx) = heads
+                .get_mut(&best.sender)
+                .and_then(|iter| iter.next())
+                .filter(affordable)
+            {
+                heap.push(Candidate {
+                    ptx,
+                    sender: best.sender,
+                });
+            }
+        }
+        selected
+    }
+
+    /// Look up pooled transactions by `Transaction::hash()`, as used in
+    /// compact block proposals. Hashes not in the pool are omitted.
+    pub fn get_transactions(&self, tx_hashes: &[[u8; 32]]) -> HashMap<[u8; 32], Transaction> {
+        let wanted: HashSet<&[u8; 32]> = tx_hashes.iter().collect();
+        wanted
             .into_iter()
-            .take(max_count)
-            .map(|ptx| ptx.tx.clone())
+            .filter_map(|hash| Some((*hash, self.get(hash)?.clone())))
             .collect()
     }
 
-    /// Total number of pending transactions.
+    /// The pooled transaction with the given hash, pending or queued.
+    fn get(&self, tx_hash: &[u8; 32]) -> Option<&Transaction> {
+        let (sender, nonce) = self.known_hashes.get(tx_hash)?;
+        self.by_sender.get(sender)?.get(*nonce).map(|ptx| &ptx.tx)
+    }
+
+    /// Number of executable transactions.
     pub fn pending_count(&self) -> usize {
-        self.total_count
+        self.by_sender.values().map(|txs| txs.pending.len()).sum()
+    }
+
+    /// Number of transactions waiting behind a nonce gap.
+    pub fn queued_count(&self) -> usize {
+        self.by_sender.values().map(|txs| txs.queued.len()).sum()
     }
 
     /// Check if a transaction hash is already in the pool.
     pub fn contains(&self, tx_hash: &[u8; 32]) -> bool {
-        self.known_hashes.contains(tx_hash)
+        self.known_hashes.contains_key(tx_hash)
     }
 
     /// Remove all pending transactions.
     pub fn clear(&mut self) {
         self.by_sender.clear();
         self.known_hashes.clear();
+        self.replaced.clear();
         self.total_count = 0;
     }
 }
 
-/// Compute the SHA-256 hash of a serialized transaction.
-pub fn compute_tx_hash(tx: &Transaction) -> [u8; 32] {
-    let encoded = serde_json::to_vec(tx).expect("transaction serialization should never fail");
-    let digest = Sha256::digest(&encoded);
-    let mut hash = [0u8; 32];
-    hash.copy_from_slice(&digest);
-    hash
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,6 +791,9 @@ mod tests {
             nonce,
             signature: vec![0u8; 64], // dummy sig for non-pool tests (hashing, structural rejection)
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         }
     }
 
@@ -172,6 +813,9 @@ mod tests {
             nonce,
             signature: vec![],
             data,
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         let message = crate::validation::build_signing_message(&tx);
@@ -180,6 +824,26 @@ mod tests {
         tx
     }
 
+    /// Helper: a signed transfer with the given fee cap and tip.
+    fn make_fee_tx(signing_key: &SigningKey, nonce: u64, max_fee: u64, tip: u64) -> Transaction {
+        let mut tx = make_real_signed_tx(signing_key, [2u8; 32], 100, nonce);
+        tx.max_fee_per_gas = max_fee;
+        tx.max_priority_fee_per_gas = tip;
+        tx.sign(signing_key);
+        tx
+    }
+
+    /// Helper: a state where each key's account can pay for plenty of
+    /// transactions.
+    fn funded(keys: &[&SigningKey]) -> StateDB {
+        let mut state = StateDB::new();
+        for sk in keys {
+            let account = trv1_state::AccountState::new(100_000_000);
+            state.set_account(sk.verifying_key().to_bytes(), account);
+        }
+        state
+    }
+
     fn default_pool() -> TransactionPool {
         TransactionPool::new(MempoolConfig::default())
     }
@@ -188,6 +852,7 @@ mod tests {
         TransactionPool::new(MempoolConfig {
             max_size: 3,
             max_tx_per_account: 2,
+            ..MempoolConfig::default()
         })
     }
 
@@ -195,8 +860,9 @@ mod tests {
     fn test_add_and_count() {
         let mut pool = default_pool();
         let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
         let tx = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
-        assert!(pool.add_transaction(tx).is_ok());
+        assert!(pool.add_transaction(tx, &state).is_ok());
         assert_eq!(pool.pending_count(), 1);
     }
 
@@ -204,9 +870,10 @@ mod tests {
     fn test_dedup_detection() {
         let mut pool = default_pool();
         let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
         let tx = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
-        assert!(pool.add_transaction(tx.clone()).is_ok());
-        let err = pool.add_transaction(tx).unwrap_err();
+        assert!(pool.add_transaction(tx.clone(), &state).is_ok());
+        let err = pool.add_transaction(tx, &state).unwrap_err();
         assert_eq!(err, MempoolError::DuplicateTransaction);
         assert_eq!(pool.pending_count(), 1);
     }
@@ -218,12 +885,13 @@ mod tests {
         let sk2 = SigningKey::generate(&mut OsRng);
         let sk3 = SigningKey::generate(&mut OsRng);
         let sk4 = SigningKey::generate(&mut OsRng);
-        pool.add_transaction(make_real_signed_tx(&sk1, [2u8; 32], 100, 0)).unwrap();
-        pool.add_transaction(make_real_signed_tx(&sk2, [3u8; 32], 200, 0)).unwrap();
-        pool.add_transaction(make_real_signed_tx(&sk3, [4u8; 32], 300, 0)).unwrap();
+        let state = funded(&[&sk1, &sk2, &sk3, &sk4]);
+        pool.add_transaction(make_real_signed_tx(&sk1, [2u8; 32], 100, 0), &state).unwrap();
+        pool.add_transaction(make_real_signed_tx(&sk2, [3u8; 32], 200, 0), &state).unwrap();
+        pool.add_transaction(make_real_signed_tx(&sk3, [4u8; 32], 300, 0), &state).unwrap();
 
         let err = pool
-            .add_transaction(make_real_signed_tx(&sk4, [5u8; 32], 400, 0))
+            .add_transaction(make_real_signed_tx(&sk4, [5u8; 32], 400, 0), &state)
             .unwrap_err();
         assert_eq!(err, MempoolError::PoolFull);
     }
@@ -232,12 +900,13 @@ mod tests {
     fn test_per_account_limit() {
         let mut pool = small_pool();
         let sk = SigningKey::generate(&mut OsRng);
-        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 0)).unwrap();
-        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 200, 1)).unwrap();
+        let state = funded(&[&sk]);
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 0), &state).unwrap();
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 200, 1), &state).unwrap();
 
         // Third tx from same sender exceeds per-account limit of 2
         let err = pool
-            .add_transaction(make_real_signed_tx(&sk, [2u8; 32], 300, 2))
+            .add_transaction(make_real_signed_tx(&sk, [2u8; 32], 300, 2), &state)
             .unwrap_err();
         assert_eq!(err, MempoolError::PoolFull);
     }
@@ -248,15 +917,34 @@ mod tests {
         let sk1 = SigningKey::generate(&mut OsRng);
         let sk2 = SigningKey::generate(&mut OsRng);
         let sk3 = SigningKey::generate(&mut OsRng);
-        pool.add_transaction(make_real_signed_tx(&sk1, [2u8; 32], 10, 0)).unwrap();
-        pool.add_transaction(make_real_signed_tx(&sk2, [3u8; 32], 500, 0)).unwrap();
-        pool.add_transaction(make_real_signed_tx(&sk3, [4u8; 32], 100, 0)).unwrap();
+        let state = funded(&[&sk1, &sk2, &sk3]);
+        pool.add_transaction(make_fee_tx(&sk1, 0, 20, 1), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk2, 0, 20, 5), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk3, 0, 20, 3), &state).unwrap();
 
         let ordered = pool.get_pending_ordered(10);
-        assert_eq!(ordered.len(), 3);
-        assert_eq!(ordered[0].amount, 500); // highest priority first
-        assert_eq!(ordered[1].amount, 100);
-        assert_eq!(ordered[2].amount, 10);
+        let tips: Vec<u64> = ordered.iter().map(|tx| tx.max_priority_fee_per_gas).collect();
+        assert_eq!(tips, vec![5, 3, 1]); // highest tip first
+    }
+
+    #[test]
+    fn test_amount_does_not_buy_priority() {
+        let mut pool = default_pool();
+        let whale = SigningKey::generate(&mut OsRng);
+        let tipper = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&whale, &tipper]);
+        let mut big = make_real_signed_tx(&whale, [2u8; 32], 1_000_000, 0);
+        big.max_priority_fee_per_gas = 1;
+        big.sign(&whale);
+        let mut small = make_real_signed_tx(&tipper, [3u8; 32], 1, 0);
+        small.max_priority_fee_per_gas = 2;
+        small.sign(&tipper);
+        pool.add_transaction(big, &state).unwrap();
+        pool.add_transaction(small, &state).unwrap();
+
+        let ordered = pool.get_pending_ordered(10);
+        assert_eq!(ordered[0].amount, 1);
+        assert_eq!(ordered[1].amount, 1_000_000);
     }
 
     #[test]
@@ -265,42 +953,61 @@ mod tests {
         let sk1 = SigningKey::generate(&mut OsRng);
         let sk2 = SigningKey::generate(&mut OsRng);
         let sk3 = SigningKey::generate(&mut OsRng);
-        pool.add_transaction(make_real_signed_tx(&sk1, [2u8; 32], 10, 0)).unwrap();
-        pool.add_transaction(make_real_signed_tx(&sk2, [3u8; 32], 500, 0)).unwrap();
-        pool.add_transaction(make_real_signed_tx(&sk3, [4u8; 32], 100, 0)).unwrap();
+        let state = funded(&[&sk1, &sk2, &sk3]);
+        pool.add_transaction(make_fee_tx(&sk1, 0, 20, 1), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk2, 0, 20, 5), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk3, 0, 20, 3), &state).unwrap();
 
         let ordered = pool.get_pending_ordered(2);
         assert_eq!(ordered.len(), 2);
-        assert_eq!(ordered[0].amount, 500);
-        assert_eq!(ordered[1].amount, 100);
+        assert_eq!(ordered[0].max_priority_fee_per_gas, 5);
+        assert_eq!(ordered[1].max_priority_fee_per_gas, 3);
     }
 
     #[test]
     fn test_contains() {
         let mut pool = default_pool();
         let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
         let tx = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
-        let hash = compute_tx_hash(&tx);
+        let hash = tx.hash();
         assert!(!pool.contains(&hash));
-        pool.add_transaction(tx).unwrap();
+        pool.add_transaction(tx, &state).unwrap();
         assert!(pool.contains(&hash));
     }
 
+    #[test]
+    fn test_get_transactions_by_block_hash() {
+        let mut pool = default_pool();
+        let sk1 = SigningKey::generate(&mut OsRng);
+        let sk2 = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk1, &sk2]);
+        let tx1 = make_real_signed_tx(&sk1, [2u8; 32], 100, 0);
+        let tx2 = make_real_signed_tx(&sk2, [3u8; 32], 200, 0);
+        pool.add_transaction(tx1.clone(), &state).unwrap();
+        pool.add_transaction(tx2, &state).unwrap();
+
+        let found = pool.get_transactions(&[tx1.hash(), [0xEE; 32]]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[&tx1.hash()].amount, 100);
+    }
+
     #[test]
     fn test_remove_committed() {
         let mut pool = default_pool();
         let sk1 = SigningKey::generate(&mut OsRng);
         let sk2 = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk1, &sk2]);
         let tx1 = make_real_signed_tx(&sk1, [2u8; 32], 100, 0);
         let tx2 = make_real_signed_tx(&sk2, [3u8; 32], 200, 0);
-        let hash1 = compute_tx_hash(&tx1);
-        let hash2 = compute_tx_hash(&tx2);
+        let hash1 = tx1.hash();
+        let hash2 = tx2.hash();
 
-        pool.add_transaction(tx1).unwrap();
-        pool.add_transaction(tx2).unwrap();
+        pool.add_transaction(tx1, &state).unwrap();
+        pool.add_transaction(tx2, &state).unwrap();
         assert_eq!(pool.pending_count(), 2);
 
-        pool.remove_committed(&[hash1]);
+        pool.remove_committed(&[hash1], &state);
         assert_eq!(pool.pending_count(), 1);
         assert!(!pool.contains(&hash1));
         assert!(pool.contains(&hash2));
@@ -311,8 +1018,9 @@ mod tests {
         let mut pool = default_pool();
         let sk1 = SigningKey::generate(&mut OsRng);
         let sk2 = SigningKey::generate(&mut OsRng);
-        pool.add_transaction(make_real_signed_tx(&sk1, [2u8; 32], 100, 0)).unwrap();
-        pool.add_transaction(make_real_signed_tx(&sk2, [3u8; 32], 200, 0)).unwrap();
+        let state = funded(&[&sk1, &sk2]);
+        pool.add_transaction(make_real_signed_tx(&sk1, [2u8; 32], 100, 0), &state).unwrap();
+        pool.add_transaction(make_real_signed_tx(&sk2, [3u8; 32], 200, 0), &state).unwrap();
         assert_eq!(pool.pending_count(), 2);
 
         pool.clear();
@@ -322,17 +1030,19 @@ mod tests {
     #[test]
     fn test_reject_zero_sender() {
         let mut pool = default_pool();
+        let state = StateDB::new();
         let tx = make_tx([0u8; 32], [2u8; 32], 100, 0);
-        let err = pool.add_transaction(tx).unwrap_err();
+        let err = pool.add_transaction(tx, &state).unwrap_err();
         assert!(matches!(err, MempoolError::InvalidTransaction(_)));
     }
 
     #[test]
     fn test_reject_empty_signature() {
         let mut pool = default_pool();
+        let state = StateDB::new();
         let mut tx = make_tx([1u8; 32], [2u8; 32], 100, 0);
         tx.signature = vec![]; // empty
-        let err = pool.add_transaction(tx).unwrap_err();
+        let err = pool.add_transaction(tx, &state).unwrap_err();
         assert!(matches!(err, MempoolError::InvalidTransaction(_)));
     }
 
@@ -340,43 +1050,560 @@ mod tests {
     fn test_reject_invalid_signature() {
         let mut pool = default_pool();
         let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
         let mut tx = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
         tx.amount = 999; // tamper with transaction after signing
-        let err = pool.add_transaction(tx).unwrap_err();
+        let err = pool.add_transaction(tx, &state).unwrap_err();
         assert_eq!(err, MempoolError::InvalidSignature);
     }
 
     #[test]
-    fn test_compute_tx_hash_deterministic() {
-        let tx = make_tx([1u8; 32], [2u8; 32], 100, 0);
-        let h1 = compute_tx_hash(&tx);
-        let h2 = compute_tx_hash(&tx);
-        assert_eq!(h1, h2);
+    fn test_real_signed_tx_in_pool() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        let tx = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
+        assert!(pool.add_transaction(tx, &state).is_ok());
+        assert_eq!(pool.pending_count(), 1);
     }
 
     #[test]
-    fn test_compute_tx_hash_different_txs() {
-        let tx1 = make_tx([1u8; 32], [2u8; 32], 100, 0);
-        let tx2 = make_tx([1u8; 32], [2u8; 32], 200, 0);
-        assert_ne!(compute_tx_hash(&tx1), compute_tx_hash(&tx2));
+    fn test_remove_nonexistent_is_noop() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 0), &state).unwrap();
+        let fake_hash = [0xffu8; 32];
+        pool.remove_committed(&[fake_hash], &state);
+        assert_eq!(pool.pending_count(), 1);
+    }
+
+    /// Helper: a state where `sk`'s account has the given nonce.
+    fn state_with_nonce(sk: &SigningKey, nonce: u64) -> StateDB {
+        let mut state = StateDB::new();
+        state.set_account(
+            sk.verifying_key().to_bytes(),
+            trv1_state::AccountState {
+                balance: 1_000_000,
+                nonce,
+            },
+        );
+        state
     }
 
     #[test]
-    fn test_real_signed_tx_in_pool() {
+    fn test_nonce_below_state_rejected() {
         let mut pool = default_pool();
         let sk = SigningKey::generate(&mut OsRng);
-        let tx = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
-        assert!(pool.add_transaction(tx).is_ok());
+        let state = state_with_nonce(&sk, 3);
+        let err = pool
+            .add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 2), &state)
+            .unwrap_err();
+        assert_eq!(err, MempoolError::NonceTooLow { expected: 3, got: 2 });
+        assert!(pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 3), &state).is_ok());
         assert_eq!(pool.pending_count(), 1);
     }
 
     #[test]
-    fn test_remove_nonexistent_is_noop() {
+    fn test_gapped_tx_queued_until_gap_fills() {
         let mut pool = default_pool();
         let sk = SigningKey::generate(&mut OsRng);
-        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 0)).unwrap();
-        let fake_hash = [0xffu8; 32];
-        pool.remove_committed(&[fake_hash]);
-        assert_eq!(pool.pending_count(), 1);
+        let state = funded(&[&sk]);
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 2), &state).unwrap();
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 1), &state).unwrap();
+        assert_eq!(pool.pending_count(), 0);
+        assert_eq!(pool.queued_count(), 2);
+        assert!(pool.get_pending_ordered(10).is_empty());
+
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 0), &state).unwrap();
+        assert_eq!(pool.pending_count(), 3);
+        assert_eq!(pool.queued_count(), 0);
+        let nonces: Vec<u64> = pool.get_pending_ordered(10).iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_underpriced_replacement_rejected() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        let original = make_fee_tx(&sk, 0, 100, 10);
+        pool.add_transaction(original.clone(), &state).unwrap();
+
+        // Same fees, different payload.
+        let err = pool
+            .add_transaction(make_real_signed_tx(&sk, [3u8; 32], 100, 0), &state)
+            .unwrap_err();
+        let underpriced = MempoolError::ReplacementUnderpriced {
+            nonce: 0,
+            min_max_fee: 110,
+            min_priority_fee: 11,
+        };
+        assert_eq!(err, underpriced);
+
+        // Bumping only one of the two fees is not enough.
+        let err = pool.add_transaction(make_fee_tx(&sk, 0, 200, 10), &state).unwrap_err();
+        assert_eq!(err, underpriced);
+        let err = pool.add_transaction(make_fee_tx(&sk, 0, 109, 11), &state).unwrap_err();
+        assert_eq!(err, underpriced);
+
+        assert!(pool.contains(&original.hash()));
+        assert!(pool.take_replaced().is_empty());
+    }
+
+    #[test]
+    fn test_replace_by_fee() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        let original = make_fee_tx(&sk, 0, 100, 10);
+        let next = make_fee_tx(&sk, 1, 100, 10);
+        pool.add_transaction(original.clone(), &state).unwrap();
+        pool.add_transaction(next.clone(), &state).unwrap();
+
+        let replacement = make_fee_tx(&sk, 0, 110, 11);
+        pool.add_transaction(replacement.clone(), &state).unwrap();
+        assert_eq!((pool.pending_count(), pool.queued_count()), (2, 0));
+        assert!(pool.contains(&replacement.hash()));
+        assert!(!pool.contains(&original.hash()), "replaced tx leaves the dedup set");
+        assert_eq!(pool.take_replaced(), vec![original.hash()]);
+        assert!(pool.take_replaced().is_empty());
+
+        let nonces: Vec<(u64, u64)> = pool
+            .get_pending_ordered(10)
+            .iter()
+            .map(|tx| (tx.nonce, tx.max_fee_per_gas))
+            .collect();
+        assert_eq!(nonces, vec![(0, 110), (1, 100)]);
+
+        // The displaced tx coming back, e.g. from a slow peer, is underpriced.
+        let err = pool.add_transaction(original, &state).unwrap_err();
+        assert!(matches!(err, MempoolError::ReplacementUnderpriced { nonce: 0, .. }));
+    }
+
+    #[test]
+    fn test_replacement_ignores_capacity_and_keeps_queued() {
+        let mut pool = small_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        pool.add_transaction(make_fee_tx(&sk, 0, 100, 10), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk, 2, 100, 10), &state).unwrap();
+
+        // At the per-account limit, a replacement still gets in.
+        let queued = make_fee_tx(&sk, 2, 200, 20);
+        pool.add_transaction(queued.clone(), &state).unwrap();
+        assert_eq!((pool.pending_count(), pool.queued_count()), (1, 1));
+        assert!(pool.contains(&queued.hash()));
+    }
+
+    #[test]
+    fn test_ordering_respects_sender_nonce() {
+        let mut pool = default_pool();
+        let sk1 = SigningKey::generate(&mut OsRng);
+        let sk2 = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk1, &sk2]);
+        // sk1's high-tip nonce 1 must wait for its low-tip nonce 0.
+        pool.add_transaction(make_fee_tx(&sk1, 1, 20, 9), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk1, 0, 20, 1), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk2, 0, 20, 4), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk2, 1, 20, 2), &state).unwrap();
+
+        let ordered = pool.get_pending_ordered(10);
+        let tips: Vec<u64> = ordered.iter().map(|tx| tx.max_priority_fee_per_gas).collect();
+        assert_eq!(tips, vec![4, 2, 1, 9]);
+
+        let top = pool.get_pending_ordered(1);
+        assert_eq!(top[0].max_priority_fee_per_gas, 4);
+    }
+
+    #[test]
+    fn test_block_selection_fits_gas() {
+        let mut pool = default_pool();
+        let sk1 = SigningKey::generate(&mut OsRng);
+        let sk2 = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk1, &sk2]);
+        let mut heavy = make_fee_tx(&sk1, 0, 20, 9);
+        heavy.gas_limit = 50_000;
+        heavy.sign(&sk1);
+        pool.add_transaction(heavy, &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk1, 1, 20, 9), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk2, 0, 20, 1), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&sk2, 1, 20, 1), &state).unwrap();
+
+        // The heavy transaction does not fit, and its sender's next one
+        // cannot run without it.
+        let selected = pool.get_pending_for_block(10, 49_999);
+        assert_eq!(selected.len(), 2);
+        assert!(selected.iter().all(|tx| tx.from == sk2.verifying_key().to_bytes()));
+
+        let selected = pool.get_pending_for_block(10, 71_000);
+        let gas: Vec<u64> = selected.iter().map(|tx| tx.gas_limit).collect();
+        assert_eq!(gas, vec![50_000, 21_000]);
+    }
+
+    #[test]
+    fn test_max_fee_below_base_fee_rejected() {
+        let mut pool = default_pool();
+        pool.set_base_fee(10);
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        let err = pool.add_transaction(make_fee_tx(&sk, 0, 9, 1), &state).unwrap_err();
+        assert_eq!(err, MempoolError::FeeTooLow { max_fee: 9, base_fee: 10 });
+        assert!(pool.add_transaction(make_fee_tx(&sk, 0, 10, 1), &state).is_ok());
+    }
+
+    #[test]
+    fn test_base_fee_change_reprioritises() {
+        let mut pool = default_pool();
+        let capped = SigningKey::generate(&mut OsRng);
+        let roomy = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&capped, &roomy]);
+        pool.add_transaction(make_fee_tx(&capped, 0, 10, 5), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&roomy, 0, 100, 3), &state).unwrap();
+        assert_eq!(pool.get_pending_ordered(1)[0].from, capped.verifying_key().to_bytes());
+
+        // At base fee 8 the capped tx only has 2 of headroom left to tip.
+        pool.set_base_fee(8);
+        assert_eq!(pool.base_fee(), 8);
+        assert_eq!(pool.get_pending_ordered(1)[0].from, roomy.verifying_key().to_bytes());
+
+        // Above its cap it is held back but kept for when the fee falls.
+        pool.set_base_fee(11);
+        let selected = pool.get_pending_ordered(10);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].from, roomy.verifying_key().to_bytes());
+        assert_eq!(pool.pending_count(), 2);
+
+        pool.set_base_fee(1);
+        assert_eq!(pool.get_pending_ordered(10).len(), 2);
+    }
+
+    #[test]
+    fn test_commit_advances_nonce_and_drops_stale() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = state_with_nonce(&sk, 0);
+        let tx0 = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
+        let tx1 = make_real_signed_tx(&sk, [2u8; 32], 100, 1);
+        let tx3 = make_real_signed_tx(&sk, [2u8; 32], 100, 3);
+        pool.add_transaction(tx0.clone(), &state).unwrap();
+        pool.add_transaction(tx1.clone(), &state).unwrap();
+        pool.add_transaction(tx3.clone(), &state).unwrap();
+        assert_eq!((pool.pending_count(), pool.queued_count()), (2, 1));
+
+        // A block executed nonces 0..=2, the last one from outside the pool.
+        pool.remove_committed(&[tx0.hash()], &state_with_nonce(&sk, 3));
+        assert!(!pool.contains(&tx1.hash()), "stale nonce should be dropped");
+        assert!(pool.contains(&tx3.hash()));
+        assert_eq!((pool.pending_count(), pool.queued_count()), (1, 0));
+    }
+
+    #[test]
+    fn test_removing_pending_tx_requeues_later_nonces() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        let txs: Vec<Transaction> = (0..3)
+            .map(|n| make_real_signed_tx(&sk, [2u8; 32], 100, n))
+            .collect();
+        for tx in &txs {
+            pool.add_transaction(tx.clone(), &state).unwrap();
+        }
+
+        // Only nonce 1 left the pool; the account is still at nonce 0.
+        pool.remove_committed(&[txs[1].hash()], &state);
+        assert_eq!((pool.pending_count(), pool.queued_count()), (1, 1));
+        let nonces: Vec<u64> = pool.get_pending_ordered(10).iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![0]);
+    }
+
+    /// Helper: a state where `sk`'s account has the given balance.
+    fn state_with_balance(sk: &SigningKey, balance: u64) -> StateDB {
+        let mut state = StateDB::new();
+        state.set_account(sk.verifying_key().to_bytes(), trv1_state::AccountState::new(balance));
+        state
+    }
+
+    #[test]
+    fn test_unfunded_sender_rejected() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let tx = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
+        let err = pool.add_transaction(tx.clone(), &StateDB::new()).unwrap_err();
+        assert_eq!(err, MempoolError::InsufficientBalance);
+
+        // Amount plus the whole gas limit at the fee cap: 100 + 21_000 * 10.
+        assert!(pool.add_transaction(tx.clone(), &state_with_balance(&sk, 210_099)).is_err());
+        assert!(pool.add_transaction(tx, &state_with_balance(&sk, 210_100)).is_ok());
+    }
+
+    #[test]
+    fn test_payload_stake_counts_toward_cost() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let mut tx = make_real_signed_tx(&sk, [2u8; 32], 0, 0);
+        tx.data = trv1_state::TxPayload::Stake {
+            amount: 5_000,
+            tier: trv1_staking::LockTier::NoLock,
+        }
+        .to_data();
+        tx.gas_limit = 100_000;
+        tx.sign(&sk);
+
+        // Gas at the fee cap alone is 1_000_000; the stake comes on top.
+        let err = pool
+            .add_transaction(tx.clone(), &state_with_balance(&sk, 1_004_999))
+            .unwrap_err();
+        assert_eq!(err, MempoolError::InsufficientBalance);
+        assert!(pool.add_transaction(tx, &state_with_balance(&sk, 1_005_000)).is_ok());
+    }
+
+    #[test]
+    fn test_balance_counts_other_pooled_transactions() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        // Enough for two transfers of 100 if one is later repriced to a
+        // fee cap of 11, but not for three.
+        let state = state_with_balance(&sk, 210_100 + 231_100);
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 0), &state).unwrap();
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 5), &state).unwrap();
+        let err = pool
+            .add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 1), &state)
+            .unwrap_err();
+        assert_eq!(err, MempoolError::InsufficientBalance);
+
+        // A replacement has to fit alongside the others, not the tx it
+        // replaces.
+        let replacement = make_fee_tx(&sk, 5, 11, 2);
+        pool.add_transaction(replacement, &state).unwrap();
+    }
+
+    #[test]
+    fn test_commit_evicts_unaffordable_with_reason() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = state_with_balance(&sk, 1_000_000);
+        let txs: Vec<Transaction> = (0..3)
+            .map(|n| make_real_signed_tx(&sk, [2u8; 32], 100, n))
+            .collect();
+        for tx in &txs {
+            pool.add_transaction(tx.clone(), &state).unwrap();
+        }
+
+        // Another block spent most of the balance without touching the nonce.
+        let evicted = pool.remove_committed(&[], &state_with_balance(&sk, 2 * 210_100));
+        assert_eq!(
+            evicted,
+            vec![EvictedTransaction {
+                tx_hash: txs[2].hash(),
+                sender: sk.verifying_key().to_bytes(),
+                nonce: 2,
+                reason: EvictionReason::InsufficientBalance,
+            }]
+        );
+        assert!(!pool.contains(&txs[2].hash()));
+        assert_eq!(pool.pending_count(), 2);
+    }
+
+    #[test]
+    fn test_commit_reports_stale_nonce_evictions() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = state_with_nonce(&sk, 0);
+        let tx0 = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
+        let tx1 = make_real_signed_tx(&sk, [2u8; 32], 100, 1);
+        pool.add_transaction(tx0.clone(), &state).unwrap();
+        pool.add_transaction(tx1.clone(), &state).unwrap();
+
+        // tx0 was committed; nonce 1 was used by a tx from outside the pool.
+        let evicted = pool.remove_committed(&[tx0.hash()], &state_with_nonce(&sk, 2));
+        assert_eq!(evicted.len(), 1, "committed transactions are not evictions");
+        assert_eq!(evicted[0].tx_hash, tx1.hash());
+        assert_eq!(evicted[0].reason, EvictionReason::NonceTooLow);
+        assert_eq!(pool.pending_count() + pool.queued_count(), 0);
+    }
+
+    #[test]
+    fn test_added_at_is_wall_clock() {
+        let mut pool = default_pool();
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        let before = now_millis();
+        let tx = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
+        pool.add_transaction(tx, &state).unwrap();
+        let after = now_millis();
+
+        let added_at = pool.by_sender[&sk.verifying_key().to_bytes()].pending[&0].added_at;
+        assert!((before..=after).contains(&added_at));
+    }
+
+    #[test]
+    fn test_expire_after_ttl() {
+        let mut pool = TransactionPool::new(MempoolConfig {
+            tx_ttl: std::time::Duration::from_secs(60),
+            ..MempoolConfig::default()
+        });
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        let tx0 = make_real_signed_tx(&sk, [2u8; 32], 100, 0);
+        pool.add_transaction(tx0.clone(), &state).unwrap();
+        let start = now_millis();
+
+        assert!(pool.expire(start + 59_000).is_empty());
+        assert!(pool.contains(&tx0.hash()));
+
+        // A later nonce arrives just before the first one expires.
+        let arrived = pool.by_sender[&sk.verifying_key().to_bytes()].pending[&0].added_at;
+        let tx1 = make_real_signed_tx(&sk, [2u8; 32], 100, 1);
+        pool.add_transaction(tx1.clone(), &state).unwrap();
+        pool.by_sender
+            .get_mut(&sk.verifying_key().to_bytes())
+            .unwrap()
+            .pending
+            .get_mut(&1)
+            .unwrap()
+            .added_at = arrived + 30_000;
+
+        let expired = pool.expire(arrived + 60_000);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].tx_hash, tx0.hash());
+        assert_eq!(expired[0].reason, EvictionReason::Expired);
+        assert!(!pool.contains(&tx0.hash()));
+        // The survivor now waits behind the gap its predecessor left.
+        assert_eq!((pool.pending_count(), pool.queued_count()), (0, 1));
+
+        assert_eq!(pool.expire(arrived + 90_000).len(), 1);
+        assert_eq!(pool.pending_count() + pool.queued_count(), 0);
+    }
+
+    #[test]
+    fn test_full_pool_evicts_lowest_tip() {
+        let mut pool = small_pool();
+        let keys: Vec<SigningKey> = (0..4).map(|_| SigningKey::generate(&mut OsRng)).collect();
+        let state = funded(&keys.iter().collect::<Vec<_>>());
+        let cheap = make_fee_tx(&keys[0], 0, 20, 1);
+        pool.add_transaction(cheap.clone(), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&keys[1], 0, 20, 3), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&keys[2], 0, 20, 2), &state).unwrap();
+
+        // Paying no more than the cheapest does not get in.
+        let err = pool.add_transaction(make_fee_tx(&keys[3], 0, 20, 1), &state).unwrap_err();
+        assert_eq!(err, MempoolError::PoolFull);
+
+        let better = make_fee_tx(&keys[3], 0, 20, 5);
+        pool.add_transaction(better.clone(), &state).unwrap();
+        assert!(pool.contains(&better.hash()));
+        assert!(!pool.contains(&cheap.hash()), "lowest tip is evicted");
+        assert_eq!(pool.pending_count(), 3);
+    }
+
+    #[test]
+    fn test_full_pool_evicts_sender_tails_and_queued_first() {
+        let mut pool = TransactionPool::new(MempoolConfig {
+            max_size: 3,
+            ..MempoolConfig::default()
+        });
+        let big = SigningKey::generate(&mut OsRng);
+        let other = SigningKey::generate(&mut OsRng);
+        let newcomer = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&big, &other, &newcomer]);
+        // big's nonce 0 pays least, but dropping it would strand nonce 1.
+        pool.add_transaction(make_fee_tx(&big, 0, 20, 1), &state).unwrap();
+        pool.add_transaction(make_fee_tx(&big, 1, 20, 4), &state).unwrap();
+        let gapped = make_fee_tx(&other, 5, 20, 9);
+        pool.add_transaction(gapped.clone(), &state).unwrap();
+
+        // The queued tx can't execute, so it goes first despite its tip.
+        pool.add_transaction(make_fee_tx(&newcomer, 0, 20, 6), &state).unwrap();
+        assert!(!pool.contains(&gapped.hash()));
+
+        // Of the tails (big's tip 4, newcomer's tip 6), big's goes.
+        pool.add_transaction(make_fee_tx(&other, 0, 20, 5), &state).unwrap();
+        let nonces: Vec<u64> = pool
+            .get_pending_ordered(10)
+            .iter()
+            .filter(|tx| tx.from == big.verifying_key().to_bytes())
+            .map(|tx| tx.nonce)
+            .collect();
+        assert_eq!(nonces, vec![0], "big's tail went, its head stayed");
+
+        // A queued newcomer never displaces a pending transaction.
+        let err = pool.add_transaction(make_fee_tx(&newcomer, 7, 20, 20), &state).unwrap_err();
+        assert_eq!(err, MempoolError::PoolFull);
+    }
+
+    #[test]
+    fn test_queued_limit_per_sender() {
+        let mut pool = TransactionPool::new(MempoolConfig {
+            max_queued_per_account: 2,
+            ..MempoolConfig::default()
+        });
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 3), &state).unwrap();
+        pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 4), &state).unwrap();
+        let err = pool
+            .add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, 5), &state)
+            .unwrap_err();
+        assert_eq!(err, MempoolError::PoolFull);
+
+        // Executable transactions are still welcome and promote the queue.
+        for nonce in 0..3 {
+            pool.add_transaction(make_real_signed_tx(&sk, [2u8; 32], 100, nonce), &state).unwrap();
+        }
+        assert_eq!((pool.pending_count(), pool.queued_count()), (5, 0));
+    }
+
+    #[test]
+    fn test_local_transactions_journaled_and_compacted() {
+        let dir = std::env::temp_dir()
+            .join(format!("trv1_pool_journal_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("mempool.journal");
+
+        let mut pool = default_pool();
+        let (journal, _) = Journal::open(&path).unwrap();
+        pool.set_journal(journal);
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let remote = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk, &remote]);
+        let tx0 = make_fee_tx(&sk, 0, 10, 1);
+        let tx1 = make_fee_tx(&sk, 1, 10, 1);
+        let tx1_bumped = make_fee_tx(&sk, 1, 20, 2);
+        pool.add_local_transaction(tx0.clone(), &state).unwrap();
+        pool.add_local_transaction(tx1.clone(), &state).unwrap();
+        pool.add_local_transaction(tx1_bumped.clone(), &state).unwrap();
+        // Gossiped transactions are not journaled.
+        pool.add_transaction(make_fee_tx(&remote, 0, 10, 1), &state).unwrap();
+        assert_eq!(pool.compact_journal().unwrap(), 2, "replaced tx1 dropped");
+
+        pool.remove_committed(&[tx0.hash()], &state);
+        assert_eq!(pool.compact_journal().unwrap(), 1);
+
+        let (_, restored) = Journal::open(&path).unwrap();
+        let hashes: Vec<[u8; 32]> = restored.iter().map(Transaction::hash).collect();
+        assert_eq!(hashes, vec![tx1_bumped.hash()]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_add_transactions_batch() {
+        let verifier = Arc::new(SignatureVerifier::default());
+        let mut pool = default_pool().with_signature_verifier(verifier.clone());
+        let sk = SigningKey::generate(&mut OsRng);
+        let state = funded(&[&sk]);
+
+        let mut txs: Vec<Transaction> = (0..5).map(|n| make_fee_tx(&sk, n, 10, 1)).collect();
+        txs[2].signature[0] ^= 1;
+        let results = pool.add_transactions(txs.clone(), &state);
+        assert_eq!(results[2], Err(MempoolError::InvalidSignature));
+        assert!(results.iter().enumerate().all(|(i, r)| i == 2 || r.is_ok()));
+        assert_eq!((pool.pending_count(), pool.queued_count()), (2, 2));
+
+        // Accepted signatures are cached in the shared verifier.
+        assert!(verifier.is_verified(&txs[0].hash()));
+        assert!(!verifier.is_verified(&txs[2].hash()));
     }
 }
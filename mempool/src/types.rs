@@ -1,3 +1,6 @@
+use std::fmt;
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use trv1_bft::block::Transaction;
@@ -17,6 +20,19 @@ pub enum MempoolError {
     #[error("nonce too low: expected {expected}, got {got}")]
     NonceTooLow { expected: u64, got: u64 },
 
+    #[error(
+        "replacement for nonce {nonce} underpriced: needs max fee per gas >= {min_max_fee} \
+         and max priority fee per gas >= {min_priority_fee}"
+    )]
+    ReplacementUnderpriced {
+        nonce: u64,
+        min_max_fee: u64,
+        min_priority_fee: u64,
+    },
+
+    #[error("max fee per gas {max_fee} is below the current base fee {base_fee}")]
+    FeeTooLow { max_fee: u64, base_fee: u64 },
+
     #[error("insufficient balance")]
     InsufficientBalance,
 
@@ -29,8 +45,19 @@ pub enum MempoolError {
 pub struct MempoolConfig {
     /// Maximum number of transactions in the pool.
     pub max_size: usize,
-    /// Maximum number of pending transactions per account.
+    /// Maximum number of transactions per account, pending and queued.
     pub max_tx_per_account: usize,
+    /// Maximum number of nonce-gapped transactions per account. These
+    /// cannot execute yet, so each sender gets only a small share.
+    pub max_queued_per_account: usize,
+    /// Percentage by which a transaction must raise both the max fee and
+    /// the priority fee of a pooled transaction to replace it at the same
+    /// (sender, nonce).
+    pub replace_bump_percent: u64,
+    /// How long a transaction may wait in the pool before it expires.
+    pub tx_ttl: Duration,
+    /// How often the node sweeps the pool for expired transactions.
+    pub expiry_interval: Duration,
 }
 
 impl Default for MempoolConfig {
@@ -38,28 +65,86 @@ impl Default for MempoolConfig {
         Self {
             max_size: 10_000,
             max_tx_per_account: 100,
+            max_queued_per_account: 16,
+            replace_bump_percent: 10,
+            tx_ttl: Duration::from_secs(3 * 60 * 60),
+            expiry_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// The smallest fee that outbids `old` by `percent`, rounded up and always
+/// strictly above `old`. `None` when no `u64` fee can outbid `old`.
+pub(crate) fn bumped_fee(old: u64, percent: u64) -> Option<u64> {
+    let bump = old.saturating_mul(percent).div_ceil(100).max(1);
+    old.checked_add(bump)
+}
+
+/// Why a pooled transaction was evicted when the pool was revalidated
+/// against a newly committed state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionReason {
+    /// The sender's account nonce moved past the transaction's nonce.
+    NonceTooLow,
+    /// The sender can no longer cover this transaction on top of its
+    /// lower-nonce pooled transactions.
+    InsufficientBalance,
+    /// The transaction outlived the pool's time-to-live.
+    Expired,
+    /// The pool was full and a higher-priority transaction took its place.
+    Outbid,
+}
+
+impl fmt::Display for EvictionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvictionReason::NonceTooLow => write!(f, "nonce too low"),
+            EvictionReason::InsufficientBalance => write!(f, "insufficient balance"),
+            EvictionReason::Expired => write!(f, "expired"),
+            EvictionReason::Outbid => write!(f, "outbid while the pool was full"),
         }
     }
 }
 
+/// A transaction dropped from the pool without being committed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvictedTransaction {
+    pub tx_hash: [u8; 32],
+    pub sender: [u8; 32],
+    pub nonce: u64,
+    pub reason: EvictionReason,
+}
+
 /// A transaction waiting in the mempool with metadata.
 #[derive(Debug, Clone)]
 pub struct PendingTransaction {
     pub tx: Transaction,
+    /// Wall-clock arrival time, in milliseconds since the Unix epoch.
     pub added_at: u64,
+    /// Arrival order within the pool, breaking ties between transactions
+    /// that arrived in the same millisecond.
+    pub seq: u64,
+    /// Effective tip per gas at the base fee last seen by the pool.
     pub fee_priority: u64,
 }
 
 impl PendingTransaction {
-    /// Wrap a transaction with mempool metadata.
-    pub fn new(tx: Transaction, added_at: u64) -> Self {
-        let fee_priority = tx.amount;
+    /// Wrap a transaction with mempool metadata, prioritised by the tip it
+    /// pays at `base_fee`.
+    pub fn new(tx: Transaction, added_at: u64, seq: u64, base_fee: u64) -> Self {
+        let fee_priority = tx.effective_tip(base_fee);
         Self {
             tx,
             added_at,
+            seq,
             fee_priority,
         }
     }
+
+    /// Recompute the priority after the base fee changed.
+    pub fn reprioritise(&mut self, base_fee: u64) {
+        self.fee_priority = self.tx.effective_tip(base_fee);
+    }
 }
 
 #[cfg(test)]
@@ -71,6 +156,21 @@ mod tests {
         let config = MempoolConfig::default();
         assert_eq!(config.max_size, 10_000);
         assert_eq!(config.max_tx_per_account, 100);
+        assert_eq!(config.max_queued_per_account, 16);
+        assert_eq!(config.replace_bump_percent, 10);
+        assert_eq!(config.tx_ttl, Duration::from_secs(10_800));
+    }
+
+    #[test]
+    fn test_bumped_fee() {
+        assert_eq!(bumped_fee(100, 10), Some(110));
+        assert_eq!(bumped_fee(15, 10), Some(17)); // 1.5 rounds up
+        assert_eq!(bumped_fee(1, 10), Some(2));
+        assert_eq!(bumped_fee(0, 10), Some(1));
+        assert_eq!(bumped_fee(7, 0), Some(8));
+        assert_eq!(bumped_fee(u64::MAX - 1, 0), Some(u64::MAX));
+        assert_eq!(bumped_fee(u64::MAX, 0), None);
+        assert_eq!(bumped_fee(u64::MAX, 10), None);
     }
 
     #[test]
@@ -82,10 +182,21 @@ mod tests {
             nonce: 0,
             signature: vec![0u8; 64],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 3,
         };
-        let ptx = PendingTransaction::new(tx, 1000);
-        assert_eq!(ptx.fee_priority, 500);
+        // The full tip fits under the fee cap; the amount plays no part.
+        let mut ptx = PendingTransaction::new(tx, 1000, 7, 5);
+        assert_eq!(ptx.fee_priority, 3);
         assert_eq!(ptx.added_at, 1000);
+        assert_eq!(ptx.seq, 7);
+
+        // Once the base fee eats into the cap, only the headroom is paid.
+        ptx.reprioritise(8);
+        assert_eq!(ptx.fee_priority, 2);
+        ptx.reprioritise(12);
+        assert_eq!(ptx.fee_priority, 0);
     }
 
     #[test]
@@ -1,6 +1,6 @@
 use ed25519_dalek::{Signature, VerifyingKey};
-use sha2::{Digest, Sha256};
 use trv1_bft::block::Transaction;
+use trv1_state::{GasSchedule, TxPayload};
 
 use crate::types::MempoolError;
 
@@ -18,22 +18,45 @@ pub fn validate_transaction(tx: &Transaction) -> Result<(), MempoolError> {
         ));
     }
 
+    let intrinsic = GasSchedule::default().intrinsic_gas(tx);
+    if tx.gas_limit < intrinsic {
+        return Err(MempoolError::InvalidTransaction(format!(
+            "gas limit {} is below the {} intrinsic gas",
+            tx.gas_limit, intrinsic
+        )));
+    }
+
+    if tx.max_priority_fee_per_gas > tx.max_fee_per_gas {
+        return Err(MempoolError::InvalidTransaction(
+            "max priority fee per gas exceeds max fee per gas".into(),
+        ));
+    }
+
+    if max_cost(tx).is_none() {
+        return Err(MempoolError::InvalidTransaction(
+            "amount, stake and maximum fee overflow".into(),
+        ));
+    }
+
     Ok(())
 }
 
+/// The most a transaction can take from its sender's balance: the amount,
+/// any stake its payload locks, and its whole gas limit at the fee cap.
+/// `None` if that overflows. Data that is not a payload fails at execution
+/// without moving tokens, so it adds nothing.
+pub fn max_cost(tx: &Transaction) -> Option<u64> {
+    let staked = TxPayload::from_data(&tx.data).map_or(0, |payload| payload.staked_amount());
+    tx.gas_limit
+        .checked_mul(tx.max_fee_per_gas)?
+        .checked_add(tx.amount)?
+        .checked_add(staked)
+}
+
 /// Build the message that must be signed for a transaction.
-/// Message = SHA256(from ++ to ++ amount.to_le_bytes() ++ nonce.to_le_bytes() ++ data)
+/// Same as `Transaction::signing_message`.
 pub fn build_signing_message(tx: &Transaction) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    hasher.update(tx.from);
-    hasher.update(tx.to);
-    hasher.update(tx.amount.to_le_bytes());
-    hasher.update(tx.nonce.to_le_bytes());
-    hasher.update(&tx.data);
-    let digest = hasher.finalize();
-    let mut hash = [0u8; 32];
-    hash.copy_from_slice(&digest);
-    hash
+    tx.signing_message()
 }
 
 /// Verify the ed25519 signature on a transaction.
@@ -65,6 +88,7 @@ mod tests {
     use ed25519_dalek::SigningKey;
     use ed25519_dalek::Signer;
     use rand::rngs::OsRng;
+    use trv1_staking::LockTier;
 
     fn make_signed_tx(signing_key: &SigningKey, to: [u8; 32], amount: u64, nonce: u64) -> Transaction {
         let from: [u8; 32] = signing_key.verifying_key().to_bytes();
@@ -77,6 +101,9 @@ mod tests {
             nonce,
             signature: vec![],
             data,
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
 
         let message = build_signing_message(&tx);
@@ -94,6 +121,9 @@ mod tests {
             nonce: 0,
             signature: vec![0u8; 64],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
         let err = validate_transaction(&tx).unwrap_err();
         assert!(matches!(err, MempoolError::InvalidTransaction(_)));
@@ -108,6 +138,9 @@ mod tests {
             nonce: 0,
             signature: vec![],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
         let err = validate_transaction(&tx).unwrap_err();
         assert!(matches!(err, MempoolError::InvalidTransaction(_)));
@@ -122,10 +155,105 @@ mod tests {
             nonce: 0,
             signature: vec![0u8; 64],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
         assert!(validate_transaction(&tx).is_ok());
     }
 
+    #[test]
+    fn test_validate_fee_fields() {
+        let valid = Transaction {
+            from: [1u8; 32],
+            to: [2u8; 32],
+            amount: 100,
+            nonce: 0,
+            signature: vec![0u8; 64],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 10,
+        };
+        assert!(validate_transaction(&valid).is_ok());
+
+        let mut low_gas = valid.clone();
+        low_gas.gas_limit = 20_999;
+        let err = validate_transaction(&low_gas).unwrap_err();
+        assert!(matches!(err, MempoolError::InvalidTransaction(_)));
+
+        // Each byte of data adds to the intrinsic gas.
+        let mut with_data = valid.clone();
+        with_data.data = vec![0; 10];
+        let err = validate_transaction(&with_data).unwrap_err();
+        assert_eq!(
+            err,
+            MempoolError::InvalidTransaction("gas limit 21000 is below the 21160 intrinsic gas".into())
+        );
+        with_data.gas_limit = 21_160;
+        assert!(validate_transaction(&with_data).is_ok());
+
+        let mut tip_above_cap = valid;
+        tip_above_cap.max_priority_fee_per_gas = 11;
+        let err = validate_transaction(&tip_above_cap).unwrap_err();
+        assert!(matches!(err, MempoolError::InvalidTransaction(_)));
+    }
+
+    #[test]
+    fn test_max_cost() {
+        let mut tx = Transaction {
+            from: [1u8; 32],
+            to: [2u8; 32],
+            amount: 100,
+            nonce: 0,
+            signature: vec![0u8; 64],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+        assert_eq!(max_cost(&tx), Some(210_100));
+
+        tx.data = TxPayload::Stake {
+            amount: 5_000,
+            tier: LockTier::NoLock,
+        }
+        .to_data();
+        tx.amount = 0;
+        assert_eq!(max_cost(&tx), Some(215_000));
+        tx.data = TxPayload::Delegate {
+            validator: [3u8; 32],
+            amount: 7_000,
+            tier: LockTier::Delegator,
+        }
+        .to_data();
+        assert_eq!(max_cost(&tx), Some(217_000));
+        tx.data = TxPayload::RegisterValidator {
+            self_stake: u64::MAX,
+            tier: LockTier::Permanent,
+            commission_rate: 500,
+        }
+        .to_data();
+        assert_eq!(max_cost(&tx), None);
+
+        tx.data = vec![];
+        tx.max_fee_per_gas = u64::MAX / 2;
+        assert_eq!(max_cost(&tx), None);
+        let err = validate_transaction(&tx).unwrap_err();
+        assert!(matches!(err, MempoolError::InvalidTransaction(_)));
+    }
+
+    #[test]
+    fn test_verify_signature_tampered_fee() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let mut tx = make_signed_tx(&signing_key, [2u8; 32], 100, 0);
+        tx.max_priority_fee_per_gas = 5; // tamper
+        assert!(matches!(
+            verify_signature(&tx),
+            Err(MempoolError::InvalidSignature)
+        ));
+    }
+
     #[test]
     fn test_verify_signature_valid() {
         let signing_key = SigningKey::generate(&mut OsRng);
@@ -166,6 +294,9 @@ mod tests {
             nonce: 0,
             signature: vec![0u8; 32], // wrong length (should be 64)
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
         assert!(matches!(
             verify_signature(&tx),
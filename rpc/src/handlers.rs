@@ -37,7 +37,8 @@ pub trait Trv1Api {
     #[method(name = "trv1_submitTransaction")]
     fn submit_transaction(&self, tx: SubmitTransactionRequest) -> RpcResult<SubmitTransactionResponse>;
 
-    /// Get account state by public key (hex string).
+    /// Get account state by public key (hex string), optionally as of a
+    /// past block height.
     #[method(name = "trv1_getAccount")]
-    fn get_account(&self, pubkey: String) -> RpcResult<AccountResponse>;
+    fn get_account(&self, pubkey: String, height: Option<u64>) -> RpcResult<AccountResponse>;
 }
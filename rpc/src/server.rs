@@ -143,6 +143,8 @@ impl Trv1ApiServer for RpcImpl {
             parent_hash: "0".repeat(64),
             proposer: "0".repeat(64),
             tx_count: 0,
+            fees_paid: 0,
+            fees_burned: 0,
             block_hash: "0".repeat(64),
         })
     }
@@ -161,6 +163,8 @@ impl Trv1ApiServer for RpcImpl {
             parent_hash: "0".repeat(64),
             proposer: "0".repeat(64),
             tx_count: 0,
+            fees_paid: 0,
+            fees_burned: 0,
             block_hash: "0".repeat(64),
         })
     }
@@ -221,13 +225,18 @@ impl Trv1ApiServer for RpcImpl {
             nonce: req.nonce,
             signature,
             data,
+            gas_limit: req.gas_limit,
+            max_fee_per_gas: req.max_fee_per_gas,
+            max_priority_fee_per_gas: req.max_priority_fee_per_gas,
         };
 
-        let tx_hash = hex::encode(trv1_mempool::pool::compute_tx_hash(&tx));
+        let tx_hash = hex::encode(tx.hash());
 
         let tx_clone = tx.clone();
+        // Lock order: state before mempool, as in the validator's commit path.
+        let state_db = self.state.state_db.read();
         let mut mempool = self.state.mempool.write();
-        match mempool.add_transaction(tx) {
+        match mempool.add_local_transaction(tx, &state_db) {
             Ok(()) => {
                 // Gossip the accepted transaction to the P2P network
                 if let Some(ref gossip_tx) = self.state.tx_gossip_tx {
@@ -246,14 +255,24 @@ impl Trv1ApiServer for RpcImpl {
         }
     }
 
-    fn get_account(&self, pubkey: String) -> RpcResult<AccountResponse> {
+    fn get_account(&self, pubkey: String, height: Option<u64>) -> RpcResult<AccountResponse> {
         let key: [u8; 32] = hex::decode(&pubkey)
             .map_err(|e| ErrorObjectOwned::owned(-32602, format!("invalid pubkey hex: {e}"), None::<()>))?
             .try_into()
             .map_err(|_| ErrorObjectOwned::owned(-32602, "pubkey must be 32 bytes", None::<()>))?;
 
         let state_db = self.state.state_db.read();
-        match state_db.get_account(&key) {
+        let account = match height {
+            Some(height) => state_db.account_at(&key, height).map_err(|e| {
+                let retained = match state_db.history_range() {
+                    Some(range) => format!("{}..={}", range.start(), range.end()),
+                    None => "none".to_string(),
+                };
+                ErrorObjectOwned::owned(-32000, format!("{e} (retained heights: {retained})"), None::<()>)
+            })?,
+            None => state_db.get_account(&key),
+        };
+        match account {
             Some(acct) => Ok(AccountResponse {
                 pubkey,
                 balance: acct.balance,
@@ -314,6 +333,8 @@ mod tests {
             parent_hash: "0".repeat(64),
             proposer: "aa".repeat(32),
             tx_count: 2,
+            fees_paid: 0,
+            fees_burned: 0,
             block_hash: "bb".repeat(32),
         });
         *state.current_height.write() = 0;
@@ -353,6 +374,8 @@ mod tests {
             parent_hash: "0".repeat(64),
             proposer: "aa".repeat(32),
             tx_count: 1,
+            fees_paid: 0,
+            fees_burned: 0,
             block_hash: "cc".repeat(32),
         });
         state.block_store.write().push(BlockResponse {
@@ -361,6 +384,8 @@ mod tests {
             parent_hash: "cc".repeat(32),
             proposer: "bb".repeat(32),
             tx_count: 3,
+            fees_paid: 0,
+            fees_burned: 0,
             block_hash: "dd".repeat(32),
         });
         *state.current_height.write() = 1;
@@ -461,6 +486,9 @@ mod tests {
             nonce,
             signature: vec![],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         };
         tx.sign(signing_key);
 
@@ -469,15 +497,25 @@ mod tests {
             to: hex::encode(to),
             amount,
             nonce,
+            gas_limit: tx.gas_limit,
+            max_fee_per_gas: tx.max_fee_per_gas,
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
             signature: hex::encode(&tx.signature),
             data: String::new(),
         }
     }
 
+    /// Helper: give `sk`'s account enough balance to pay for transfers.
+    fn fund(rpc: &RpcImpl, sk: &SigningKey) {
+        let pubkey = sk.verifying_key().to_bytes();
+        rpc.state.state_db.write().set_account(pubkey, AccountState::new(10_000_000));
+    }
+
     #[test]
     fn rpc_submit_transaction_accepted() {
         let rpc = mock_rpc();
         let sk = SigningKey::generate(&mut OsRng);
+        fund(&rpc, &sk);
         let req = make_signed_submit_request(&sk, [2u8; 32], 100, 0);
 
         let resp = rpc.submit_transaction(req).unwrap();
@@ -489,6 +527,7 @@ mod tests {
     fn rpc_submit_transaction_duplicate_rejected() {
         let rpc = mock_rpc();
         let sk = SigningKey::generate(&mut OsRng);
+        fund(&rpc, &sk);
         let req = make_signed_submit_request(&sk, [2u8; 32], 100, 0);
 
         let resp1 = rpc.submit_transaction(req.clone()).unwrap();
@@ -499,6 +538,29 @@ mod tests {
         assert!(resp2.is_err());
     }
 
+    #[test]
+    fn rpc_submit_transaction_below_base_fee_rejected() {
+        let rpc = mock_rpc();
+        rpc.state.mempool.write().set_base_fee(11);
+        let sk = SigningKey::generate(&mut OsRng);
+        fund(&rpc, &sk);
+        // The helper signs with a max fee of 10.
+        let req = make_signed_submit_request(&sk, [2u8; 32], 100, 0);
+
+        let err = rpc.submit_transaction(req).unwrap_err();
+        assert!(err.message().contains("below the current base fee"));
+    }
+
+    #[test]
+    fn rpc_submit_transaction_unfunded_rejected() {
+        let rpc = mock_rpc();
+        let sk = SigningKey::generate(&mut OsRng);
+        let req = make_signed_submit_request(&sk, [2u8; 32], 100, 0);
+
+        let err = rpc.submit_transaction(req).unwrap_err();
+        assert!(err.message().contains("insufficient balance"));
+    }
+
     #[test]
     fn rpc_submit_transaction_bad_hex() {
         let rpc = mock_rpc();
@@ -507,6 +569,9 @@ mod tests {
             to: hex::encode([2u8; 32]),
             amount: 100,
             nonce: 0,
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
             signature: hex::encode([0u8; 64]),
             data: String::new(),
         };
@@ -523,6 +588,9 @@ mod tests {
             to: hex::encode([2u8; 32]),
             amount: 100,
             nonce: 0,
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
             signature: hex::encode([0u8; 64]),
             data: String::new(),
         };
@@ -549,7 +617,7 @@ mod tests {
             state: state.clone(),
         };
 
-        let resp = rpc.get_account(hex::encode(pubkey)).unwrap();
+        let resp = rpc.get_account(hex::encode(pubkey), None).unwrap();
         assert_eq!(resp.balance, 5000);
         assert_eq!(resp.nonce, 2);
         assert_eq!(resp.pubkey, hex::encode(pubkey));
@@ -559,7 +627,7 @@ mod tests {
     fn rpc_get_account_nonexistent() {
         let rpc = mock_rpc();
         let pubkey = [0xbb; 32];
-        let resp = rpc.get_account(hex::encode(pubkey)).unwrap();
+        let resp = rpc.get_account(hex::encode(pubkey), None).unwrap();
         assert_eq!(resp.balance, 0);
         assert_eq!(resp.nonce, 0);
     }
@@ -567,14 +635,35 @@ mod tests {
     #[test]
     fn rpc_get_account_bad_hex() {
         let rpc = mock_rpc();
-        let resp = rpc.get_account("not_hex!!".to_string());
+        let resp = rpc.get_account("not_hex!!".to_string(), None);
         assert!(resp.is_err());
     }
 
     #[test]
     fn rpc_get_account_wrong_length() {
         let rpc = mock_rpc();
-        let resp = rpc.get_account(hex::encode([1u8; 16]));
+        let resp = rpc.get_account(hex::encode([1u8; 16]), None);
         assert!(resp.is_err());
     }
+
+    #[test]
+    fn rpc_get_account_at_height() {
+        let state = Arc::new(RpcState::new_mock());
+        let pubkey = [0xccu8; 32];
+        {
+            let mut db = state.state_db.write();
+            db.set_account(pubkey, AccountState::new(100));
+            db.commit(0).unwrap();
+            db.set_account(pubkey, AccountState::new(250));
+            db.commit(1).unwrap();
+        }
+        let rpc = RpcImpl { state };
+
+        assert_eq!(rpc.get_account(hex::encode(pubkey), Some(0)).unwrap().balance, 100);
+        assert_eq!(rpc.get_account(hex::encode(pubkey), Some(1)).unwrap().balance, 250);
+        assert_eq!(rpc.get_account(hex::encode(pubkey), None).unwrap().balance, 250);
+
+        let err = rpc.get_account(hex::encode(pubkey), Some(2)).unwrap_err();
+        assert!(err.message().contains("retained heights: 0..=1"), "{}", err.message());
+    }
 }
@@ -8,6 +8,12 @@ pub struct BlockResponse {
     pub parent_hash: String,
     pub proposer: String,
     pub tx_count: usize,
+    /// Total fees the block's transactions paid.
+    #[serde(default)]
+    pub fees_paid: u64,
+    /// The part of `fees_paid` that was burned.
+    #[serde(default)]
+    pub fees_burned: u64,
     pub block_hash: String,
 }
 
@@ -57,6 +63,12 @@ pub struct SubmitTransactionRequest {
     pub amount: u64,
     /// Sender nonce.
     pub nonce: u64,
+    /// Maximum gas the transaction may consume.
+    pub gas_limit: u64,
+    /// Maximum total price per gas unit (base fee plus tip).
+    pub max_fee_per_gas: u64,
+    /// Maximum tip per gas unit for the block proposer.
+    pub max_priority_fee_per_gas: u64,
     /// ed25519 signature as hex string (128 hex chars = 64 bytes).
     pub signature: String,
     /// Arbitrary data as hex string.
@@ -95,6 +107,8 @@ mod tests {
             parent_hash: "aa".repeat(32),
             proposer: "bb".repeat(32),
             tx_count: 5,
+            fees_paid: 0,
+            fees_burned: 0,
             block_hash: "cc".repeat(32),
         };
         let json = serde_json::to_string(&resp).unwrap();
@@ -139,6 +153,9 @@ mod tests {
             to: "bb".repeat(32),
             amount: 100,
             nonce: 0,
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
             signature: "cc".repeat(64),
             data: String::new(),
         };
@@ -0,0 +1,252 @@
+//! The functions a contract may import, all from the `env` module.
+//!
+//! Pointers and lengths are `i32` offsets into the contract's exported
+//! `memory`; addresses are 32 bytes. Amounts and heights are `i64`, read
+//! as unsigned.
+//!
+//! | Function | Signature | Effect |
+//! |----------|-----------|--------|
+//! | `input_len` | `() -> i32` | Length of the call's input |
+//! | `input_read` | `(ptr)` | Copy the input to `ptr` |
+//! | `output_write` | `(ptr, len)` | Set the call's output |
+//! | `caller` | `(ptr)` | Write the calling account's address to `ptr` |
+//! | `address` | `(ptr)` | Write the contract's own address to `ptr` |
+//! | `value` | `() -> i64` | Amount sent with the call |
+//! | `block_height` | `() -> i64` | Height of the executing block |
+//! | `storage_read` | `(key_ptr, key_len, val_ptr, val_cap) -> i32` | Copy up to `val_cap` bytes of the value at the key to `val_ptr`; returns its full length, or -1 if there is none |
+//! | `storage_write` | `(key_ptr, key_len, val_ptr, val_len)` | Set the value at the key |
+//! | `storage_remove` | `(key_ptr, key_len)` | Remove the value at the key |
+//! | `balance` | `(addr_ptr) -> i64` | Balance of the account at `addr_ptr` |
+//! | `transfer` | `(to_ptr, amount) -> i32` | Send `amount` of the contract's balance to `to_ptr`; returns 0, or 1 if the balance is too small |
+//!
+//! Every call costs `host_call` gas, plus the storage and transfer costs
+//! of what it does. Bad pointers and oversized keys, values or output
+//! trap.
+
+use std::collections::BTreeMap;
+
+use wasmi::errors::LinkerError;
+use wasmi::{Caller, Error, Extern, Linker, Memory, StoreLimits, TrapCode, ValType};
+
+use crate::types::*;
+
+/// The chain state contracts live in: account balances, deployed
+/// contracts and their storage.
+pub trait Host {
+    /// Balance of `account`, zero if it does not exist.
+    fn balance(&self, account: &Address) -> u64;
+
+    /// Move `amount` from `from` to `to`. On error nothing changes.
+    fn transfer(&mut self, from: &Address, to: &Address, amount: u64) -> Result<(), String>;
+
+    /// The contract deployed at `address`, if any.
+    fn contract(&self, address: &Address) -> Option<Contract>;
+
+    /// Record `contract` as deployed at `address`.
+    fn set_contract(&mut self, address: Address, contract: Contract);
+
+    /// The value `contract` stores under `key`, if any.
+    fn storage(&self, contract: &Address, key: &[u8]) -> Option<Vec<u8>>;
+
+    /// Set the value `contract` stores under `key`, or remove it if
+    /// `value` is `None`.
+    fn set_storage(&mut self, contract: &Address, key: &[u8], value: Option<Vec<u8>>);
+}
+
+/// Name, parameters and results of every host function.
+pub(crate) const HOST_FUNCTIONS: &[(&str, &[ValType], &[ValType])] = {
+    use ValType::{I32, I64};
+    &[
+        ("input_len", &[], &[I32]),
+        ("input_read", &[I32], &[]),
+        ("output_write", &[I32, I32], &[]),
+        ("caller", &[I32], &[]),
+        ("address", &[I32], &[]),
+        ("value", &[], &[I64]),
+        ("block_height", &[], &[I64]),
+        ("storage_read", &[I32, I32, I32, I32], &[I32]),
+        ("storage_write", &[I32, I32, I32, I32], &[]),
+        ("storage_remove", &[I32, I32], &[]),
+        ("balance", &[I32], &[I64]),
+        ("transfer", &[I32, I64], &[I32]),
+    ]
+};
+
+/// What a call's host functions act on.
+pub(crate) struct Env<'a> {
+    pub host: &'a mut dyn Host,
+    pub ctx: &'a CallContext,
+    pub gas: &'a ContractGas,
+    /// Storage changes made by the call, kept apart from the host's until
+    /// it succeeds. `None` removes the key.
+    pub writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    pub output: Vec<u8>,
+    pub limits: StoreLimits,
+}
+
+impl Env<'_> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.writes.get(key) {
+            Some(value) => value.clone(),
+            None => self.host.storage(&self.ctx.contract, key),
+        }
+    }
+}
+
+/// Use up `gas` of the call's remaining fuel, or all of it and trap if
+/// there is not enough.
+fn charge(caller: &mut Caller<'_, Env<'_>>, gas: u64) -> Result<(), Error> {
+    let fuel = caller.get_fuel()?;
+    match fuel.checked_sub(gas) {
+        Some(left) => caller.set_fuel(left),
+        None => {
+            caller.set_fuel(0)?;
+            Err(TrapCode::OutOfFuel.into())
+        }
+    }
+}
+
+/// Charge for one call to a host function.
+fn host_call(caller: &mut Caller<'_, Env<'_>>) -> Result<(), Error> {
+    let gas = caller.data().gas.host_call;
+    charge(caller, gas)
+}
+
+fn memory(caller: &Caller<'_, Env<'_>>) -> Result<Memory, Error> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Error::new("contract exports no memory"))
+}
+
+fn read_bytes(caller: &Caller<'_, Env<'_>>, ptr: i32, len: i32, max: usize) -> Result<Vec<u8>, Error> {
+    let len = usize::try_from(len).map_err(|_| Error::new("negative length"))?;
+    if len > max {
+        return Err(Error::new(format!("{len} bytes is above the {max} byte limit")));
+    }
+    let mut buf = vec![0; len];
+    memory(caller)?
+        .read(caller, ptr as u32 as usize, &mut buf)
+        .map_err(|_| Error::new("read out of bounds"))?;
+    Ok(buf)
+}
+
+fn read_address(caller: &Caller<'_, Env<'_>>, ptr: i32) -> Result<Address, Error> {
+    let bytes = read_bytes(caller, ptr, 32, 32)?;
+    Ok(bytes.try_into().expect("read exactly 32 bytes"))
+}
+
+fn write_bytes(caller: &mut Caller<'_, Env<'_>>, ptr: i32, bytes: &[u8]) -> Result<(), Error> {
+    memory(caller)?
+        .write(caller, ptr as u32 as usize, bytes)
+        .map_err(|_| Error::new("write out of bounds"))
+}
+
+/// A linker providing every host function.
+pub(crate) fn linker<'a>(engine: &wasmi::Engine) -> Linker<Env<'a>> {
+    let mut linker = Linker::new(engine);
+    link(&mut linker).expect("host functions have distinct names");
+    linker
+}
+
+fn link(linker: &mut Linker<Env<'_>>) -> Result<(), LinkerError> {
+    linker.func_wrap("env", "input_len", |mut caller: Caller<'_, Env<'_>>| -> Result<i32, Error> {
+        host_call(&mut caller)?;
+        Ok(caller.data().ctx.input.len() as i32)
+    })?;
+    linker.func_wrap("env", "input_read", |mut caller: Caller<'_, Env<'_>>, ptr: i32| -> Result<(), Error> {
+        host_call(&mut caller)?;
+        let input = caller.data().ctx.input.clone();
+        write_bytes(&mut caller, ptr, &input)
+    })?;
+    linker.func_wrap(
+        "env",
+        "output_write",
+        |mut caller: Caller<'_, Env<'_>>, ptr: i32, len: i32| -> Result<(), Error> {
+            host_call(&mut caller)?;
+            let output = read_bytes(&caller, ptr, len, MAX_OUTPUT)?;
+            caller.data_mut().output = output;
+            Ok(())
+        },
+    )?;
+    linker.func_wrap("env", "caller", |mut caller: Caller<'_, Env<'_>>, ptr: i32| -> Result<(), Error> {
+        host_call(&mut caller)?;
+        let address = caller.data().ctx.caller;
+        write_bytes(&mut caller, ptr, &address)
+    })?;
+    linker.func_wrap("env", "address", |mut caller: Caller<'_, Env<'_>>, ptr: i32| -> Result<(), Error> {
+        host_call(&mut caller)?;
+        let address = caller.data().ctx.contract;
+        write_bytes(&mut caller, ptr, &address)
+    })?;
+    linker.func_wrap("env", "value", |mut caller: Caller<'_, Env<'_>>| -> Result<i64, Error> {
+        host_call(&mut caller)?;
+        Ok(caller.data().ctx.value as i64)
+    })?;
+    linker.func_wrap("env", "block_height", |mut caller: Caller<'_, Env<'_>>| -> Result<i64, Error> {
+        host_call(&mut caller)?;
+        Ok(caller.data().ctx.height as i64)
+    })?;
+    linker.func_wrap(
+        "env",
+        "storage_read",
+        |mut caller: Caller<'_, Env<'_>>, key_ptr: i32, key_len: i32, val_ptr: i32, val_cap: i32| -> Result<i32, Error> {
+            let gas = caller.data().gas;
+            charge(&mut caller, gas.host_call + gas.storage_read)?;
+            let key = read_bytes(&caller, key_ptr, key_len, MAX_STORAGE_KEY)?;
+            let Some(value) = caller.data().get(&key) else {
+                return Ok(-1);
+            };
+            charge(&mut caller, (value.len() as u64) * gas.storage_byte)?;
+            let cap = usize::try_from(val_cap).map_err(|_| Error::new("negative capacity"))?;
+            write_bytes(&mut caller, val_ptr, &value[..value.len().min(cap)])?;
+            Ok(value.len() as i32)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        "storage_write",
+        |mut caller: Caller<'_, Env<'_>>, key_ptr: i32, key_len: i32, val_ptr: i32, val_len: i32| -> Result<(), Error> {
+            let gas = caller.data().gas;
+            charge(&mut caller, gas.host_call + gas.storage_write)?;
+            let key = read_bytes(&caller, key_ptr, key_len, MAX_STORAGE_KEY)?;
+            let value = read_bytes(&caller, val_ptr, val_len, MAX_STORAGE_VALUE)?;
+            charge(&mut caller, ((key.len() + value.len()) as u64) * gas.storage_byte)?;
+            caller.data_mut().writes.insert(key, Some(value));
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        "storage_remove",
+        |mut caller: Caller<'_, Env<'_>>, key_ptr: i32, key_len: i32| -> Result<(), Error> {
+            let gas = caller.data().gas;
+            charge(&mut caller, gas.host_call + gas.storage_write)?;
+            let key = read_bytes(&caller, key_ptr, key_len, MAX_STORAGE_KEY)?;
+            caller.data_mut().writes.insert(key, None);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap("env", "balance", |mut caller: Caller<'_, Env<'_>>, ptr: i32| -> Result<i64, Error> {
+        let gas = caller.data().gas;
+        charge(&mut caller, gas.host_call + gas.storage_read)?;
+        let account = read_address(&caller, ptr)?;
+        Ok(caller.data().host.balance(&account) as i64)
+    })?;
+    linker.func_wrap(
+        "env",
+        "transfer",
+        |mut caller: Caller<'_, Env<'_>>, to_ptr: i32, amount: i64| -> Result<i32, Error> {
+            let gas = caller.data().gas;
+            charge(&mut caller, gas.host_call + gas.transfer)?;
+            let to = read_address(&caller, to_ptr)?;
+            let env = caller.data_mut();
+            let from = env.ctx.contract;
+            Ok(match env.host.transfer(&from, &to, amount as u64) {
+                Ok(()) => 0,
+                Err(_) => 1,
+            })
+        },
+    )?;
+    Ok(())
+}
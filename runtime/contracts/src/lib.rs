@@ -0,0 +1,7 @@
+pub mod host;
+pub mod runtime;
+pub mod types;
+
+pub use host::Host;
+pub use runtime::ContractRuntime;
+pub use types::*;
@@ -0,0 +1,461 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+
+use sha2::{Digest, Sha256};
+use wasmi::{CompilationMode, Config, Engine, ExternType, Module, Store, StoreLimitsBuilder, TrapCode};
+
+use crate::host::{linker, Env, Host, HOST_FUNCTIONS};
+use crate::types::*;
+
+/// Default number of compiled modules a runtime keeps.
+pub const DEFAULT_MODULE_CACHE_CAPACITY: usize = 256;
+
+/// The interpreter that deploys and runs contracts.
+///
+/// Contracts are WASM modules run by a sandboxed interpreter. Execution is
+/// deterministic: floating-point instructions are rejected at deploy, and
+/// memory, stack, storage and output are bounded. Every instruction uses
+/// fuel, and host functions take more for the work they do, priced by a
+/// `ContractGas`; a call given `gas_limit` gas may use that much fuel.
+///
+/// A contract exports a `memory` and a `call` function taking and
+/// returning nothing, and imports only the host functions in
+/// [`crate::host`]. A call that traps or runs out of gas leaves the
+/// contract's storage as it was.
+///
+/// Contracts and their storage are kept by the `Host`; the runtime keeps
+/// only compiled code. Code is checked when it is deployed and compiled
+/// when a contract is called, and the compiled module is kept for every
+/// contract with the same code. Only the most recently used modules are
+/// kept; any other is compiled again from the contract's stored code.
+pub struct ContractRuntime {
+    engine: Engine,
+    /// Compiled modules, by the SHA-256 hash of their code.
+    modules: LruCache<[u8; 32], Module>,
+}
+
+impl std::fmt::Debug for ContractRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContractRuntime")
+            .field("modules", &self.modules.len())
+            .finish()
+    }
+}
+
+impl Default for ContractRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContractRuntime {
+    /// A runtime with no contracts.
+    pub fn new() -> Self {
+        Self::with_cache_capacity(DEFAULT_MODULE_CACHE_CAPACITY)
+    }
+
+    /// A runtime keeping up to `capacity` compiled modules.
+    pub fn with_cache_capacity(capacity: usize) -> Self {
+        let mut config = Config::default();
+        config
+            .consume_fuel(true)
+            .floats(false)
+            .compilation_mode(CompilationMode::Eager);
+        Self {
+            engine: Engine::new(&config),
+            modules: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+        }
+    }
+
+    /// Check that `code` is a contract this runtime can run.
+    pub fn validate(&self, code: &[u8]) -> ContractResult<()> {
+        self.compile(code).map(drop)
+    }
+
+    /// Compile `code`, checking it is a contract this runtime can run.
+    fn compile(&self, code: &[u8]) -> ContractResult<Module> {
+        if code.len() > MAX_CODE_SIZE {
+            return Err(ContractError::CodeTooLarge {
+                size: code.len(),
+                max: MAX_CODE_SIZE,
+            });
+        }
+        let module = Module::new(&self.engine, code).map_err(|e| ContractError::InvalidCode(e.to_string()))?;
+
+        for import in module.imports() {
+            let expected = HOST_FUNCTIONS.iter().find(|(name, _, _)| *name == import.name());
+            let matches = match (import.module(), import.ty(), expected) {
+                ("env", ExternType::Func(ty), Some((_, params, results))) => {
+                    ty.params() == *params && ty.results() == *results
+                }
+                _ => false,
+            };
+            if !matches {
+                return Err(ContractError::InvalidCode(format!(
+                    "unknown import {}.{}",
+                    import.module(),
+                    import.name()
+                )));
+            }
+        }
+        match module.get_export("call") {
+            Some(ExternType::Func(ty)) if ty.params().is_empty() && ty.results().is_empty() => {}
+            _ => return Err(ContractError::InvalidCode("no `call` function exported".into())),
+        }
+        if !matches!(module.get_export("memory"), Some(ExternType::Memory(_))) {
+            return Err(ContractError::InvalidCode("no memory exported".into()));
+        }
+        Ok(module)
+    }
+
+    /// The module compiled from `code`, whose hash is `code_hash`,
+    /// compiling it unless it already has been.
+    fn module(&mut self, code_hash: [u8; 32], code: &[u8]) -> ContractResult<&Module> {
+        if !self.modules.contains(&code_hash) {
+            let module = self.compile(code)?;
+            self.modules.put(code_hash, module);
+        }
+        Ok(self.modules.get(&code_hash).expect("the module was just cached"))
+    }
+
+    /// Deploy `code` at `address` in `host`, on behalf of `deployer`.
+    pub fn deploy(
+        &mut self,
+        host: &mut dyn Host,
+        address: Address,
+        deployer: Address,
+        code: Vec<u8>,
+        height: u64,
+    ) -> ContractResult<()> {
+        if host.contract(&address).is_some() {
+            return Err(ContractError::AlreadyDeployed);
+        }
+        self.validate(&code)?;
+        let code_hash: [u8; 32] = Sha256::digest(&code).into();
+        host.set_contract(
+            address,
+            Contract {
+                deployer,
+                code,
+                code_hash,
+                deploy_height: height,
+            },
+        );
+        Ok(())
+    }
+
+    /// Run the contract at `ctx.contract` with up to `gas_limit` gas,
+    /// charging `gas` for host work and moving balances through `host`.
+    /// Returns the gas used alongside the call's output.
+    ///
+    /// On error the contract's storage is unchanged, but balance transfers
+    /// already made through `host` are not undone here. Running out of gas
+    /// uses all of `gas_limit`.
+    pub fn call(
+        &mut self,
+        host: &mut dyn Host,
+        ctx: &CallContext,
+        gas: &ContractGas,
+        gas_limit: u64,
+    ) -> (u64, ContractResult<Vec<u8>>) {
+        let Some(contract) = host.contract(&ctx.contract) else {
+            return (0, Err(ContractError::NotFound));
+        };
+        let load_gas = (contract.code.len() as u64).saturating_mul(gas.code_load_byte);
+        if load_gas > gas_limit {
+            return (gas_limit, Err(ContractError::OutOfGas));
+        }
+        let engine = self.engine.clone();
+        let module = match self.module(contract.code_hash, &contract.code) {
+            Ok(module) => module,
+            Err(e) => return (load_gas, Err(e)),
+        };
+
+        let env = Env {
+            host,
+            ctx,
+            gas,
+            writes: BTreeMap::new(),
+            output: Vec::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .instances(1)
+                .build(),
+        };
+        let mut store = Store::new(&engine, env);
+        store.limiter(|env| &mut env.limits);
+        store
+            .set_fuel(gas_limit - load_gas)
+            .expect("fuel metering is enabled");
+
+        let result = linker(&engine)
+            .instantiate_and_start(&mut store, module)
+            .and_then(|instance| instance.get_typed_func::<(), ()>(&store, "call"))
+            .and_then(|call| call.call(&mut store, ()));
+        let gas_used = gas_limit - store.get_fuel().expect("fuel metering is enabled");
+        match result {
+            // Running out uses up all the gas, even if the instruction that
+            // could not be paid for cost more than was left.
+            Err(e) if e.as_trap_code() == Some(TrapCode::OutOfFuel) => {
+                return (gas_limit, Err(ContractError::OutOfGas));
+            }
+            Err(e) => return (gas_used, Err(ContractError::Trap(e.to_string()))),
+            Ok(()) => {}
+        }
+
+        let Env { host, writes, output, .. } = store.into_data();
+        for (key, value) in writes {
+            host.set_storage(&ctx.contract, &key, value);
+        }
+        (gas_used, Ok(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestHost {
+        balances: BTreeMap<Address, u64>,
+        contracts: BTreeMap<Address, Contract>,
+        storage: BTreeMap<(Address, Vec<u8>), Vec<u8>>,
+    }
+
+    impl Host for TestHost {
+        fn balance(&self, account: &Address) -> u64 {
+            self.balances.get(account).copied().unwrap_or(0)
+        }
+
+        fn transfer(&mut self, from: &Address, to: &Address, amount: u64) -> Result<(), String> {
+            let left = self.balance(from).checked_sub(amount).ok_or("insufficient balance")?;
+            self.balances.insert(*from, left);
+            *self.balances.entry(*to).or_default() += amount;
+            Ok(())
+        }
+
+        fn contract(&self, address: &Address) -> Option<Contract> {
+            self.contracts.get(address).cloned()
+        }
+
+        fn set_contract(&mut self, address: Address, contract: Contract) {
+            self.contracts.insert(address, contract);
+        }
+
+        fn storage(&self, contract: &Address, key: &[u8]) -> Option<Vec<u8>> {
+            self.storage.get(&(*contract, key.to_vec())).cloned()
+        }
+
+        fn set_storage(&mut self, contract: &Address, key: &[u8], value: Option<Vec<u8>>) {
+            match value {
+                Some(value) => self.storage.insert((*contract, key.to_vec()), value),
+                None => self.storage.remove(&(*contract, key.to_vec())),
+            };
+        }
+    }
+
+    const CONTRACT: Address = [7; 32];
+    const CALLER: Address = [1; 32];
+
+    /// Increments the u64 stored under "n" and returns the new value.
+    const COUNTER: &str = r#"
+        (module
+          (import "env" "storage_read" (func $read (param i32 i32 i32 i32) (result i32)))
+          (import "env" "storage_write" (func $write (param i32 i32 i32 i32)))
+          (import "env" "output_write" (func $output (param i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "n")
+          (func (export "call")
+            (drop (call $read (i32.const 0) (i32.const 1) (i32.const 8) (i32.const 8)))
+            (i64.store (i32.const 8) (i64.add (i64.load (i32.const 8)) (i64.const 1)))
+            (call $write (i32.const 0) (i32.const 1) (i32.const 8) (i32.const 8))
+            (call $output (i32.const 8) (i32.const 8))))"#;
+
+    fn deployed(wat: &str) -> (ContractRuntime, TestHost) {
+        let mut runtime = ContractRuntime::default();
+        let mut host = TestHost::default();
+        runtime
+            .deploy(&mut host, CONTRACT, CALLER, wat::parse_str(wat).unwrap(), 3)
+            .unwrap();
+        (runtime, host)
+    }
+
+    fn ctx(value: u64) -> CallContext {
+        CallContext {
+            contract: CONTRACT,
+            caller: CALLER,
+            value,
+            height: 5,
+            input: vec![],
+        }
+    }
+
+    #[test]
+    fn test_call_reads_and_writes_storage() {
+        let (mut runtime, mut host) = deployed(COUNTER);
+        for expected in 1..=3u64 {
+            let (gas_used, output) = runtime.call(&mut host, &ctx(0), &ContractGas::default(), 1_000_000);
+            assert_eq!(output.unwrap(), expected.to_le_bytes());
+            assert!(gas_used > 2_100 + 5_000);
+        }
+        assert_eq!(host.storage(&CONTRACT, b"n"), Some(3u64.to_le_bytes().to_vec()));
+        assert_eq!(host.contract(&CONTRACT).unwrap().deploy_height, 3);
+    }
+
+    #[test]
+    fn test_call_is_deterministic() {
+        let (mut a, mut host_a) = deployed(COUNTER);
+        let (mut b, mut host_b) = deployed(COUNTER);
+        let (gas_a, out_a) = a.call(&mut host_a, &ctx(0), &ContractGas::default(), 1_000_000);
+        let (gas_b, out_b) = b.call(&mut host_b, &ctx(0), &ContractGas::default(), 1_000_000);
+        assert_eq!((gas_a, out_a), (gas_b, out_b));
+    }
+
+    #[test]
+    fn test_out_of_gas_and_traps_leave_storage() {
+        let (mut runtime, mut host) = deployed(COUNTER);
+        let (gas_used, result) = runtime.call(&mut host, &ctx(0), &ContractGas::default(), 3_000);
+        assert_eq!(result, Err(ContractError::OutOfGas));
+        assert_eq!(gas_used, 3_000);
+        assert_eq!(host.storage(&CONTRACT, b"n"), None);
+
+        // Writes, then traps.
+        let (mut runtime, mut host) = deployed(
+            r#"(module
+                 (import "env" "storage_write" (func $write (param i32 i32 i32 i32)))
+                 (memory (export "memory") 1)
+                 (func (export "call")
+                   (call $write (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 1))
+                   unreachable))"#,
+        );
+        let (_, result) = runtime.call(&mut host, &ctx(0), &ContractGas::default(), 1_000_000);
+        assert!(matches!(result, Err(ContractError::Trap(_))));
+        assert!(host.storage.is_empty());
+
+        // Loops forever.
+        let (mut runtime, mut host) = deployed(
+            r#"(module (memory (export "memory") 1) (func (export "call") (loop $l (br $l))))"#,
+        );
+        let (gas_used, result) = runtime.call(&mut host, &ctx(0), &ContractGas::default(), 50_000);
+        assert_eq!((gas_used, result), (50_000, Err(ContractError::OutOfGas)));
+    }
+
+    #[test]
+    fn test_caller_info_and_transfer() {
+        // Sends the value it was given back to its caller, and returns the
+        // transfer's status.
+        let (mut runtime, mut host) = deployed(
+            r#"(module
+                 (import "env" "caller" (func $caller (param i32)))
+                 (import "env" "value" (func $value (result i64)))
+                 (import "env" "transfer" (func $transfer (param i32 i64) (result i32)))
+                 (import "env" "output_write" (func $output (param i32 i32)))
+                 (memory (export "memory") 1)
+                 (func (export "call")
+                   (call $caller (i32.const 0))
+                   (i32.store (i32.const 32) (call $transfer (i32.const 0) (call $value)))
+                   (call $output (i32.const 32) (i32.const 4))))"#,
+        );
+        host.balances.insert(CONTRACT, 100);
+        let (_, output) = runtime.call(&mut host, &ctx(40), &ContractGas::default(), 1_000_000);
+        assert_eq!(output.unwrap(), 0i32.to_le_bytes());
+        assert_eq!(host.balance(&CALLER), 40);
+        assert_eq!(host.balance(&CONTRACT), 60);
+
+        let (_, output) = runtime.call(&mut host, &ctx(61), &ContractGas::default(), 1_000_000);
+        assert_eq!(output.unwrap(), 1i32.to_le_bytes());
+        assert_eq!(host.balance(&CONTRACT), 60);
+    }
+
+    #[test]
+    fn test_deploy_validation() {
+        let mut runtime = ContractRuntime::default();
+        let mut host = TestHost::default();
+        let mut deploy =
+            |wat: &str| runtime.deploy(&mut host, [9; 32], CALLER, wat::parse_str(wat).unwrap(), 0);
+
+        let floats = r#"(module (memory (export "memory") 1)
+            (func (export "call") (drop (f64.add (f64.const 1) (f64.const 2)))))"#;
+        assert!(matches!(deploy(floats), Err(ContractError::InvalidCode(_))));
+
+        let unknown_import = r#"(module (import "env" "random" (func (result i64)))
+            (memory (export "memory") 1) (func (export "call")))"#;
+        assert!(matches!(deploy(unknown_import), Err(ContractError::InvalidCode(_))));
+
+        let wrong_signature = r#"(module (import "env" "value" (func (result i32)))
+            (memory (export "memory") 1) (func (export "call")))"#;
+        assert!(matches!(deploy(wrong_signature), Err(ContractError::InvalidCode(_))));
+
+        let no_call = r#"(module (memory (export "memory") 1))"#;
+        assert!(matches!(deploy(no_call), Err(ContractError::InvalidCode(_))));
+
+        let minimal = r#"(module (memory (export "memory") 1) (func (export "call")))"#;
+        assert!(deploy(minimal).is_ok());
+        assert_eq!(deploy(minimal), Err(ContractError::AlreadyDeployed));
+        assert_eq!(
+            runtime.call(&mut host, &ctx(0), &ContractGas::default(), 1_000).1,
+            Err(ContractError::NotFound)
+        );
+        assert_eq!(host.contracts.len(), 1);
+    }
+
+    #[test]
+    fn test_same_code_compiled_once() {
+        let (mut runtime, mut host) = deployed(COUNTER);
+        let code = wat::parse_str(COUNTER).unwrap();
+        runtime.deploy(&mut host, [8; 32], CALLER, code, 4).unwrap();
+        assert_eq!(runtime.modules.len(), 0);
+        assert_eq!(
+            host.contract(&CONTRACT).unwrap().code_hash,
+            host.contract(&[8; 32]).unwrap().code_hash
+        );
+
+        // Each contract still has its own storage.
+        runtime.call(&mut host, &ctx(0), &ContractGas::default(), 1_000_000).1.unwrap();
+        let other = CallContext { contract: [8; 32], ..ctx(0) };
+        runtime.call(&mut host, &other, &ContractGas::default(), 1_000_000).1.unwrap();
+        assert_eq!(runtime.modules.len(), 1);
+        assert_eq!(host.storage(&CONTRACT, b"n"), Some(1u64.to_le_bytes().to_vec()));
+        assert_eq!(host.storage(&[8; 32], b"n"), Some(1u64.to_le_bytes().to_vec()));
+    }
+
+    #[test]
+    fn test_module_cache_is_bounded() {
+        let mut runtime = ContractRuntime::with_cache_capacity(1);
+        let mut host = TestHost::default();
+        let minimal = r#"(module (memory (export "memory") 1) (func (export "call")))"#;
+        runtime.deploy(&mut host, CONTRACT, CALLER, wat::parse_str(COUNTER).unwrap(), 3).unwrap();
+        runtime.deploy(&mut host, [8; 32], CALLER, wat::parse_str(minimal).unwrap(), 3).unwrap();
+
+        let other = CallContext { contract: [8; 32], ..ctx(0) };
+        for expected in 1..=2u64 {
+            let (_, output) = runtime.call(&mut host, &ctx(0), &ContractGas::default(), 1_000_000);
+            assert_eq!(output.unwrap(), expected.to_le_bytes());
+            runtime.call(&mut host, &other, &ContractGas::default(), 1_000_000).1.unwrap();
+            assert_eq!(runtime.modules.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_new_runtime_calls_deployed_contracts() {
+        // The host outlives the runtime, as the chain state outlives a
+        // restart.
+        let (mut runtime, mut host) = deployed(COUNTER);
+        runtime.call(&mut host, &ctx(0), &ContractGas::default(), 1_000_000).1.unwrap();
+
+        let mut runtime = ContractRuntime::new();
+        let (_, output) = runtime.call(&mut host, &ctx(0), &ContractGas::default(), 1_000_000);
+        assert_eq!(output.unwrap(), 2u64.to_le_bytes());
+        assert_eq!(runtime.modules.len(), 1);
+    }
+
+    #[test]
+    fn test_contract_bytes_roundtrip() {
+        let (_, host) = deployed(COUNTER);
+        let contract = host.contract(&CONTRACT).unwrap();
+        assert_eq!(Contract::from_bytes(&contract.to_bytes()), Some(contract));
+        assert_eq!(Contract::from_bytes(&[0; 71]), None);
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A 32-byte account or contract address.
+pub type Address = [u8; 32];
+
+/// Largest contract code accepted, in bytes.
+pub const MAX_CODE_SIZE: usize = 256 * 1024;
+
+/// Most linear memory a contract may use, in bytes.
+pub const MAX_MEMORY_BYTES: usize = 1024 * 1024;
+
+/// Longest storage key, in bytes.
+pub const MAX_STORAGE_KEY: usize = 256;
+
+/// Longest storage value, in bytes.
+pub const MAX_STORAGE_VALUE: usize = 16 * 1024;
+
+/// Longest output a call may return, in bytes.
+pub const MAX_OUTPUT: usize = 16 * 1024;
+
+/// A deployed contract. Its storage is kept by the `Host`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contract {
+    /// Account that deployed the contract.
+    pub deployer: Address,
+    /// The contract's WASM module.
+    pub code: Vec<u8>,
+    /// SHA-256 hash of `code`.
+    pub code_hash: [u8; 32],
+    /// Block height at which the contract was deployed.
+    pub deploy_height: u64,
+}
+
+impl Contract {
+    /// The contract as a `Host` stores it: the deployer, the code hash and
+    /// the deploy height, then the code.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.deployer[..],
+            &self.code_hash,
+            &self.deploy_height.to_le_bytes(),
+            &self.code,
+        ]
+        .concat()
+    }
+
+    /// Read a contract written by `to_bytes`, or `None` if `bytes` is too
+    /// short to be one.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 72 {
+            return None;
+        }
+        Some(Self {
+            deployer: bytes[..32].try_into().unwrap(),
+            code_hash: bytes[32..64].try_into().unwrap(),
+            deploy_height: u64::from_le_bytes(bytes[64..72].try_into().unwrap()),
+            code: bytes[72..].to_vec(),
+        })
+    }
+}
+
+/// What a contract call is given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallContext {
+    /// The contract called.
+    pub contract: Address,
+    /// The account that signed the calling transaction.
+    pub caller: Address,
+    /// Amount the caller sent to the contract with the call, already
+    /// credited to it.
+    pub value: u64,
+    /// Height of the block the call executes in.
+    pub height: u64,
+    /// Input passed to the contract.
+    pub input: Vec<u8>,
+}
+
+/// Gas charged for contract work beyond one gas per unit of the
+/// interpreter's fuel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractGas {
+    /// Per call to a host function.
+    pub host_call: u64,
+    /// Per byte of code loaded to run a call.
+    pub code_load_byte: u64,
+    /// Per storage entry read.
+    pub storage_read: u64,
+    /// Per storage entry written or removed.
+    pub storage_write: u64,
+    /// Per byte of storage key or value read or written.
+    pub storage_byte: u64,
+    /// Per balance transfer out of the contract.
+    pub transfer: u64,
+}
+
+impl Default for ContractGas {
+    fn default() -> Self {
+        Self {
+            host_call: 100,
+            code_load_byte: 3,
+            storage_read: 2_100,
+            storage_write: 5_000,
+            storage_byte: 50,
+            transfer: 9_000,
+        }
+    }
+}
+
+/// Errors from deploying or calling contracts.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ContractError {
+    #[error("invalid contract code: {0}")]
+    InvalidCode(String),
+
+    #[error("contract code is {size} bytes, above the {max} byte limit")]
+    CodeTooLarge { size: usize, max: usize },
+
+    #[error("a contract is already deployed at this address")]
+    AlreadyDeployed,
+
+    #[error("no contract is deployed at this address")]
+    NotFound,
+
+    #[error("out of gas")]
+    OutOfGas,
+
+    #[error("contract trapped: {0}")]
+    Trap(String),
+}
+
+pub type ContractResult<T> = Result<T, ContractError>;
@@ -101,21 +101,13 @@ impl SlashingEngine {
             return None;
         }
 
-        // Apply the slash — reduce the validator's recorded stake.
-        let new_stake = validator_own_stake.saturating_sub(slash_amount);
-        if let Err(e) = validator_set.update_stake(&evidence.offender, new_stake) {
-            tracing::error!(
-                offender = ?evidence.offender,
-                error = %e,
-                "failed to update stake during slash"
-            );
-            return None;
-        }
-
-        // Unstake the slashed amount from the staking pool (validator's own entry).
-        // If the validator's stake has an unlocked portion, remove it. If all locked,
-        // we still reduce the recorded stake above; the staking pool unstake is best-effort.
-        let _ = staking_pool.unstake(evidence.offender, slash_amount);
+        // Apply the slash — take it out of the validator's own stake in the
+        // pool, locked or not, and record what is left as its stake. The
+        // caller moves the slashed tokens out of the staking account.
+        let slash_amount = staking_pool.slash(&evidence.offender, slash_amount);
+        validator_set
+            .update_stake(&evidence.offender, staking_pool.stake_of(&evidence.offender))
+            .expect("the validator was looked up above");
 
         // Send slashed amount to treasury.
         self.treasury = self.treasury.saturating_add(slash_amount);
@@ -220,6 +212,7 @@ mod tests {
             active_set_cap: 200,
             epoch_length: 100,
             min_stake: 100,
+            ..Default::default()
         });
         let mut pool = StakingPool::new();
 
@@ -252,6 +245,9 @@ mod tests {
 
         // Validator's recorded stake should be reduced.
         assert_eq!(vs.get_validator(&pubkey(1)).unwrap().stake, 9_500);
+        // And so should its stake in the pool, though it is locked.
+        assert_eq!(pool.stake_of(&pubkey(1)), 9_500);
+        assert_eq!(pool.total_staked(), 34_500);
 
         // Slashed amount goes to treasury.
         assert_eq!(engine.treasury, 500);
@@ -383,7 +379,9 @@ mod tests {
             .slash_validator(&pubkey(1), SlashingOffense::DoubleSign, 100, &mut vs, &mut pool)
             .unwrap();
 
-        // Unjail so we can slash again.
+        // Serve the jail period, then unjail so we can slash again.
+        vs.epoch_rotation(&pool);
+        vs.epoch_rotation(&pool);
         vs.unjail(&pubkey(1)).unwrap();
 
         // Second slash: 9,500 * 1% = 95.
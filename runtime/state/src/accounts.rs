@@ -33,6 +33,24 @@ impl AccountState {
         Ok(())
     }
 
+    /// The bytes committed to in the state tree: balance then nonce, both
+    /// little-endian.
+    pub fn to_leaf_bytes(&self) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        out[..8].copy_from_slice(&self.balance.to_le_bytes());
+        out[8..].copy_from_slice(&self.nonce.to_le_bytes());
+        out
+    }
+
+    /// Decode the bytes written by `to_leaf_bytes`.
+    pub fn from_leaf_bytes(bytes: &[u8; 16]) -> Self {
+        let (balance, nonce) = bytes.split_at(8);
+        Self {
+            balance: u64::from_le_bytes(balance.try_into().unwrap()),
+            nonce: u64::from_le_bytes(nonce.try_into().unwrap()),
+        }
+    }
+
     /// Increment the nonce by 1.
     pub fn increment_nonce(&mut self) {
         self.nonce += 1;
@@ -0,0 +1,463 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use trv1_storage::{StorageError, StorageKey, StorageValue, TieredStorage, WriteBatch};
+
+use crate::accounts::AccountState;
+use crate::smt::{NodeStore, TreeChanges, EMPTY_HASH};
+use crate::types::StateError;
+
+/// An account to store, or `None` to delete it.
+pub type AccountChange = ([u8; 32], Option<AccountState>);
+
+/// Where a data entry is kept: the account that owns it, and its key.
+pub type DataKey = ([u8; 32], Vec<u8>);
+
+/// A data entry to store, or `None` to delete it.
+pub type DataChange = (DataKey, Option<Vec<u8>>);
+
+/// What a stored state holds as a whole, to open it without reading every
+/// account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateSummary {
+    /// Root of the state's sparse Merkle tree.
+    pub root: [u8; 32],
+    /// Number of entries in the tree: accounts and data entries.
+    pub entries: u64,
+    /// Number of accounts.
+    pub accounts: u64,
+    /// Sum of all account balances.
+    pub supply: u64,
+    /// Hash of the last committed block, zero if none was recorded.
+    pub block_hash: [u8; 32],
+}
+
+impl Default for StateSummary {
+    fn default() -> Self {
+        Self {
+            root: EMPTY_HASH,
+            entries: 0,
+            accounts: 0,
+            supply: 0,
+            block_hash: [0; 32],
+        }
+    }
+}
+
+/// One block's state changes, as handed to `StateBackend::commit`.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockCommit<'a> {
+    pub height: u64,
+    /// The accounts the block changed, with their new values.
+    pub changes: &'a [AccountChange],
+    /// The data entries the block changed, with their new values.
+    pub data: &'a [DataChange],
+    /// The state tree's nodes to write and delete.
+    pub nodes: &'a TreeChanges,
+    /// The state after the block.
+    pub summary: StateSummary,
+    /// The same accounts with their values before the block, to keep as
+    /// history, or `None` if no history is kept. A block without an undo
+    /// record drops all stored history.
+    pub undo: Option<&'a [AccountChange]>,
+    /// Drop the undo records of blocks below this height.
+    pub prune_below: Option<u64>,
+}
+
+/// Durable storage behind a `StateDB`.
+///
+/// The state is written once per block: `commit` stores the accounts and
+/// data entries that changed, the state tree's changed nodes and the summary together with
+/// the block height, atomically, so after a crash the backend holds exactly
+/// the state as of some committed height. Blocks' undo records are stored
+/// in the same write, for queries at past heights.
+///
+/// Accounts, data entries and tree nodes are read one at a time as they are needed, so
+/// opening a state reads only its summary.
+pub trait StateBackend: NodeStore + fmt::Debug {
+    /// Height of the last committed block, or `None` if nothing has been
+    /// committed yet.
+    fn committed_height(&self) -> Result<Option<u64>, StateError>;
+
+    /// The summary of the committed state, empty if nothing has been
+    /// committed yet.
+    fn load_summary(&self) -> Result<StateSummary, StateError>;
+
+    /// The committed account `pubkey`, if it exists.
+    fn load_account(&self, pubkey: &[u8; 32]) -> Result<Option<AccountState>, StateError>;
+
+    /// The committed value of the data entry at `key`, if it exists.
+    fn load_data(&self, key: &DataKey) -> Result<Option<Vec<u8>>, StateError>;
+
+    /// Store the block's changes, tree nodes, summary and undo record and
+    /// record its height as committed, all or nothing. A `None` account or
+    /// data entry is deleted.
+    fn commit(&self, commit: &BlockCommit<'_>) -> Result<(), StateError>;
+
+    /// Heights of the blocks whose undo records are stored.
+    fn history_range(&self) -> Result<Option<RangeInclusive<u64>>, StateError>;
+
+    /// The undo record stored for block `height`.
+    fn load_undo(&self, height: u64) -> Result<Vec<AccountChange>, StateError>;
+}
+
+impl From<StorageError> for StateError {
+    fn from(e: StorageError) -> Self {
+        StateError::Storage(e.to_string())
+    }
+}
+
+/// Key of the last committed height.
+const HEIGHT_KEY: &str = "state/height";
+
+/// Key of the committed state's summary.
+const SUMMARY_KEY: &str = "state/summary";
+
+/// Key of the oldest block height with a stored undo record.
+const HISTORY_START_KEY: &str = "state/history/start";
+
+/// Size of a stored summary: root, entry count, account count, supply and
+/// block hash.
+const SUMMARY_LEN: usize = 32 + 8 + 8 + 8 + 32;
+
+/// Size of one undo entry: pubkey, a byte that is 1 if the account
+/// existed, then `to_leaf_bytes` (zero if it did not).
+const UNDO_RECORD_LEN: usize = 32 + 1 + 16;
+
+/// `StateBackend` on `TieredStorage`.
+///
+/// Each account is stored under its own key as its `to_leaf_bytes`, each
+/// data entry under its owner and key, and each state tree node under its
+/// hash, so a commit writes only what the
+/// block changed and every read is one key. Each block's undo record is
+/// stored under its own key.
+pub struct StorageBackend {
+    storage: Arc<TieredStorage>,
+}
+
+impl StorageBackend {
+    pub fn new(storage: Arc<TieredStorage>) -> Self {
+        Self { storage }
+    }
+
+    fn account_key(pubkey: &[u8; 32]) -> StorageKey {
+        StorageKey::from(format!("state/account/{}", hex::encode(pubkey)).as_str())
+    }
+
+    fn data_key((owner, key): &DataKey) -> StorageKey {
+        StorageKey::from(format!("state/data/{}/{}", hex::encode(owner), hex::encode(key)).as_str())
+    }
+
+    fn node_key(hash: &[u8; 32]) -> StorageKey {
+        StorageKey::from(format!("state/node/{}", hex::encode(hash)).as_str())
+    }
+
+    fn history_key(height: u64) -> StorageKey {
+        StorageKey::from(format!("state/history/{height:016x}").as_str())
+    }
+
+    fn read_u64(&self, key: &str) -> Result<Option<u64>, StateError> {
+        let Some(value) = self.storage.get(&StorageKey::from(key))? else {
+            return Ok(None);
+        };
+        let bytes: [u8; 8] = value
+            .as_bytes()
+            .try_into()
+            .map_err(|_| StateError::Storage(format!("corrupt value under {key}")))?;
+        Ok(Some(u64::from_le_bytes(bytes)))
+    }
+
+    fn encode_summary(summary: &StateSummary) -> StorageValue {
+        let mut out = Vec::with_capacity(SUMMARY_LEN);
+        out.extend_from_slice(&summary.root);
+        out.extend_from_slice(&summary.entries.to_le_bytes());
+        out.extend_from_slice(&summary.accounts.to_le_bytes());
+        out.extend_from_slice(&summary.supply.to_le_bytes());
+        out.extend_from_slice(&summary.block_hash);
+        StorageValue(out)
+    }
+
+    fn encode_undo(undo: &[AccountChange]) -> StorageValue {
+        let mut out = Vec::with_capacity(undo.len() * UNDO_RECORD_LEN);
+        for (pubkey, account) in undo {
+            out.extend_from_slice(pubkey);
+            match account {
+                Some(account) => {
+                    out.push(1);
+                    out.extend_from_slice(&account.to_leaf_bytes());
+                }
+                None => out.extend_from_slice(&[0; 17]),
+            }
+        }
+        StorageValue(out)
+    }
+}
+
+impl NodeStore for StorageBackend {
+    fn load_node(&self, hash: &[u8; 32]) -> Result<Option<Vec<u8>>, StateError> {
+        Ok(self.storage.get(&Self::node_key(hash))?.map(|value| value.0))
+    }
+}
+
+impl StateBackend for StorageBackend {
+    fn committed_height(&self) -> Result<Option<u64>, StateError> {
+        self.read_u64(HEIGHT_KEY)
+    }
+
+    fn load_summary(&self) -> Result<StateSummary, StateError> {
+        let Some(value) = self.storage.get(&StorageKey::from(SUMMARY_KEY))? else {
+            return Ok(StateSummary::default());
+        };
+        let bytes = value.as_bytes();
+        if bytes.len() != SUMMARY_LEN {
+            return Err(StateError::Storage(format!(
+                "state summary is {} bytes, not {SUMMARY_LEN}",
+                bytes.len()
+            )));
+        }
+        Ok(StateSummary {
+            root: bytes[..32].try_into().unwrap(),
+            entries: u64::from_le_bytes(bytes[32..40].try_into().unwrap()),
+            accounts: u64::from_le_bytes(bytes[40..48].try_into().unwrap()),
+            supply: u64::from_le_bytes(bytes[48..56].try_into().unwrap()),
+            block_hash: bytes[56..].try_into().unwrap(),
+        })
+    }
+
+    fn load_account(&self, pubkey: &[u8; 32]) -> Result<Option<AccountState>, StateError> {
+        let Some(value) = self.storage.get(&Self::account_key(pubkey))? else {
+            return Ok(None);
+        };
+        let bytes: [u8; 16] = value.as_bytes().try_into().map_err(|_| {
+            StateError::Storage(format!("corrupt account {}", hex::encode(pubkey)))
+        })?;
+        Ok(Some(AccountState::from_leaf_bytes(&bytes)))
+    }
+
+    fn load_data(&self, key: &DataKey) -> Result<Option<Vec<u8>>, StateError> {
+        Ok(self.storage.get(&Self::data_key(key))?.map(|value| value.0))
+    }
+
+    fn commit(&self, commit: &BlockCommit<'_>) -> Result<(), StateError> {
+        let height = commit.height;
+        let mut batch = WriteBatch::new();
+        for (pubkey, account) in commit.changes {
+            match account {
+                Some(account) => batch.put(
+                    Self::account_key(pubkey),
+                    StorageValue(account.to_leaf_bytes().to_vec()),
+                ),
+                None => batch.delete(Self::account_key(pubkey)),
+            }
+        }
+        for (key, value) in commit.data {
+            match value {
+                Some(value) => batch.put(Self::data_key(key), StorageValue(value.clone())),
+                None => batch.delete(Self::data_key(key)),
+            }
+        }
+        for hash in &commit.nodes.delete {
+            batch.delete(Self::node_key(hash));
+        }
+        for (hash, node) in &commit.nodes.put {
+            batch.put(Self::node_key(hash), StorageValue(node.clone()));
+        }
+        batch.put(StorageKey::from(SUMMARY_KEY), Self::encode_summary(&commit.summary));
+
+        let old_start = self.read_u64(HISTORY_START_KEY)?;
+        let new_start = commit.undo.map(|undo| {
+            batch.put(Self::history_key(height), Self::encode_undo(undo));
+            let start = old_start.unwrap_or(height);
+            commit.prune_below.map_or(start, |below| start.max(below))
+        });
+        if let Some(old_start) = old_start {
+            for pruned in old_start..new_start.unwrap_or(height) {
+                batch.delete(Self::history_key(pruned));
+            }
+        }
+        match new_start {
+            Some(start) => batch.put(
+                StorageKey::from(HISTORY_START_KEY),
+                StorageValue::new(start.to_le_bytes().to_vec()),
+            ),
+            None if old_start.is_some() => batch.delete(StorageKey::from(HISTORY_START_KEY)),
+            None => {}
+        }
+
+        batch.put(
+            StorageKey::from(HEIGHT_KEY),
+            StorageValue::new(height.to_le_bytes().to_vec()),
+        );
+        self.storage.write_batch(&batch)?;
+        Ok(())
+    }
+
+    fn history_range(&self) -> Result<Option<RangeInclusive<u64>>, StateError> {
+        let (Some(start), Some(end)) = (self.read_u64(HISTORY_START_KEY)?, self.committed_height()?)
+        else {
+            return Ok(None);
+        };
+        Ok((start <= end).then_some(start..=end))
+    }
+
+    fn load_undo(&self, height: u64) -> Result<Vec<AccountChange>, StateError> {
+        let Some(value) = self.storage.get(&Self::history_key(height))? else {
+            return Err(StateError::Storage(format!("missing undo record for height {height}")));
+        };
+        let bytes = value.as_bytes();
+        if bytes.len() % UNDO_RECORD_LEN != 0 {
+            return Err(StateError::Storage(format!(
+                "undo record for height {height} is {} bytes, not a multiple of {UNDO_RECORD_LEN}",
+                bytes.len()
+            )));
+        }
+        Ok(bytes
+            .chunks_exact(UNDO_RECORD_LEN)
+            .map(|record| {
+                let (pubkey, rest) = record.split_at(32);
+                let account = (rest[0] == 1)
+                    .then(|| AccountState::from_leaf_bytes(rest[1..].try_into().unwrap()));
+                (pubkey.try_into().unwrap(), account)
+            })
+            .collect())
+    }
+}
+
+impl fmt::Debug for StorageBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StorageBackend").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use trv1_storage::StorageConfig;
+
+    fn temp_storage(name: &str) -> (Arc<TieredStorage>, PathBuf) {
+        let dir = env::temp_dir().join(format!("trv1_state_backend_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = StorageConfig {
+            lru_capacity: 16,
+            nvme_path: dir.join("warm").to_string_lossy().into_owned(),
+            archive_path: dir.join("cold").to_string_lossy().into_owned(),
+            max_ram_bytes: 1024,
+        };
+        (Arc::new(TieredStorage::new(&config).unwrap()), dir)
+    }
+
+    const NO_NODES: &TreeChanges = &TreeChanges {
+        put: Vec::new(),
+        delete: Vec::new(),
+    };
+
+    fn block(height: u64, changes: &[AccountChange]) -> BlockCommit<'_> {
+        BlockCommit {
+            height,
+            changes,
+            data: &[],
+            nodes: NO_NODES,
+            summary: StateSummary::default(),
+            undo: None,
+            prune_below: None,
+        }
+    }
+
+    #[test]
+    fn test_commit_and_load() {
+        let (storage, dir) = temp_storage("roundtrip");
+        let backend = StorageBackend::new(storage);
+        assert_eq!(backend.committed_height().unwrap(), None);
+        assert_eq!(backend.load_summary().unwrap(), StateSummary::default());
+
+        let a = [1u8; 32];
+        let mut b = [1u8; 32];
+        b[31] = 2;
+        let c = [9u8; 32];
+        backend
+            .commit(&block(
+                0,
+                &[
+                    (a, Some(AccountState::new(100))),
+                    (b, Some(AccountState::new(200))),
+                    (c, Some(AccountState::new(300))),
+                ],
+            ))
+            .unwrap();
+        let summary = StateSummary {
+            root: [7; 32],
+            entries: 3,
+            accounts: 2,
+            supply: 250,
+            block_hash: [8; 32],
+        };
+        let nodes = TreeChanges {
+            put: vec![([7; 32], vec![1; 65])],
+            delete: vec![],
+        };
+        backend
+            .commit(&BlockCommit {
+                data: &[(([9; 32], b"key".to_vec()), Some(b"value".to_vec()))],
+                nodes: &nodes,
+                summary,
+                ..block(1, &[(a, Some(AccountState { balance: 50, nonce: 1 })), (c, None)])
+            })
+            .unwrap();
+
+        assert_eq!(backend.committed_height().unwrap(), Some(1));
+        assert_eq!(backend.load_summary().unwrap(), summary);
+        assert_eq!(backend.load_account(&a).unwrap(), Some(AccountState { balance: 50, nonce: 1 }));
+        assert_eq!(backend.load_account(&b).unwrap(), Some(AccountState::new(200)));
+        assert_eq!(backend.load_account(&c).unwrap(), None);
+        assert_eq!(backend.load_node(&[7; 32]).unwrap(), Some(vec![1; 65]));
+        assert_eq!(backend.load_data(&([9; 32], b"key".to_vec())).unwrap(), Some(b"value".to_vec()));
+        assert_eq!(backend.load_data(&([9; 32], b"other".to_vec())).unwrap(), None);
+
+        backend
+            .commit(&BlockCommit {
+                nodes: &TreeChanges {
+                    put: vec![],
+                    delete: vec![[7; 32]],
+                },
+                ..block(2, &[])
+            })
+            .unwrap();
+        assert_eq!(backend.load_node(&[7; 32]).unwrap(), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_undo_records_pruned() {
+        let (storage, dir) = temp_storage("history");
+        let backend = StorageBackend::new(storage);
+        let a = [1u8; 32];
+        assert_eq!(backend.history_range().unwrap(), None);
+
+        for height in 0..4 {
+            let changes = [(a, Some(AccountState::new(height + 1)))];
+            let undo = [(a, (height > 0).then(|| AccountState::new(height)))];
+            backend
+                .commit(&BlockCommit {
+                    undo: Some(&undo),
+                    prune_below: Some(height.saturating_sub(1)),
+                    ..block(height, &changes)
+                })
+                .unwrap();
+        }
+        assert_eq!(backend.history_range().unwrap(), Some(2..=3));
+        assert_eq!(backend.load_undo(3).unwrap(), vec![(a, Some(AccountState::new(3)))]);
+        assert!(backend.load_undo(1).is_err());
+
+        // A block without an undo record drops the history.
+        backend.commit(&block(4, &[])).unwrap();
+        assert_eq!(backend.history_range().unwrap(), None);
+        assert!(backend.load_undo(3).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
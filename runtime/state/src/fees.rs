@@ -0,0 +1,110 @@
+//! Charging transaction fees during block execution and distributing them.
+//!
+//! A transaction pays `base_fee` plus its tip for each unit of gas it uses,
+//! as metered by the `GasSchedule`. Each fee is split
+//! with the chain's `FeeSplit` at the block's epoch: the burn share leaves
+//! circulation, the validator share goes to the block's proposer, and the
+//! treasury and developer shares go to protocol-owned module accounts.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use trv1_bft::block::Transaction;
+use trv1_fees::{FeeSplit, SplitResult};
+
+use crate::gas::GasSchedule;
+use crate::types::StateError;
+
+/// Address of the protocol-owned account called `name`.
+///
+/// Module accounts are derived by hashing, so no one holds a signing key
+/// for them; only the protocol moves their funds.
+pub fn module_account(name: &str) -> [u8; 32] {
+    let digest = Sha256::digest(format!("trv1/module/{name}").as_bytes());
+    digest.into()
+}
+
+/// The account that receives the treasury share of fees.
+pub fn treasury_account() -> [u8; 32] {
+    module_account("treasury")
+}
+
+/// The account that holds the developer share of fees until it is paid out
+/// to contract developers.
+pub fn developer_pool_account() -> [u8; 32] {
+    module_account("developer-pool")
+}
+
+/// What block execution needs to charge and distribute fees.
+#[derive(Debug, Clone)]
+pub struct FeeContext {
+    /// Base fee per gas the block is priced at.
+    pub base_fee: u64,
+    /// Epoch the block belongs to, which sets the split ratios.
+    pub epoch: u64,
+    /// Proposer of the block, paid the validator share. If unknown, that
+    /// share is burned.
+    pub proposer: Option<[u8; 32]>,
+    pub split: FeeSplit,
+    /// What each transaction's work costs in gas.
+    pub gas: GasSchedule,
+    /// Most gas the block's transactions may use between them.
+    pub max_gas_per_block: u64,
+}
+
+impl FeeContext {
+    /// What `tx` pays per unit of gas: the base fee plus its tip.
+    pub fn price_per_gas(&self, tx: &Transaction) -> Result<u64, StateError> {
+        if tx.max_fee_per_gas < self.base_fee {
+            return Err(StateError::FeeTooLow {
+                max_fee: tx.max_fee_per_gas,
+                base_fee: self.base_fee,
+            });
+        }
+        Ok(self.base_fee + tx.effective_tip(self.base_fee))
+    }
+
+    /// Fail unless `tx`'s gas limit covers its intrinsic gas. Returns the
+    /// intrinsic gas.
+    pub fn check_gas_limit(&self, tx: &Transaction) -> Result<u64, StateError> {
+        let intrinsic = self.gas.intrinsic_gas(tx);
+        if tx.gas_limit < intrinsic {
+            return Err(StateError::GasLimitTooLow {
+                limit: tx.gas_limit,
+                required: intrinsic,
+            });
+        }
+        Ok(intrinsic)
+    }
+}
+
+/// The fees charged in one block and where they went.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockFees {
+    /// Sum of every fee charged.
+    pub total: u64,
+    pub burned: u64,
+    /// Paid to the proposer.
+    pub validator: u64,
+    /// Paid to `treasury_account()`.
+    pub treasury: u64,
+    /// Paid to `developer_pool_account()`.
+    pub developer: u64,
+    /// The developer share of the fee of each successful contract call,
+    /// by the contract called, so it can be attributed to its deployer.
+    pub developer_shares: Vec<([u8; 32], u64)>,
+}
+
+impl BlockFees {
+    /// Account for one transaction's fee, split as `split`, whose developer
+    /// share is owed to `contract` if it was a successful call to one.
+    pub(crate) fn add(&mut self, contract: Option<[u8; 32]>, fee: u64, split: &SplitResult) {
+        self.total += fee;
+        self.burned += split.burn;
+        self.validator += split.validator;
+        self.treasury += split.treasury;
+        self.developer += split.developer;
+        if let Some(contract) = contract.filter(|_| split.developer > 0) {
+            self.developer_shares.push((contract, split.developer));
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! What transactions cost in gas.
+//!
+//! A transaction's gas is its intrinsic gas, paid before it runs, plus the
+//! gas its payload uses. The intrinsic gas is a flat `tx_base`, which also
+//! covers reading and writing the sender's and recipient's accounts, and
+//! `payload_byte` for each byte of `data`. A plain transfer with no data
+//! therefore costs exactly `TRANSFER_GAS`. Every other payload pays for
+//! the module state it reads and writes, and for any extra work its type
+//! does, such as storing code or checking signatures. A contract call also
+//! pays for the instructions its contract runs and the host functions it
+//! uses.
+//!
+//! A transaction declares the most gas it may use in `gas_limit`. One
+//! whose limit is below its intrinsic gas is invalid and never runs; one
+//! that runs out while executing fails with `OutOfGas`, its payload's
+//! changes are undone, and it pays for its whole limit.
+
+use trv1_bft::block::Transaction;
+use trv1_contracts::ContractGas;
+use trv1_fees::TRANSFER_GAS;
+
+use crate::payload::TxPayload;
+use crate::types::StateError;
+
+/// Gas prices of each kind of work a transaction can do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasSchedule {
+    /// Paid by every transaction, covering its signature and the sender's
+    /// and recipient's accounts.
+    pub tx_base: u64,
+    /// Per byte of transaction `data`.
+    pub payload_byte: u64,
+    /// Per module state entry read.
+    pub state_read: u64,
+    /// Per state entry written beyond the sender's and recipient's
+    /// accounts.
+    pub state_write: u64,
+    /// Per byte of contract code stored.
+    pub code_byte: u64,
+    /// Per signature checked while executing, beyond the transaction's own.
+    pub signature_check: u64,
+    /// What contract calls pay for host work, on top of one gas per unit
+    /// of interpreter fuel.
+    pub contract: ContractGas,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self {
+            tx_base: TRANSFER_GAS,
+            payload_byte: 16,
+            state_read: 2_100,
+            state_write: 5_000,
+            code_byte: 200,
+            signature_check: 3_000,
+            contract: ContractGas::default(),
+        }
+    }
+}
+
+impl GasSchedule {
+    /// Gas `tx` pays before it runs: the base cost and its data.
+    pub fn intrinsic_gas(&self, tx: &Transaction) -> u64 {
+        (tx.data.len() as u64)
+            .saturating_mul(self.payload_byte)
+            .saturating_add(self.tx_base)
+    }
+
+    /// Gas executing `payload` uses on top of the intrinsic gas.
+    pub fn execution_gas(&self, payload: &TxPayload) -> u64 {
+        // (state reads, state writes, extra work)
+        let (reads, writes, extra) = match payload {
+            TxPayload::Transfer => (0, 0, 0),
+            // The stake entry and validator record are read; the staking
+            // account, stake entry and validator stake are written.
+            TxPayload::Stake { .. } | TxPayload::Unstake { .. } => (2, 3, 0),
+            // The validator record and delegation are read; the staking
+            // account and delegation are written.
+            TxPayload::Delegate { .. } => (2, 2, 0),
+            TxPayload::Undelegate { .. } => (1, 2, 0),
+            // As staking, plus the pending registration.
+            TxPayload::RegisterValidator { .. } => (2, 4, 0),
+            TxPayload::EditValidator {
+                commission_rate,
+                metadata,
+            } => (
+                1,
+                u64::from(commission_rate.is_some()) + u64::from(metadata.is_some()),
+                0,
+            ),
+            TxPayload::Unjail => (1, 1, 0),
+            TxPayload::DeployContract { code } => (
+                1,
+                1,
+                (code.len() as u64).saturating_mul(self.code_byte),
+            ),
+            // Both votes' signatures are checked before the evidence is
+            // queued.
+            TxPayload::SubmitEvidence(_) => (1, 1, 2 * self.signature_check),
+            // The contract is looked up; running it is metered as it goes.
+            TxPayload::CallContract { .. } => (1, 0, 0),
+        };
+        (reads * self.state_read)
+            .saturating_add(writes * self.state_write)
+            .saturating_add(extra)
+    }
+}
+
+/// Counts the gas a transaction uses against its limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasMeter {
+    limit: u64,
+    used: u64,
+}
+
+impl GasMeter {
+    /// A meter allowing `limit` gas.
+    pub fn new(limit: u64) -> Self {
+        Self { limit, used: 0 }
+    }
+
+    /// Use `gas` more. If that would pass the limit, all of the limit is
+    /// used up and `OutOfGas` is returned.
+    pub fn charge(&mut self, gas: u64) -> Result<(), StateError> {
+        match self.used.checked_add(gas).filter(|&used| used <= self.limit) {
+            Some(used) => {
+                self.used = used;
+                Ok(())
+            }
+            None => {
+                self.used = self.limit;
+                Err(StateError::OutOfGas { limit: self.limit })
+            }
+        }
+    }
+
+    /// Gas used so far.
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Gas left before the limit.
+    pub fn remaining(&self) -> u64 {
+        self.limit - self.used
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trv1_staking::LockTier;
+
+    fn tx_with_data(data: Vec<u8>) -> Transaction {
+        Transaction {
+            from: [1; 32],
+            to: [2; 32],
+            amount: 0,
+            nonce: 0,
+            signature: vec![],
+            data,
+            gas_limit: 0,
+            max_fee_per_gas: 0,
+            max_priority_fee_per_gas: 0,
+        }
+    }
+
+    #[test]
+    fn test_transfer_costs_transfer_gas() {
+        let schedule = GasSchedule::default();
+        assert_eq!(schedule.intrinsic_gas(&tx_with_data(vec![])), TRANSFER_GAS);
+        assert_eq!(schedule.execution_gas(&TxPayload::Transfer), 0);
+    }
+
+    #[test]
+    fn test_gas_by_type_bytes_and_state() {
+        let schedule = GasSchedule::default();
+        let stake = TxPayload::Stake {
+            amount: 1_000,
+            tier: LockTier::NoLock,
+        };
+        let data = stake.to_data();
+        assert_eq!(
+            schedule.intrinsic_gas(&tx_with_data(data.clone())),
+            TRANSFER_GAS + 16 * data.len() as u64
+        );
+        assert_eq!(schedule.execution_gas(&stake), 2 * 2_100 + 3 * 5_000);
+
+        let small = TxPayload::DeployContract { code: vec![0; 10] };
+        let large = TxPayload::DeployContract { code: vec![0; 100] };
+        assert_eq!(
+            schedule.execution_gas(&large) - schedule.execution_gas(&small),
+            90 * 200
+        );
+    }
+
+    #[test]
+    fn test_meter_out_of_gas_uses_whole_limit() {
+        let mut meter = GasMeter::new(50_000);
+        meter.charge(30_000).unwrap();
+        assert_eq!(meter.used(), 30_000);
+        meter.charge(20_000).unwrap();
+        assert_eq!(meter.used(), 50_000);
+
+        let mut meter = GasMeter::new(50_000);
+        meter.charge(30_000).unwrap();
+        assert_eq!(
+            meter.charge(30_000),
+            Err(StateError::OutOfGas { limit: 50_000 })
+        );
+        assert_eq!(meter.used(), 50_000);
+        assert!(meter.charge(u64::MAX).is_err());
+    }
+}
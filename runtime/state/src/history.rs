@@ -0,0 +1,108 @@
+//! Account state at past heights.
+//!
+//! For each committed block the history keeps an undo record: the value
+//! every account the block changed had before the block. The state at a
+//! past height `h` is the current state with, for each account, the undo
+//! value from the first block after `h` that changed it. Accounts no later
+//! block changed are as they are now.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+
+use crate::accounts::AccountState;
+use crate::backend::AccountChange;
+
+/// Default number of past blocks whose state stays queryable.
+pub const DEFAULT_HISTORY_RETENTION: u64 = 1024;
+
+/// How much state history a `StateDB` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryRetention {
+    /// Keep the state as of the latest block and the given number of
+    /// blocks before it. `Recent(0)` keeps no history.
+    Recent(u64),
+    /// Keep the state at every height since history was first kept.
+    Archive,
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        HistoryRetention::Recent(DEFAULT_HISTORY_RETENTION)
+    }
+}
+
+/// Undo records of the retained blocks.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StateHistory {
+    retention: HistoryRetention,
+    /// Each account's value before each retained block that changed it,
+    /// keyed by the block's height.
+    before: HashMap<[u8; 32], BTreeMap<u64, Option<AccountState>>>,
+    /// Accounts changed by each retained block.
+    blocks: BTreeMap<u64, Vec<[u8; 32]>>,
+}
+
+impl StateHistory {
+    /// Change the retention. Records it no longer keeps are dropped by the
+    /// next `prune`.
+    pub(crate) fn set_retention(&mut self, retention: HistoryRetention) {
+        self.retention = retention;
+    }
+
+    /// Whether blocks' undo records are kept at all.
+    pub(crate) fn enabled(&self) -> bool {
+        self.retention != HistoryRetention::Recent(0)
+    }
+
+    /// Lowest block height whose undo record is still needed once `latest`
+    /// is committed, or `None` if nothing is ever pruned.
+    pub(crate) fn keep_from(&self, latest: u64) -> Option<u64> {
+        match self.retention {
+            HistoryRetention::Recent(blocks) => Some((latest + 1).saturating_sub(blocks)),
+            HistoryRetention::Archive => None,
+        }
+    }
+
+    /// Record block `height`'s undo record.
+    pub(crate) fn record(&mut self, height: u64, undo: &[AccountChange]) {
+        for (pubkey, account) in undo {
+            self.before
+                .entry(*pubkey)
+                .or_default()
+                .insert(height, account.clone());
+        }
+        self.blocks
+            .insert(height, undo.iter().map(|(pubkey, _)| *pubkey).collect());
+    }
+
+    /// Drop the undo records of blocks below `height`.
+    pub(crate) fn prune(&mut self, height: u64) {
+        let kept = self.blocks.split_off(&height);
+        for (block, pubkeys) in std::mem::replace(&mut self.blocks, kept) {
+            for pubkey in pubkeys {
+                if let Entry::Occupied(mut entry) = self.before.entry(pubkey) {
+                    entry.get_mut().remove(&block);
+                    if entry.get().is_empty() {
+                        entry.remove();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Oldest height whose state can be rebuilt, if any block is retained.
+    pub(crate) fn oldest(&self) -> Option<u64> {
+        self.blocks.keys().next().map(|height| height.saturating_sub(1))
+    }
+
+    /// `pubkey`'s value at `height`, or `None` if no retained block after
+    /// `height` changed it.
+    pub(crate) fn account_at(&self, pubkey: &[u8; 32], height: u64) -> Option<&Option<AccountState>> {
+        self.before
+            .get(pubkey)?
+            .range((Bound::Excluded(height), Bound::Unbounded))
+            .next()
+            .map(|(_, account)| account)
+    }
+}
@@ -1,7 +1,19 @@
 pub mod accounts;
+pub mod backend;
+pub mod fees;
+pub mod gas;
+pub mod history;
+pub mod payload;
+pub mod smt;
 pub mod state_db;
 pub mod types;
 
 pub use accounts::AccountState;
-pub use state_db::StateDB;
+pub use backend::{AccountChange, BlockCommit, StateBackend, StorageBackend};
+pub use fees::{developer_pool_account, module_account, treasury_account, BlockFees, FeeContext};
+pub use gas::{GasMeter, GasSchedule};
+pub use history::{HistoryRetention, DEFAULT_HISTORY_RETENTION};
+pub use payload::{contract_address, evidence_record, staking_account, Modules, TxPayload, PAYLOAD_VERSION};
+pub use smt::{verify_proof, SmtProof, SparseMerkleTree};
+pub use state_db::{AccountProof, Checkpoint, StateDB};
 pub use types::*;
@@ -0,0 +1,439 @@
+//! Typed transaction payloads.
+//!
+//! A transaction's `data` says what it does. Empty data is a plain
+//! transfer of `amount` to `to`, so transfers signed before payloads
+//! existed keep their meaning. Anything else is `PAYLOAD_VERSION` followed
+//! by the canonical encoding of a `TxPayload`: a one-byte variant tag and
+//! the variant's fields, laid out by the rules in `trv1_bft::encoding`.
+//!
+//! Only transfers and contract calls move `amount`. Every other payload
+//! carries its own amounts and must leave `amount` at zero.
+
+use sha2::{Digest, Sha256};
+use trv1_contracts::ContractRuntime;
+use trv1_bft::encoding::{Decode, DecodeError, Encode};
+use trv1_bft::types::DuplicateVoteEvidence;
+use trv1_rewards::DeveloperRewards;
+use trv1_slashing::{EvidenceRecord, SlashingEngine, SlashingOffense};
+use trv1_staking::{LockTier, StakingPool};
+use trv1_validator_set::{ValidatorMetadata, ValidatorSetManager};
+
+use crate::fees::module_account;
+
+/// Version byte leading every non-empty payload.
+pub const PAYLOAD_VERSION: u8 = 1;
+
+/// What a transaction does.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxPayload {
+    /// Move the transaction's `amount` to its `to` account.
+    Transfer,
+    /// Lock `amount` of the sender's balance in the staking pool.
+    Stake { amount: u64, tier: LockTier },
+    /// Return `amount` of the sender's unlocked stake to their balance.
+    Unstake { amount: u64 },
+    /// Lock `amount` of the sender's balance as stake delegated to
+    /// `validator`.
+    Delegate {
+        validator: [u8; 32],
+        amount: u64,
+        tier: LockTier,
+    },
+    /// Return `amount` of the sender's unlocked delegation to `validator`.
+    Undelegate { validator: [u8; 32], amount: u64 },
+    /// Stake `self_stake` and register the sender as a validator charging
+    /// `commission_rate` basis points, from the next epoch.
+    RegisterValidator {
+        self_stake: u64,
+        tier: LockTier,
+        commission_rate: u16,
+    },
+    /// Change the sender's commission rate from the next epoch, and its
+    /// metadata at once. `None` leaves a field as it is.
+    EditValidator {
+        commission_rate: Option<u16>,
+        metadata: Option<ValidatorMetadata>,
+    },
+    /// Move the sender, a jailed validator, back to standby.
+    Unjail,
+    /// Deploy `code`, a WASM module, as a contract owned by the sender.
+    DeployContract { code: Vec<u8> },
+    /// Report a validator that signed two conflicting votes.
+    SubmitEvidence(DuplicateVoteEvidence),
+    /// Send `amount` to the contract at `to` and run it with `input`.
+    CallContract { input: Vec<u8> },
+}
+
+impl TxPayload {
+    /// Short name of the variant, for errors and logs.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TxPayload::Transfer => "transfer",
+            TxPayload::Stake { .. } => "stake",
+            TxPayload::Unstake { .. } => "unstake",
+            TxPayload::Delegate { .. } => "delegate",
+            TxPayload::Undelegate { .. } => "undelegate",
+            TxPayload::RegisterValidator { .. } => "register validator",
+            TxPayload::EditValidator { .. } => "edit validator",
+            TxPayload::Unjail => "unjail",
+            TxPayload::DeployContract { .. } => "deploy contract",
+            TxPayload::SubmitEvidence(_) => "submit evidence",
+            TxPayload::CallContract { .. } => "call contract",
+        }
+    }
+
+    /// Tokens this payload locks from the sender's balance, on top of the
+    /// transaction's `amount`.
+    pub fn staked_amount(&self) -> u64 {
+        match self {
+            TxPayload::Stake { amount, .. } | TxPayload::Delegate { amount, .. } => *amount,
+            TxPayload::RegisterValidator { self_stake, .. } => *self_stake,
+            _ => 0,
+        }
+    }
+
+    /// The transaction `data` carrying this payload.
+    pub fn to_data(&self) -> Vec<u8> {
+        if *self == TxPayload::Transfer {
+            return Vec::new();
+        }
+        let mut out = vec![PAYLOAD_VERSION];
+        self.encode(&mut out);
+        out
+    }
+
+    /// Read the payload from a transaction's `data`. Only the canonical
+    /// form is accepted, so each payload has exactly one byte encoding.
+    pub fn from_data(data: &[u8]) -> Result<Self, DecodeError> {
+        if data.is_empty() {
+            return Ok(TxPayload::Transfer);
+        }
+        let mut input = data;
+        let version = u8::decode(&mut input)?;
+        if version != PAYLOAD_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let payload = TxPayload::decode(&mut input)?;
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes(input.len()));
+        }
+        if payload.to_data() != data {
+            return Err(DecodeError::NonCanonical);
+        }
+        Ok(payload)
+    }
+}
+
+fn encode_tier(tier: LockTier, out: &mut Vec<u8>) {
+    out.push(match tier {
+        LockTier::NoLock => 0,
+        LockTier::ThirtyDay => 1,
+        LockTier::NinetyDay => 2,
+        LockTier::OneEightyDay => 3,
+        LockTier::ThreeSixtyDay => 4,
+        LockTier::Delegator => 5,
+        LockTier::Permanent => 6,
+    });
+}
+
+fn decode_tier(input: &mut &[u8]) -> Result<LockTier, DecodeError> {
+    Ok(match u8::decode(input)? {
+        0 => LockTier::NoLock,
+        1 => LockTier::ThirtyDay,
+        2 => LockTier::NinetyDay,
+        3 => LockTier::OneEightyDay,
+        4 => LockTier::ThreeSixtyDay,
+        5 => LockTier::Delegator,
+        6 => LockTier::Permanent,
+        tag => return Err(DecodeError::InvalidTag { what: "lock tier", tag }),
+    })
+}
+
+fn encode_metadata(metadata: &Option<ValidatorMetadata>, out: &mut Vec<u8>) {
+    match metadata {
+        None => out.push(0x00),
+        Some(metadata) => {
+            out.push(0x01);
+            metadata.moniker.encode(out);
+            metadata.website.encode(out);
+            metadata.details.encode(out);
+        }
+    }
+}
+
+fn decode_metadata(input: &mut &[u8]) -> Result<Option<ValidatorMetadata>, DecodeError> {
+    match u8::decode(input)? {
+        0x00 => Ok(None),
+        0x01 => Ok(Some(ValidatorMetadata {
+            moniker: Decode::decode(input)?,
+            website: Decode::decode(input)?,
+            details: Decode::decode(input)?,
+        })),
+        tag => Err(DecodeError::InvalidTag { what: "option", tag }),
+    }
+}
+
+impl Encode for TxPayload {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            TxPayload::Transfer => out.push(0),
+            TxPayload::Stake { amount, tier } => {
+                out.push(1);
+                amount.encode(out);
+                encode_tier(*tier, out);
+            }
+            TxPayload::Unstake { amount } => {
+                out.push(2);
+                amount.encode(out);
+            }
+            TxPayload::Delegate {
+                validator,
+                amount,
+                tier,
+            } => {
+                out.push(3);
+                validator.encode(out);
+                amount.encode(out);
+                encode_tier(*tier, out);
+            }
+            TxPayload::Undelegate { validator, amount } => {
+                out.push(4);
+                validator.encode(out);
+                amount.encode(out);
+            }
+            TxPayload::RegisterValidator {
+                self_stake,
+                tier,
+                commission_rate,
+            } => {
+                out.push(5);
+                self_stake.encode(out);
+                encode_tier(*tier, out);
+                commission_rate.encode(out);
+            }
+            TxPayload::Unjail => out.push(6),
+            TxPayload::DeployContract { code } => {
+                out.push(7);
+                code.encode(out);
+            }
+            TxPayload::SubmitEvidence(evidence) => {
+                out.push(8);
+                evidence.encode(out);
+            }
+            TxPayload::EditValidator {
+                commission_rate,
+                metadata,
+            } => {
+                out.push(9);
+                commission_rate.encode(out);
+                encode_metadata(metadata, out);
+            }
+            TxPayload::CallContract { input } => {
+                out.push(10);
+                input.encode(out);
+            }
+        }
+    }
+}
+
+impl Decode for TxPayload {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(match u8::decode(input)? {
+            0 => TxPayload::Transfer,
+            1 => TxPayload::Stake {
+                amount: Decode::decode(input)?,
+                tier: decode_tier(input)?,
+            },
+            2 => TxPayload::Unstake {
+                amount: Decode::decode(input)?,
+            },
+            3 => TxPayload::Delegate {
+                validator: Decode::decode(input)?,
+                amount: Decode::decode(input)?,
+                tier: decode_tier(input)?,
+            },
+            4 => TxPayload::Undelegate {
+                validator: Decode::decode(input)?,
+                amount: Decode::decode(input)?,
+            },
+            5 => TxPayload::RegisterValidator {
+                self_stake: Decode::decode(input)?,
+                tier: decode_tier(input)?,
+                commission_rate: Decode::decode(input)?,
+            },
+            6 => TxPayload::Unjail,
+            7 => TxPayload::DeployContract {
+                code: Decode::decode(input)?,
+            },
+            8 => TxPayload::SubmitEvidence(Decode::decode(input)?),
+            9 => TxPayload::EditValidator {
+                commission_rate: Decode::decode(input)?,
+                metadata: decode_metadata(input)?,
+            },
+            10 => TxPayload::CallContract {
+                input: Decode::decode(input)?,
+            },
+            tag => return Err(DecodeError::InvalidTag { what: "payload", tag }),
+        })
+    }
+}
+
+/// The account holding every staked and delegated token.
+pub fn staking_account() -> [u8; 32] {
+    module_account("staking")
+}
+
+/// Address of the contract `deployer` deploys with the transaction of
+/// nonce `nonce`.
+pub fn contract_address(deployer: &[u8; 32], nonce: u64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"trv1/contract");
+    hasher.update(deployer);
+    hasher.update(nonce.to_le_bytes());
+    hasher.finalize().into()
+}
+
+/// The slashing engine's record of double-sign `evidence`. Evidence seen
+/// by consensus and evidence submitted in a transaction map to the same
+/// record, so the engine deduplicates them.
+pub fn evidence_record(evidence: &DuplicateVoteEvidence) -> EvidenceRecord {
+    EvidenceRecord {
+        offense: SlashingOffense::DoubleSign,
+        offender: *evidence.offender().as_bytes(),
+        height: evidence.vote_a.height.0,
+        data: serde_json::to_vec(evidence).unwrap_or_default(),
+        processed: false,
+    }
+}
+
+/// The runtime modules payloads other than transfers act on.
+pub struct Modules<'a> {
+    /// Height of the block being executed.
+    pub height: u64,
+    /// Epoch the block belongs to. Stake locks expire by epoch.
+    pub epoch: u64,
+    pub staking: &'a mut StakingPool,
+    pub validators: &'a mut ValidatorSetManager,
+    pub slashing: &'a mut SlashingEngine,
+    pub rewards: &'a mut DeveloperRewards,
+    pub contracts: &'a mut ContractRuntime,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use trv1_bft::types::{BlockHash, Height, Round, Vote, VoteType};
+
+    fn evidence() -> DuplicateVoteEvidence {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let vote = |hash| Vote::new(VoteType::Prevote, Height(4), Round(0), Some(BlockHash(hash)), &key);
+        DuplicateVoteEvidence {
+            vote_a: vote([1; 32]),
+            vote_b: vote([2; 32]),
+        }
+    }
+
+    #[test]
+    fn test_payload_roundtrip() {
+        let payloads = vec![
+            TxPayload::Stake {
+                amount: 500,
+                tier: LockTier::NinetyDay,
+            },
+            TxPayload::Unstake { amount: 5 },
+            TxPayload::Delegate {
+                validator: [3; 32],
+                amount: 9,
+                tier: LockTier::Delegator,
+            },
+            TxPayload::Undelegate {
+                validator: [3; 32],
+                amount: 9,
+            },
+            TxPayload::RegisterValidator {
+                self_stake: 1_000_000,
+                tier: LockTier::Permanent,
+                commission_rate: 500,
+            },
+            TxPayload::EditValidator {
+                commission_rate: Some(700),
+                metadata: Some(ValidatorMetadata {
+                    moniker: "node-1".into(),
+                    website: String::new(),
+                    details: "Operated from Lisbon".into(),
+                }),
+            },
+            TxPayload::EditValidator {
+                commission_rate: None,
+                metadata: None,
+            },
+            TxPayload::Unjail,
+            TxPayload::DeployContract {
+                code: vec![0, 0x61, 0x73, 0x6d],
+            },
+            TxPayload::SubmitEvidence(evidence()),
+            TxPayload::CallContract {
+                input: b"increment".to_vec(),
+            },
+        ];
+        for payload in payloads {
+            let data = payload.to_data();
+            assert_eq!(data[0], PAYLOAD_VERSION);
+            assert_eq!(TxPayload::from_data(&data).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn test_empty_data_is_transfer() {
+        assert!(TxPayload::Transfer.to_data().is_empty());
+        assert_eq!(TxPayload::from_data(&[]).unwrap(), TxPayload::Transfer);
+    }
+
+    #[test]
+    fn test_decode_rejects_non_canonical_payloads() {
+        // A transfer is only ever empty data, never a tagged payload.
+        assert_eq!(
+            TxPayload::from_data(&[PAYLOAD_VERSION, 0]).unwrap_err(),
+            DecodeError::NonCanonical
+        );
+        let canonical = TxPayload::Stake {
+            amount: 1000,
+            tier: LockTier::ThirtyDay,
+        }
+        .to_data();
+        assert!(TxPayload::from_data(&canonical).is_ok());
+    }
+
+    // Golden vector: payloads are signed as part of the transaction, so
+    // their layout must not change without a new `PAYLOAD_VERSION`.
+    #[test]
+    fn test_golden_stake_payload() {
+        let payload = TxPayload::Stake {
+            amount: 1000,
+            tier: LockTier::ThirtyDay,
+        };
+        assert_eq!(hex::encode(payload.to_data()), "0101e80300000000000001");
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_payloads() {
+        assert_eq!(
+            TxPayload::from_data(&[9, 6]).unwrap_err(),
+            DecodeError::UnsupportedVersion(9)
+        );
+        assert_eq!(
+            TxPayload::from_data(&[PAYLOAD_VERSION, 42]).unwrap_err(),
+            DecodeError::InvalidTag { what: "payload", tag: 42 }
+        );
+        assert_eq!(
+            TxPayload::from_data(&[PAYLOAD_VERSION, 6, 0]).unwrap_err(),
+            DecodeError::TrailingBytes(1)
+        );
+        assert_eq!(
+            TxPayload::from_data(&[PAYLOAD_VERSION, 1, 0, 0, 0, 0, 0, 0, 0, 0, 7]).unwrap_err(),
+            DecodeError::InvalidTag { what: "lock tier", tag: 7 }
+        );
+    }
+}
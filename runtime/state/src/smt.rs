@@ -0,0 +1,743 @@
+//! Sparse Merkle tree committing to a key-value map.
+//!
+//! Keys are placed along a 256-bit path, `SHA-256(key)`, so the tree stays
+//! balanced whatever keys are chosen. The tree is kept in canonical form:
+//! an empty subtree hashes to `EMPTY_HASH`, a subtree holding a single
+//! entry is that entry's leaf (however deep its path goes), and only
+//! subtrees holding two or more entries are internal nodes. The root
+//! therefore depends only on the entries, never on insertion order.
+//!
+//! Hashes are domain-separated:
+//! - leaf: `SHA-256(0x00 || path || SHA-256(value))`
+//! - internal: `SHA-256(0x01 || left || right)`
+//!
+//! Nodes are shared behind `Arc`s and never mutated in place. An update
+//! copies the O(log n) nodes on its path, so cloning a tree to keep an
+//! old version is cheap.
+//!
+//! A tree can live in a `NodeStore`, which keeps each node under its hash.
+//! Such a tree reads nodes from the store the first time they are needed,
+//! and `changes` lists the nodes to write and delete to store a new
+//! version, so memory and writes scale with the entries changed, not the
+//! size of the tree.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::types::StateError;
+
+/// Hash of an empty subtree, and the root of an empty tree.
+pub const EMPTY_HASH: [u8; 32] = [0u8; 32];
+
+/// Number of bits in a key path, and so the tree's maximum depth.
+pub const TREE_DEPTH: usize = 256;
+
+const LEAF_TAG: u8 = 0x00;
+const INTERNAL_TAG: u8 = 0x01;
+
+fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// The path of `key` through the tree.
+pub fn key_path(key: &[u8]) -> [u8; 32] {
+    sha256(&[key])
+}
+
+/// Hash of a stored value.
+pub fn value_hash(value: &[u8]) -> [u8; 32] {
+    sha256(&[value])
+}
+
+/// Hash of the leaf holding a value with `value_hash` at `path`.
+pub fn leaf_hash(path: &[u8; 32], value_hash: &[u8; 32]) -> [u8; 32] {
+    sha256(&[&[LEAF_TAG], path, value_hash])
+}
+
+/// Hash of an internal node over its children's hashes.
+pub fn internal_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    sha256(&[&[INTERNAL_TAG], left, right])
+}
+
+/// Bit `depth` of `path`, most significant first. Set means "go right".
+fn bit(path: &[u8; 32], depth: usize) -> bool {
+    (path[depth / 8] >> (7 - depth % 8)) & 1 == 1
+}
+
+/// Number of leading bits `a` and `b` share.
+fn common_prefix_len(a: &[u8; 32], b: &[u8; 32]) -> usize {
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        let diff = x ^ y;
+        if diff != 0 {
+            return i * 8 + diff.leading_zeros() as usize;
+        }
+    }
+    TREE_DEPTH
+}
+
+/// Where a tree's nodes are kept, by hash.
+pub trait NodeStore: Send + Sync {
+    /// The node with hash `hash`, as encoded in `TreeChanges::put`, or
+    /// `None` if there is none.
+    fn load_node(&self, hash: &[u8; 32]) -> Result<Option<Vec<u8>>, StateError>;
+}
+
+/// What to write to a `NodeStore` to store a new version of a tree.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeChanges {
+    /// Nodes to store, encoded, by hash.
+    pub put: Vec<([u8; 32], Vec<u8>)>,
+    /// Hashes of nodes the new version no longer has.
+    pub delete: Vec<[u8; 32]>,
+}
+
+#[derive(Clone)]
+enum Node {
+    Empty,
+    Leaf {
+        path: [u8; 32],
+        value_hash: [u8; 32],
+        hash: [u8; 32],
+    },
+    Internal {
+        left: Arc<Node>,
+        right: Arc<Node>,
+        hash: [u8; 32],
+    },
+    /// A node in the tree's store, read the first time it is needed.
+    Stored {
+        hash: [u8; 32],
+        node: OnceLock<Arc<Node>>,
+    },
+}
+
+impl Node {
+    fn leaf(path: [u8; 32], value_hash: [u8; 32]) -> Arc<Self> {
+        Arc::new(Node::Leaf {
+            path,
+            value_hash,
+            hash: leaf_hash(&path, &value_hash),
+        })
+    }
+
+    fn internal(left: Arc<Node>, right: Arc<Node>) -> Arc<Self> {
+        let hash = internal_hash(&left.hash(), &right.hash());
+        Arc::new(Node::Internal { left, right, hash })
+    }
+
+    /// The node with hash `hash`, to be read from the store when needed.
+    fn stored(hash: [u8; 32]) -> Arc<Self> {
+        Arc::new(if hash == EMPTY_HASH {
+            Node::Empty
+        } else {
+            Node::Stored {
+                hash,
+                node: OnceLock::new(),
+            }
+        })
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        match self {
+            Node::Empty => EMPTY_HASH,
+            Node::Leaf { hash, .. } | Node::Internal { hash, .. } | Node::Stored { hash, .. } => *hash,
+        }
+    }
+
+    /// The node's encoding in a `NodeStore`: the leaf's path and value
+    /// hash, or the internal node's children's hashes, after its tag.
+    fn encode(&self) -> Vec<u8> {
+        let (tag, a, b) = match self {
+            Node::Leaf { path, value_hash, .. } => (LEAF_TAG, *path, *value_hash),
+            Node::Internal { left, right, .. } => (INTERNAL_TAG, left.hash(), right.hash()),
+            Node::Empty | Node::Stored { .. } => unreachable!("only new nodes are encoded"),
+        };
+        [&[tag][..], &a, &b].concat()
+    }
+
+    /// Decode a node stored under `hash`, with its children left in the
+    /// store.
+    fn decode(hash: &[u8; 32], bytes: &[u8]) -> Result<Arc<Self>, String> {
+        if bytes.len() != 65 {
+            return Err(format!("node is {} bytes, not 65", bytes.len()));
+        }
+        let a: [u8; 32] = bytes[1..33].try_into().unwrap();
+        let b: [u8; 32] = bytes[33..].try_into().unwrap();
+        let node = match bytes[0] {
+            LEAF_TAG => Node::leaf(a, b),
+            INTERNAL_TAG => Node::internal(Node::stored(a), Node::stored(b)),
+            tag => return Err(format!("unknown node tag {tag}")),
+        };
+        if node.hash() != *hash {
+            return Err("node does not match its hash".into());
+        }
+        Ok(node)
+    }
+
+    /// Join two children at `depth`, putting each on its side of `path`.
+    fn join(path: &[u8; 32], depth: usize, child: Arc<Node>, sibling: Arc<Node>) -> Arc<Self> {
+        if bit(path, depth) {
+            Node::internal(sibling, child)
+        } else {
+            Node::internal(child, sibling)
+        }
+    }
+}
+
+/// `node`, read from `store` if it is a stored node not yet read.
+///
+/// # Panics
+///
+/// If the node cannot be read: the tree is then missing part of the state
+/// and nothing built on it could be trusted.
+fn resolve<'a>(node: &'a Arc<Node>, store: Option<&dyn NodeStore>) -> &'a Arc<Node> {
+    let Node::Stored { hash, node: loaded } = node.as_ref() else {
+        return node;
+    };
+    loaded.get_or_init(|| {
+        let store = store.expect("a tree with stored nodes has a store");
+        let bytes = store
+            .load_node(hash)
+            .and_then(|bytes| bytes.ok_or_else(|| StateError::Storage("node not found".into())))
+            .and_then(|bytes| Node::decode(hash, &bytes).map_err(StateError::Storage));
+        bytes.unwrap_or_else(|e| panic!("failed to read state tree node {}: {e}", hex::encode(hash)))
+    })
+}
+
+/// Build the subtree at `depth` holding exactly the two leaves `a` and `b`.
+fn split(depth: usize, a: Arc<Node>, a_path: [u8; 32], b: Arc<Node>, b_path: [u8; 32]) -> Arc<Node> {
+    if bit(&a_path, depth) == bit(&b_path, depth) {
+        let child = split(depth + 1, a, a_path, b, b_path);
+        Node::join(&a_path, depth, child, Arc::new(Node::Empty))
+    } else {
+        Node::join(&a_path, depth, a, b)
+    }
+}
+
+/// Insert or overwrite the value at `path` in the subtree at `depth`.
+/// Returns the new subtree and whether the entry is new.
+fn insert(
+    node: &Arc<Node>,
+    depth: usize,
+    path: [u8; 32],
+    value_hash: [u8; 32],
+    store: Option<&dyn NodeStore>,
+) -> (Arc<Node>, bool) {
+    match resolve(node, store).as_ref() {
+        Node::Empty => (Node::leaf(path, value_hash), true),
+        Node::Leaf { path: existing, .. } if *existing == path => {
+            (Node::leaf(path, value_hash), false)
+        }
+        Node::Leaf { path: existing, .. } => {
+            let existing = *existing;
+            (split(depth, node.clone(), existing, Node::leaf(path, value_hash), path), true)
+        }
+        Node::Internal { left, right, .. } => {
+            let (child, sibling) = if bit(&path, depth) { (right, left) } else { (left, right) };
+            let (child, added) = insert(child, depth + 1, path, value_hash, store);
+            (Node::join(&path, depth, child, sibling.clone()), added)
+        }
+        Node::Stored { .. } => unreachable!("resolved above"),
+    }
+}
+
+/// Remove the entry at `path` from the subtree at `depth`, collapsing any
+/// subtree left with a single leaf. Returns `None` if there is no entry.
+fn remove(
+    node: &Arc<Node>,
+    depth: usize,
+    path: &[u8; 32],
+    store: Option<&dyn NodeStore>,
+) -> Option<Arc<Node>> {
+    match resolve(node, store).as_ref() {
+        Node::Empty => None,
+        Node::Leaf { path: existing, .. } => {
+            (existing == path).then(|| Arc::new(Node::Empty))
+        }
+        Node::Internal { left, right, .. } => {
+            let (child, sibling) = if bit(path, depth) { (right, left) } else { (left, right) };
+            let child = remove(child, depth + 1, path, store)?;
+            let hoisted = match (resolve(&child, store).as_ref(), resolve(sibling, store).as_ref()) {
+                (Node::Empty, Node::Leaf { .. }) => Some(sibling.clone()),
+                (Node::Leaf { .. }, Node::Empty) => Some(child.clone()),
+                _ => None,
+            };
+            Some(hoisted.unwrap_or_else(|| Node::join(path, depth, child, sibling.clone())))
+        }
+        Node::Stored { .. } => unreachable!("resolved above"),
+    }
+}
+
+/// Add every node of `node` not yet in the store to `put`, and the hash of
+/// every node it keeps, new or stored, to `kept`.
+fn collect_new(node: &Arc<Node>, kept: &mut HashSet<[u8; 32]>, put: &mut Vec<([u8; 32], Vec<u8>)>) {
+    match node.as_ref() {
+        Node::Empty => {}
+        Node::Stored { hash, .. } => {
+            kept.insert(*hash);
+        }
+        Node::Leaf { hash, .. } => {
+            kept.insert(*hash);
+            put.push((*hash, node.encode()));
+        }
+        Node::Internal { left, right, hash } => {
+            kept.insert(*hash);
+            put.push((*hash, node.encode()));
+            collect_new(left, kept, put);
+            collect_new(right, kept, put);
+        }
+    }
+}
+
+/// Add the hash of every stored node of `node` not in `kept` to `delete`.
+///
+/// Only nodes read from the store are looked into. One never read cannot
+/// have changed, so the new version still holds it.
+fn collect_gone(node: &Arc<Node>, kept: &HashSet<[u8; 32]>, delete: &mut Vec<[u8; 32]>) {
+    let Node::Stored { hash, node: loaded } = node.as_ref() else {
+        return;
+    };
+    if kept.contains(hash) {
+        return;
+    }
+    delete.push(*hash);
+    if let Some(Node::Internal { left, right, .. }) = loaded.get().map(|node| node.as_ref()) {
+        collect_gone(left, kept, delete);
+        collect_gone(right, kept, delete);
+    }
+}
+
+/// A sparse Merkle tree mapping keys to value hashes.
+///
+/// The tree stores only value hashes; the values themselves live with the
+/// caller, which passes them in when they change.
+#[derive(Clone)]
+pub struct SparseMerkleTree {
+    root: Arc<Node>,
+    len: usize,
+    store: Option<Arc<dyn NodeStore>>,
+    /// The root of the version in the store, to find what `changes` drops.
+    stored_root: Arc<Node>,
+}
+
+impl SparseMerkleTree {
+    /// Create an empty tree.
+    pub fn new() -> Self {
+        Self {
+            root: Arc::new(Node::Empty),
+            len: 0,
+            store: None,
+            stored_root: Arc::new(Node::Empty),
+        }
+    }
+
+    /// The tree with root `root` and `len` entries in `store`, whose nodes
+    /// are read as they are needed.
+    pub fn stored(root: [u8; 32], len: usize, store: Arc<dyn NodeStore>) -> Self {
+        let root = Node::stored(root);
+        Self {
+            stored_root: root.clone(),
+            root,
+            len,
+            store: Some(store),
+        }
+    }
+
+    /// The root hash committing to every entry.
+    pub fn root(&self) -> [u8; 32] {
+        self.root.hash()
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Insert or overwrite the value for `key`.
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        let (root, added) = insert(&self.root, 0, key_path(key), value_hash(value), self.store.as_deref());
+        self.root = root;
+        if added {
+            self.len += 1;
+        }
+    }
+
+    /// Remove the entry for `key`, if any.
+    pub fn remove(&mut self, key: &[u8]) {
+        if let Some(root) = remove(&self.root, 0, &key_path(key), self.store.as_deref()) {
+            self.root = root;
+            self.len -= 1;
+        }
+    }
+
+    /// The nodes to write to, and delete from, the tree's store to store
+    /// this version in place of the one stored.
+    pub fn changes(&self) -> TreeChanges {
+        let mut kept = HashSet::new();
+        let mut changes = TreeChanges::default();
+        collect_new(&self.root, &mut kept, &mut changes.put);
+        collect_gone(&self.stored_root, &kept, &mut changes.delete);
+        changes
+    }
+
+    /// Record that `changes` was written to the store, dropping the nodes
+    /// read so far: they are read again if needed.
+    ///
+    /// # Panics
+    ///
+    /// If the tree has no store.
+    pub fn mark_stored(&mut self) {
+        assert!(self.store.is_some(), "a tree without a store cannot be stored");
+        self.root = Node::stored(self.root.hash());
+        self.stored_root = self.root.clone();
+    }
+
+    /// Prove that `key` is, or is not, in the tree.
+    pub fn prove(&self, key: &[u8]) -> SmtProof {
+        let path = key_path(key);
+        let mut siblings = Vec::new();
+        let mut node = &self.root;
+        loop {
+            match resolve(node, self.store.as_deref()).as_ref() {
+                Node::Empty => return SmtProof { siblings, leaf: None },
+                Node::Leaf {
+                    path: leaf_path,
+                    value_hash,
+                    ..
+                } => {
+                    let leaf = ProofLeaf {
+                        path: *leaf_path,
+                        value_hash: *value_hash,
+                    };
+                    return SmtProof {
+                        siblings,
+                        leaf: Some(leaf),
+                    };
+                }
+                Node::Internal { left, right, .. } => {
+                    let depth = siblings.len();
+                    let (child, sibling) = if bit(&path, depth) { (right, left) } else { (left, right) };
+                    siblings.push(sibling.hash());
+                    node = child;
+                }
+                Node::Stored { .. } => unreachable!("resolved above"),
+            }
+        }
+    }
+}
+
+impl Default for SparseMerkleTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for SparseMerkleTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SparseMerkleTree")
+            .field("root", &hex::encode(self.root()))
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// The leaf a proof's path ends at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofLeaf {
+    pub path: [u8; 32],
+    pub value_hash: [u8; 32],
+}
+
+/// A Merkle proof for one key: the sibling hashes from the root down to
+/// where the key's path ends, and the leaf found there, if any.
+///
+/// If the leaf is the key's own, the proof shows membership. If it is
+/// another key's leaf, or the path ends in an empty subtree, the proof
+/// shows the key is absent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmtProof {
+    /// Sibling hashes, root first.
+    pub siblings: Vec<[u8; 32]>,
+    pub leaf: Option<ProofLeaf>,
+}
+
+impl SmtProof {
+    /// Check this proof against `root`: with `Some(value)` that `key` maps
+    /// to `value`, with `None` that `key` is absent.
+    pub fn verify(&self, root: &[u8; 32], key: &[u8], value: Option<&[u8]>) -> bool {
+        verify_proof(root, key, value, self)
+    }
+}
+
+/// Verify a proof produced by `SparseMerkleTree::prove` against a trusted
+/// root, needing nothing but the root itself.
+///
+/// With `Some(value)` this checks that `key` maps to `value`; with `None`
+/// that `key` is absent.
+pub fn verify_proof(root: &[u8; 32], key: &[u8], value: Option<&[u8]>, proof: &SmtProof) -> bool {
+    let depth = proof.siblings.len();
+    if depth > TREE_DEPTH {
+        return false;
+    }
+    let path = key_path(key);
+    let bottom = match (value, &proof.leaf) {
+        (Some(value), Some(leaf)) => {
+            if leaf.path != path || leaf.value_hash != value_hash(value) {
+                return false;
+            }
+            leaf_hash(&leaf.path, &leaf.value_hash)
+        }
+        (None, Some(leaf)) => {
+            // Another key's leaf must sit on our path to prove we are absent.
+            if leaf.path == path || common_prefix_len(&leaf.path, &path) < depth {
+                return false;
+            }
+            leaf_hash(&leaf.path, &leaf.value_hash)
+        }
+        (None, None) => EMPTY_HASH,
+        (Some(_), None) => return false,
+    };
+
+    let computed = proof
+        .siblings
+        .iter()
+        .enumerate()
+        .rev()
+        .fold(bottom, |node, (d, sibling)| {
+            if bit(&path, d) {
+                internal_hash(sibling, &node)
+            } else {
+                internal_hash(&node, sibling)
+            }
+        });
+    computed == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: u32) -> [u8; 32] {
+        let mut k = [0u8; 32];
+        k[..4].copy_from_slice(&i.to_le_bytes());
+        k
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let tree = SparseMerkleTree::new();
+        assert_eq!(tree.root(), EMPTY_HASH);
+        assert!(tree.prove(&key(1)).verify(&EMPTY_HASH, &key(1), None));
+    }
+
+    #[test]
+    fn test_single_entry_root_is_leaf() {
+        let mut tree = SparseMerkleTree::new();
+        tree.insert(&key(1), b"one");
+        let path = key_path(&key(1));
+        assert_eq!(tree.root(), leaf_hash(&path, &value_hash(b"one")));
+    }
+
+    #[test]
+    fn test_root_independent_of_insertion_order() {
+        let mut a = SparseMerkleTree::new();
+        let mut b = SparseMerkleTree::new();
+        for i in 0..50 {
+            a.insert(&key(i), &i.to_le_bytes());
+        }
+        for i in (0..50).rev() {
+            b.insert(&key(i), &i.to_le_bytes());
+        }
+        assert_eq!(a.root(), b.root());
+        assert_eq!(a.len(), 50);
+    }
+
+    #[test]
+    fn test_remove_restores_previous_root() {
+        let mut tree = SparseMerkleTree::new();
+        for i in 0..20 {
+            tree.insert(&key(i), b"v");
+        }
+        let before = tree.root();
+        tree.insert(&key(99), b"extra");
+        assert_ne!(tree.root(), before);
+        tree.remove(&key(99));
+        assert_eq!(tree.root(), before);
+        assert_eq!(tree.len(), 20);
+
+        for i in 0..20 {
+            tree.remove(&key(i));
+        }
+        assert_eq!(tree.root(), EMPTY_HASH);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_overwrite_changes_root_not_len() {
+        let mut tree = SparseMerkleTree::new();
+        tree.insert(&key(1), b"a");
+        tree.insert(&key(2), b"b");
+        let before = tree.root();
+        tree.insert(&key(1), b"c");
+        assert_ne!(tree.root(), before);
+        assert_eq!(tree.len(), 2);
+    }
+
+    #[test]
+    fn test_membership_proofs() {
+        let mut tree = SparseMerkleTree::new();
+        for i in 0..100 {
+            tree.insert(&key(i), &i.to_le_bytes());
+        }
+        let root = tree.root();
+        for i in 0..100 {
+            let proof = tree.prove(&key(i));
+            assert!(proof.verify(&root, &key(i), Some(&i.to_le_bytes())));
+            assert!(!proof.verify(&root, &key(i), Some(b"wrong")));
+            assert!(!proof.verify(&root, &key(i), None));
+        }
+    }
+
+    #[test]
+    fn test_non_membership_proofs() {
+        let mut tree = SparseMerkleTree::new();
+        for i in 0..100 {
+            tree.insert(&key(i), b"v");
+        }
+        let root = tree.root();
+        for i in 100..200 {
+            let proof = tree.prove(&key(i));
+            assert!(proof.verify(&root, &key(i), None), "absent key {i}");
+            assert!(!proof.verify(&root, &key(i), Some(b"v")));
+        }
+    }
+
+    #[test]
+    fn test_proof_rejected_against_other_root() {
+        let mut tree = SparseMerkleTree::new();
+        tree.insert(&key(1), b"a");
+        tree.insert(&key(2), b"b");
+        let proof = tree.prove(&key(1));
+        let old_root = tree.root();
+        tree.insert(&key(1), b"changed");
+        assert!(proof.verify(&old_root, &key(1), Some(b"a")));
+        assert!(!proof.verify(&tree.root(), &key(1), Some(b"a")));
+    }
+
+    #[test]
+    fn test_member_leaf_cannot_prove_absence_of_another_key() {
+        let mut tree = SparseMerkleTree::new();
+        tree.insert(&key(1), b"a");
+        tree.insert(&key(2), b"b");
+        // Key 1's proof ends at key 1's leaf; it must not prove that an
+        // arbitrary other key, off that path, is absent.
+        let proof = tree.prove(&key(1));
+        let root = tree.root();
+        assert!(!proof.verify(&root, &key(2), None));
+    }
+
+    #[derive(Default)]
+    struct MemStore(parking_lot::Mutex<std::collections::HashMap<[u8; 32], Vec<u8>>>);
+
+    impl NodeStore for MemStore {
+        fn load_node(&self, hash: &[u8; 32]) -> Result<Option<Vec<u8>>, StateError> {
+            Ok(self.0.lock().get(hash).cloned())
+        }
+    }
+
+    impl MemStore {
+        fn apply(&self, changes: TreeChanges) {
+            let mut nodes = self.0.lock();
+            for hash in changes.delete {
+                nodes.remove(&hash);
+            }
+            nodes.extend(changes.put);
+        }
+    }
+
+    #[test]
+    fn test_stored_tree_matches_in_memory_tree() {
+        let store = Arc::new(MemStore::default());
+        let mut stored = SparseMerkleTree::stored(EMPTY_HASH, 0, store.clone());
+        let mut memory = SparseMerkleTree::new();
+        let mut entries = std::collections::BTreeMap::new();
+        for round in 0..5u32 {
+            for i in 0..40 {
+                let k = key((i * 7 + round * 13) % 60);
+                if (i + round) % 3 == 0 {
+                    stored.remove(&k);
+                    memory.remove(&k);
+                    entries.remove(&k);
+                } else {
+                    stored.insert(&k, &round.to_le_bytes());
+                    memory.insert(&k, &round.to_le_bytes());
+                    entries.insert(k, round);
+                }
+            }
+            store.apply(stored.changes());
+            stored.mark_stored();
+
+            // Reopen from the store alone, as after a restart.
+            stored = SparseMerkleTree::stored(stored.root(), stored.len(), store.clone());
+            assert_eq!(stored.root(), memory.root());
+            for i in 0..60 {
+                assert_eq!(stored.prove(&key(i)), memory.prove(&key(i)));
+            }
+
+            // The store holds exactly the nodes of a fresh copy: every node
+            // only older versions had has been deleted.
+            let fresh = Arc::new(MemStore::default());
+            let mut copy = SparseMerkleTree::stored(EMPTY_HASH, 0, fresh.clone());
+            for (k, v) in &entries {
+                copy.insert(k, &v.to_le_bytes());
+            }
+            fresh.apply(copy.changes());
+            let mut hashes: Vec<_> = store.0.lock().keys().copied().collect();
+            let mut expected: Vec<_> = fresh.0.lock().keys().copied().collect();
+            hashes.sort();
+            expected.sort();
+            assert_eq!(hashes, expected, "round {round}");
+        }
+    }
+
+    #[test]
+    fn test_stored_tree_writes_only_changed_path() {
+        let store = Arc::new(MemStore::default());
+        let mut tree = SparseMerkleTree::stored(EMPTY_HASH, 0, store.clone());
+        for i in 0..1000 {
+            tree.insert(&key(i), b"v");
+        }
+        store.apply(tree.changes());
+        tree.mark_stored();
+
+        tree.insert(&key(5), b"changed");
+        let changes = tree.changes();
+        // The new leaf and the internal nodes above it replace the old ones.
+        assert!(changes.put.len() < 40, "{} nodes written", changes.put.len());
+        assert_eq!(changes.put.len(), changes.delete.len());
+    }
+
+    #[test]
+    fn test_clone_keeps_old_version() {
+        let mut tree = SparseMerkleTree::new();
+        tree.insert(&key(1), b"a");
+        let snapshot = tree.clone();
+        tree.insert(&key(1), b"b");
+        tree.insert(&key(2), b"c");
+        assert_ne!(snapshot.root(), tree.root());
+        assert!(snapshot.prove(&key(1)).verify(&snapshot.root(), &key(1), Some(b"a")));
+        assert_eq!(snapshot.len(), 1);
+    }
+}
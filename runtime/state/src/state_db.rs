@@ -1,16 +1,146 @@
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
 use std::path::Path;
+use std::sync::Arc;
 
-use sha2::{Digest, Sha256};
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use tracing::warn;
+use trv1_bft::block::Transaction;
+use trv1_bft::SignatureVerifier;
+use trv1_contracts::{CallContext, Contract, ContractError, Host};
+use trv1_slashing::SlashEvent;
 
 use crate::accounts::AccountState;
-use crate::types::{StateError, TransactionReceipt};
+use crate::backend::{AccountChange, BlockCommit, DataChange, DataKey, StateBackend, StateSummary};
+use crate::fees::{developer_pool_account, module_account, treasury_account, BlockFees, FeeContext};
+use crate::gas::{GasMeter, GasSchedule};
+use crate::history::{HistoryRetention, StateHistory};
+use crate::payload::{contract_address, evidence_record, staking_account, Modules, TxPayload};
+use crate::smt::{NodeStore, SmtProof, SparseMerkleTree};
+use crate::types::{BlockOutcome, StateError, TransactionReceipt, TxOutcome};
+
+/// An entry of the state tree: an account, or a data entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum TreeKey {
+    Account([u8; 32]),
+    Data(DataKey),
+}
 
-/// In-memory account state database.
+impl TreeKey {
+    /// The entry's key in the tree. A data entry's key is its owner then
+    /// its own non-empty key, so it is longer than any account's.
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            TreeKey::Account(pubkey) => pubkey.to_vec(),
+            TreeKey::Data((owner, key)) => [&owner[..], key].concat(),
+        }
+    }
+}
+
+/// An entry's value before a change.
 #[derive(Debug, Clone)]
+enum Undo {
+    Account(AccountChange),
+    Data(DataChange),
+}
+
+/// The sparse Merkle tree over all accounts and data entries, and the
+/// entries changed since it was last brought up to date.
+#[derive(Debug, Clone, Default)]
+struct Commitment {
+    tree: SparseMerkleTree,
+    dirty: HashSet<TreeKey>,
+}
+
+/// Values entries had before they were changed under the open
+/// checkpoints, so the changes can be undone.
+#[derive(Debug, Clone, Default)]
+struct Journal {
+    /// Each entry's value before its first change under the checkpoint
+    /// that was innermost at the time, oldest first.
+    entries: Vec<Undo>,
+    /// Open checkpoints, outermost first: where each begins in `entries`,
+    /// and the entries journaled since.
+    checkpoints: Vec<(usize, HashSet<TreeKey>)>,
+}
+
+/// A point in a `StateDB`'s changes that can be returned to with
+/// `revert_to`, or kept with `commit_checkpoint`.
+///
+/// Checkpoints nest: reverting or committing one also closes every
+/// checkpoint taken after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub struct Checkpoint(usize);
+
+/// Account state database.
+///
+/// Besides accounts, the state holds data entries: values an account
+/// stores under keys of its own, such as a contract's code and storage.
+/// Both are committed to by a sparse Merkle tree. Changes are recorded
+/// as they happen and folded into the tree when the state root or a proof
+/// is next asked for, so computing the root costs O(log n) per changed
+/// account rather than rehashing every account.
+///
+/// A `StateDB` opened on a `StateBackend` persists the accounts changed by
+/// each block when `commit` is called with the block's height. It holds
+/// only the accounts changed since then, reading the others from the
+/// backend as they are needed, and so do the tree's nodes. Without a
+/// backend the state lives only in memory.
+///
+/// The backend is trusted to hold the state it committed: failing to read
+/// from it is a panic, like a failed memory access would be.
+///
+/// Each `commit` also records the values the block's changes replaced, so
+/// accounts can be read as of recent heights (`account_at`). How far back
+/// is set by the `HistoryRetention`.
+///
+/// Changes can be made tentatively under a `checkpoint` and undone with
+/// `revert_to`. Only the accounts that change are copied, once each per
+/// checkpoint.
+#[derive(Debug)]
 pub struct StateDB {
-    accounts: HashMap<[u8; 32], AccountState>,
+    /// Every account of an in-memory state, or the accounts of a backed
+    /// state changed since the last `commit`. `None` marks one that does
+    /// not exist.
+    accounts: HashMap<[u8; 32], Option<AccountState>>,
+    commitment: Mutex<Commitment>,
+    backend: Option<Arc<dyn StateBackend>>,
+    /// Accounts changed since the last `commit`, with their values as of
+    /// that commit.
+    uncommitted: HashMap<[u8; 32], Option<AccountState>>,
+    /// Data entries, held like `accounts`.
+    data: HashMap<DataKey, Option<Vec<u8>>>,
+    /// Data entries changed since the last `commit`, like `uncommitted`.
+    uncommitted_data: HashMap<DataKey, Option<Vec<u8>>>,
+    /// Number of accounts as of the last `commit`.
+    committed_accounts: u64,
+    /// Sum of all balances as of the last `commit`.
+    committed_supply: u64,
+    committed_height: Option<u64>,
+    committed_block_hash: [u8; 32],
+    history: StateHistory,
+    journal: Journal,
+}
+
+/// Proof of an account's state, or of its absence, against a state root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountProof {
+    /// The account's state, or `None` if it does not exist.
+    pub account: Option<AccountState>,
+    pub proof: SmtProof,
+}
+
+impl AccountProof {
+    /// Check the proof for `pubkey` against a trusted `state_root`.
+    pub fn verify(&self, state_root: &[u8; 32], pubkey: &[u8; 32]) -> bool {
+        let leaf = self.account.as_ref().map(AccountState::to_leaf_bytes);
+        self.proof
+            .verify(state_root, pubkey, leaf.as_ref().map(|bytes| &bytes[..]))
+    }
 }
 
 impl StateDB {
@@ -18,33 +148,443 @@ impl StateDB {
     pub fn new() -> Self {
         Self {
             accounts: HashMap::new(),
+            commitment: Mutex::new(Commitment::default()),
+            backend: None,
+            uncommitted: HashMap::new(),
+            data: HashMap::new(),
+            uncommitted_data: HashMap::new(),
+            committed_accounts: 0,
+            committed_supply: 0,
+            committed_height: None,
+            committed_block_hash: [0; 32],
+            history: StateHistory::default(),
+            journal: Journal::default(),
+        }
+    }
+
+    /// Open the state stored in `backend`, as of its last committed height,
+    /// together with as much of its stored history as `retention` keeps.
+    /// Later `commit`s write to it.
+    ///
+    /// Only the state's summary is read here; accounts, data entries and
+    /// tree nodes are read as they are needed.
+    pub fn open(
+        backend: Arc<dyn StateBackend>,
+        retention: HistoryRetention,
+    ) -> Result<Self, StateError> {
+        let committed_height = backend.committed_height()?;
+        let summary = backend.load_summary()?;
+        let store: Arc<dyn NodeStore> = backend.clone();
+        let tree = SparseMerkleTree::stored(summary.root, summary.entries as usize, store);
+        let mut db = Self::new().with_history_retention(retention);
+        db.commitment.get_mut().tree = tree;
+        db.committed_accounts = summary.accounts;
+        db.committed_supply = summary.supply;
+        if let (Some(range), Some(latest)) = (backend.history_range()?, committed_height) {
+            if db.history.enabled() {
+                let keep_from = db.history.keep_from(latest).unwrap_or(0);
+                for height in keep_from.max(*range.start())..=*range.end() {
+                    db.history.record(height, &backend.load_undo(height)?);
+                }
+            }
+        }
+        db.backend = Some(backend);
+        db.committed_height = committed_height;
+        db.committed_block_hash = summary.block_hash;
+        Ok(db)
+    }
+
+    /// Keep history according to `retention` from now on.
+    pub fn with_history_retention(mut self, retention: HistoryRetention) -> Self {
+        self.history.set_retention(retention);
+        if let Some(keep_from) = self.committed_height.and_then(|h| self.history.keep_from(h)) {
+            self.history.prune(keep_from);
+        }
+        self
+    }
+
+    /// A state holding `accounts`, with none of them yet committed.
+    fn from_accounts(accounts: HashMap<[u8; 32], AccountState>) -> Self {
+        let mut db = Self::new();
+        db.commitment.get_mut().dirty = accounts.keys().map(|pubkey| TreeKey::Account(*pubkey)).collect();
+        db.uncommitted = accounts.keys().map(|pubkey| (*pubkey, None)).collect();
+        db.accounts = accounts
+            .into_iter()
+            .map(|(pubkey, account)| (pubkey, Some(account)))
+            .collect();
+        db
+    }
+
+    /// `pubkey`'s current account, read from the backend unless it is
+    /// held in memory.
+    ///
+    /// # Panics
+    ///
+    /// If the backend cannot be read.
+    fn load(&self, pubkey: &[u8; 32]) -> Option<AccountState> {
+        if let Some(account) = self.accounts.get(pubkey) {
+            return account.clone();
+        }
+        let backend = self.backend.as_ref()?;
+        backend.load_account(pubkey).unwrap_or_else(|e| {
+            panic!("failed to read account {} from the backend: {e}", hex::encode(pubkey))
+        })
+    }
+
+    /// The current value of the data entry at `key`, read from the backend
+    /// unless it is held in memory.
+    ///
+    /// # Panics
+    ///
+    /// If the backend cannot be read.
+    fn load_data(&self, key: &DataKey) -> Option<Vec<u8>> {
+        if let Some(value) = self.data.get(key) {
+            return value.clone();
+        }
+        let backend = self.backend.as_ref()?;
+        backend.load_data(key).unwrap_or_else(|e| {
+            panic!("failed to read data entry {} from the backend: {e}", hex::encode(TreeKey::Data(key.clone()).bytes()))
+        })
+    }
+
+    /// Record that the entry at `key` is about to change, given its
+    /// current value as `undo`: mark it for the tree and journal it.
+    fn journal(&mut self, key: TreeKey, undo: impl FnOnce() -> Undo) {
+        if let Some((_, journaled)) = self.journal.checkpoints.last_mut() {
+            if journaled.insert(key.clone()) {
+                self.journal.entries.push(undo());
+            }
+        }
+        self.commitment.get_mut().dirty.insert(key);
+    }
+
+    /// Record that `pubkey`'s account is about to change, bringing it into
+    /// memory.
+    fn touch(&mut self, pubkey: &[u8; 32]) {
+        let current = self.load(pubkey);
+        self.journal(TreeKey::Account(*pubkey), || Undo::Account((*pubkey, current.clone())));
+        if let Entry::Vacant(entry) = self.uncommitted.entry(*pubkey) {
+            entry.insert(current.clone());
+        }
+        self.accounts.entry(*pubkey).or_insert(current);
+    }
+
+    /// Record that the data entry at `key` is about to change, bringing it
+    /// into memory.
+    fn touch_data(&mut self, key: &DataKey) {
+        let current = self.load_data(key);
+        self.journal(TreeKey::Data(key.clone()), || Undo::Data((key.clone(), current.clone())));
+        if let Entry::Vacant(entry) = self.uncommitted_data.entry(key.clone()) {
+            entry.insert(current.clone());
+        }
+        self.data.entry(key.clone()).or_insert(current);
+    }
+
+    /// Open a checkpoint. Changes made from now on can be undone with
+    /// `revert_to`, until it is committed with `commit_checkpoint`.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.journal
+            .checkpoints
+            .push((self.journal.entries.len(), HashSet::new()));
+        Checkpoint(self.journal.checkpoints.len() - 1)
+    }
+
+    /// Undo every change made since `checkpoint` was opened, and close it
+    /// and any checkpoint opened after it.
+    ///
+    /// # Panics
+    ///
+    /// If `checkpoint` has already been closed.
+    pub fn revert_to(&mut self, checkpoint: Checkpoint) {
+        let (start, _) = self.close_checkpoints(checkpoint);
+        let undo: Vec<Undo> = self.journal.entries.drain(start..).collect();
+        let dirty = &mut self.commitment.get_mut().dirty;
+        for undo in undo.into_iter().rev() {
+            match undo {
+                Undo::Account((pubkey, account)) => {
+                    dirty.insert(TreeKey::Account(pubkey));
+                    self.accounts.insert(pubkey, account);
+                }
+                Undo::Data((key, value)) => {
+                    dirty.insert(TreeKey::Data(key.clone()));
+                    self.data.insert(key, value);
+                }
+            }
+        }
+    }
+
+    /// Keep the changes made since `checkpoint` was opened, and close it
+    /// and any checkpoint opened after it. The changes become part of the
+    /// enclosing checkpoint, if there is one.
+    ///
+    /// # Panics
+    ///
+    /// If `checkpoint` has already been closed.
+    pub fn commit_checkpoint(&mut self, checkpoint: Checkpoint) {
+        let (_, journaled) = self.close_checkpoints(checkpoint);
+        match self.journal.checkpoints.last_mut() {
+            Some((_, parent)) => parent.extend(journaled),
+            None => self.journal.entries.clear(),
+        }
+    }
+
+    /// Close `checkpoint` and those opened after it, returning where it
+    /// begins in the journal and every entry journaled since.
+    fn close_checkpoints(&mut self, checkpoint: Checkpoint) -> (usize, HashSet<TreeKey>) {
+        assert!(
+            checkpoint.0 < self.journal.checkpoints.len(),
+            "checkpoint {} is already closed",
+            checkpoint.0
+        );
+        let mut closed = self.journal.checkpoints.drain(checkpoint.0..);
+        let (start, mut journaled) = closed.next().unwrap();
+        for (_, inner) in closed {
+            journaled.extend(inner);
+        }
+        (start, journaled)
+    }
+
+    /// Run `f`, undoing every change it made if it returns an error.
+    pub fn atomically<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, StateError>,
+    ) -> Result<T, StateError> {
+        let checkpoint = self.checkpoint();
+        let result = f(self);
+        match result {
+            Ok(_) => self.commit_checkpoint(checkpoint),
+            Err(_) => self.revert_to(checkpoint),
+        }
+        result
+    }
+
+    /// `commit_block` without recording a block hash.
+    pub fn commit(&mut self, height: u64) -> Result<(), StateError> {
+        self.commit_block(height, [0; 32])
+    }
+
+    /// Mark the state as of the end of block `height`, whose hash is
+    /// `block_hash`, writing every account and data entry changed since the
+    /// last commit to the backend in one atomic batch together with the
+    /// state tree's changed nodes, the height, the block hash and the
+    /// block's undo record.
+    ///
+    /// On error nothing is marked committed, so the same changes are
+    /// written again by the next successful commit.
+    ///
+    /// Must not be called while a checkpoint is open.
+    pub fn commit_block(&mut self, height: u64, block_hash: [u8; 32]) -> Result<(), StateError> {
+        debug_assert!(
+            self.journal.checkpoints.is_empty(),
+            "commit with an open checkpoint"
+        );
+        let mut changes: Vec<AccountChange> = Vec::new();
+        let mut undo: Vec<AccountChange> = Vec::new();
+        for (pubkey, before) in &self.uncommitted {
+            let after = &self.accounts[pubkey];
+            if before != after {
+                changes.push((*pubkey, after.clone()));
+                undo.push((*pubkey, before.clone()));
+            }
+        }
+        changes.sort_unstable_by_key(|(pubkey, _)| *pubkey);
+        undo.sort_unstable_by_key(|(pubkey, _)| *pubkey);
+        let mut data: Vec<DataChange> = self
+            .uncommitted_data
+            .iter()
+            .filter(|(key, before)| self.data[*key] != **before)
+            .map(|(key, _)| (key.clone(), self.data[key].clone()))
+            .collect();
+        data.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        let accounts = self.account_count() as u64;
+        let supply = self.total_supply();
+
+        let keep_history = self.history.enabled();
+        let prune_below = self.history.keep_from(height);
+        if let Some(backend) = &self.backend {
+            let mut commitment = self.commitment();
+            let summary = StateSummary {
+                root: commitment.tree.root(),
+                entries: commitment.tree.len() as u64,
+                accounts,
+                supply,
+                block_hash,
+            };
+            backend.commit(&BlockCommit {
+                height,
+                changes: &changes,
+                data: &data,
+                nodes: &commitment.tree.changes(),
+                summary,
+                undo: keep_history.then_some(&undo[..]),
+                prune_below,
+            })?;
+            commitment.tree.mark_stored();
+            drop(commitment);
+            self.accounts.clear();
+            self.data.clear();
+        } else {
+            for pubkey in self.uncommitted.keys() {
+                if self.accounts[pubkey].is_none() {
+                    self.accounts.remove(pubkey);
+                }
+            }
+            for key in self.uncommitted_data.keys() {
+                if self.data[key].is_none() {
+                    self.data.remove(key);
+                }
+            }
+        }
+        if keep_history {
+            self.history.record(height, &undo);
+        }
+        if let Some(below) = prune_below {
+            self.history.prune(below);
+        }
+        self.uncommitted.clear();
+        self.uncommitted_data.clear();
+        self.committed_accounts = accounts;
+        self.committed_supply = supply;
+        self.committed_height = Some(height);
+        self.committed_block_hash = block_hash;
+        Ok(())
+    }
+
+    /// Height of the last `commit`, or for a freshly opened state the last
+    /// height committed to its backend.
+    pub fn committed_height(&self) -> Option<u64> {
+        self.committed_height
+    }
+
+    /// Hash of the block last committed with `commit_block`, zero if none.
+    pub fn committed_block_hash(&self) -> [u8; 32] {
+        self.committed_block_hash
+    }
+
+    /// Heights at which accounts can be read with `account_at`: the last
+    /// committed height and the retained ones before it.
+    pub fn history_range(&self) -> Option<RangeInclusive<u64>> {
+        let latest = self.committed_height?;
+        Some(self.history.oldest().unwrap_or(latest)..=latest)
+    }
+
+    /// `pubkey`'s account as of the end of block `height`, or `None` if it
+    /// did not exist then. Fails if `height` is outside `history_range()`.
+    pub fn account_at(
+        &self,
+        pubkey: &[u8; 32],
+        height: u64,
+    ) -> Result<Option<AccountState>, StateError> {
+        if !self.history_range().is_some_and(|range| range.contains(&height)) {
+            return Err(StateError::HistoryUnavailable { height });
+        }
+        if let Some(account) = self.history.account_at(pubkey, height) {
+            return Ok(account.clone());
+        }
+        Ok(match self.uncommitted.get(pubkey) {
+            Some(committed) => committed.clone(),
+            None => self.load(pubkey),
+        })
+    }
+
+    /// Iterate over the accounts held in memory: every account of a state
+    /// without a backend, only those changed since the last `commit` of one
+    /// with a backend.
+    pub fn accounts(&self) -> impl Iterator<Item = (&[u8; 32], &AccountState)> {
+        self.accounts
+            .iter()
+            .filter_map(|(pubkey, account)| Some((pubkey, account.as_ref()?)))
+    }
+
+    /// Fold every changed entry into the tree.
+    fn commitment(&self) -> parking_lot::MutexGuard<'_, Commitment> {
+        let mut commitment = self.commitment.lock();
+        let Commitment { tree, dirty } = &mut *commitment;
+        for key in dirty.drain() {
+            let value = match &key {
+                TreeKey::Account(pubkey) => self
+                    .accounts
+                    .get(pubkey)
+                    .and_then(Option::as_ref)
+                    .map(|account| account.to_leaf_bytes().to_vec()),
+                TreeKey::Data(data_key) => self.data.get(data_key).cloned().flatten(),
+            };
+            match value {
+                Some(value) => tree.insert(&key.bytes(), &value),
+                None => tree.remove(&key.bytes()),
+            }
+        }
+        commitment
+    }
+
+    /// The value `owner` stores under `key`, if any.
+    pub fn data(&self, owner: &[u8; 32], key: &[u8]) -> Option<Vec<u8>> {
+        self.load_data(&(*owner, key.to_vec()))
+    }
+
+    /// Set the value `owner` stores under `key`, or remove it if `value`
+    /// is `None`. Data entries are committed to by the state root and
+    /// undone by checkpoints like accounts.
+    ///
+    /// # Panics
+    ///
+    /// If `key` is empty.
+    pub fn set_data(&mut self, owner: &[u8; 32], key: &[u8], value: Option<Vec<u8>>) {
+        assert!(!key.is_empty(), "data keys must not be empty");
+        let key = (*owner, key.to_vec());
+        self.touch_data(&key);
+        self.data.insert(key, value);
+    }
+
+    /// Record `state` as the state of the runtime module `name`, in a data
+    /// entry of its module account, so it commits with the block it
+    /// belongs to and counts toward the state root.
+    pub fn set_module_state<T: Serialize>(&mut self, name: &str, state: &T) {
+        let owner = module_account(name);
+        let bytes = bincode::serialize(state).expect("module state serializes");
+        if self.data(&owner, MODULE_STATE_KEY).as_ref() != Some(&bytes) {
+            self.set_data(&owner, MODULE_STATE_KEY, Some(bytes));
         }
     }
 
+    /// The state last recorded for the runtime module `name`, if any.
+    pub fn module_state<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, StateError> {
+        self.data(&module_account(name), MODULE_STATE_KEY)
+            .map(|bytes| {
+                bincode::deserialize(&bytes)
+                    .map_err(|e| StateError::Storage(format!("invalid {name} module state: {e}")))
+            })
+            .transpose()
+    }
+
     /// Look up an account by public key.
-    pub fn get_account(&self, pubkey: &[u8; 32]) -> Option<&AccountState> {
-        self.accounts.get(pubkey)
+    pub fn get_account(&self, pubkey: &[u8; 32]) -> Option<AccountState> {
+        self.load(pubkey)
     }
 
     /// Look up an account mutably by public key.
     pub fn get_account_mut(&mut self, pubkey: &[u8; 32]) -> Option<&mut AccountState> {
-        self.accounts.get_mut(pubkey)
+        self.touch(pubkey);
+        self.accounts.get_mut(pubkey)?.as_mut()
     }
 
     /// Get an existing account or create a zero-balance one.
     pub fn get_or_create_account(&mut self, pubkey: &[u8; 32]) -> &mut AccountState {
+        self.touch(pubkey);
         self.accounts
-            .entry(*pubkey)
-            .or_insert_with(AccountState::default)
+            .get_mut(pubkey)
+            .unwrap()
+            .get_or_insert_with(AccountState::default)
     }
 
     /// Insert or overwrite an account.
     pub fn set_account(&mut self, pubkey: [u8; 32], state: AccountState) {
-        self.accounts.insert(pubkey, state);
+        self.touch(&pubkey);
+        self.accounts.insert(pubkey, Some(state));
     }
 
     /// Apply a single transfer: validate nonce, debit sender, credit receiver,
-    /// then increment sender nonce.
+    /// then increment sender nonce. On error the state is left unchanged.
     pub fn apply_transfer(
         &mut self,
         from: &[u8; 32],
@@ -53,10 +593,7 @@ impl StateDB {
         expected_nonce: u64,
     ) -> Result<(), StateError> {
         // Check sender exists
-        let sender = self
-            .accounts
-            .get(from)
-            .ok_or(StateError::AccountNotFound)?;
+        let sender = self.get_account(from).ok_or(StateError::AccountNotFound)?;
 
         // Validate nonce
         if sender.nonce != expected_nonce {
@@ -74,44 +611,397 @@ impl StateDB {
             });
         }
 
-        // Handle self-transfer: just increment nonce
-        if from == to {
-            let sender = self.accounts.get_mut(from).unwrap();
-            sender.increment_nonce();
+        self.atomically(|db| {
+            db.transfer(from, to, amount)?;
+            db.get_account_mut(from).unwrap().increment_nonce();
+            Ok(())
+        })
+    }
+
+    /// Move `amount` from `from` to `to`, creating `to` if needed. On error
+    /// neither account changes.
+    pub fn transfer(&mut self, from: &[u8; 32], to: &[u8; 32], amount: u64) -> Result<(), StateError> {
+        self.atomically(|db| {
+            db.get_account_mut(from)
+                .ok_or(StateError::AccountNotFound)?
+                .debit(amount)?;
+            db.get_or_create_account(to).credit(amount)
+        })
+    }
+
+    /// Add `amount` to `pubkey`'s balance, creating the account unless
+    /// `amount` is zero.
+    fn credit(&mut self, pubkey: &[u8; 32], amount: u64) -> Result<(), StateError> {
+        if amount == 0 {
             return Ok(());
         }
+        self.get_or_create_account(pubkey).credit(amount)
+    }
 
-        // Debit sender
-        let sender = self.accounts.get_mut(from).unwrap();
-        sender.debit(amount)?;
-        sender.increment_nonce();
-
-        // Credit receiver (create if needed)
-        let receiver = self.get_or_create_account(to);
-        receiver.credit(amount)?;
+    /// Take payment for `tx`'s whole gas limit and bump the sender's nonce,
+    /// execute its payload under its own checkpoint while metering its gas,
+    /// then refund the gas it did not use and distribute the fee. Returns
+    /// the fee charged and the gas used alongside the payload's result.
+    ///
+    /// A transaction that cannot pay for its gas limit, or whose limit is
+    /// more than the `block_gas_left`, fails without changing the state and
+    /// pays nothing. One whose payload fails still pays for the gas it used
+    /// and uses up its nonce; one that runs out of gas pays for all of it.
+    fn apply_with_fee(
+        &mut self,
+        tx: &Transaction,
+        ctx: &FeeContext,
+        fees: &mut BlockFees,
+        block_gas_left: u64,
+        modules: Option<&mut Modules<'_>>,
+    ) -> (u64, u64, Result<TxOutcome, StateError>) {
+        let charged = self.atomically(|db| {
+            let per_gas = ctx.price_per_gas(tx)?;
+            let intrinsic = ctx.check_gas_limit(tx)?;
+            if tx.gas_limit > block_gas_left {
+                return Err(StateError::BlockGasExhausted {
+                    limit: tx.gas_limit,
+                    remaining: block_gas_left,
+                });
+            }
+            let max_fee = tx.gas_limit.checked_mul(per_gas).ok_or(StateError::Overflow)?;
+            db.check_nonce(tx)?;
+            let sender = db.get_account_mut(&tx.from).unwrap();
+            sender.debit(max_fee)?;
+            sender.increment_nonce();
+            Ok((per_gas, intrinsic))
+        });
+        let (per_gas, intrinsic) = match charged {
+            Ok(charged) => charged,
+            Err(e) => return (0, 0, Err(e)),
+        };
+
+        let mut meter = GasMeter::new(tx.gas_limit);
+        meter
+            .charge(intrinsic)
+            .expect("the gas limit was checked to cover the intrinsic gas");
+        let result = self.atomically(|db| db.execute(tx, &ctx.gas, &mut meter, modules));
+        let gas_used = meter.used();
+
+        // Both are parts of the maximum fee the sender already paid, so
+        // neither overflows and crediting them cannot either.
+        let fee = gas_used * per_gas;
+        let refund = (tx.gas_limit - gas_used) * per_gas;
+        let mut split = ctx.split.split_fee(fee, ctx.epoch);
+        if ctx.proposer.is_none() {
+            split.burn += split.validator;
+            split.validator = 0;
+        }
+        let settled = self.atomically(|db| {
+            db.credit(&tx.from, refund)?;
+            if let Some(proposer) = ctx.proposer {
+                db.credit(&proposer, split.validator)?;
+            }
+            db.credit(&treasury_account(), split.treasury)?;
+            db.credit(&developer_pool_account(), split.developer)
+        });
+        settled.expect("refunds and fee shares come out of the sender's payment");
+        let called = matches!(result, Ok(TxOutcome::ContractCalled { .. }));
+        fees.add(called.then_some(tx.to), fee, &split);
+        (fee, gas_used, result)
+    }
 
+    /// Fail unless `tx`'s nonce is its sender's next one.
+    fn check_nonce(&self, tx: &Transaction) -> Result<(), StateError> {
+        let sender = self.get_account(&tx.from).ok_or(StateError::AccountNotFound)?;
+        if sender.nonce != tx.nonce {
+            return Err(StateError::InvalidNonce {
+                expected: sender.nonce,
+                got: tx.nonce,
+            });
+        }
         Ok(())
     }
 
+    /// Carry out `tx`'s payload, charging `meter` for it as `schedule`
+    /// prices it. The nonce, fee and intrinsic gas are already handled.
+    ///
+    /// The payload's gas is charged before it changes anything, so one
+    /// that runs out of gas leaves the state and the modules untouched.
+    fn execute(
+        &mut self,
+        tx: &Transaction,
+        schedule: &GasSchedule,
+        meter: &mut GasMeter,
+        modules: Option<&mut Modules<'_>>,
+    ) -> Result<TxOutcome, StateError> {
+        let payload =
+            TxPayload::from_data(&tx.data).map_err(|e| StateError::InvalidPayload(e.to_string()))?;
+        meter.charge(schedule.execution_gas(&payload))?;
+        if payload == TxPayload::Transfer {
+            self.transfer(&tx.from, &tx.to, tx.amount)?;
+            return Ok(TxOutcome::Transferred {
+                to: tx.to,
+                amount: tx.amount,
+            });
+        }
+        if tx.amount != 0 && !matches!(payload, TxPayload::CallContract { .. }) {
+            return Err(StateError::InvalidPayload(format!(
+                "{} transactions must not carry an amount",
+                payload.kind()
+            )));
+        }
+        let modules = modules.ok_or(StateError::UnsupportedPayload(payload.kind()))?;
+        let from = tx.from;
+
+        // The caller's checkpoint undoes balance moves, but not module
+        // changes. So each arm checks everything that can fail before its
+        // one module change that can, and the modules reject bad calls
+        // before changing anything.
+        match payload {
+            TxPayload::Transfer => unreachable!("transfers are handled above"),
+            TxPayload::Stake { amount, tier } => {
+                self.transfer(&from, &staking_account(), amount)?;
+                modules.staking.stake(from, amount, tier).map_err(staking_error)?;
+                sync_validator_stake(modules, &from);
+                Ok(TxOutcome::Staked { amount, tier })
+            }
+            TxPayload::Unstake { amount } => {
+                self.transfer(&staking_account(), &from, amount)?;
+                modules.staking.unstake(from, amount).map_err(staking_error)?;
+                sync_validator_stake(modules, &from);
+                Ok(TxOutcome::Unstaked { amount })
+            }
+            TxPayload::Delegate {
+                validator,
+                amount,
+                tier,
+            } => {
+                if modules.validators.get_validator(&validator).is_none() {
+                    return Err(validator_set_error(trv1_validator_set::ValidatorSetError::NotFound(
+                        validator,
+                    )));
+                }
+                self.transfer(&from, &staking_account(), amount)?;
+                modules
+                    .staking
+                    .delegate(from, validator, amount, tier)
+                    .map_err(staking_error)?;
+                Ok(TxOutcome::Delegated {
+                    validator,
+                    amount,
+                    tier,
+                })
+            }
+            TxPayload::Undelegate { validator, amount } => {
+                self.transfer(&staking_account(), &from, amount)?;
+                modules
+                    .staking
+                    .undelegate(from, validator, amount)
+                    .map_err(staking_error)?;
+                Ok(TxOutcome::Undelegated { validator, amount })
+            }
+            TxPayload::RegisterValidator {
+                self_stake,
+                tier,
+                commission_rate,
+            } => {
+                let stake = modules.staking.stake_of(&from).saturating_add(self_stake);
+                modules
+                    .validators
+                    .check_registration(&from, stake, commission_rate)
+                    .map_err(validator_set_error)?;
+                self.transfer(&from, &staking_account(), self_stake)?;
+                modules.staking.stake(from, self_stake, tier).map_err(staking_error)?;
+                let epoch = modules
+                    .validators
+                    .queue_registration(from, stake, commission_rate, modules.height)
+                    .expect("the registration was checked above");
+                Ok(TxOutcome::ValidatorRegistered { epoch })
+            }
+            TxPayload::EditValidator {
+                commission_rate,
+                metadata,
+            } => {
+                // Check everything before changing anything.
+                let validators = &mut *modules.validators;
+                if validators.get_validator(&from).is_none() {
+                    return Err(validator_set_error(trv1_validator_set::ValidatorSetError::NotFound(from)));
+                }
+                if let Some(metadata) = &metadata {
+                    metadata.validate().map_err(validator_set_error)?;
+                }
+                let commission_epoch = commission_rate
+                    .map(|rate| validators.set_commission(&from, rate))
+                    .transpose()
+                    .map_err(validator_set_error)?;
+                if let Some(metadata) = metadata {
+                    validators.set_metadata(&from, metadata).map_err(validator_set_error)?;
+                }
+                Ok(TxOutcome::ValidatorEdited { commission_epoch })
+            }
+            TxPayload::Unjail => {
+                modules.validators.unjail(&from).map_err(validator_set_error)?;
+                Ok(TxOutcome::Unjailed)
+            }
+            TxPayload::DeployContract { code } => {
+                let address = contract_address(&from, tx.nonce);
+                if modules.rewards.get_contract(&address).is_some() {
+                    return Err(StateError::Rewards(
+                        trv1_rewards::RewardsError::AlreadyRegistered(address).to_string(),
+                    ));
+                }
+                modules
+                    .contracts
+                    .deploy(self, address, from, code, modules.height)
+                    .map_err(contract_error)?;
+                modules
+                    .rewards
+                    .register_contract(address, from, modules.height)
+                    .expect("the contract was checked to be unregistered above");
+                Ok(TxOutcome::ContractDeployed { address })
+            }
+            TxPayload::CallContract { input } => {
+                if self.data(&tx.to, CONTRACT_KEY).is_none() {
+                    return Err(contract_error(ContractError::NotFound));
+                }
+                self.transfer(&from, &tx.to, tx.amount)?;
+                let ctx = CallContext {
+                    contract: tx.to,
+                    caller: from,
+                    value: tx.amount,
+                    height: modules.height,
+                    input,
+                };
+                let (used, result) = modules
+                    .contracts
+                    .call(self, &ctx, &schedule.contract, meter.remaining());
+                meter.charge(used)?;
+                let output = match result {
+                    Err(ContractError::OutOfGas) => {
+                        return Err(StateError::OutOfGas { limit: meter.limit() })
+                    }
+                    result => result.map_err(contract_error)?,
+                };
+                Ok(TxOutcome::ContractCalled {
+                    contract: tx.to,
+                    output,
+                })
+            }
+            TxPayload::SubmitEvidence(evidence) => {
+                if !evidence.verify() {
+                    return Err(StateError::Slashing(
+                        "evidence does not show two conflicting signed votes".into(),
+                    ));
+                }
+                let evidence_hash = modules
+                    .slashing
+                    .submit_evidence(evidence_record(&evidence))
+                    .map_err(|e| StateError::Slashing(e.to_string()))?;
+                Ok(TxOutcome::EvidenceSubmitted { evidence_hash })
+            }
+        }
+    }
+
     /// Apply all transactions in a block, returning a receipt for each.
     /// Failed transactions produce a receipt with `success=false` but do not
     /// revert other successful transactions.
-    pub fn apply_block(
+    ///
+    /// No fees are charged and gas limits are not enforced, though the gas
+    /// each transaction would use is still reported. With no runtime
+    /// modules to act on, every payload but a transfer fails; the node
+    /// executes blocks with `apply_verified_block`.
+    pub fn apply_block(&mut self, transactions: &[Transaction]) -> Vec<TransactionReceipt> {
+        self.apply_transactions(transactions, None, None, |_| true).receipts
+    }
+
+    /// Like `apply_block`, but first checks every signature with `verifier`,
+    /// meters gas and charges and distributes each transaction's fee as
+    /// `fees` directs, and dispatches payloads to `modules`. Transactions
+    /// with an invalid signature fail without touching state, as do those
+    /// whose gas limit does not fit in what is left of the block's
+    /// `max_gas_per_block`.
+    ///
+    /// The signatures are verified in parallel, and any the verifier already
+    /// checked when the transactions reached the mempool are not checked
+    /// again.
+    pub fn apply_verified_block(
         &mut self,
-        transactions: &[trv1_bft::block::Transaction],
-    ) -> Vec<TransactionReceipt> {
-        let mut receipts = Vec::with_capacity(transactions.len());
+        transactions: &[Transaction],
+        verifier: &SignatureVerifier,
+        fees: &FeeContext,
+        modules: &mut Modules<'_>,
+    ) -> BlockOutcome {
+        let valid = verifier.verify_all(transactions);
+        modules.staking.set_epoch(modules.epoch);
+        self.apply_transactions(transactions, Some(fees), Some(modules), |i| valid[i])
+    }
 
-        for tx in transactions {
-            let tx_hash = Self::hash_transaction(tx);
+    /// Slash and jail the validator each piece of evidence pending in
+    /// `modules.slashing` names, and move the slashed tokens from the
+    /// staking account to the treasury. Returns the slashes applied.
+    pub fn process_evidence(&mut self, modules: &mut Modules<'_>) -> Vec<SlashEvent> {
+        let events = modules
+            .slashing
+            .process_all_evidence(modules.validators, modules.staking);
+        for event in events.iter().filter(|event| event.slash_amount > 0) {
+            if let Err(e) = self.transfer(&staking_account(), &treasury_account(), event.slash_amount) {
+                warn!(error = %e, amount = event.slash_amount, "failed to move slashed stake to the treasury");
+            }
+        }
+        events
+    }
 
-            match self.apply_transfer(&tx.from, &tx.to, tx.amount, tx.nonce) {
-                Ok(()) => {
-                    receipts.push(TransactionReceipt {
+    /// Execute `transactions` in order, skipping with an `InvalidSignature`
+    /// receipt each one whose index `signature_ok` rejects, charging fees
+    /// and enforcing gas limits if `fees` is given and dispatching payloads
+    /// to `modules`.
+    ///
+    /// A transaction that fails leaves no partial changes behind, except
+    /// that a transaction that could pay its fee keeps paying it.
+    fn apply_transactions(
+        &mut self,
+        transactions: &[Transaction],
+        fees: Option<&FeeContext>,
+        mut modules: Option<&mut Modules<'_>>,
+        signature_ok: impl Fn(usize) -> bool,
+    ) -> BlockOutcome {
+        let mut outcome = BlockOutcome {
+            receipts: Vec::with_capacity(transactions.len()),
+            fees: BlockFees::default(),
+            gas_used: 0,
+        };
+        let unmetered = GasSchedule::default();
+
+        for (i, tx) in transactions.iter().enumerate() {
+            let tx_hash = tx.hash();
+
+            let (fee_paid, gas_used, result) = if !signature_ok(i) {
+                (0, 0, Err(StateError::InvalidSignature))
+            } else if let Some(ctx) = fees {
+                let block_gas_left = ctx.max_gas_per_block.saturating_sub(outcome.gas_used);
+                self.apply_with_fee(
+                    tx,
+                    ctx,
+                    &mut outcome.fees,
+                    block_gas_left,
+                    modules.as_deref_mut(),
+                )
+            } else {
+                let mut meter = GasMeter::new(u64::MAX);
+                let result = self.atomically(|db| {
+                    db.check_nonce(tx)?;
+                    db.get_account_mut(&tx.from).unwrap().increment_nonce();
+                    meter.charge(unmetered.intrinsic_gas(tx))?;
+                    db.execute(tx, &unmetered, &mut meter, modules.as_deref_mut())
+                });
+                (0, meter.used(), result)
+            };
+            outcome.gas_used += gas_used;
+            match result {
+                Ok(tx_outcome) => {
+                    outcome.receipts.push(TransactionReceipt {
                         tx_hash,
                         success: true,
-                        fee_paid: 0,
+                        fee_paid,
+                        gas_used,
                         error: None,
+                        outcome: Some(tx_outcome),
                     });
                 }
                 Err(e) => {
@@ -120,56 +1010,69 @@ impl StateDB {
                         error = %e,
                         "transaction failed during block execution"
                     );
-                    receipts.push(TransactionReceipt {
+                    outcome.receipts.push(TransactionReceipt {
                         tx_hash,
                         success: false,
-                        fee_paid: 0,
+                        fee_paid,
+                        gas_used,
                         error: Some(e.to_string()),
+                        outcome: None,
                     });
                 }
             }
         }
 
-        receipts
+        outcome
     }
 
-    /// Compute a deterministic state root by hashing sorted serialized accounts.
+    /// The state root: the root of the sparse Merkle tree over every
+    /// account. It depends only on the accounts, not on the order in which
+    /// they changed.
     pub fn compute_state_root(&self) -> [u8; 32] {
-        let mut entries: Vec<_> = self.accounts.iter().collect();
-        entries.sort_by_key(|(k, _)| *k);
+        self.commitment().tree.root()
+    }
 
-        let mut hasher = Sha256::new();
-        for (pubkey, state) in &entries {
-            hasher.update(pubkey);
-            let serialized =
-                serde_json::to_vec(state).expect("account state serialization should never fail");
-            hasher.update(&serialized);
+    /// Prove `pubkey`'s account state, or its absence, against
+    /// `compute_state_root()`.
+    pub fn prove_account(&self, pubkey: &[u8; 32]) -> AccountProof {
+        let proof = self.commitment().tree.prove(pubkey);
+        AccountProof {
+            account: self.load(pubkey),
+            proof,
         }
-
-        let digest = hasher.finalize();
-        let mut root = [0u8; 32];
-        root.copy_from_slice(&digest);
-        root
     }
 
-    /// Number of accounts in the state.
+    /// Number of accounts in the state: the number as of the last
+    /// `commit`, plus those created and less those deleted since.
     pub fn account_count(&self) -> usize {
-        self.accounts.len()
+        let created: i64 = self
+            .uncommitted
+            .iter()
+            .map(|(pubkey, before)| self.accounts[pubkey].is_some() as i64 - before.is_some() as i64)
+            .sum();
+        (self.committed_accounts as i64 + created) as usize
     }
 
-    /// Sum of all account balances.
+    /// Sum of all account balances: the sum as of the last `commit`, plus
+    /// what the accounts changed since then gained and lost.
     pub fn total_supply(&self) -> u64 {
-        self.accounts.values().map(|a| a.balance).sum()
+        let balance = |account: &Option<AccountState>| account.as_ref().map_or(0, |a| a.balance as i128);
+        let change: i128 = self
+            .uncommitted
+            .iter()
+            .map(|(pubkey, before)| balance(&self.accounts[pubkey]) - balance(before))
+            .sum();
+        (self.committed_supply as i128 + change) as u64
     }
 
-    /// Serialize the state database to JSON and write it to a file.
+    /// Serialize the accounts held in memory, as `accounts` lists them, to
+    /// JSON and write them to a file.
     ///
     /// Account keys (`[u8; 32]`) are stored as hex strings so the output is
     /// valid JSON with string keys.
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), StateError> {
         let hex_map: HashMap<String, &AccountState> = self
-            .accounts
-            .iter()
+            .accounts()
             .map(|(k, v)| (hex::encode(k), v))
             .collect();
 
@@ -199,17 +1102,7 @@ impl StateDB {
             accounts.insert(key, state);
         }
 
-        Ok(Self { accounts })
-    }
-
-    /// SHA-256 hash of a transaction (used for receipts).
-    fn hash_transaction(tx: &trv1_bft::block::Transaction) -> [u8; 32] {
-        let encoded =
-            serde_json::to_vec(tx).expect("transaction serialization should never fail");
-        let digest = Sha256::digest(&encoded);
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&digest);
-        hash
+        Ok(Self::from_accounts(accounts))
     }
 }
 
@@ -219,6 +1112,66 @@ impl Default for StateDB {
     }
 }
 
+/// Data key of a runtime module's state, under its module account.
+const MODULE_STATE_KEY: &[u8] = b"state";
+
+/// Data key of a contract's record, under the contract's address.
+const CONTRACT_KEY: &[u8] = &[0];
+
+/// First byte of the data keys of a contract's storage, under the
+/// contract's address. The storage key follows.
+const CONTRACT_STORAGE_TAG: u8 = 1;
+
+/// Contracts and their storage are data entries under the contract's
+/// address. Contracts change them, and move balances, under the calling
+/// transaction's checkpoint, so a failed call undoes them.
+impl Host for StateDB {
+    fn balance(&self, account: &[u8; 32]) -> u64 {
+        self.get_account(account).map_or(0, |account| account.balance)
+    }
+
+    fn transfer(&mut self, from: &[u8; 32], to: &[u8; 32], amount: u64) -> Result<(), String> {
+        StateDB::transfer(self, from, to, amount).map_err(|e| e.to_string())
+    }
+
+    fn contract(&self, address: &[u8; 32]) -> Option<Contract> {
+        let bytes = self.data(address, CONTRACT_KEY)?;
+        Some(Contract::from_bytes(&bytes).expect("contracts are stored by set_contract"))
+    }
+
+    fn set_contract(&mut self, address: [u8; 32], contract: Contract) {
+        self.set_data(&address, CONTRACT_KEY, Some(contract.to_bytes()));
+    }
+
+    fn storage(&self, contract: &[u8; 32], key: &[u8]) -> Option<Vec<u8>> {
+        self.data(contract, &[&[CONTRACT_STORAGE_TAG][..], key].concat())
+    }
+
+    fn set_storage(&mut self, contract: &[u8; 32], key: &[u8], value: Option<Vec<u8>>) {
+        self.set_data(contract, &[&[CONTRACT_STORAGE_TAG][..], key].concat(), value);
+    }
+}
+
+/// Record `staker`'s direct stake as their validator stake, if they are a
+/// validator. Slashing takes its cut from this figure.
+fn sync_validator_stake(modules: &mut Modules<'_>, staker: &[u8; 32]) {
+    let stake = modules.staking.stake_of(staker);
+    // Fails only for stakers who are not validators.
+    let _ = modules.validators.update_stake(staker, stake);
+}
+
+fn staking_error(e: trv1_staking::StakingError) -> StateError {
+    StateError::Staking(e.to_string())
+}
+
+fn contract_error(e: ContractError) -> StateError {
+    StateError::Contract(e.to_string())
+}
+
+fn validator_set_error(e: trv1_validator_set::ValidatorSetError) -> StateError {
+    StateError::ValidatorSet(e.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -395,6 +1348,41 @@ mod tests {
         assert_eq!(db1.compute_state_root(), db2.compute_state_root());
     }
 
+    #[test]
+    fn test_state_root_incremental_matches_rebuild() {
+        let mut db = setup_funded_state();
+        db.compute_state_root();
+        db.apply_transfer(&alice(), &charlie(), 100, 0).unwrap();
+        db.get_account_mut(&bob()).unwrap().increment_nonce();
+
+        let mut rebuilt = StateDB::new();
+        rebuilt.set_account(alice(), AccountState { balance: 900, nonce: 1 });
+        rebuilt.set_account(bob(), AccountState { balance: 500, nonce: 1 });
+        rebuilt.set_account(charlie(), AccountState::new(100));
+        assert_eq!(db.compute_state_root(), rebuilt.compute_state_root());
+    }
+
+    #[test]
+    fn test_account_proofs() {
+        let mut db = setup_funded_state();
+        db.apply_transfer(&alice(), &bob(), 100, 0).unwrap();
+        let root = db.compute_state_root();
+
+        let proof = db.prove_account(&alice());
+        assert_eq!(proof.account, Some(AccountState { balance: 900, nonce: 1 }));
+        assert!(proof.verify(&root, &alice()));
+        assert!(!proof.verify(&root, &bob()));
+
+        let absent = db.prove_account(&charlie());
+        assert_eq!(absent.account, None);
+        assert!(absent.verify(&root, &charlie()));
+
+        // A proof claiming a different balance does not verify.
+        let mut forged = proof.clone();
+        forged.account = Some(AccountState { balance: 1_000_000, nonce: 1 });
+        assert!(!forged.verify(&root, &alice()));
+    }
+
     // --- apply_block tests ---
 
     #[test]
@@ -408,6 +1396,9 @@ mod tests {
                 nonce: 0,
                 signature: vec![],
                 data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
             },
             Transaction {
                 from: bob(),
@@ -416,6 +1407,9 @@ mod tests {
                 nonce: 0,
                 signature: vec![],
                 data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
             },
         ];
 
@@ -440,6 +1434,9 @@ mod tests {
                 nonce: 0,
                 signature: vec![],
                 data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
             },
             // Bad tx: Alice -> Bob with wrong nonce (should be 1, using 0)
             Transaction {
@@ -449,6 +1446,9 @@ mod tests {
                 nonce: 0, // wrong nonce, should be 1
                 signature: vec![],
                 data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
             },
             // Good tx: Bob -> Charlie
             Transaction {
@@ -458,6 +1458,9 @@ mod tests {
                 nonce: 0,
                 signature: vec![],
                 data: vec![],
+                gas_limit: 21_000,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 1,
             },
         ];
 
@@ -486,6 +1489,9 @@ mod tests {
             nonce: 0,
             signature: vec![],
             data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
         }];
 
         let receipts = db.apply_block(&txs);
@@ -502,6 +1508,684 @@ mod tests {
         assert!(receipts.is_empty());
     }
 
+    #[test]
+    fn test_apply_verified_block_rejects_bad_signature() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+
+        let mut txs: Vec<Transaction> = (0..2).map(|nonce| signed_transfer(&sk, 100, nonce)).collect();
+        txs[1].amount = 900;
+
+        let verifier = SignatureVerifier::default();
+        let receipts = db.apply_verified_block(&txs, &verifier, &fee_context(0), &mut TestModules::default().at(1)).receipts;
+        assert!(receipts[0].success);
+        assert!(!receipts[1].success);
+        assert_eq!(
+            receipts[1].error.as_deref(),
+            Some("invalid transaction signature")
+        );
+        assert_eq!(receipts[1].fee_paid, 0);
+        assert_eq!(db.get_account(&sender).unwrap().balance, 1_000_000 - 126_000 - 100);
+        assert_eq!(db.get_account(&sender).unwrap().nonce, 1);
+    }
+
+    // --- Fees ---
+
+    /// A transfer of `amount` to bob paying a tip of 1 per gas.
+    fn signed_transfer(sk: &ed25519_dalek::SigningKey, amount: u64, nonce: u64) -> Transaction {
+        let mut tx = Transaction {
+            from: sk.verifying_key().to_bytes(),
+            to: bob(),
+            amount,
+            nonce,
+            signature: vec![],
+            data: vec![],
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+        tx.sign(sk);
+        tx
+    }
+
+    /// Base fee 5 with charlie proposing, so each transfer pays
+    /// 21_000 * (5 + 1) = 126_000.
+    fn fee_context(epoch: u64) -> FeeContext {
+        FeeContext {
+            base_fee: 5,
+            epoch,
+            proposer: Some(charlie()),
+            split: trv1_fees::FeeSplit::new(),
+            gas: GasSchedule::default(),
+            max_gas_per_block: 30_000_000,
+        }
+    }
+
+    #[test]
+    fn test_fees_charged_and_split() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+
+        // At maturity the fee is split evenly four ways.
+        let txs = [signed_transfer(&sk, 1000, 0), signed_transfer(&sk, 1000, 1)];
+        let outcome = db.apply_verified_block(&txs, &SignatureVerifier::default(), &fee_context(1825), &mut TestModules::default().at(1));
+        assert!(outcome.receipts.iter().all(|r| r.success && r.fee_paid == 126_000));
+        assert_eq!(outcome.fees.total, 252_000);
+        assert_eq!(outcome.fees.burned, 63_000);
+        assert_eq!(outcome.fees.developer, 63_000);
+        // Only contract calls are owed a developer share.
+        assert!(outcome.fees.developer_shares.is_empty());
+
+        assert_eq!(db.get_account(&sender).unwrap().balance, 1_000_000 - 252_000 - 2000);
+        assert_eq!(db.get_account(&charlie()).unwrap().balance, 63_000);
+        assert_eq!(db.get_account(&treasury_account()).unwrap().balance, 63_000);
+        assert_eq!(db.get_account(&developer_pool_account()).unwrap().balance, 63_000);
+        assert_eq!(db.total_supply(), 1_000_000 - 63_000);
+    }
+
+    #[test]
+    fn test_failed_transfer_still_pays_fee() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(200_000));
+
+        // The fee is affordable but the amount on top of it is not.
+        let tx = signed_transfer(&sk, 100_000, 0);
+        let outcome = db.apply_verified_block(&[tx], &SignatureVerifier::default(), &fee_context(0), &mut TestModules::default().at(1));
+        let receipt = &outcome.receipts[0];
+        assert!(!receipt.success);
+        assert_eq!(receipt.fee_paid, 126_000);
+        assert_eq!(db.get_account(&sender).unwrap(), AccountState { balance: 74_000, nonce: 1 });
+        assert!(db.get_account(&bob()).is_none());
+        // Launch split: no validator share, so the proposer gets nothing.
+        assert!(db.get_account(&charlie()).is_none());
+        assert_eq!(outcome.fees.burned, 12_600);
+    }
+
+    #[test]
+    fn test_unpayable_fee_changes_nothing() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(100_000));
+        let root = db.compute_state_root();
+
+        let mut underpriced = signed_transfer(&sk, 10, 0);
+        underpriced.max_fee_per_gas = 4;
+        underpriced.sign(&sk);
+        let txs = [signed_transfer(&sk, 10, 0), underpriced];
+        let outcome = db.apply_verified_block(&txs, &SignatureVerifier::default(), &fee_context(0), &mut TestModules::default().at(1));
+
+        assert!(outcome.receipts.iter().all(|r| !r.success && r.fee_paid == 0));
+        assert!(outcome.receipts[0].error.as_deref().unwrap().contains("insufficient balance"));
+        assert_eq!(
+            outcome.receipts[1].error.as_deref(),
+            Some("max fee per gas 4 is below the base fee 5")
+        );
+        assert_eq!(outcome.fees, BlockFees::default());
+        assert_eq!(db.compute_state_root(), root);
+    }
+
+    #[test]
+    fn test_unused_gas_is_refunded() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+
+        // The sender must afford the whole limit but pays only for the
+        // 21_000 gas a transfer uses.
+        let mut tx = signed_transfer(&sk, 100, 0);
+        tx.gas_limit = 150_000;
+        tx.sign(&sk);
+        let outcome = db.apply_verified_block(&[tx], &SignatureVerifier::default(), &fee_context(0), &mut TestModules::default().at(1));
+        let receipt = &outcome.receipts[0];
+        assert!(receipt.success);
+        assert_eq!(receipt.gas_used, 21_000);
+        assert_eq!(receipt.fee_paid, 126_000);
+        assert_eq!(outcome.gas_used, 21_000);
+        assert_eq!(db.get_account(&sender).unwrap().balance, 1_000_000 - 126_000 - 100);
+    }
+
+    #[test]
+    fn test_block_gas_limit_enforced() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+
+        let ctx = FeeContext {
+            max_gas_per_block: 50_000,
+            ..fee_context(0)
+        };
+        let txs: Vec<_> = (0..3).map(|nonce| signed_transfer(&sk, 10, nonce)).collect();
+        let outcome = db.apply_verified_block(&txs, &SignatureVerifier::default(), &ctx, &mut TestModules::default().at(1));
+        assert!(outcome.receipts[0].success && outcome.receipts[1].success);
+        assert_eq!(
+            outcome.receipts[2].error.as_deref(),
+            Some("gas limit 21000 exceeds the 8000 gas left in the block")
+        );
+        assert_eq!(outcome.receipts[2].fee_paid, 0);
+        assert_eq!(outcome.gas_used, 42_000);
+        assert_eq!(db.get_account(&sender).unwrap().nonce, 2);
+    }
+
+    // --- Payloads ---
+
+    #[derive(Default)]
+    struct TestModules {
+        staking: trv1_staking::StakingPool,
+        validators: trv1_validator_set::ValidatorSetManager,
+        slashing: trv1_slashing::SlashingEngine,
+        rewards: trv1_rewards::DeveloperRewards,
+        contracts: trv1_contracts::ContractRuntime,
+    }
+
+    impl TestModules {
+        fn at(&mut self, height: u64) -> Modules<'_> {
+            self.at_epoch(height, 0)
+        }
+
+        fn at_epoch(&mut self, height: u64, epoch: u64) -> Modules<'_> {
+            Modules {
+                height,
+                epoch,
+                staking: &mut self.staking,
+                validators: &mut self.validators,
+                slashing: &mut self.slashing,
+                rewards: &mut self.rewards,
+                contracts: &mut self.contracts,
+            }
+        }
+    }
+
+    /// A transaction carrying `payload` whose gas limit is exactly the gas
+    /// it uses.
+    fn signed_payload(sk: &ed25519_dalek::SigningKey, payload: &TxPayload, nonce: u64) -> Transaction {
+        let mut tx = signed_transfer(sk, 0, nonce);
+        tx.data = payload.to_data();
+        tx.gas_limit = payload_gas(payload);
+        tx.sign(sk);
+        tx
+    }
+
+    fn payload_gas(payload: &TxPayload) -> u64 {
+        let schedule = GasSchedule::default();
+        let tx = Transaction {
+            data: payload.to_data(),
+            ..signed_transfer(&ed25519_dalek::SigningKey::from_bytes(&[1; 32]), 0, 0)
+        };
+        schedule.intrinsic_gas(&tx) + schedule.execution_gas(payload)
+    }
+
+    #[test]
+    fn test_stake_and_unstake_move_balance() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_staking::LockTier;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+        let mut modules = TestModules::default();
+
+        let stake = TxPayload::Stake { amount: 10_000, tier: LockTier::NoLock };
+        let unstake = TxPayload::Unstake { amount: 4_000 };
+        let txs = vec![signed_payload(&sk, &stake, 0), signed_payload(&sk, &unstake, 1)];
+        let outcome = db.apply_verified_block(&txs, &SignatureVerifier::default(), &fee_context(0), &mut modules.at(1));
+        assert_eq!(
+            outcome.receipts[0].outcome,
+            Some(TxOutcome::Staked { amount: 10_000, tier: LockTier::NoLock })
+        );
+        assert_eq!(outcome.receipts[1].outcome, Some(TxOutcome::Unstaked { amount: 4_000 }));
+        assert_eq!(modules.staking.total_staked(), 6_000);
+        assert_eq!(db.get_account(&staking_account()).unwrap().balance, 6_000);
+        // Each pays for its gas at 5 + 1 per unit.
+        let gas = payload_gas(&stake) + payload_gas(&unstake);
+        assert_eq!(outcome.gas_used, gas);
+        assert_eq!(db.get_account(&sender).unwrap().balance, 1_000_000 - 6 * gas - 6_000);
+    }
+
+    #[test]
+    fn test_failed_module_call_reverts_balance() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_staking::LockTier;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+        let mut modules = TestModules::default();
+
+        // The stake is locked for 30 epochs, so it cannot be unstaked yet,
+        // and the pool rejects a zero stake after the balance has moved.
+        let txs = vec![
+            signed_payload(&sk, &TxPayload::Stake { amount: 10_000, tier: LockTier::ThirtyDay }, 0),
+            signed_payload(&sk, &TxPayload::Unstake { amount: 10_000 }, 1),
+            signed_payload(&sk, &TxPayload::Stake { amount: 0, tier: LockTier::NoLock }, 2),
+        ];
+        let receipts = db
+            .apply_verified_block(&txs, &SignatureVerifier::default(), &fee_context(0), &mut modules.at(1))
+            .receipts;
+        assert!(receipts[0].success);
+        assert!(!receipts[1].success);
+        assert!(receipts[1].error.as_deref().unwrap().starts_with("staking error"));
+        assert!(!receipts[2].success);
+        assert_eq!(modules.staking.total_staked(), 10_000);
+        assert_eq!(db.get_account(&staking_account()).unwrap().balance, 10_000);
+        // Failed calls still pay for their gas.
+        let fees = 6 * (2 * payload_gas(&TxPayload::Stake { amount: 0, tier: LockTier::NoLock })
+            + payload_gas(&TxPayload::Unstake { amount: 0 }));
+        assert_eq!(receipts.iter().map(|r| r.fee_paid).sum::<u64>(), fees);
+        assert_eq!(db.get_account(&sender).unwrap().balance, 1_000_000 - fees - 10_000);
+        assert_eq!(db.get_account(&sender).unwrap().nonce, 3);
+    }
+
+    #[test]
+    fn test_out_of_gas_reverts_payload() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_staking::LockTier;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+        let mut modules = TestModules::default();
+
+        let stake = TxPayload::Stake { amount: 10_000, tier: LockTier::NoLock };
+        let mut tx = signed_payload(&sk, &stake, 0);
+        tx.gas_limit -= 1;
+        tx.sign(&sk);
+        let limit = tx.gas_limit;
+        let receipt = db
+            .apply_verified_block(&[tx], &SignatureVerifier::default(), &fee_context(0), &mut modules.at(1))
+            .receipts
+            .remove(0);
+        assert!(receipt.error.as_deref().unwrap().starts_with("out of gas"));
+        assert_eq!(receipt.gas_used, limit);
+        assert_eq!(receipt.fee_paid, 6 * limit);
+        assert_eq!(modules.staking.total_staked(), 0);
+        assert!(db.get_account(&staking_account()).is_none());
+        assert_eq!(db.get_account(&sender).unwrap(), AccountState { balance: 1_000_000 - 6 * limit, nonce: 1 });
+    }
+
+    #[test]
+    fn test_stake_unlocks_after_lock_duration() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_staking::LockTier;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+        let mut modules = TestModules::default();
+        let verifier = SignatureVerifier::default();
+
+        let stake = TxPayload::Stake { amount: 50_000, tier: LockTier::ThirtyDay };
+        let unstake = TxPayload::Unstake { amount: 50_000 };
+        let mut run = |db: &mut StateDB, tx: Transaction, epoch: u64| {
+            db.apply_verified_block(&[tx], &verifier, &fee_context(epoch), &mut modules.at_epoch(1, epoch))
+                .receipts
+                .remove(0)
+        };
+        assert!(run(&mut db, signed_payload(&sk, &stake, 0), 2).success);
+        // Staked at epoch 2, the 30-epoch lock runs out at epoch 32.
+        assert!(!run(&mut db, signed_payload(&sk, &unstake, 1), 31).success);
+        assert_eq!(db.get_account(&staking_account()).unwrap().balance, 50_000);
+        assert!(run(&mut db, signed_payload(&sk, &unstake, 2), 32).success);
+        assert_eq!(db.get_account(&staking_account()).unwrap().balance, 0);
+        assert_eq!(modules.staking.total_staked(), 0);
+        let fees = 6 * (payload_gas(&stake) + 2 * payload_gas(&unstake));
+        assert_eq!(db.get_account(&sender).unwrap().balance, 1_000_000 - fees);
+    }
+
+    #[test]
+    fn test_validator_stake_follows_stake_changes() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_staking::LockTier;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(5_000_000));
+        let mut modules = TestModules::default();
+
+        let txs = vec![
+            signed_payload(&sk, &TxPayload::Stake { amount: 300_000, tier: LockTier::NoLock }, 0),
+            signed_payload(
+                &sk,
+                &TxPayload::RegisterValidator {
+                    self_stake: 1_000_000,
+                    tier: LockTier::Delegator,
+                    commission_rate: 500,
+                },
+                1,
+            ),
+            signed_payload(&sk, &TxPayload::Unstake { amount: 100_000 }, 2),
+        ];
+        let receipts = db
+            .apply_verified_block(&txs, &SignatureVerifier::default(), &fee_context(0), &mut modules.at(1))
+            .receipts;
+        assert!(receipts.iter().all(|r| r.success));
+
+        // The validator joins with its stake as of the rotation.
+        modules.validators.epoch_rotation(&modules.staking);
+        assert_eq!(modules.validators.get_validator(&sender).unwrap().stake, 1_200_000);
+
+        let tx = signed_payload(&sk, &TxPayload::Unstake { amount: 50_000 }, 3);
+        db.apply_verified_block(&[tx], &SignatureVerifier::default(), &fee_context(0), &mut modules.at(2));
+        assert_eq!(modules.validators.get_validator(&sender).unwrap().stake, 1_150_000);
+    }
+
+    #[test]
+    fn test_edit_validator_and_unjail() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_validator_set::{ValidatorMetadata, ValidatorStatus};
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+        let mut modules = TestModules::default();
+        modules.validators.register_validator(sender, 1_000_000, 500, 0).unwrap();
+        let verifier = SignatureVerifier::default();
+
+        let metadata = ValidatorMetadata {
+            moniker: "node-1".into(),
+            ..Default::default()
+        };
+        let edit = TxPayload::EditValidator {
+            commission_rate: Some(800),
+            metadata: Some(metadata.clone()),
+        };
+        let too_high = TxPayload::EditValidator {
+            commission_rate: Some(10_001),
+            metadata: Some(ValidatorMetadata::default()),
+        };
+        let txs = vec![signed_payload(&sk, &edit, 0), signed_payload(&sk, &too_high, 1)];
+        let receipts = db.apply_verified_block(&txs, &verifier, &fee_context(0), &mut modules.at(1)).receipts;
+        assert_eq!(
+            receipts[0].outcome,
+            Some(TxOutcome::ValidatorEdited { commission_epoch: Some(1) })
+        );
+        // A rejected edit changes nothing, not even the metadata.
+        assert!(!receipts[1].success);
+        let validator = modules.validators.get_validator(&sender).unwrap();
+        assert_eq!(validator.metadata, metadata);
+        assert_eq!(validator.commission_rate, 500);
+        modules.validators.epoch_rotation(&modules.staking);
+        assert_eq!(modules.validators.get_validator(&sender).unwrap().commission_rate, 800);
+
+        // Jailed in epoch 1, the validator may unjail from epoch 3.
+        modules.validators.jail(&sender).unwrap();
+        modules.validators.epoch_rotation(&modules.staking);
+        let tx = signed_payload(&sk, &TxPayload::Unjail, 2);
+        let receipt = db.apply_verified_block(&[tx], &verifier, &fee_context(0), &mut modules.at(2)).receipts.remove(0);
+        assert_eq!(
+            receipt.error.as_deref(),
+            Some("validator set error: validator is jailed until epoch 3")
+        );
+        modules.validators.epoch_rotation(&modules.staking);
+        let tx = signed_payload(&sk, &TxPayload::Unjail, 3);
+        let receipt = db.apply_verified_block(&[tx], &verifier, &fee_context(0), &mut modules.at(3)).receipts.remove(0);
+        assert_eq!(receipt.outcome, Some(TxOutcome::Unjailed));
+        assert_eq!(modules.validators.get_validator(&sender).unwrap().status, ValidatorStatus::Standby);
+    }
+
+    #[test]
+    fn test_register_validator_and_deploy_contract() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_staking::LockTier;
+        use trv1_validator_set::ValidatorStatus;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(5_000_000));
+        let mut modules = TestModules::default();
+
+        let register = TxPayload::RegisterValidator {
+            self_stake: 1_000_000,
+            tier: LockTier::Delegator,
+            commission_rate: 500,
+        };
+        let txs = vec![
+            signed_payload(&sk, &register, 0),
+            signed_payload(&sk, &register, 1),
+            signed_payload(&sk, &TxPayload::DeployContract { code: EMPTY_CONTRACT.clone() }, 2),
+            signed_payload(&sk, &TxPayload::DeployContract { code: vec![1, 2, 3] }, 3),
+        ];
+        let receipts = db
+            .apply_verified_block(&txs, &SignatureVerifier::default(), &fee_context(0), &mut modules.at(7))
+            .receipts;
+        assert_eq!(receipts[0].outcome, Some(TxOutcome::ValidatorRegistered { epoch: 1 }));
+        // Registering twice fails without staking the second self-stake.
+        assert!(!receipts[1].success);
+        assert_eq!(modules.staking.total_staked(), 1_000_000);
+        assert!(modules.validators.get_validator(&sender).is_none());
+
+        modules.validators.epoch_rotation(&modules.staking);
+        let validator = modules.validators.get_validator(&sender).unwrap();
+        assert_eq!(validator.status, ValidatorStatus::Active);
+        assert_eq!(validator.commission_rate, 500);
+        assert_eq!(validator.join_height, 7);
+
+        let address = contract_address(&sender, 2);
+        assert_eq!(receipts[2].outcome, Some(TxOutcome::ContractDeployed { address }));
+        let contract = modules.rewards.get_contract(&address).unwrap();
+        assert_eq!(contract.deployer, sender);
+        assert_eq!(contract.deploy_height, 7);
+        assert_eq!(db.contract(&address).unwrap().code, *EMPTY_CONTRACT);
+
+        // Code that is not a valid contract is neither stored nor
+        // registered for rewards.
+        assert!(!receipts[3].success);
+        assert!(db.contract(&contract_address(&sender, 3)).is_none());
+        assert!(modules.rewards.get_contract(&contract_address(&sender, 3)).is_none());
+
+        // A deploy that cannot be registered for rewards stores nothing.
+        let taken = contract_address(&sender, 4);
+        modules.rewards.register_contract(taken, bob(), 0).unwrap();
+        let deploy = signed_payload(&sk, &TxPayload::DeployContract { code: EMPTY_CONTRACT.clone() }, 4);
+        let receipts = db
+            .apply_verified_block(&[deploy], &SignatureVerifier::default(), &fee_context(0), &mut modules.at(8))
+            .receipts;
+        assert!(receipts[0].error.as_deref().unwrap().contains("already registered"));
+        assert!(db.contract(&taken).is_none());
+        assert_eq!(modules.rewards.get_contract(&taken).unwrap().deployer, bob());
+    }
+
+    static EMPTY_CONTRACT: std::sync::LazyLock<Vec<u8>> = std::sync::LazyLock::new(|| {
+        wat::parse_str(r#"(module (memory (export "memory") 1) (func (export "call")))"#).unwrap()
+    });
+
+    /// Adds the value sent with each call to a total it keeps in storage
+    /// and returns the new total. Traps if given any input.
+    const TOTAL_CONTRACT: &str = r#"
+        (module
+          (import "env" "value" (func $value (result i64)))
+          (import "env" "input_len" (func $input_len (result i32)))
+          (import "env" "storage_read" (func $read (param i32 i32 i32 i32) (result i32)))
+          (import "env" "storage_write" (func $write (param i32 i32 i32 i32)))
+          (import "env" "output_write" (func $output (param i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "total")
+          (func (export "call")
+            (if (call $input_len) (then unreachable))
+            (drop (call $read (i32.const 0) (i32.const 5) (i32.const 8) (i32.const 8)))
+            (i64.store (i32.const 8) (i64.add (i64.load (i32.const 8)) (call $value)))
+            (call $write (i32.const 0) (i32.const 5) (i32.const 8) (i32.const 8))
+            (call $output (i32.const 8) (i32.const 8))))
+    "#;
+
+    fn contract_call(
+        sk: &ed25519_dalek::SigningKey,
+        contract: [u8; 32],
+        amount: u64,
+        input: Vec<u8>,
+        gas_limit: u64,
+        nonce: u64,
+    ) -> Transaction {
+        let mut tx = signed_transfer(sk, amount, nonce);
+        tx.to = contract;
+        tx.data = TxPayload::CallContract { input }.to_data();
+        tx.gas_limit = gas_limit;
+        tx.sign(sk);
+        tx
+    }
+
+    #[test]
+    fn test_call_contract() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(10_000_000));
+        let mut modules = TestModules::default();
+
+        let deploy = TxPayload::DeployContract {
+            code: wat::parse_str(TOTAL_CONTRACT).unwrap(),
+        };
+        let contract = contract_address(&sender, 0);
+        let txs = vec![
+            signed_payload(&sk, &deploy, 0),
+            contract_call(&sk, contract, 500, vec![], 100_000, 1),
+            contract_call(&sk, contract, 200, vec![], 100_000, 2),
+            // Traps, so the value sent comes back.
+            contract_call(&sk, contract, 300, vec![1], 100_000, 3),
+            contract_call(&sk, contract, 300, vec![], 30_000, 4),
+            contract_call(&sk, bob(), 300, vec![], 100_000, 5),
+        ];
+        let outcome = db.apply_verified_block(&txs, &SignatureVerifier::default(), &fee_context(1825), &mut modules.at(1));
+        let receipts = &outcome.receipts;
+        assert_eq!(
+            receipts[2].outcome,
+            Some(TxOutcome::ContractCalled {
+                contract,
+                output: 700u64.to_le_bytes().to_vec()
+            })
+        );
+        assert!(receipts[1].gas_used > payload_gas(&TxPayload::CallContract { input: vec![] }));
+        assert!(receipts[1].gas_used < 100_000);
+        assert!(receipts[3].error.as_deref().unwrap().starts_with("contract error: contract trapped"));
+        assert_eq!(receipts[4].error.as_deref(), Some("out of gas: used all of the 30000 gas limit"));
+        assert_eq!(receipts[4].gas_used, 30_000);
+        assert_eq!(
+            receipts[5].error.as_deref(),
+            Some("contract error: no contract is deployed at this address")
+        );
+
+        assert_eq!(db.get_account(&contract).unwrap().balance, 700);
+        assert_eq!(db.storage(&contract, b"total"), Some(700u64.to_le_bytes().to_vec()));
+        let fees: u64 = receipts.iter().map(|r| r.fee_paid).sum();
+        assert_eq!(db.get_account(&sender).unwrap().balance, 10_000_000 - fees - 700);
+
+        // Developer shares of the fees of successful calls are owed to the
+        // contract called; failed calls pay theirs into the pool unowed.
+        let shares: Vec<_> = outcome.fees.developer_shares.iter().map(|(to, _)| *to).collect();
+        assert_eq!(shares, vec![contract, contract]);
+    }
+
+    #[test]
+    fn test_submit_evidence_payload() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_bft::types::{BlockHash, DuplicateVoteEvidence, Height, Round, Vote, VoteType};
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(1_000_000));
+        let mut modules = TestModules::default();
+
+        let offender = SigningKey::generate(&mut OsRng);
+        let vote = |hash| Vote::new(VoteType::Precommit, Height(3), Round(0), Some(BlockHash(hash)), &offender);
+        let valid = DuplicateVoteEvidence {
+            vote_a: vote([1; 32]),
+            vote_b: vote([2; 32]),
+        };
+        let bogus = DuplicateVoteEvidence {
+            vote_a: vote([1; 32]),
+            vote_b: vote([1; 32]),
+        };
+        let txs = vec![
+            signed_payload(&sk, &TxPayload::SubmitEvidence(valid.clone()), 0),
+            signed_payload(&sk, &TxPayload::SubmitEvidence(bogus), 1),
+            signed_payload(&sk, &TxPayload::SubmitEvidence(valid), 2),
+        ];
+        let receipts = db
+            .apply_verified_block(&txs, &SignatureVerifier::default(), &fee_context(0), &mut modules.at(4))
+            .receipts;
+        assert!(matches!(receipts[0].outcome, Some(TxOutcome::EvidenceSubmitted { .. })));
+        assert!(!receipts[1].success);
+        // The same evidence is only accepted once.
+        assert!(!receipts[2].success);
+        assert_eq!(modules.slashing.evidence_pool().get_pending_evidence().len(), 1);
+    }
+
+    #[test]
+    fn test_payloads_rejected_without_modules() {
+        let mut db = setup_funded_state();
+        let stake = TxPayload::Stake {
+            amount: 100,
+            tier: trv1_staking::LockTier::NoLock,
+        };
+        let mut tx = Transaction {
+            from: alice(),
+            to: bob(),
+            amount: 0,
+            nonce: 0,
+            signature: vec![],
+            data: stake.to_data(),
+            gas_limit: 21_000,
+            max_fee_per_gas: 10,
+            max_priority_fee_per_gas: 1,
+        };
+        let receipts = db.apply_block(std::slice::from_ref(&tx));
+        assert_eq!(
+            receipts[0].error.as_deref(),
+            Some("stake transactions cannot be executed without the runtime modules")
+        );
+
+        // Only transfers carry an amount.
+        tx.amount = 5;
+        let receipts = db.apply_block(&[tx]);
+        assert!(receipts[0].error.as_deref().unwrap().starts_with("invalid payload"));
+        assert_eq!(db.get_account(&alice()).unwrap().nonce, 0);
+    }
+
     // --- total_supply ---
 
     #[test]
@@ -560,6 +2244,485 @@ mod tests {
         let _ = std::fs::remove_file(&path);
     }
 
+    // --- Checkpoint tests ---
+
+    #[test]
+    fn test_revert_to_checkpoint() {
+        let mut db = StateDB::new();
+        db.set_account(alice(), AccountState::new(1000));
+        let root = db.compute_state_root();
+
+        let checkpoint = db.checkpoint();
+        db.apply_transfer(&alice(), &bob(), 300, 0).unwrap();
+        db.apply_transfer(&alice(), &bob(), 200, 1).unwrap();
+        assert_ne!(db.compute_state_root(), root);
+        db.revert_to(checkpoint);
+
+        assert_eq!(db.get_account(&alice()).unwrap(), AccountState::new(1000));
+        assert!(db.get_account(&bob()).is_none());
+        assert_eq!(db.compute_state_root(), root);
+    }
+
+    #[test]
+    fn test_nested_checkpoints() {
+        let mut db = StateDB::new();
+        db.set_account(alice(), AccountState::new(1000));
+
+        let outer = db.checkpoint();
+        db.apply_transfer(&alice(), &bob(), 100, 0).unwrap();
+        let inner = db.checkpoint();
+        db.apply_transfer(&alice(), &charlie(), 100, 1).unwrap();
+        db.revert_to(inner);
+        assert!(db.get_account(&charlie()).is_none());
+        assert_eq!(db.get_account(&alice()).unwrap(), AccountState { balance: 900, nonce: 1 });
+
+        // A committed inner checkpoint is still undone by the outer one.
+        let inner = db.checkpoint();
+        db.apply_transfer(&bob(), &charlie(), 40, 0).unwrap();
+        db.commit_checkpoint(inner);
+        assert_eq!(db.get_account(&charlie()).unwrap(), AccountState::new(40));
+        db.revert_to(outer);
+
+        assert_eq!(db.get_account(&alice()).unwrap(), AccountState::new(1000));
+        assert!(db.get_account(&bob()).is_none());
+        assert!(db.get_account(&charlie()).is_none());
+    }
+
+    #[test]
+    fn test_data_entries_follow_checkpoints() {
+        let mut db = setup_funded_state();
+        let root = db.compute_state_root();
+        let count = db.account_count();
+
+        let checkpoint = db.checkpoint();
+        db.set_data(&alice(), b"key", Some(b"value".to_vec()));
+        assert_eq!(db.data(&alice(), b"key"), Some(b"value".to_vec()));
+        assert_ne!(db.compute_state_root(), root);
+        // Data entries are not accounts.
+        assert_eq!(db.account_count(), count);
+        db.revert_to(checkpoint);
+
+        assert_eq!(db.data(&alice(), b"key"), None);
+        assert_eq!(db.compute_state_root(), root);
+
+        db.set_data(&alice(), b"key", Some(b"value".to_vec()));
+        db.commit(0).unwrap();
+        db.set_data(&alice(), b"key", None);
+        db.commit(1).unwrap();
+        assert_eq!(db.compute_state_root(), root);
+    }
+
+    #[test]
+    #[should_panic(expected = "already closed")]
+    fn test_closed_checkpoint_panics() {
+        let mut db = StateDB::new();
+        let checkpoint = db.checkpoint();
+        db.commit_checkpoint(checkpoint);
+        db.revert_to(checkpoint);
+    }
+
+    #[test]
+    fn test_failed_transfer_is_rolled_back() {
+        let mut db = StateDB::new();
+        db.set_account(alice(), AccountState::new(1000));
+        db.set_account(bob(), AccountState::new(u64::MAX - 10));
+
+        // Crediting bob overflows after alice has been debited.
+        let err = db.apply_transfer(&alice(), &bob(), 100, 0).unwrap_err();
+        assert_eq!(err, StateError::Overflow);
+        assert_eq!(db.get_account(&alice()).unwrap(), AccountState::new(1000));
+        assert_eq!(db.get_account(&bob()).unwrap(), AccountState::new(u64::MAX - 10));
+    }
+
+    #[test]
+    fn test_slashed_stake_cannot_be_unstaked() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_slashing::{EvidenceRecord, SlashingOffense};
+        use trv1_staking::LockTier;
+
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let mut db = StateDB::new();
+        db.set_account(sender, AccountState::new(5_000_000));
+        let mut modules = TestModules::default();
+        let verifier = SignatureVerifier::default();
+
+        let register = TxPayload::RegisterValidator {
+            self_stake: 1_000_000,
+            tier: LockTier::NoLock,
+            commission_rate: 500,
+        };
+        let tx = signed_payload(&sk, &register, 0);
+        assert!(db.apply_verified_block(&[tx], &verifier, &fee_context(0), &mut modules.at(1)).receipts[0].success);
+        modules.validators.epoch_rotation(&modules.staking);
+
+        modules
+            .slashing
+            .submit_evidence(EvidenceRecord {
+                offense: SlashingOffense::DoubleSign,
+                offender: sender,
+                height: 1,
+                data: b"vote_a:vote_b".to_vec(),
+                processed: false,
+            })
+            .unwrap();
+        let treasury = db.get_account(&treasury_account()).map_or(0, |a| a.balance);
+        let events = db.process_evidence(&mut modules.at(2));
+        // 5% of the validator's stake leaves the pool and the staking
+        // account for the treasury.
+        assert_eq!(events[0].slash_amount, 50_000);
+        assert_eq!(modules.staking.stake_of(&sender), 950_000);
+        assert_eq!(db.get_account(&staking_account()).unwrap().balance, 950_000);
+        assert_eq!(db.get_account(&treasury_account()).unwrap().balance, treasury + 50_000);
+
+        let run = |db: &mut StateDB, modules: &mut TestModules, amount, nonce| {
+            let tx = signed_payload(&sk, &TxPayload::Unstake { amount }, nonce);
+            db.apply_verified_block(&[tx], &verifier, &fee_context(0), &mut modules.at(3))
+                .receipts
+                .remove(0)
+        };
+        assert!(!run(&mut db, &mut modules, 1_000_000, 1).success);
+        assert!(run(&mut db, &mut modules, 950_000, 2).success);
+        assert_eq!(db.get_account(&staking_account()).unwrap().balance, 0);
+        assert_eq!(modules.validators.get_validator(&sender).unwrap().stake, 0);
+    }
+
+    // --- Backend tests ---
+
+    fn temp_backend(name: &str) -> (Arc<dyn StateBackend>, std::path::PathBuf) {
+        let dir = std::env::temp_dir()
+            .join(format!("trv1_state_db_backend_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = trv1_storage::StorageConfig {
+            lru_capacity: 16,
+            nvme_path: dir.join("warm").to_string_lossy().into_owned(),
+            archive_path: dir.join("cold").to_string_lossy().into_owned(),
+            max_ram_bytes: 1024,
+        };
+        let storage = Arc::new(trv1_storage::TieredStorage::new(&config).unwrap());
+        (Arc::new(crate::StorageBackend::new(storage)), dir)
+    }
+
+    #[test]
+    fn test_reopen_resumes_last_commit() {
+        let (backend, dir) = temp_backend("reopen");
+        {
+            let mut db = StateDB::open(backend.clone(), HistoryRetention::default()).unwrap();
+            assert_eq!(db.committed_height(), None);
+            db.set_account(alice(), AccountState::new(1000));
+            db.set_account(bob(), AccountState::new(500));
+            db.commit(0).unwrap();
+
+            db.apply_transfer(&alice(), &charlie(), 100, 0).unwrap();
+            db.commit_block(1, [7; 32]).unwrap();
+            assert_eq!(db.committed_block_hash(), [7; 32]);
+
+            // Changes after the last commit are lost in a crash.
+            db.apply_transfer(&bob(), &charlie(), 50, 0).unwrap();
+        }
+
+        let db = StateDB::open(backend, HistoryRetention::default()).unwrap();
+        assert_eq!(db.committed_height(), Some(1));
+        assert_eq!(db.committed_block_hash(), [7; 32]);
+        assert_eq!(db.account_count(), 3);
+        assert_eq!(db.get_account(&alice()).unwrap(), AccountState { balance: 900, nonce: 1 });
+        assert_eq!(db.get_account(&bob()).unwrap(), AccountState::new(500));
+        assert_eq!(db.get_account(&charlie()).unwrap(), AccountState::new(100));
+
+        let mut expected = StateDB::new();
+        expected.set_account(alice(), AccountState { balance: 900, nonce: 1 });
+        expected.set_account(bob(), AccountState::new(500));
+        expected.set_account(charlie(), AccountState::new(100));
+        assert_eq!(db.compute_state_root(), expected.compute_state_root());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// A backend counting the accounts it reads and writes.
+    #[derive(Debug)]
+    struct CountingBackend {
+        inner: Arc<dyn StateBackend>,
+        reads: std::sync::atomic::AtomicUsize,
+        writes: std::sync::atomic::AtomicUsize,
+    }
+
+    impl NodeStore for CountingBackend {
+        fn load_node(&self, hash: &[u8; 32]) -> Result<Option<Vec<u8>>, StateError> {
+            self.inner.load_node(hash)
+        }
+    }
+
+    impl StateBackend for CountingBackend {
+        fn committed_height(&self) -> Result<Option<u64>, StateError> {
+            self.inner.committed_height()
+        }
+
+        fn load_summary(&self) -> Result<StateSummary, StateError> {
+            self.inner.load_summary()
+        }
+
+        fn load_account(&self, pubkey: &[u8; 32]) -> Result<Option<AccountState>, StateError> {
+            self.reads.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.inner.load_account(pubkey)
+        }
+
+        fn load_data(&self, key: &DataKey) -> Result<Option<Vec<u8>>, StateError> {
+            self.inner.load_data(key)
+        }
+
+        fn commit(&self, commit: &BlockCommit<'_>) -> Result<(), StateError> {
+            self.writes
+                .fetch_add(commit.changes.len(), std::sync::atomic::Ordering::Relaxed);
+            self.inner.commit(commit)
+        }
+
+        fn history_range(&self) -> Result<Option<RangeInclusive<u64>>, StateError> {
+            self.inner.history_range()
+        }
+
+        fn load_undo(&self, height: u64) -> Result<Vec<AccountChange>, StateError> {
+            self.inner.load_undo(height)
+        }
+    }
+
+    #[test]
+    fn test_backed_state_reads_and_writes_only_what_changes() {
+        use std::sync::atomic::Ordering;
+
+        let (inner, dir) = temp_backend("lazy");
+        let account = |i: u32| {
+            let mut pubkey = [0xaa; 32];
+            pubkey[..4].copy_from_slice(&i.to_le_bytes());
+            pubkey
+        };
+        {
+            let mut db = StateDB::open(inner.clone(), HistoryRetention::default()).unwrap();
+            for i in 0..500 {
+                db.set_account(account(i), AccountState::new(10));
+            }
+            db.commit(0).unwrap();
+        }
+
+        let backend = Arc::new(CountingBackend {
+            inner,
+            reads: Default::default(),
+            writes: Default::default(),
+        });
+        let mut db = StateDB::open(backend.clone(), HistoryRetention::default()).unwrap();
+        assert_eq!(db.account_count(), 500);
+        assert_eq!(db.total_supply(), 5000);
+        assert_eq!(backend.reads.load(Ordering::Relaxed), 0);
+
+        db.apply_transfer(&account(1), &account(2), 5, 0).unwrap();
+        db.commit(1).unwrap();
+        assert!(backend.reads.load(Ordering::Relaxed) <= 4);
+        assert_eq!(backend.writes.load(Ordering::Relaxed), 2);
+        assert_eq!(db.accounts().count(), 0);
+
+        let mut expected = StateDB::new();
+        for i in 0..500 {
+            expected.set_account(account(i), AccountState::new(10));
+        }
+        expected.apply_transfer(&account(1), &account(2), 5, 0).unwrap();
+        assert_eq!(db.compute_state_root(), expected.compute_state_root());
+        assert!(db.prove_account(&account(2)).verify(&db.compute_state_root(), &account(2)));
+        assert_eq!(db.total_supply(), 5000);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_contracts_persist_across_reopen() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+
+        let (backend, dir) = temp_backend("contracts");
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let contract = contract_address(&sender, 0);
+        let root = {
+            let mut db = StateDB::open(backend.clone(), HistoryRetention::default()).unwrap();
+            db.set_account(sender, AccountState::new(10_000_000));
+            let deploy = TxPayload::DeployContract {
+                code: wat::parse_str(TOTAL_CONTRACT).unwrap(),
+            };
+            let txs = [
+                signed_payload(&sk, &deploy, 0),
+                contract_call(&sk, contract, 500, vec![], 100_000, 1),
+            ];
+            let receipts = db
+                .apply_verified_block(&txs, &SignatureVerifier::default(), &fee_context(0), &mut TestModules::default().at(1))
+                .receipts;
+            assert!(receipts.iter().all(|r| r.success));
+            let root = db.compute_state_root();
+
+            // The code and storage are part of the state root.
+            let mut without = StateDB::new();
+            for pubkey in [sender, contract, treasury_account(), developer_pool_account()] {
+                if let Some(account) = db.get_account(&pubkey) {
+                    without.set_account(pubkey, account);
+                }
+            }
+            assert_eq!(without.account_count(), db.account_count());
+            assert_ne!(without.compute_state_root(), root);
+
+            db.commit(1).unwrap();
+            root
+        };
+
+        // A restarted node has a fresh runtime, which compiles the stored
+        // code when the contract is next called.
+        let mut db = StateDB::open(backend, HistoryRetention::default()).unwrap();
+        assert_eq!(db.compute_state_root(), root);
+        assert_eq!(db.contract(&contract).unwrap().deployer, sender);
+        let receipts = db
+            .apply_verified_block(
+                &[contract_call(&sk, contract, 200, vec![], 100_000, 2)],
+                &SignatureVerifier::default(),
+                &fee_context(0),
+                &mut TestModules::default().at(2),
+            )
+            .receipts;
+        assert_eq!(
+            receipts[0].outcome,
+            Some(TxOutcome::ContractCalled {
+                contract,
+                output: 700u64.to_le_bytes().to_vec()
+            })
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_stake_survives_restart() {
+        use ed25519_dalek::SigningKey;
+        use rand::rngs::OsRng;
+        use trv1_staking::LockTier;
+
+        let (backend, dir) = temp_backend("modules");
+        let sk = SigningKey::generate(&mut OsRng);
+        let sender = sk.verifying_key().to_bytes();
+        let stake = TxPayload::Stake { amount: 10_000, tier: LockTier::NoLock };
+        let unstake = TxPayload::Unstake { amount: 10_000 };
+        let root = {
+            let mut db = StateDB::open(backend.clone(), HistoryRetention::default()).unwrap();
+            db.set_account(sender, AccountState::new(1_000_000));
+            let mut modules = TestModules::default();
+            let receipts = db
+                .apply_verified_block(&[signed_payload(&sk, &stake, 0)], &SignatureVerifier::default(), &fee_context(0), &mut modules.at(1))
+                .receipts;
+            assert!(receipts[0].success);
+            let root = db.compute_state_root();
+            db.set_module_state("staking", &modules.staking);
+            db.set_module_state("validators", &modules.validators);
+            // The module state is part of the state root.
+            assert_ne!(db.compute_state_root(), root);
+            db.commit(1).unwrap();
+            db.compute_state_root()
+        };
+
+        // A restarted node picks up the pool it committed, so the stake
+        // can still be unstaked.
+        let mut db = StateDB::open(backend, HistoryRetention::default()).unwrap();
+        assert_eq!(db.compute_state_root(), root);
+        let mut modules = TestModules {
+            staking: db.module_state("staking").unwrap().unwrap(),
+            validators: db.module_state("validators").unwrap().unwrap(),
+            ..TestModules::default()
+        };
+        assert_eq!(modules.staking.stake_of(&sender), 10_000);
+        let receipts = db
+            .apply_verified_block(&[signed_payload(&sk, &unstake, 1)], &SignatureVerifier::default(), &fee_context(0), &mut modules.at(2))
+            .receipts;
+        assert_eq!(receipts[0].outcome, Some(TxOutcome::Unstaked { amount: 10_000 }));
+        assert_eq!(db.get_account(&staking_account()).unwrap().balance, 0);
+        let fees = 6 * (payload_gas(&stake) + payload_gas(&unstake));
+        assert_eq!(db.get_account(&sender).unwrap().balance, 1_000_000 - fees);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_account_at_past_heights() {
+        let mut db = StateDB::new().with_history_retention(HistoryRetention::Recent(2));
+        assert_eq!(db.history_range(), None);
+        assert!(db.account_at(&alice(), 0).is_err());
+
+        db.set_account(alice(), AccountState::new(1000));
+        db.commit(0).unwrap();
+        for height in 1..=3 {
+            db.apply_transfer(&alice(), &bob(), 100, height - 1).unwrap();
+            db.commit(height).unwrap();
+        }
+        // Uncommitted changes are not visible at any height.
+        db.apply_transfer(&alice(), &charlie(), 100, 3).unwrap();
+
+        assert_eq!(db.history_range(), Some(1..=3));
+        assert_eq!(
+            db.account_at(&alice(), 1).unwrap(),
+            Some(AccountState { balance: 900, nonce: 1 })
+        );
+        assert_eq!(db.account_at(&bob(), 1).unwrap(), Some(AccountState::new(100)));
+        assert_eq!(db.account_at(&bob(), 3).unwrap(), Some(AccountState::new(300)));
+        assert_eq!(db.account_at(&charlie(), 3).unwrap(), None);
+        assert_eq!(
+            db.account_at(&alice(), 0).unwrap_err(),
+            StateError::HistoryUnavailable { height: 0 }
+        );
+        assert!(db.account_at(&alice(), 4).is_err());
+    }
+
+    #[test]
+    fn test_archive_keeps_every_height() {
+        let mut db = StateDB::new().with_history_retention(HistoryRetention::Archive);
+        db.set_account(alice(), AccountState::new(1000));
+        db.commit(0).unwrap();
+        for height in 1..=50 {
+            db.apply_transfer(&alice(), &bob(), 1, height - 1).unwrap();
+            db.commit(height).unwrap();
+        }
+        assert_eq!(db.history_range(), Some(0..=50));
+        assert_eq!(db.account_at(&alice(), 0).unwrap(), Some(AccountState::new(1000)));
+        assert_eq!(db.account_at(&bob(), 0).unwrap(), None);
+        assert_eq!(db.account_at(&bob(), 25).unwrap(), Some(AccountState::new(25)));
+
+        let mut db = db.with_history_retention(HistoryRetention::Recent(0));
+        assert_eq!(db.history_range(), Some(50..=50));
+        db.commit(51).unwrap();
+        assert_eq!(db.history_range(), Some(51..=51));
+    }
+
+    #[test]
+    fn test_reopen_restores_history() {
+        let (backend, dir) = temp_backend("history");
+        {
+            let mut db = StateDB::open(backend.clone(), HistoryRetention::Recent(5)).unwrap();
+            db.set_account(alice(), AccountState::new(1000));
+            db.commit(0).unwrap();
+            for height in 1..=10 {
+                db.apply_transfer(&alice(), &bob(), 10, height - 1).unwrap();
+                db.commit(height).unwrap();
+            }
+        }
+
+        let db = StateDB::open(backend.clone(), HistoryRetention::Recent(5)).unwrap();
+        assert_eq!(db.history_range(), Some(5..=10));
+        assert_eq!(db.account_at(&bob(), 5).unwrap(), Some(AccountState::new(50)));
+        assert_eq!(
+            db.account_at(&alice(), 7).unwrap(),
+            Some(AccountState { balance: 930, nonce: 7 })
+        );
+
+        // Reopening with a shorter window keeps only what it covers.
+        let db = StateDB::open(backend, HistoryRetention::Recent(2)).unwrap();
+        assert_eq!(db.history_range(), Some(8..=10));
+        assert_eq!(db.account_at(&bob(), 8).unwrap(), Some(AccountState::new(80)));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_load_nonexistent_file() {
         let result = StateDB::load_from_file("/tmp/trv1_definitely_does_not_exist.json");
@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use trv1_staking::LockTier;
+
+use crate::fees::BlockFees;
+
 /// Errors that can occur during state transitions.
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
 pub enum StateError {
@@ -13,6 +17,9 @@ pub enum StateError {
     #[error("account not found")]
     AccountNotFound,
 
+    #[error("invalid transaction signature")]
+    InvalidSignature,
+
     #[error("arithmetic overflow")]
     Overflow,
 
@@ -21,6 +28,45 @@ pub enum StateError {
 
     #[error("JSON error: {0}")]
     Json(String),
+
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    #[error("max fee per gas {max_fee} is below the base fee {base_fee}")]
+    FeeTooLow { max_fee: u64, base_fee: u64 },
+
+    #[error("gas limit {limit} is below the {required} gas required")]
+    GasLimitTooLow { limit: u64, required: u64 },
+
+    #[error("out of gas: used all of the {limit} gas limit")]
+    OutOfGas { limit: u64 },
+
+    #[error("gas limit {limit} exceeds the {remaining} gas left in the block")]
+    BlockGasExhausted { limit: u64, remaining: u64 },
+
+    #[error("state at height {height} is not retained")]
+    HistoryUnavailable { height: u64 },
+
+    #[error("invalid payload: {0}")]
+    InvalidPayload(String),
+
+    #[error("{0} transactions cannot be executed without the runtime modules")]
+    UnsupportedPayload(&'static str),
+
+    #[error("staking error: {0}")]
+    Staking(String),
+
+    #[error("validator set error: {0}")]
+    ValidatorSet(String),
+
+    #[error("slashing error: {0}")]
+    Slashing(String),
+
+    #[error("rewards error: {0}")]
+    Rewards(String),
+
+    #[error("contract error: {0}")]
+    Contract(String),
 }
 
 /// Receipt produced after executing a transaction.
@@ -29,7 +75,47 @@ pub struct TransactionReceipt {
     pub tx_hash: [u8; 32],
     pub success: bool,
     pub fee_paid: u64,
+    /// Gas the transaction used, and paid for if fees were charged.
+    #[serde(default)]
+    pub gas_used: u64,
     pub error: Option<String>,
+    /// What the transaction did, if it succeeded.
+    #[serde(default)]
+    pub outcome: Option<TxOutcome>,
+}
+
+/// The effect of a successful transaction, by payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxOutcome {
+    Transferred { to: [u8; 32], amount: u64 },
+    Staked { amount: u64, tier: LockTier },
+    Unstaked { amount: u64 },
+    Delegated {
+        validator: [u8; 32],
+        amount: u64,
+        tier: LockTier,
+    },
+    Undelegated { validator: [u8; 32], amount: u64 },
+    /// The sender joins the validator set at the start of `epoch`.
+    ValidatorRegistered { epoch: u64 },
+    /// The sender's metadata changed. Its commission changes to the new
+    /// rate at the start of `commission_epoch`, if a rate was given.
+    ValidatorEdited { commission_epoch: Option<u64> },
+    Unjailed,
+    ContractDeployed { address: [u8; 32] },
+    /// The contract ran and returned `output`.
+    ContractCalled { contract: [u8; 32], output: Vec<u8> },
+    /// The evidence was queued for the slashing engine under `evidence_hash`.
+    EvidenceSubmitted { evidence_hash: [u8; 32] },
+}
+
+/// Receipts, fee totals and gas used from executing a block.
+#[derive(Debug, Clone, Default)]
+pub struct BlockOutcome {
+    pub receipts: Vec<TransactionReceipt>,
+    pub fees: BlockFees,
+    /// Gas used by all of the block's transactions.
+    pub gas_used: u64,
 }
 
 #[cfg(test)]
@@ -57,11 +143,14 @@ mod tests {
             tx_hash: [0xab; 32],
             success: true,
             fee_paid: 0,
+            gas_used: 21_000,
             error: None,
+            outcome: Some(TxOutcome::Unjailed),
         };
         let json = serde_json::to_string(&receipt).unwrap();
         let deserialized: TransactionReceipt = serde_json::from_str(&json).unwrap();
-        assert_eq!(deserialized.success, true);
+        assert!(deserialized.success);
         assert_eq!(deserialized.tx_hash, [0xab; 32]);
+        assert_eq!(deserialized.outcome, Some(TxOutcome::Unjailed));
     }
 }
@@ -4,6 +4,9 @@ use std::num::NonZeroUsize;
 
 use crate::types::{StorageKey, StorageValue};
 
+/// Callback invoked with each entry evicted from the hot tier.
+type EvictionCallback = Box<dyn Fn(StorageKey, StorageValue) + Send + Sync>;
+
 /// RAM-based LRU cache — the "hot" tier.
 ///
 /// Thread-safe via `parking_lot::RwLock`. Entries that exceed the capacity
@@ -13,7 +16,7 @@ pub struct HotCache {
     inner: RwLock<LruCache<Vec<u8>, StorageValue>>,
     capacity: usize,
     /// Called when an entry is evicted to make room for new data.
-    eviction_callback: RwLock<Option<Box<dyn Fn(StorageKey, StorageValue) + Send + Sync>>>,
+    eviction_callback: RwLock<Option<EvictionCallback>>,
 }
 
 impl HotCache {
@@ -1,6 +1,7 @@
+use parking_lot::Mutex;
 use sha2::{Digest, Sha256};
-use std::fs;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
 use crate::types::{StorageKey, StorageValue};
@@ -11,6 +12,9 @@ use crate::types::{StorageKey, StorageValue};
 /// Values are stored as raw bytes on disk.
 pub struct WarmStore {
     base_dir: PathBuf,
+    /// Files and directories changed by unsynced writes since the last
+    /// `sync`.
+    unsynced: Mutex<Vec<PathBuf>>,
 }
 
 impl WarmStore {
@@ -18,7 +22,10 @@ impl WarmStore {
     pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
         let base_dir = path.as_ref().to_path_buf();
         fs::create_dir_all(&base_dir)?;
-        Ok(Self { base_dir })
+        Ok(Self {
+            base_dir,
+            unsynced: Mutex::new(Vec::new()),
+        })
     }
 
     /// Retrieve a value by key from disk.
@@ -32,9 +39,82 @@ impl WarmStore {
     }
 
     /// Write a key-value pair to disk.
+    ///
+    /// The value is written to a temporary file, synced, and renamed over
+    /// the old one, so a crash leaves either the old value or the new one.
     pub fn put(&self, key: &StorageKey, value: &StorageValue) -> io::Result<()> {
+        self.write(key, value, true)
+    }
+
+    /// Like `put`, but without waiting for the value to reach the disk: it
+    /// is durable once `sync` next returns.
+    pub fn put_unsynced(&self, key: &StorageKey, value: &StorageValue) -> io::Result<()> {
+        self.write(key, value, false)
+    }
+
+    fn write(&self, key: &StorageKey, value: &StorageValue, sync: bool) -> io::Result<()> {
         let path = self.key_path(key);
-        fs::write(&path, &value.0)
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&value.0)?;
+        if sync {
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, &path)?;
+        if !sync {
+            let mut unsynced = self.unsynced.lock();
+            unsynced.push(path.parent().expect("keys live in a shard directory").to_path_buf());
+            unsynced.push(path);
+        }
+        Ok(())
+    }
+
+    /// Like `remove`, but the removal is durable once `sync` next returns.
+    pub fn remove_unsynced(&self, key: &StorageKey) -> io::Result<Option<StorageValue>> {
+        let removed = self.remove(key)?;
+        if removed.is_some() {
+            let path = self.key_path(key);
+            self.unsynced
+                .lock()
+                .push(path.parent().expect("keys live in a shard directory").to_path_buf());
+        }
+        Ok(removed)
+    }
+
+    /// Wait until every unsynced write and removal so far is on disk.
+    ///
+    /// On Linux this is one `syncfs` of the store's filesystem, however
+    /// many keys were written; elsewhere each changed file and directory
+    /// is synced in turn.
+    pub fn sync(&self) -> io::Result<()> {
+        let mut unsynced = self.unsynced.lock();
+        if unsynced.is_empty() {
+            return Ok(());
+        }
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::fd::AsRawFd;
+            let dir = File::open(&self.base_dir)?;
+            // SAFETY: `dir` is an open descriptor for the call's duration.
+            if unsafe { libc::syncfs(dir.as_raw_fd()) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            unsynced.sort();
+            unsynced.dedup();
+            for path in unsynced.iter() {
+                match File::open(path) {
+                    Ok(file) => file.sync_all()?,
+                    // Removed again after it was written.
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        unsynced.clear();
+        Ok(())
     }
 
     /// Remove a key from disk, returning the old value if it existed.
@@ -150,6 +230,25 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[test]
+    fn unsynced_writes_visible_before_sync() {
+        let dir = temp_dir("unsynced");
+        cleanup(&dir);
+
+        let store = WarmStore::new(&dir).unwrap();
+        let key = StorageKey::from("k");
+        store.put_unsynced(&key, &StorageValue::from("v")).unwrap();
+        assert_eq!(store.get(&key).unwrap(), Some(StorageValue::from("v")));
+        store.sync().unwrap();
+        assert!(store.unsynced.lock().is_empty());
+
+        assert_eq!(store.remove_unsynced(&key).unwrap(), Some(StorageValue::from("v")));
+        store.sync().unwrap();
+        assert_eq!(store.get(&key).unwrap(), None);
+
+        cleanup(&dir);
+    }
+
     #[test]
     fn contains_check() {
         let dir = temp_dir("contains");
@@ -6,6 +6,10 @@ use crate::lru_cache::HotCache;
 use crate::nvme::WarmStore;
 use crate::types::*;
 
+/// Warm-tier key holding the batch being applied by `write_batch`, until
+/// every operation in it has been written.
+const BATCH_LOG_KEY: &str = "__trv1_write_batch__";
+
 /// Tiered storage — orchestrates Hot (LRU), Warm (NVMe), and Cold (archive) tiers.
 ///
 /// Read path: hot -> warm -> cold, with promotion back to hot on access.
@@ -37,12 +41,70 @@ impl TieredStorage {
             stats_for_cb.write().evictions += 1;
         });
 
-        Ok(Self {
+        let storage = Self {
             hot,
             warm,
             cold,
             stats,
-        })
+        };
+        storage.recover_batch()?;
+        Ok(storage)
+    }
+
+    /// Apply every operation in `batch` atomically with respect to crashes.
+    ///
+    /// The batch is first written as a single record to the warm tier,
+    /// then applied key by key, then the record is removed. If the node
+    /// dies partway through, `TieredStorage::new` finds the record and
+    /// applies the batch again.
+    ///
+    /// Only the record is synced on its own. The operations are written
+    /// without waiting for the disk and synced together before the record
+    /// is removed, so a batch costs two syncs however many keys it has.
+    pub fn write_batch(&self, batch: &WriteBatch) -> StorageResult<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let record = bincode::serialize(batch)
+            .map_err(|e| StorageError::Serialization(e.to_string()))?;
+        let log_key = StorageKey::from(BATCH_LOG_KEY);
+        self.warm.put(&log_key, &StorageValue(record))?;
+        self.apply_batch(batch)?;
+        self.warm.remove(&log_key)?;
+        Ok(())
+    }
+
+    /// Apply `batch`'s operations and wait until they are on disk.
+    fn apply_batch(&self, batch: &WriteBatch) -> StorageResult<()> {
+        for (key, value) in batch.ops() {
+            match value {
+                Some(value) => {
+                    self.warm.put_unsynced(key, value)?;
+                    self.hot.put(key.clone(), value.clone());
+                }
+                None => {
+                    self.hot.remove(key);
+                    self.warm.remove_unsynced(key)?;
+                    self.cold.remove(key)?;
+                }
+            }
+        }
+        self.warm.sync()?;
+        Ok(())
+    }
+
+    /// Finish a `write_batch` interrupted by a crash, if there was one.
+    fn recover_batch(&self) -> StorageResult<()> {
+        let log_key = StorageKey::from(BATCH_LOG_KEY);
+        let Some(record) = self.warm.get(&log_key)? else {
+            return Ok(());
+        };
+        let batch: WriteBatch = bincode::deserialize(record.as_bytes())
+            .map_err(|e| StorageError::Serialization(e.to_string()))?;
+        tracing::warn!(ops = batch.len(), "replaying interrupted write batch");
+        self.apply_batch(&batch)?;
+        self.warm.remove(&log_key)?;
+        Ok(())
     }
 
     /// Get a value, checking hot -> warm -> cold. Promotes to hot on access.
@@ -294,6 +356,50 @@ mod tests {
         cleanup(&warm, &cold);
     }
 
+    #[test]
+    fn write_batch_applies_puts_and_deletes() {
+        let (cfg, warm, cold) = test_config("batch");
+        let ts = TieredStorage::new(&cfg).unwrap();
+        ts.put(StorageKey::from("gone"), StorageValue::from("x")).unwrap();
+
+        let mut batch = WriteBatch::new();
+        batch.put(StorageKey::from("a"), StorageValue::from("1"));
+        batch.put(StorageKey::from("b"), StorageValue::from("2"));
+        batch.delete(StorageKey::from("gone"));
+        ts.write_batch(&batch).unwrap();
+
+        assert_eq!(ts.get(&StorageKey::from("a")).unwrap(), Some(StorageValue::from("1")));
+        assert_eq!(ts.get(&StorageKey::from("b")).unwrap(), Some(StorageValue::from("2")));
+        assert_eq!(ts.get(&StorageKey::from("gone")).unwrap(), None);
+        assert!(!ts.warm.contains(&StorageKey::from(BATCH_LOG_KEY)));
+
+        cleanup(&warm, &cold);
+    }
+
+    #[test]
+    fn interrupted_batch_replayed_on_open() {
+        let (cfg, warm, cold) = test_config("batch_recover");
+        {
+            let ts = TieredStorage::new(&cfg).unwrap();
+            ts.put(StorageKey::from("a"), StorageValue::from("old")).unwrap();
+
+            // Simulate a crash after the batch record was written but before
+            // any of its operations were applied.
+            let mut batch = WriteBatch::new();
+            batch.put(StorageKey::from("a"), StorageValue::from("new"));
+            batch.put(StorageKey::from("b"), StorageValue::from("2"));
+            let record = bincode::serialize(&batch).unwrap();
+            ts.warm.put(&StorageKey::from(BATCH_LOG_KEY), &StorageValue(record)).unwrap();
+        }
+
+        let ts = TieredStorage::new(&cfg).unwrap();
+        assert_eq!(ts.get(&StorageKey::from("a")).unwrap(), Some(StorageValue::from("new")));
+        assert_eq!(ts.get(&StorageKey::from("b")).unwrap(), Some(StorageValue::from("2")));
+        assert!(!ts.warm.contains(&StorageKey::from(BATCH_LOG_KEY)));
+
+        cleanup(&warm, &cold);
+    }
+
     #[test]
     fn stats_tracking() {
         let (cfg, warm, cold) = test_config("stats");
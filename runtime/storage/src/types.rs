@@ -79,6 +79,43 @@ impl From<&str> for StorageValue {
     }
 }
 
+/// A set of puts and deletes applied together by
+/// `TieredStorage::write_batch`: after a crash, either all of them have
+/// taken effect or none has.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteBatch {
+    ops: Vec<(StorageKey, Option<StorageValue>)>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a write of `value` under `key`.
+    pub fn put(&mut self, key: StorageKey, value: StorageValue) {
+        self.ops.push((key, Some(value)));
+    }
+
+    /// Queue a removal of `key`.
+    pub fn delete(&mut self, key: StorageKey) {
+        self.ops.push((key, None));
+    }
+
+    /// The queued operations, in order. `None` marks a delete.
+    pub fn ops(&self) -> &[(StorageKey, Option<StorageValue>)] {
+        &self.ops
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
 /// Configuration for the tiered storage system.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StorageConfig {
@@ -45,12 +45,8 @@ impl ValidatorSetManager {
             return Err(ValidatorSetError::AlreadyRegistered(pubkey));
         }
 
-        if initial_stake < self.state.config.min_stake {
-            return Err(ValidatorSetError::InsufficientStake {
-                have: initial_stake,
-                need: self.state.config.min_stake,
-            });
-        }
+        self.check_stake(initial_stake)?;
+        self.check_commission(commission_rate)?;
 
         let active_count = self.active_count();
         let status = if active_count < self.state.config.active_set_cap {
@@ -66,12 +62,94 @@ impl ValidatorSetManager {
             status,
             performance_score: 10_000, // Start at perfect score.
             join_height: current_height,
+            metadata: ValidatorMetadata::default(),
+            jailed_epoch: None,
         };
 
         self.state.validators.insert(pubkey, info);
         Ok(status)
     }
 
+    /// Check that `queue_registration` would accept a registration,
+    /// without queueing it.
+    pub fn check_registration(
+        &self,
+        pubkey: &PublicKey,
+        stake: u64,
+        commission_rate: u16,
+    ) -> ValidatorSetResult<()> {
+        if self.state.validators.contains_key(pubkey)
+            || self.state.pending_registrations.contains_key(pubkey)
+        {
+            return Err(ValidatorSetError::AlreadyRegistered(*pubkey));
+        }
+        self.check_stake(stake)?;
+        self.check_commission(commission_rate)
+    }
+
+    /// Queue a validator registration to take effect at the next epoch
+    /// rotation, checked now against the stake the validator will hold.
+    /// Returns the epoch the validator joins in.
+    pub fn queue_registration(
+        &mut self,
+        pubkey: PublicKey,
+        stake: u64,
+        commission_rate: u16,
+        current_height: u64,
+    ) -> ValidatorSetResult<u64> {
+        self.check_registration(&pubkey, stake, commission_rate)?;
+
+        self.state.pending_registrations.insert(
+            pubkey,
+            PendingRegistration {
+                commission_rate,
+                height: current_height,
+            },
+        );
+        Ok(self.state.current_epoch + 1)
+    }
+
+    /// Queue a change to a validator's commission rate, taking effect at
+    /// the next epoch rotation. Returns the epoch it takes effect in.
+    pub fn set_commission(&mut self, pubkey: &PublicKey, commission_rate: u16) -> ValidatorSetResult<u64> {
+        if !self.state.validators.contains_key(pubkey) {
+            return Err(ValidatorSetError::NotFound(*pubkey));
+        }
+        self.check_commission(commission_rate)?;
+        self.state.pending_commissions.insert(*pubkey, commission_rate);
+        Ok(self.state.current_epoch + 1)
+    }
+
+    /// Replace a validator's metadata.
+    pub fn set_metadata(&mut self, pubkey: &PublicKey, metadata: ValidatorMetadata) -> ValidatorSetResult<()> {
+        metadata.validate()?;
+        let validator = self
+            .state
+            .validators
+            .get_mut(pubkey)
+            .ok_or(ValidatorSetError::NotFound(*pubkey))?;
+        validator.metadata = metadata;
+        Ok(())
+    }
+
+    fn check_stake(&self, stake: u64) -> ValidatorSetResult<()> {
+        if stake < self.state.config.min_stake {
+            return Err(ValidatorSetError::InsufficientStake {
+                have: stake,
+                need: self.state.config.min_stake,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_commission(&self, rate: u16) -> ValidatorSetResult<()> {
+        let max = self.state.config.max_commission_rate;
+        if rate > max {
+            return Err(ValidatorSetError::CommissionTooHigh { rate, max });
+        }
+        Ok(())
+    }
+
     /// Deregister a validator, removing them from the set entirely.
     pub fn deregister_validator(&mut self, pubkey: &PublicKey) -> ValidatorSetResult<ValidatorInfo> {
         self.state
@@ -133,12 +211,28 @@ impl ValidatorSetManager {
             .collect()
     }
 
-    /// Trigger epoch rotation: swap standby validators in if they have higher
+    /// Trigger epoch rotation: apply queued commission changes and
+    /// registrations, then swap standby validators in if they have higher
     /// effective stake than the weakest active validators.
+    ///
+    /// A queued validator joins with its direct stake in `staking_pool`,
+    /// and is dropped if that has fallen below the minimum.
     pub fn epoch_rotation(
         &mut self,
         staking_pool: &StakingPool,
     ) -> Vec<(PublicKey, PublicKey)> {
+        for (pubkey, rate) in std::mem::take(&mut self.state.pending_commissions) {
+            if let Some(validator) = self.state.validators.get_mut(&pubkey) {
+                validator.commission_rate = rate;
+            }
+        }
+        for (pubkey, pending) in std::mem::take(&mut self.state.pending_registrations) {
+            let stake = staking_pool.stake_of(&pubkey);
+            if let Err(e) = self.register_validator(pubkey, stake, pending.commission_rate, pending.height) {
+                tracing::warn!(pubkey = ?pubkey, error = %e, "dropped queued validator registration");
+            }
+        }
+
         let swaps = rotation::rotate(
             &mut self.state.validators,
             staking_pool,
@@ -153,7 +247,7 @@ impl ValidatorSetManager {
         self.state
             .validators
             .get(pubkey)
-            .map_or(false, |v| v.status == ValidatorStatus::Active)
+            .is_some_and(|v| v.status == ValidatorStatus::Active)
     }
 
     /// Get information about a specific validator.
@@ -168,14 +262,35 @@ impl ValidatorSetManager {
 
     /// Jail a validator (move to Jailed status).
     pub fn jail(&mut self, pubkey: &PublicKey) -> ValidatorSetResult<()> {
-        rotation::jail(&mut self.state.validators, pubkey)
+        rotation::jail(&mut self.state.validators, pubkey)?;
+        if let Some(validator) = self.state.validators.get_mut(pubkey) {
+            validator.jailed_epoch = Some(self.state.current_epoch);
+        }
+        Ok(())
     }
 
-    /// Unjail a validator (move from Jailed to Standby).
+    /// Unjail a validator (move from Jailed to Standby). Fails until
+    /// `min_jail_epochs` epoch rotations have passed since it was jailed.
     pub fn unjail(&mut self, pubkey: &PublicKey) -> ValidatorSetResult<()> {
+        let validator = self
+            .state
+            .validators
+            .get(pubkey)
+            .ok_or(ValidatorSetError::NotFound(*pubkey))?;
+        if let (ValidatorStatus::Jailed, Some(jailed)) = (validator.status, validator.jailed_epoch) {
+            let until_epoch = jailed.saturating_add(self.state.config.min_jail_epochs);
+            if self.state.current_epoch < until_epoch {
+                return Err(ValidatorSetError::StillJailed { until_epoch });
+            }
+        }
         rotation::unjail(&mut self.state.validators, pubkey)
     }
 
+    /// Registrations waiting for the next epoch rotation.
+    pub fn pending_registrations(&self) -> &std::collections::BTreeMap<PublicKey, PendingRegistration> {
+        &self.state.pending_registrations
+    }
+
     /// Deterministic proposer selection for a given block height.
     ///
     /// Uses weighted round-robin: validators are assigned contiguous ranges
@@ -239,12 +354,12 @@ impl ValidatorSetManager {
     }
 
     /// Access the full validators map (for slashing module).
-    pub fn validators(&self) -> &std::collections::HashMap<PublicKey, ValidatorInfo> {
+    pub fn validators(&self) -> &std::collections::BTreeMap<PublicKey, ValidatorInfo> {
         &self.state.validators
     }
 
     /// Mutable access to the validators map (for slashing module).
-    pub fn validators_mut(&mut self) -> &mut std::collections::HashMap<PublicKey, ValidatorInfo> {
+    pub fn validators_mut(&mut self) -> &mut std::collections::BTreeMap<PublicKey, ValidatorInfo> {
         &mut self.state.validators
     }
 }
@@ -275,6 +390,7 @@ mod tests {
             active_set_cap: cap,
             epoch_length: 100,
             min_stake: 100,
+            ..Default::default()
         });
 
         for &(n, amount, tier) in entries {
@@ -452,6 +568,14 @@ mod tests {
             ValidatorStatus::Jailed
         );
 
+        // The jail period is two epoch rotations.
+        let pool = StakingPool::new();
+        manager.epoch_rotation(&pool);
+        assert_eq!(
+            manager.unjail(&pubkey(1)),
+            Err(ValidatorSetError::StillJailed { until_epoch: 2 })
+        );
+        manager.epoch_rotation(&pool);
         manager.unjail(&pubkey(1)).unwrap();
         assert_eq!(
             manager.get_validator(&pubkey(1)).unwrap().status,
@@ -459,6 +583,90 @@ mod tests {
         );
     }
 
+    #[test]
+    fn queued_registration_joins_at_next_rotation() {
+        let mut manager = ValidatorSetManager::with_config(ValidatorSetConfig {
+            min_stake: 100,
+            ..Default::default()
+        });
+        let mut pool = StakingPool::new();
+        pool.stake(pubkey(1), 1000, LockTier::Delegator).unwrap();
+
+        assert_eq!(manager.queue_registration(pubkey(1), 1000, 500, 7), Ok(1));
+        assert_eq!(
+            manager.queue_registration(pubkey(1), 1000, 500, 7),
+            Err(ValidatorSetError::AlreadyRegistered(pubkey(1)))
+        );
+        assert!(manager.get_validator(&pubkey(1)).is_none());
+
+        manager.epoch_rotation(&pool);
+        let validator = manager.get_validator(&pubkey(1)).unwrap();
+        assert_eq!(validator.status, ValidatorStatus::Active);
+        assert_eq!(validator.stake, 1000);
+        assert_eq!(validator.join_height, 7);
+        assert!(manager.pending_registrations().is_empty());
+    }
+
+    #[test]
+    fn queued_registration_dropped_below_min_stake() {
+        let mut manager = ValidatorSetManager::with_config(ValidatorSetConfig {
+            min_stake: 100,
+            ..Default::default()
+        });
+        manager.queue_registration(pubkey(1), 1000, 500, 0).unwrap();
+
+        // The stake was withdrawn before the rotation.
+        manager.epoch_rotation(&StakingPool::new());
+        assert!(manager.get_validator(&pubkey(1)).is_none());
+    }
+
+    #[test]
+    fn commission_bounded_and_applied_at_rotation() {
+        let mut manager = ValidatorSetManager::with_config(ValidatorSetConfig {
+            min_stake: 100,
+            max_commission_rate: 2_000,
+            ..Default::default()
+        });
+        assert_eq!(
+            manager.register_validator(pubkey(1), 1000, 2_001, 0),
+            Err(ValidatorSetError::CommissionTooHigh { rate: 2_001, max: 2_000 })
+        );
+        manager.register_validator(pubkey(1), 1000, 500, 0).unwrap();
+
+        assert_eq!(manager.set_commission(&pubkey(1), 1_500), Ok(1));
+        assert!(manager.set_commission(&pubkey(1), 5_000).is_err());
+        assert_eq!(manager.get_validator(&pubkey(1)).unwrap().commission_rate, 500);
+
+        manager.epoch_rotation(&StakingPool::new());
+        assert_eq!(manager.get_validator(&pubkey(1)).unwrap().commission_rate, 1_500);
+    }
+
+    #[test]
+    fn metadata_length_checked() {
+        let mut manager = ValidatorSetManager::with_config(ValidatorSetConfig {
+            min_stake: 100,
+            ..Default::default()
+        });
+        manager.register_validator(pubkey(1), 1000, 500, 0).unwrap();
+
+        let metadata = ValidatorMetadata {
+            moniker: "node-1".into(),
+            website: "https://example.org".into(),
+            details: String::new(),
+        };
+        manager.set_metadata(&pubkey(1), metadata.clone()).unwrap();
+        assert_eq!(manager.get_validator(&pubkey(1)).unwrap().metadata, metadata);
+
+        let too_long = ValidatorMetadata {
+            moniker: "x".repeat(MAX_MONIKER_LEN + 1),
+            ..metadata
+        };
+        assert_eq!(
+            manager.set_metadata(&pubkey(1), too_long),
+            Err(ValidatorSetError::MetadataTooLong { field: "moniker", max: MAX_MONIKER_LEN })
+        );
+    }
+
     #[test]
     fn get_active_set_sorted_by_stake() {
         let (pool, manager) = setup_pool_and_manager(
@@ -41,7 +41,7 @@ pub fn sort_by_stake(
 /// If any standby validator has higher effective stake than the lowest-ranked
 /// active validator, swap them. Returns a list of (promoted, demoted) pairs.
 pub fn rotate(
-    validators: &mut std::collections::HashMap<PublicKey, ValidatorInfo>,
+    validators: &mut std::collections::BTreeMap<PublicKey, ValidatorInfo>,
     staking_pool: &StakingPool,
     active_cap: usize,
 ) -> Vec<(PublicKey, PublicKey)> {
@@ -117,7 +117,7 @@ pub fn rotate(
 
 /// Jail a validator — move them to Jailed status regardless of current status.
 pub fn jail(
-    validators: &mut std::collections::HashMap<PublicKey, ValidatorInfo>,
+    validators: &mut std::collections::BTreeMap<PublicKey, ValidatorInfo>,
     pubkey: &PublicKey,
 ) -> ValidatorSetResult<()> {
     let validator = validators
@@ -130,7 +130,7 @@ pub fn jail(
 
 /// Unjail a validator — move them from Jailed back to Standby.
 pub fn unjail(
-    validators: &mut std::collections::HashMap<PublicKey, ValidatorInfo>,
+    validators: &mut std::collections::BTreeMap<PublicKey, ValidatorInfo>,
     pubkey: &PublicKey,
 ) -> ValidatorSetResult<()> {
     let validator = validators
@@ -148,7 +148,7 @@ pub fn unjail(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
     use trv1_staking::LockTier;
 
     fn pubkey(n: u8) -> PublicKey {
@@ -165,6 +165,8 @@ mod tests {
             status,
             performance_score: 10_000,
             join_height: 0,
+            metadata: ValidatorMetadata::default(),
+            jailed_epoch: None,
         }
     }
 
@@ -204,7 +206,7 @@ mod tests {
             (3, 500, LockTier::Delegator),  // standby, strongest
         ]);
 
-        let mut validators: HashMap<PublicKey, ValidatorInfo> = HashMap::new();
+        let mut validators: BTreeMap<PublicKey, ValidatorInfo> = BTreeMap::new();
         validators.insert(pubkey(1), make_validator(1, 100, ValidatorStatus::Active));
         validators.insert(pubkey(2), make_validator(2, 200, ValidatorStatus::Active));
         validators.insert(pubkey(3), make_validator(3, 500, ValidatorStatus::Standby));
@@ -227,7 +229,7 @@ mod tests {
             (3, 100, LockTier::Delegator),
         ]);
 
-        let mut validators: HashMap<PublicKey, ValidatorInfo> = HashMap::new();
+        let mut validators: BTreeMap<PublicKey, ValidatorInfo> = BTreeMap::new();
         validators.insert(pubkey(1), make_validator(1, 500, ValidatorStatus::Active));
         validators.insert(pubkey(2), make_validator(2, 300, ValidatorStatus::Active));
         validators.insert(pubkey(3), make_validator(3, 100, ValidatorStatus::Standby));
@@ -243,7 +245,7 @@ mod tests {
             (2, 300, LockTier::Delegator),
         ]);
 
-        let mut validators: HashMap<PublicKey, ValidatorInfo> = HashMap::new();
+        let mut validators: BTreeMap<PublicKey, ValidatorInfo> = BTreeMap::new();
         validators.insert(pubkey(1), make_validator(1, 500, ValidatorStatus::Active));
         validators.insert(pubkey(2), make_validator(2, 300, ValidatorStatus::Standby));
 
@@ -255,7 +257,7 @@ mod tests {
 
     #[test]
     fn jail_and_unjail() {
-        let mut validators: HashMap<PublicKey, ValidatorInfo> = HashMap::new();
+        let mut validators: BTreeMap<PublicKey, ValidatorInfo> = BTreeMap::new();
         validators.insert(pubkey(1), make_validator(1, 100, ValidatorStatus::Active));
 
         jail(&mut validators, &pubkey(1)).unwrap();
@@ -267,7 +269,7 @@ mod tests {
 
     #[test]
     fn unjail_non_jailed_fails() {
-        let mut validators: HashMap<PublicKey, ValidatorInfo> = HashMap::new();
+        let mut validators: BTreeMap<PublicKey, ValidatorInfo> = BTreeMap::new();
         validators.insert(pubkey(1), make_validator(1, 100, ValidatorStatus::Active));
 
         let result = unjail(&mut validators, &pubkey(1));
@@ -276,7 +278,7 @@ mod tests {
 
     #[test]
     fn jail_not_found() {
-        let mut validators: HashMap<PublicKey, ValidatorInfo> = HashMap::new();
+        let mut validators: BTreeMap<PublicKey, ValidatorInfo> = BTreeMap::new();
         let result = jail(&mut validators, &pubkey(99));
         assert_eq!(result, Err(ValidatorSetError::NotFound(pubkey(99))));
     }
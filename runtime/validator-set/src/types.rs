@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 /// A 32-byte compressed Ed25519 public key used as validator identity.
 pub type PublicKey = [u8; 32];
@@ -30,6 +30,53 @@ pub struct ValidatorInfo {
     pub performance_score: u16,
     /// Block height at which this validator joined.
     pub join_height: u64,
+    /// Self-description shown to delegators.
+    #[serde(default)]
+    pub metadata: ValidatorMetadata,
+    /// Epoch in which the validator was last jailed.
+    #[serde(default)]
+    pub jailed_epoch: Option<u64>,
+}
+
+/// Longest allowed `ValidatorMetadata::moniker`, in bytes.
+pub const MAX_MONIKER_LEN: usize = 70;
+/// Longest allowed `ValidatorMetadata::website`, in bytes.
+pub const MAX_WEBSITE_LEN: usize = 140;
+/// Longest allowed `ValidatorMetadata::details`, in bytes.
+pub const MAX_DETAILS_LEN: usize = 280;
+
+/// A validator's self-description.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorMetadata {
+    /// Display name.
+    pub moniker: String,
+    pub website: String,
+    /// Free-form description.
+    pub details: String,
+}
+
+impl ValidatorMetadata {
+    /// Check every field is within its length limit.
+    pub fn validate(&self) -> ValidatorSetResult<()> {
+        for (field, value, max) in [
+            ("moniker", &self.moniker, MAX_MONIKER_LEN),
+            ("website", &self.website, MAX_WEBSITE_LEN),
+            ("details", &self.details, MAX_DETAILS_LEN),
+        ] {
+            if value.len() > max {
+                return Err(ValidatorSetError::MetadataTooLong { field, max });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A registration waiting for the next epoch rotation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingRegistration {
+    pub commission_rate: u16,
+    /// Block height the registration was submitted at.
+    pub height: u64,
 }
 
 impl ValidatorInfo {
@@ -49,6 +96,10 @@ pub struct ValidatorSetConfig {
     pub epoch_length: u64,
     /// Minimum stake required to register as a validator.
     pub min_stake: u64,
+    /// Highest commission rate a validator may charge, in basis points.
+    pub max_commission_rate: u16,
+    /// Epoch rotations a jailed validator must wait before it may unjail.
+    pub min_jail_epochs: u64,
 }
 
 impl Default for ValidatorSetConfig {
@@ -57,19 +108,30 @@ impl Default for ValidatorSetConfig {
             active_set_cap: 200,
             epoch_length: 100,
             min_stake: 1_000_000,
+            max_commission_rate: 10_000,
+            min_jail_epochs: 2,
         }
     }
 }
 
 /// The complete state of the validator set.
+///
+/// Ordered maps keep iteration, and so the serialized state, identical on
+/// every node.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ValidatorSetState {
     /// All registered validators keyed by public key.
-    pub validators: HashMap<PublicKey, ValidatorInfo>,
+    pub validators: BTreeMap<PublicKey, ValidatorInfo>,
     /// Configuration.
     pub config: ValidatorSetConfig,
     /// Current epoch number.
     pub current_epoch: u64,
+    /// Registrations that join the set at the next epoch rotation.
+    #[serde(default)]
+    pub pending_registrations: BTreeMap<PublicKey, PendingRegistration>,
+    /// Commission changes that take effect at the next epoch rotation.
+    #[serde(default)]
+    pub pending_commissions: BTreeMap<PublicKey, u16>,
 }
 
 
@@ -91,6 +153,15 @@ pub enum ValidatorSetError {
     #[error("validator is not jailed")]
     NotJailed,
 
+    #[error("validator is jailed until epoch {until_epoch}")]
+    StillJailed { until_epoch: u64 },
+
+    #[error("commission rate {rate} exceeds the maximum of {max}")]
+    CommissionTooHigh { rate: u16, max: u16 },
+
+    #[error("{field} is longer than {max} bytes")]
+    MetadataTooLong { field: &'static str, max: usize },
+
     #[error("validator is already active")]
     AlreadyActive,
 
@@ -122,6 +193,8 @@ mod tests {
             status: ValidatorStatus::Active,
             performance_score: 10_000,
             join_height: 0,
+            metadata: ValidatorMetadata::default(),
+            jailed_epoch: None,
         };
         // 1x vote weight
         assert_eq!(info.effective_stake(1000), 1_000_000);
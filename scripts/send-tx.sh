@@ -3,7 +3,8 @@
 # send-tx.sh -- Send a test transaction to a TRv1 node via JSON-RPC.
 #
 # Usage:
-#   ./scripts/send-tx.sh [rpc_port] [from_hex] [to_hex] [amount] [nonce]
+#   ./scripts/send-tx.sh [rpc_port] [from_hex] [to_hex] [amount] [nonce] \
+#       [gas_limit] [max_fee_per_gas] [max_priority_fee_per_gas]
 #
 # Arguments:
 #   rpc_port  RPC port of the target node (default: 9944)
@@ -11,6 +12,11 @@
 #   to_hex    Recipient public key, 64 hex characters (default: 32 0x02 bytes)
 #   amount    Transfer amount (default: 100)
 #   nonce     Sender nonce (default: 0)
+#   gas_limit                 Gas limit (default: 21000, a plain transfer)
+#   max_fee_per_gas           Most paid per unit of gas, base fee included
+#                             (default: 10)
+#   max_priority_fee_per_gas  Most of that paid to the proposer as a tip
+#                             (default: 1)
 #
 # The signature and data fields are set to dummy values.
 # For real usage, transactions should be properly signed with ed25519.
@@ -22,6 +28,9 @@ FROM="${2:-$(printf '%064d' 1)}"
 TO="${3:-$(printf '02%.0s' {1..32})}"
 AMOUNT="${4:-100}"
 NONCE="${5:-0}"
+GAS_LIMIT="${6:-21000}"
+MAX_FEE="${7:-10}"
+PRIORITY_FEE="${8:-1}"
 
 # Dummy 64-byte signature (128 hex chars)
 SIGNATURE=$(printf '00%.0s' {1..128})
@@ -34,6 +43,7 @@ echo "  From   : ${FROM}"
 echo "  To     : ${TO}"
 echo "  Amount : ${AMOUNT}"
 echo "  Nonce  : ${NONCE}"
+echo "  Gas    : ${GAS_LIMIT} (max fee ${MAX_FEE}, tip ${PRIORITY_FEE} per gas)"
 echo ""
 
 RESPONSE=$(curl -s -X POST "${RPC_URL}" \
@@ -47,6 +57,9 @@ RESPONSE=$(curl -s -X POST "${RPC_URL}" \
             \"to\": \"${TO}\",
             \"amount\": ${AMOUNT},
             \"nonce\": ${NONCE},
+            \"gas_limit\": ${GAS_LIMIT},
+            \"max_fee_per_gas\": ${MAX_FEE},
+            \"max_priority_fee_per_gas\": ${PRIORITY_FEE},
             \"signature\": \"${SIGNATURE}\",
             \"data\": \"${DATA}\"
         }]
@@ -27,6 +27,9 @@ fn make_signed_tx(
         nonce,
         signature: vec![],
         data: vec![],
+        gas_limit: 21_000,
+        max_fee_per_gas: 10,
+        max_priority_fee_per_gas: 1,
     };
     tx.sign(signing_key);
     tx
@@ -138,13 +141,21 @@ fn transaction_different_keys_produce_different_hashes() {
 // Mempool tests
 // ---------------------------------------------------------------------------
 
+/// Helper: a state where `sk` can pay for a transfer and its fees.
+fn funded_state(sk: &SigningKey) -> StateDB {
+    let mut state = StateDB::new();
+    state.set_account(sk.verifying_key().to_bytes(), AccountState::new(1_000_000));
+    state
+}
+
 #[test]
 fn mempool_accepts_valid_signed_transaction() {
     let mut pool = TransactionPool::new(MempoolConfig::default());
     let sk = SigningKey::generate(&mut OsRng);
+    let state = funded_state(&sk);
     let tx = make_signed_tx(&sk, [2u8; 32], 100, 0);
 
-    pool.add_transaction(tx).expect("valid tx should be accepted");
+    pool.add_transaction(tx, &state).expect("valid tx should be accepted");
     assert_eq!(pool.pending_count(), 1);
 }
 
@@ -152,10 +163,11 @@ fn mempool_accepts_valid_signed_transaction() {
 fn mempool_rejects_duplicate_transaction() {
     let mut pool = TransactionPool::new(MempoolConfig::default());
     let sk = SigningKey::generate(&mut OsRng);
+    let state = funded_state(&sk);
     let tx = make_signed_tx(&sk, [2u8; 32], 100, 0);
 
-    pool.add_transaction(tx.clone()).expect("first should succeed");
-    let result = pool.add_transaction(tx);
+    pool.add_transaction(tx.clone(), &state).expect("first should succeed");
+    let result = pool.add_transaction(tx, &state);
     assert!(result.is_err(), "duplicate should be rejected");
     assert_eq!(pool.pending_count(), 1);
 }
@@ -163,11 +175,13 @@ fn mempool_rejects_duplicate_transaction() {
 #[test]
 fn mempool_multiple_senders() {
     let mut pool = TransactionPool::new(MempoolConfig::default());
+    let mut state = StateDB::new();
 
     for _ in 0..5 {
         let sk = SigningKey::generate(&mut OsRng);
+        state.set_account(sk.verifying_key().to_bytes(), AccountState::new(1_000_000));
         let tx = make_signed_tx(&sk, [2u8; 32], 50, 0);
-        pool.add_transaction(tx).expect("should accept from different senders");
+        pool.add_transaction(tx, &state).expect("should accept from different senders");
     }
 
     assert_eq!(pool.pending_count(), 5);
@@ -1,33 +1,49 @@
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use clap::Parser;
-use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use libp2p::identity::Keypair;
-use libp2p::Multiaddr;
+use libp2p::{Multiaddr, PeerId};
 use tokio::signal;
 use tokio::sync::mpsc;
 
 use trv1_bft::block::{Block, BlockHeader, Transaction};
+use trv1_bft::SignatureVerifier;
 use trv1_bft::{
-    BftStateMachine, BlockHash, ConsensusMessage, Height, Proposal, Round, TimeoutConfig,
-    TimeoutEvent, TimeoutStep, ValidatorId, Vote, VoteType,
+    BftStateMachine, BlockHash, CompactBlock, ConsensusMessage, DuplicateVoteEvidence, Height,
+    Proposal, Round, TimeoutConfig, TimeoutEvent, TimeoutStep, ValidatorId, Vote, VoteType,
 };
-use trv1_fees::{FeeConfig, FeeMarket};
+use trv1_contracts::ContractRuntime;
+use trv1_fees::{FeeConfig, FeeMarket, FeeSplit};
 use trv1_genesis::GenesisConfig;
+use trv1_mempool::{Journal, MempoolError};
 
+use trv1_net::codec::NetworkMessage;
 use trv1_net::network::NetworkConfig;
-use trv1_net::{ConsensusNetwork, NetworkHandle};
+use trv1_net::peer::PeerPolicy;
+use trv1_net::{ConsensusNetwork, NetworkHandle, TxFetcher};
 use trv1_rewards::DeveloperRewards;
 use trv1_rpc::server::{RpcServer, RpcState};
 use trv1_rpc::types::{BlockResponse, ValidatorResponse};
 use trv1_slashing::SlashingEngine;
 use trv1_staking::StakingPool;
-use trv1_state::{AccountState, StateDB};
+use trv1_state::{
+    developer_pool_account, evidence_record, staking_account, AccountState, FeeContext, GasSchedule, HistoryRetention, Modules,
+    StateDB, StateError, StorageBackend,
+};
 use trv1_storage::{StorageConfig, TieredStorage};
 use trv1_validator_set::{ValidatorSetConfig, ValidatorSetManager};
 
+/// Most gossiped transactions admitted to the mempool in one batch.
+const GOSSIP_BATCH_SIZE: usize = 256;
+
+/// Names under which the staking pool and validator set are kept in state.
+const STAKING_MODULE: &str = "staking";
+const VALIDATOR_SET_MODULE: &str = "validators";
+
 /// TRv1 Validator Node
 #[derive(Parser)]
 #[command(name = "trv1-validator", version, about = "TRv1 validator node")]
@@ -40,9 +56,11 @@ struct Args {
     #[arg(long, default_value = "/tmp/trv1-data")]
     data_dir: PathBuf,
 
-    /// P2P listen address (libp2p multiaddr format)
-    #[arg(long, default_value = "/ip4/0.0.0.0/tcp/30333")]
-    listen: String,
+    /// Comma-separated P2P listen addresses (libp2p multiaddr format).
+    /// QUIC addresses (`/ip4/0.0.0.0/udp/30333/quic-v1`) need the `quic`
+    /// feature; list one next to a TCP address to listen on both.
+    #[arg(long, value_delimiter = ',', default_value = "/ip4/0.0.0.0/tcp/30333")]
+    listen: Vec<String>,
 
     /// JSON-RPC server port
     #[arg(long, default_value = "9944")]
@@ -56,6 +74,42 @@ struct Args {
     /// Comma-separated list of peer multiaddrs to dial on startup.
     #[arg(long, value_delimiter = ',')]
     peers: Vec<String>,
+
+    /// Comma-separated peer multiaddrs, each ending in `/p2p/<peer id>`,
+    /// to stay connected to and redial when the connection drops.
+    #[arg(long, value_delimiter = ',')]
+    persistent_peers: Vec<String>,
+
+    /// Comma-separated peer IDs never shared with other peers. Sentries
+    /// list the validator they protect here.
+    #[arg(long, value_delimiter = ',')]
+    private_peer_ids: Vec<String>,
+
+    /// Comma-separated peer IDs exempt from the peer limit and bans.
+    #[arg(long, value_delimiter = ',')]
+    unconditional_peer_ids: Vec<String>,
+
+    /// Only accept connections from persistent and unconditional peers.
+    /// Validators behind sentries should set this.
+    #[arg(long)]
+    allow_list_only: bool,
+
+    /// Maximum number of connected peers, not counting unconditional ones.
+    #[arg(long, default_value = "50")]
+    max_peers: usize,
+
+    /// Seconds a transaction may wait in the mempool before it expires.
+    #[arg(long, default_value = "10800")]
+    mempool_tx_ttl: u64,
+
+    /// Number of past blocks whose account state stays queryable over RPC.
+    #[arg(long, default_value = "1024")]
+    state_history: u64,
+
+    /// Keep account state at every height (archive node). Overrides
+    /// `--state-history`.
+    #[arg(long)]
+    archive: bool,
 }
 
 /// Format a byte slice as a hex string.
@@ -71,6 +125,41 @@ fn now_secs() -> u64 {
         .as_secs()
 }
 
+/// Get the current Unix timestamp in milliseconds.
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Build the peer policy from the command-line peer options.
+fn peer_policy(args: &Args) -> Result<PeerPolicy, Box<dyn std::error::Error>> {
+    fn peer_ids(list: &[String]) -> Result<HashSet<PeerId>, Box<dyn std::error::Error>> {
+        let mut ids = HashSet::new();
+        for s in list.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            ids.insert(s.parse().map_err(|e| format!("invalid peer id {s}: {e}"))?);
+        }
+        Ok(ids)
+    }
+
+    let mut persistent_peers = Vec::new();
+    for s in args.persistent_peers.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let addr: Multiaddr = s.parse().map_err(|e| format!("invalid address {s}: {e}"))?;
+        if trv1_net::peer::peer_id_of_addr(&addr).is_none() {
+            return Err(format!("persistent peer {s} must end in /p2p/<peer id>").into());
+        }
+        persistent_peers.push(addr);
+    }
+    Ok(PeerPolicy {
+        persistent_peers,
+        private_peer_ids: peer_ids(&args.private_peer_ids)?,
+        unconditional_peer_ids: peer_ids(&args.unconditional_peer_ids)?,
+        allow_list_only: args.allow_list_only,
+        max_peers: args.max_peers,
+    })
+}
+
 /// Load an ed25519 signing key from a hex-encoded file.
 fn load_signing_key(path: &PathBuf) -> Result<SigningKey, Box<dyn std::error::Error>> {
     let contents = std::fs::read_to_string(path)?;
@@ -118,23 +207,7 @@ fn sign_proposal(
     block_hash: BlockHash,
     signing_key: &SigningKey,
 ) -> Proposal {
-    let proposer = ValidatorId(signing_key.verifying_key());
-    let msg = format!(
-        "propose:{}:{}:{}",
-        height.0,
-        round.0,
-        to_hex(&block_hash.0)
-    );
-    let sig = signing_key.sign(msg.as_bytes());
-
-    Proposal {
-        height,
-        round,
-        block_hash,
-        proposer,
-        signature: sig,
-        valid_round: None,
-    }
+    Proposal::new(height, round, block_hash, None, signing_key)
 }
 
 /// Sign a vote (prevote or precommit).
@@ -183,7 +256,10 @@ fn process_bft_output(
             ConsensusMessage::CommitBlock { .. } => {
                 to_broadcast.push(msg);
             }
-            ConsensusMessage::ProposeBlock { .. } => {
+            ConsensusMessage::ProposeBlock { .. } | ConsensusMessage::ProposeCompact { .. } => {
+                to_broadcast.push(msg);
+            }
+            ConsensusMessage::SubmitEvidence(_) => {
                 to_broadcast.push(msg);
             }
         }
@@ -237,13 +313,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         archive_path: cold_path.to_string_lossy().into_owned(),
         max_ram_bytes: 512 * 1024 * 1024,
     };
-    let storage = TieredStorage::new(&storage_config)?;
+    let storage = Arc::new(TieredStorage::new(&storage_config)?);
     tracing::info!("tiered storage initialized");
 
     // --- Initialize economics ---
-    let mut staking_pool = StakingPool::new();
     let fee_market = FeeMarket::new(FeeConfig::default(), genesis.chain_params.base_fee_floor)?;
     let developer_rewards = DeveloperRewards::new();
+    let contract_runtime = ContractRuntime::new();
 
     tracing::info!(
         base_fee = fee_market.current_base_fee(),
@@ -251,47 +327,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     // --- Initialize validator set ---
-    let validator_set_config = ValidatorSetConfig {
-        active_set_cap: genesis.chain_params.max_validators,
-        epoch_length: genesis.chain_params.epoch_length,
-        min_stake: 1_000_000,
-    };
-    let mut validator_set = ValidatorSetManager::with_config(validator_set_config);
-
-    // Register genesis validators into the staking pool and validator set.
-    for gv in &genesis.validators {
-        if let Err(e) =
-            staking_pool.stake(gv.pubkey, gv.initial_stake, trv1_staking::LockTier::Delegator)
-        {
-            tracing::warn!(
-                pubkey = %to_hex(&gv.pubkey),
-                error = %e,
-                "failed to stake genesis validator"
-            );
-        }
-        match validator_set.register_validator(
-            gv.pubkey,
-            gv.initial_stake,
-            gv.commission_rate,
-            0,
-        ) {
-            Ok(status) => {
-                tracing::info!(
-                    pubkey = %to_hex(&gv.pubkey),
-                    ?status,
-                    stake = gv.initial_stake,
-                    "registered genesis validator"
-                );
-            }
-            Err(e) => {
-                tracing::warn!(
-                    pubkey = %to_hex(&gv.pubkey),
-                    error = %e,
-                    "failed to register genesis validator"
-                );
-            }
-        }
-    }
+    let (mut staking_pool, mut validator_set) = genesis_modules(&genesis);
 
     // --- Initialize slashing ---
     let slashing_engine = SlashingEngine::new();
@@ -313,53 +349,110 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Channel for tx gossip: RPC submissions → event loop → P2P broadcast
     let (tx_gossip_tx, mut tx_gossip_rx) = mpsc::channel::<Transaction>(256);
 
+    let mempool_config = trv1_mempool::MempoolConfig {
+        tx_ttl: std::time::Duration::from_secs(args.mempool_tx_ttl),
+        ..trv1_mempool::MempoolConfig::default()
+    };
+    // One verifier for the network, mempool and block execution, so each
+    // transaction's signature is checked once.
+    let sig_verifier = Arc::new(SignatureVerifier::default());
+
+    let mut mempool_expiry = tokio::time::interval(mempool_config.expiry_interval);
+    mempool_expiry.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
     let rpc_state = Arc::new(
         RpcState::new(
-            Arc::new(parking_lot::RwLock::new(trv1_mempool::TransactionPool::new(
-                trv1_mempool::MempoolConfig::default(),
-            ))),
+            Arc::new(parking_lot::RwLock::new(
+                trv1_mempool::TransactionPool::new(mempool_config)
+                    .with_signature_verifier(sig_verifier.clone()),
+            )),
             Arc::new(parking_lot::RwLock::new(StateDB::new())),
             genesis_validators,
         )
         .with_tx_gossip(tx_gossip_tx),
     );
 
-    // Try to load persisted state, otherwise populate from genesis accounts
+    // Open the state committed to storage. A node without committed state
+    // imports a `state.json` left by an older version, or starts from the
+    // genesis accounts.
     let state_file = args.data_dir.join("state.json");
-    if state_file.exists() {
-        match StateDB::load_from_file(&state_file) {
-            Ok(loaded) => {
-                let mut db = rpc_state.state_db.write();
-                *db = loaded;
+    let mut modules_restored = false;
+    {
+        let backend = Arc::new(StorageBackend::new(storage.clone()));
+        let retention = if args.archive {
+            HistoryRetention::Archive
+        } else {
+            HistoryRetention::Recent(args.state_history)
+        };
+        let mut db = StateDB::open(backend, retention)?;
+        match db.committed_height() {
+            Some(height) => {
                 tracing::info!(
+                    height,
                     accounts = db.account_count(),
                     total_supply = db.total_supply(),
-                    "state database restored from {}", state_file.display()
+                    "state database restored from storage"
                 );
+                // The staking pool and validator set commit with each
+                // block; state committed by an older version has neither.
+                let restored = db.module_state(STAKING_MODULE).and_then(|pool| {
+                    Ok(pool.zip(db.module_state(VALIDATOR_SET_MODULE)?))
+                });
+                match restored {
+                    Ok(Some((pool, validators))) => {
+                        staking_pool = pool;
+                        validator_set = validators;
+                        modules_restored = true;
+                        tracing::info!(
+                            total_staked = staking_pool.total_staked(),
+                            validators = validator_set.validators().len(),
+                            "staking and validator set restored from storage"
+                        );
+                    }
+                    Ok(None) => {
+                        tracing::warn!("committed state has no staking or validator set, using genesis");
+                    }
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to restore staking and validator set");
+                        std::process::exit(1);
+                    }
+                }
             }
-            Err(e) => {
-                tracing::warn!(error = %e, "failed to load state file, falling back to genesis");
-                let mut db = rpc_state.state_db.write();
-                for acct in &genesis.accounts {
-                    db.set_account(acct.pubkey, AccountState::new(acct.balance));
+            None => {
+                let imported = if state_file.exists() {
+                    match StateDB::load_from_file(&state_file) {
+                        Ok(loaded) => Some(loaded),
+                        Err(e) => {
+                            tracing::warn!(error = %e, "failed to load state file, falling back to genesis");
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                match imported {
+                    Some(loaded) => {
+                        for (pubkey, account) in loaded.accounts() {
+                            db.set_account(*pubkey, account.clone());
+                        }
+                        tracing::info!(
+                            accounts = db.account_count(),
+                            total_supply = db.total_supply(),
+                            "state database imported from {}", state_file.display()
+                        );
+                    }
+                    None => {
+                        genesis_state(&mut db, &genesis);
+                        tracing::info!(
+                            accounts = db.account_count(),
+                            total_supply = db.total_supply(),
+                            "state database initialized from genesis"
+                        );
+                    }
                 }
-                tracing::info!(
-                    accounts = db.account_count(),
-                    total_supply = db.total_supply(),
-                    "state database initialized from genesis"
-                );
             }
         }
-    } else {
-        let mut db = rpc_state.state_db.write();
-        for acct in &genesis.accounts {
-            db.set_account(acct.pubkey, AccountState::new(acct.balance));
-        }
-        tracing::info!(
-            accounts = db.account_count(),
-            total_supply = db.total_supply(),
-            "state database initialized from genesis"
-        );
+        *rpc_state.state_db.write() = db;
     }
 
     tracing::info!("transaction mempool initialized");
@@ -385,15 +478,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     // --- Initialize BFT consensus ---
-    let bft_validators: Vec<ValidatorId> = genesis
-        .validators
-        .iter()
-        .filter_map(|gv| {
-            VerifyingKey::from_bytes(&gv.pubkey)
-                .ok()
-                .map(ValidatorId)
-        })
-        .collect();
+    // A restarted node resumes with the active set as of its last commit.
+    let bft_validators: Vec<ValidatorId> = if modules_restored {
+        active_validators(&validator_set, &staking_pool)
+    } else {
+        genesis
+            .validators
+            .iter()
+            .filter_map(|gv| {
+                VerifyingKey::from_bytes(&gv.pubkey)
+                    .ok()
+                    .map(ValidatorId)
+            })
+            .collect()
+    };
 
     // Find our index in the validator set
     let our_validator_index: Option<usize> = signing_key.as_ref().and_then(|sk| {
@@ -407,9 +505,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         tracing::warn!("signing key loaded but not found in genesis validator set -- observer mode");
     }
 
+    // Resume consensus at the block after the last one whose state was
+    // committed.
+    let committed_height = rpc_state.state_db.read().committed_height();
+    let start_height = Height(committed_height.map_or(0, |h| h + 1));
+
     let timeout_config = TimeoutConfig::default();
     let mut bft = BftStateMachine::new(
-        Height(0),
+        start_height,
         bft_validators.clone(),
         our_validator_index,
         timeout_config,
@@ -435,13 +538,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Keypair::generate_ed25519()
     };
 
-    let listen_addr: Multiaddr = args.listen.parse().unwrap_or_else(|e| {
-        tracing::error!(error = %format!("{e:?}"), "invalid listen address");
+    let listen_addrs: Vec<Multiaddr> = args
+        .listen
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse().unwrap_or_else(|e| {
+                tracing::error!(error = %format!("{e:?}"), addr = s, "invalid listen address");
+                std::process::exit(1);
+            })
+        })
+        .collect();
+    let Some(listen_addr) = listen_addrs.first().cloned() else {
+        tracing::error!("at least one listen address is required");
+        std::process::exit(1);
+    };
+
+    let peer_policy = peer_policy(&args).unwrap_or_else(|e| {
+        tracing::error!(error = %e, "invalid peer options");
         std::process::exit(1);
     });
+    if peer_policy.allow_list_only && peer_policy.persistent_peers.is_empty() {
+        tracing::warn!("allow-list-only mode without persistent peers; only unconditional peers can connect");
+    }
 
     let net_config = NetworkConfig {
         listen_address: listen_addr.clone(),
+        peer_policy,
+        sig_verifier: sig_verifier.clone(),
         ..NetworkConfig::default()
     };
 
@@ -456,10 +581,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         "P2P network created"
     );
 
+    // Only votes and proposals signed by the current validator set are
+    // accepted from gossip and re-propagated.
+    handle.set_validators(&bft_validators);
+
     runner.start(listen_addr).unwrap_or_else(|e| {
         tracing::error!(error = %e, "failed to start P2P listener");
         std::process::exit(1);
     });
+    for addr in listen_addrs.into_iter().skip(1) {
+        runner.listen(addr).unwrap_or_else(|e| {
+            tracing::error!(error = %e, "failed to start P2P listener");
+            std::process::exit(1);
+        });
+    }
 
     // Dial initial peers
     for peer_addr_str in &args.peers {
@@ -487,22 +622,73 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Extract the transaction receiver so we can poll it independently in select!
     let mut net_tx_rx = handle.take_tx_receiver();
 
+    // Compact proposals whose missing transactions had to be fetched from
+    // peers are fed back into the event loop as full proposals.
+    let tx_fetcher = handle.tx_fetcher();
+    let (rebuilt_tx, mut rebuilt_rx) = mpsc::channel::<NetworkMessage>(16);
+
     // --- Wrap remaining mutable state ---
     let fee_market = Arc::new(std::sync::RwLock::new(fee_market));
     let staking_pool = Arc::new(std::sync::RwLock::new(staking_pool));
     let validator_set = Arc::new(std::sync::RwLock::new(validator_set));
-    let _slashing_engine = Arc::new(std::sync::RwLock::new(slashing_engine));
+    let slashing_engine = Arc::new(std::sync::RwLock::new(slashing_engine));
     let developer_rewards = Arc::new(std::sync::RwLock::new(developer_rewards));
-    let _storage = Arc::new(storage);
+    let contract_runtime = Arc::new(std::sync::RwLock::new(contract_runtime));
 
-    // Track the last committed block hash
-    let mut last_block_hash = BlockHash::default();
+    // Build on the last committed block, whose hash is stored with its state.
+    let mut last_block_hash = BlockHash(rpc_state.state_db.read().committed_block_hash());
 
     // Set initial RPC state
     {
-        *rpc_state.current_height.write() = 0;
+        *rpc_state.current_height.write() = committed_height.unwrap_or(0);
         *rpc_state.validator_count.write() = bft_validators.len();
-        *rpc_state.base_fee.write() = fee_market.read().unwrap().current_base_fee();
+        let base_fee = fee_market.read().unwrap().current_base_fee();
+        *rpc_state.base_fee.write() = base_fee;
+        rpc_state.mempool.write().set_base_fee(base_fee);
+    }
+
+    // Restore locally submitted transactions from the mempool journal
+    let journal_path = args.data_dir.join("mempool.journal");
+    match Journal::open(&journal_path) {
+        Ok((journal, journaled)) => {
+            let (mut restored, mut dropped) = (0usize, 0usize);
+            {
+                let state_db = rpc_state.state_db.read();
+                let mut mempool = rpc_state.mempool.write();
+                for tx in journaled {
+                    match mempool.add_transaction(tx.clone(), &state_db) {
+                        Ok(()) => {
+                            restored += 1;
+                            // Re-announce to peers once the network is up
+                            if let Some(ref gossip_tx) = rpc_state.tx_gossip_tx {
+                                let _ = gossip_tx.try_send(tx);
+                            }
+                        }
+                        Err(e) => {
+                            dropped += 1;
+                            tracing::debug!(error = %e, "dropped journaled transaction");
+                        }
+                    }
+                }
+                mempool.set_journal(journal);
+                if let Err(e) = mempool.compact_journal() {
+                    tracing::warn!(error = %e, "failed to compact mempool journal");
+                }
+            }
+            tracing::info!(
+                restored,
+                dropped,
+                path = %journal_path.display(),
+                "mempool journal replayed"
+            );
+        }
+        Err(e) => {
+            tracing::warn!(
+                error = %e,
+                path = %journal_path.display(),
+                "failed to open mempool journal, local transactions will not persist"
+            );
+        }
     }
 
     let rpc_server = RpcServer::new(args.rpc_port, rpc_state.clone());
@@ -523,19 +709,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // If we are the proposer for round 0, build and broadcast a block
     if bft.is_proposer() {
         if let Some(ref sk) = signing_key {
-            let txs = rpc_state.mempool.read().get_pending_ordered(100);
+            let txs = rpc_state.mempool.read().get_pending_for_block(100, max_block_gas());
             let block = build_block(
-                Height(0),
+                start_height,
                 last_block_hash,
                 &ValidatorId(sk.verifying_key()),
                 txs,
                 &rpc_state.state_db.read(),
             );
             let block_hash = compute_block_hash(&block);
-            let proposal = sign_proposal(Height(0), Round(0), block_hash, sk);
+            let proposal = sign_proposal(start_height, Round(0), block_hash, sk);
 
             tracing::info!(
-                height = 0,
+                height = start_height.0,
                 block_hash = %to_hex(&block_hash.0),
                 txs = block.transactions.len(),
                 "proposing block"
@@ -544,13 +730,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Feed the proposal into our own BFT state machine
             bft.on_proposal(&proposal, Some(&block));
 
-            if let Err(e) = handle
-                .broadcast_message(&ConsensusMessage::ProposeBlock {
-                    proposal,
-                    block: Some(block),
-                })
-                .await
-            {
+            if let Err(e) = handle.broadcast_proposal(&proposal, &block).await {
                 tracing::warn!(error = %e, "failed to broadcast proposal");
             }
         }
@@ -565,6 +745,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // --- Main event loop ---
     tracing::info!("entering main event loop");
+    let mut halted = None;
 
     tokio::select! {
         // Run the RPC server.
@@ -575,12 +756,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        // Run the consensus + P2P event loop.
-        _ = async {
+        // Run the consensus + P2P event loop. It stops the node if a block's
+        // state cannot be committed.
+        result = async {
             loop {
                 tokio::select! {
                     // Receive messages from the P2P network.
-                    net_msg = handle.next_message() => {
+                    net_msg = next_inbound(&mut handle, &mut rebuilt_rx) => {
                         let Some(net_msg) = net_msg else {
                             tracing::warn!("network message channel closed");
                             break;
@@ -595,8 +777,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     has_block = block.is_some(),
                                     "received proposal"
                                 );
+                                if let Some(ref block) = block {
+                                    handle.cache_block(block);
+                                }
                                 bft.on_proposal(&proposal, block.as_ref())
                             }
+                            ConsensusMessage::ProposeCompact { proposal, block: compact } => {
+                                tracing::debug!(
+                                    height = proposal.height.0,
+                                    round = proposal.round.0,
+                                    proposer = %to_hex(proposal.proposer.as_bytes()),
+                                    txs = compact.tx_hashes.len(),
+                                    "received compact proposal"
+                                );
+                                let known =
+                                    rpc_state.mempool.read().get_transactions(&compact.tx_hashes);
+                                match compact.reconstruct(|h| known.get(h).cloned()) {
+                                    Ok(block) => {
+                                        handle.cache_block(&block);
+                                        bft.on_proposal(&proposal, Some(&block))
+                                    }
+                                    Err(missing) => {
+                                        tracing::debug!(
+                                            missing = missing.len(),
+                                            "fetching transactions missing from mempool"
+                                        );
+                                        let peers: Vec<PeerId> = [&net_msg.sender, &net_msg.relayer]
+                                            .into_iter()
+                                            .filter_map(|p| PeerId::from_bytes(p).ok())
+                                            .collect();
+                                        tokio::spawn(fetch_missing_transactions(
+                                            tx_fetcher.clone(),
+                                            sig_verifier.clone(),
+                                            rebuilt_tx.clone(),
+                                            peers,
+                                            net_msg.sender.clone(),
+                                            proposal,
+                                            compact,
+                                            known,
+                                        ));
+                                        vec![]
+                                    }
+                                }
+                            }
                             ConsensusMessage::CastVote(ref vote) => {
                                 match vote.vote_type {
                                     VoteType::Prevote => {
@@ -624,7 +847,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     "received commit block from network"
                                 );
                                 let committed_block = bft.get_committed_block(&block_hash).cloned();
-                                apply_commit(
+                                let next_validators = apply_commit(
                                     height,
                                     block_hash,
                                     committed_block.as_ref(),
@@ -634,9 +857,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     &genesis,
                                     &staking_pool,
                                     &developer_rewards,
+                                    &contract_runtime,
                                     &validator_set,
-                                );
+                                    &slashing_engine,
+                                )?;
 
+                                if let Some(validators) = next_validators {
+                                    set_validators(validators, &mut bft, &handle, signing_key.as_ref(), &rpc_state);
+                                }
                                 let next_height = Height(height.0 + 1);
                                 let advance_msgs = bft.advance_height(next_height);
                                 let inner_broadcasts = process_bft_output(
@@ -668,6 +896,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                                 vec![] // already handled
                             }
+                            ConsensusMessage::SubmitEvidence(ref evidence) => {
+                                record_evidence(evidence, &slashing_engine);
+                                vec![]
+                            }
                             ConsensusMessage::ScheduleTimeout(_) => {
                                 vec![]
                             }
@@ -691,7 +923,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     );
 
                                     let committed_block = bft.get_committed_block(block_hash).cloned();
-                                    apply_commit(
+                                    let next_validators = apply_commit(
                                         *height,
                                         *block_hash,
                                         committed_block.as_ref(),
@@ -701,9 +933,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                         &genesis,
                                         &staking_pool,
                                         &developer_rewards,
+                                        &contract_runtime,
                                         &validator_set,
-                                    );
+                                        &slashing_engine,
+                                    )?;
 
+                                    if let Some(validators) = next_validators {
+                                        set_validators(validators, &mut bft, &handle, signing_key.as_ref(), &rpc_state);
+                                    }
                                     let next_height = Height(height.0 + 1);
                                     let advance_msgs = bft.advance_height(next_height);
                                     let advance_broadcasts = process_bft_output(
@@ -733,6 +970,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                         }
                                     }
                                 }
+                                ConsensusMessage::SubmitEvidence(evidence) => {
+                                    record_evidence(evidence, &slashing_engine);
+                                    if let Err(e) = handle.broadcast_message(msg).await {
+                                        tracing::debug!(error = %e, "failed to broadcast evidence");
+                                    }
+                                }
                                 _ => {
                                     if let Err(e) = handle.broadcast_message(msg).await {
                                         tracing::debug!(error = %e, "failed to broadcast");
@@ -782,7 +1025,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             match msg {
                                 ConsensusMessage::CommitBlock { height, block_hash } => {
                                     let committed_block = bft.get_committed_block(block_hash).cloned();
-                                    apply_commit(
+                                    let next_validators = apply_commit(
                                         *height,
                                         *block_hash,
                                         committed_block.as_ref(),
@@ -792,9 +1035,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                         &genesis,
                                         &staking_pool,
                                         &developer_rewards,
+                                        &contract_runtime,
                                         &validator_set,
-                                    );
+                                        &slashing_engine,
+                                    )?;
 
+                                    if let Some(validators) = next_validators {
+                                        set_validators(validators, &mut bft, &handle, signing_key.as_ref(), &rpc_state);
+                                    }
                                     let next_height = Height(height.0 + 1);
                                     let advance_msgs = bft.advance_height(next_height);
                                     let advance_broadcasts = process_bft_output(
@@ -833,24 +1081,61 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     }
 
-                    // Receive gossiped transactions from other nodes.
+                    // Receive gossiped transactions from other nodes, taking
+                    // whatever else is queued so signatures are verified in
+                    // one batch.
                     Some(tx) = net_tx_rx.recv() => {
-                        tracing::debug!(
-                            from = %to_hex(&tx.from),
-                            to = %to_hex(&tx.to),
-                            amount = tx.amount,
-                            nonce = tx.nonce,
-                            "received gossiped transaction"
-                        );
-
-                        match rpc_state.mempool.write().add_transaction(tx) {
-                            Ok(_) => {
-                                tracing::debug!("gossiped transaction added to mempool");
+                        let mut txs = vec![tx];
+                        while txs.len() < GOSSIP_BATCH_SIZE {
+                            match net_tx_rx.try_recv() {
+                                Ok(tx) => txs.push(tx),
+                                Err(_) => break,
                             }
-                            Err(e) => {
-                                tracing::debug!(error = %e, "rejected gossiped transaction");
+                        }
+                        tracing::debug!(count = txs.len(), "received gossiped transactions");
+
+                        let tx_hashes: Vec<[u8; 32]> = txs.iter().map(Transaction::hash).collect();
+                        let (results, mut withdrawn) = {
+                            let state_db = rpc_state.state_db.read();
+                            let mut mempool = rpc_state.mempool.write();
+                            let results = mempool.add_transactions(txs, &state_db);
+                            (results, mempool.take_replaced())
+                        };
+                        for (tx_hash, result) in tx_hashes.into_iter().zip(results) {
+                            match result {
+                                Ok(_) => {
+                                    tracing::debug!(
+                                        tx_hash = %to_hex(&tx_hash),
+                                        "gossiped transaction added to mempool"
+                                    );
+                                }
+                                Err(e) => {
+                                    // A tx that lost a replace-by-fee race must not
+                                    // keep spreading from here.
+                                    if matches!(e, MempoolError::ReplacementUnderpriced { .. }) {
+                                        withdrawn.push(tx_hash);
+                                    }
+                                    tracing::debug!(
+                                        tx_hash = %to_hex(&tx_hash),
+                                        error = %e,
+                                        "rejected gossiped transaction"
+                                    );
+                                }
                             }
                         }
+                        withdraw_transactions(&handle, withdrawn).await;
+                    }
+
+                    // Drop transactions that have waited too long.
+                    _ = mempool_expiry.tick() => {
+                        let mut mempool = rpc_state.mempool.write();
+                        let expired = mempool.expire(now_millis());
+                        if !expired.is_empty() {
+                            tracing::info!(expired = expired.len(), "expired mempool transactions");
+                        }
+                        if let Err(e) = mempool.compact_journal() {
+                            tracing::warn!(error = %e, "failed to compact mempool journal");
+                        }
                     }
 
                     // Broadcast locally submitted transactions (from RPC) to P2P network.
@@ -858,10 +1143,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         if let Err(e) = handle.broadcast_transaction(&tx).await {
                             tracing::debug!(error = %e, "failed to gossip transaction to network");
                         }
+                        let replaced = rpc_state.mempool.write().take_replaced();
+                        withdraw_transactions(&handle, replaced).await;
                     }
                 }
             }
-        } => {}
+            Ok::<(), StateError>(())
+        } => {
+            if let Err(e) = result {
+                tracing::error!(error = %e, "halting: committed state could not be stored");
+                halted = Some(e);
+            }
+        }
 
         // Wait for shutdown signal (SIGINT/SIGTERM).
         _ = signal::ctrl_c() => {
@@ -869,20 +1162,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    // Persist state to disk before exiting
-    {
-        let db = rpc_state.state_db.read();
-        if let Err(e) = db.save_to_file(&state_file) {
-            tracing::error!(error = %e, "failed to save state to disk");
-        } else {
-            tracing::info!(
-                accounts = db.account_count(),
-                path = %state_file.display(),
-                "state saved to disk"
-            );
-        }
+    if let Err(e) = rpc_state.mempool.write().compact_journal() {
+        tracing::warn!(error = %e, "failed to compact mempool journal");
     }
 
+    // State is written to storage as each block commits; nothing is lost
+    // beyond the block in progress.
+    if let Some(height) = rpc_state.state_db.read().committed_height() {
+        tracing::info!(height, "state committed to storage");
+    }
+
+    if let Some(e) = halted {
+        return Err(e.into());
+    }
     tracing::info!("TRv1 Validator shutting down gracefully");
     Ok(())
 }
@@ -892,6 +1184,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// `committed_block` is the actual block from the BFT proposal cache. If available,
 /// we use its transactions instead of blindly pulling from the mempool — this ensures
 /// we execute exactly the transactions the proposer included.
+///
+/// At an epoch boundary, returns the rotated validator set, which takes over
+/// from the next height. Fails, with nothing after the state updated, if
+/// the block's state cannot be committed to storage; the node must then
+/// stop rather than finalize more blocks on state that is not on disk.
+#[allow(clippy::too_many_arguments)]
 fn apply_commit(
     height: Height,
     block_hash: BlockHash,
@@ -901,25 +1199,131 @@ fn apply_commit(
     last_block_hash: &mut BlockHash,
     genesis: &GenesisConfig,
     staking_pool: &Arc<std::sync::RwLock<StakingPool>>,
-    _developer_rewards: &Arc<std::sync::RwLock<DeveloperRewards>>,
+    developer_rewards: &Arc<std::sync::RwLock<DeveloperRewards>>,
+    contract_runtime: &Arc<std::sync::RwLock<ContractRuntime>>,
     validator_set: &Arc<std::sync::RwLock<ValidatorSetManager>>,
-) {
+    slashing_engine: &Arc<std::sync::RwLock<SlashingEngine>>,
+) -> Result<Option<Vec<ValidatorId>>, StateError> {
     // Use the committed block's transactions if available, else fall back to mempool
-    let (txs, proposer_hex) = match committed_block {
-        Some(block) => {
-            let proposer = to_hex(block.header.proposer.as_bytes());
-            (block.transactions.clone(), proposer)
-        }
+    let (txs, proposer) = match committed_block {
+        Some(block) => (block.transactions.clone(), Some(*block.header.proposer.as_bytes())),
         None => {
-            let txs = rpc_state.mempool.read().get_pending_ordered(100);
-            (txs, String::new())
+            let txs = rpc_state.mempool.read().get_pending_for_block(100, max_block_gas());
+            (txs, None)
         }
     };
+    let proposer_hex = proposer.map(|p| to_hex(&p)).unwrap_or_default();
 
-    let receipts = {
+    let epoch_length = genesis.chain_params.epoch_length;
+    let epoch_boundary = epoch_length > 0 && height.0 > 0 && height.0.is_multiple_of(epoch_length);
+    let fees = {
+        let fm = fee_market.read().unwrap();
+        FeeContext {
+            base_fee: fm.current_base_fee(),
+            epoch: height.0.checked_div(epoch_length).unwrap_or(0),
+            proposer,
+            split: FeeSplit::with_config(genesis.chain_params.fee_split_config())
+                .expect("fee split is checked when the genesis is validated"),
+            gas: GasSchedule::default(),
+            max_gas_per_block: fm.config().max_gas_per_block,
+        }
+    };
+
+    // Signatures checked on admission to the mempool are cached, so only
+    // transactions this node never saw before are verified here.
+    let verifier = rpc_state.mempool.read().signature_verifier().clone();
+    let (outcome, rotated) = {
         let mut db = rpc_state.state_db.write();
-        db.apply_block(&txs)
+        let mut staking = staking_pool.write().unwrap();
+        let mut validators = validator_set.write().unwrap();
+        let mut slashing = slashing_engine.write().unwrap();
+        let mut rewards = developer_rewards.write().unwrap();
+        let mut contracts = contract_runtime.write().unwrap();
+        let mut modules = Modules {
+            height: height.0,
+            epoch: fees.epoch,
+            staking: &mut staking,
+            validators: &mut validators,
+            slashing: &mut slashing,
+            rewards: &mut rewards,
+            contracts: &mut contracts,
+        };
+        let outcome = db.apply_verified_block(&txs, &verifier, &fees, &mut modules);
+
+        // Evidence submitted in this block or seen by consensus slashes and
+        // jails its offender before the block's state is committed.
+        for event in db.process_evidence(&mut modules) {
+            tracing::warn!(
+                offender = %to_hex(&event.offender),
+                offense = %event.offense,
+                amount = event.slash_amount,
+                height = height.0,
+                "validator slashed and jailed"
+            );
+        }
+        drop((staking, validators, slashing, contracts));
+
+        // The developer share of each successful call to a registered
+        // contract is owed to its deployer, and paid out of the developer
+        // pool at the end of each epoch.
+        for (contract, amount) in &outcome.fees.developer_shares {
+            if rewards.get_contract(contract).is_some() {
+                if let Err(e) = rewards.record_fee(*contract, *amount) {
+                    tracing::warn!(contract = %to_hex(contract), error = %e, "failed to record developer fee");
+                }
+            }
+        }
+        if epoch_boundary {
+            rewards.set_height(height.0);
+            for event in rewards.distribute_rewards() {
+                if let Err(e) = db.transfer(&developer_pool_account(), &event.developer, event.amount) {
+                    tracing::error!(
+                        developer = %to_hex(&event.developer),
+                        amount = event.amount,
+                        error = %e,
+                        "failed to pay developer reward"
+                    );
+                }
+            }
+        }
+        drop(rewards);
+
+        // The rotation, and the staking and validator set it leaves, commit
+        // with the block.
+        let staking = staking_pool.read().unwrap();
+        let mut validators = validator_set.write().unwrap();
+        let rotated = epoch_boundary.then(|| {
+            let epoch = height.0 / epoch_length;
+            tracing::info!(epoch, "epoch boundary reached");
+
+            let swaps = validators.epoch_rotation(&staking);
+            tracing::info!(
+                swaps = swaps.len(),
+                "validator set rotated"
+            );
+
+            let rewards = staking.distribute_epoch_rewards();
+            let total_rewards: u64 = rewards.iter().map(|(_, r)| r).sum();
+            tracing::info!(total_rewards, recipients = rewards.len(), "epoch rewards distributed");
+
+            let active = active_validators(&validators, &staking);
+            if active.is_empty() {
+                tracing::error!(epoch, "no active validators after rotation, keeping the current set");
+                return None;
+            }
+            Some(active)
+        });
+        db.set_module_state(STAKING_MODULE, &*staking);
+        db.set_module_state(VALIDATOR_SET_MODULE, &*validators);
+        drop((staking, validators));
+
+        if let Err(e) = db.commit_block(height.0, block_hash.0) {
+            tracing::error!(height = height.0, error = %e, "failed to commit state to storage");
+            return Err(e);
+        }
+        (outcome, rotated.flatten())
     };
+    let receipts = &outcome.receipts;
 
     let success_count = receipts.iter().filter(|r| r.success).count();
     let fail_count = receipts.len() - success_count;
@@ -930,26 +1334,53 @@ fn apply_commit(
         total_txs = txs.len(),
         success = success_count,
         failed = fail_count,
+        fees_paid = outcome.fees.total,
+        fees_burned = outcome.fees.burned,
+        gas_used = outcome.gas_used,
         "block committed"
     );
 
-    // Remove committed transactions from mempool
+    // Remove committed transactions from mempool and revalidate the rest
+    // against the new state
     let committed_hashes: Vec<[u8; 32]> = txs
         .iter()
         .map(|tx: &Transaction| tx.hash())
         .collect();
-    rpc_state.mempool.write().remove_committed(&committed_hashes);
+    let evicted = {
+        let state_db = rpc_state.state_db.read();
+        rpc_state
+            .mempool
+            .write()
+            .remove_committed(&committed_hashes, &state_db)
+    };
+    for eviction in &evicted {
+        tracing::debug!(
+            tx_hash = %to_hex(&eviction.tx_hash),
+            sender = %to_hex(&eviction.sender),
+            nonce = eviction.nonce,
+            reason = %eviction.reason,
+            "evicted transaction from mempool"
+        );
+    }
+    if !evicted.is_empty() {
+        tracing::info!(
+            height = height.0,
+            evicted = evicted.len(),
+            "mempool revalidated after commit"
+        );
+    }
 
-    // Update fee market
-    {
+    // Update fee market and re-prioritise the mempool for the new base fee
+    let base_fee = {
         let mut fm = fee_market.write().unwrap();
-        let gas_used = (txs.len() as u64) * 21_000;
-        fm.update_base_fee(gas_used);
-    }
+        fm.update_base_fee(outcome.gas_used);
+        fm.current_base_fee()
+    };
+    rpc_state.mempool.write().set_base_fee(base_fee);
 
     // Update RPC state
     *rpc_state.current_height.write() = height.0;
-    *rpc_state.base_fee.write() = fee_market.read().unwrap().current_base_fee();
+    *rpc_state.base_fee.write() = base_fee;
 
     // Store committed block for RPC queries
     rpc_state.block_store.write().push(BlockResponse {
@@ -958,31 +1389,219 @@ fn apply_commit(
         parent_hash: to_hex(&last_block_hash.0),
         proposer: proposer_hex,
         tx_count: txs.len(),
+        fees_paid: outcome.fees.total,
+        fees_burned: outcome.fees.burned,
         block_hash: to_hex(&block_hash.0),
     });
 
     // Update last block hash
     *last_block_hash = block_hash;
 
-    // Epoch handling
-    let epoch_length = genesis.chain_params.epoch_length;
-    if epoch_length > 0 && height.0 > 0 && height.0 % epoch_length == 0 {
-        let epoch = height.0 / epoch_length;
-        tracing::info!(epoch, "epoch boundary reached");
+    Ok(rotated)
+}
 
-        let pool = staking_pool.read().unwrap();
-        let swaps = validator_set.write().unwrap().epoch_rotation(&pool);
-        tracing::info!(
-            swaps = swaps.len(),
-            "validator set rotated"
-        );
+/// The consensus identities of `validators`' active set.
+fn active_validators(validators: &ValidatorSetManager, pool: &StakingPool) -> Vec<ValidatorId> {
+    validators
+        .get_active_set(pool)
+        .iter()
+        .filter_map(|v| VerifyingKey::from_bytes(&v.pubkey).ok().map(ValidatorId))
+        .collect()
+}
+
+/// The staking pool and validator set holding the genesis validators and
+/// their initial stakes.
+fn genesis_modules(genesis: &GenesisConfig) -> (StakingPool, ValidatorSetManager) {
+    let validator_set_config = ValidatorSetConfig {
+        active_set_cap: genesis.chain_params.max_validators,
+        epoch_length: genesis.chain_params.epoch_length,
+        min_stake: 1_000_000,
+        ..ValidatorSetConfig::default()
+    };
+    let mut staking_pool = StakingPool::new();
+    let mut validator_set = ValidatorSetManager::with_config(validator_set_config);
+
+    // Register genesis validators into the staking pool and validator set.
+    for gv in &genesis.validators {
+        if let Err(e) =
+            staking_pool.stake(gv.pubkey, gv.initial_stake, trv1_staking::LockTier::Delegator)
+        {
+            tracing::warn!(
+                pubkey = %to_hex(&gv.pubkey),
+                error = %e,
+                "failed to stake genesis validator"
+            );
+        }
+        match validator_set.register_validator(
+            gv.pubkey,
+            gv.initial_stake,
+            gv.commission_rate,
+            0,
+        ) {
+            Ok(status) => {
+                tracing::info!(
+                    pubkey = %to_hex(&gv.pubkey),
+                    ?status,
+                    stake = gv.initial_stake,
+                    "registered genesis validator"
+                );
+            }
+            Err(e) => {
+                tracing::warn!(
+                    pubkey = %to_hex(&gv.pubkey),
+                    error = %e,
+                    "failed to register genesis validator"
+                );
+            }
+        }
+    }
+
+    (staking_pool, validator_set)
+}
+
+/// Credit the genesis accounts in `db`. Genesis stakes move from the
+/// validators' accounts to the staking account, which holds them like
+/// stakes made later, so they can be unstaked.
+fn genesis_state(db: &mut StateDB, genesis: &GenesisConfig) {
+    for (pubkey, balance) in genesis.account_balances() {
+        db.set_account(pubkey, AccountState::new(balance));
+    }
+    let genesis_stake: u64 = genesis.validators.iter().map(|gv| gv.initial_stake).sum();
+    db.set_account(staking_account(), AccountState::new(genesis_stake));
+}
+
+/// Make `validators` the set that runs consensus from the next height and
+/// whose votes and proposals are accepted from gossip.
+fn set_validators(
+    validators: Vec<ValidatorId>,
+    bft: &mut BftStateMachine,
+    handle: &NetworkHandle,
+    signing_key: Option<&SigningKey>,
+    rpc_state: &RpcState,
+) {
+    let index = signing_key.and_then(|sk| validators.iter().position(|v| v.0 == sk.verifying_key()));
+    match (index, bft.validator_index) {
+        (Some(index), None) => tracing::info!(index, "joined the validator set"),
+        (None, Some(_)) => tracing::info!("left the validator set, now observing"),
+        _ => {}
+    }
+    handle.set_validators(&validators);
+    *rpc_state.validator_count.write() = validators.len();
+    tracing::info!(validators = validators.len(), "validator set updated");
+    bft.set_validators(validators, index);
+}
+
+/// Submit duplicate-vote evidence to the slashing engine's evidence pool.
+fn record_evidence(
+    evidence: &DuplicateVoteEvidence,
+    slashing_engine: &Arc<std::sync::RwLock<SlashingEngine>>,
+) {
+    match slashing_engine.write().unwrap().submit_evidence(evidence_record(evidence)) {
+        Ok(hash) => tracing::warn!(
+            offender = %to_hex(evidence.offender().as_bytes()),
+            height = evidence.vote_a.height.0,
+            evidence_hash = %to_hex(&hash),
+            "double-sign evidence recorded"
+        ),
+        Err(e) => tracing::debug!(error = %e, "evidence not recorded"),
+    }
+}
+
+/// Receive the next consensus message, preferring proposals rebuilt after a
+/// transaction fetch over new network traffic.
+async fn next_inbound(
+    handle: &mut NetworkHandle,
+    rebuilt_rx: &mut mpsc::Receiver<NetworkMessage>,
+) -> Option<NetworkMessage> {
+    tokio::select! {
+        biased;
+        Some(msg) = rebuilt_rx.recv() => Some(msg),
+        msg = handle.next_message() => msg,
+    }
+}
+
+/// Stop gossiping transactions the mempool replaced by fee or refused as
+/// underpriced replacements.
+async fn withdraw_transactions(handle: &NetworkHandle, hashes: Vec<[u8; 32]>) {
+    for hash in hashes {
+        if let Err(e) = handle.withdraw_transaction(hash).await {
+            tracing::debug!(error = %e, "failed to withdraw transaction from gossip");
+        }
+    }
+}
+
+/// Fetch the transactions of a compact proposal that are missing from the
+/// mempool, then hand the rebuilt block back to the event loop.
+///
+/// `peers` are asked in order (the proposer first, then the relaying peer)
+/// until every transaction is found. If the block still cannot be rebuilt
+/// the proposal is dropped and the round proceeds on its propose timeout.
+///
+/// Fetched signatures are verified here, off the event loop, so
+/// committing the block finds them cached.
+#[allow(clippy::too_many_arguments)]
+async fn fetch_missing_transactions(
+    fetcher: TxFetcher,
+    verifier: Arc<SignatureVerifier>,
+    rebuilt_tx: mpsc::Sender<NetworkMessage>,
+    peers: Vec<PeerId>,
+    sender: Vec<u8>,
+    proposal: Proposal,
+    compact: CompactBlock,
+    mut known: HashMap<[u8; 32], Transaction>,
+) {
+    for peer in peers {
+        let missing: Vec<[u8; 32]> = compact
+            .tx_hashes
+            .iter()
+            .filter(|h| !known.contains_key(*h))
+            .copied()
+            .collect();
+        if missing.is_empty() {
+            break;
+        }
+        match fetcher.fetch(peer, proposal.block_hash, missing).await {
+            Ok(txs) => {
+                let verifier = verifier.clone();
+                let txs = tokio::task::spawn_blocking(move || {
+                    verifier.verify_all(&txs);
+                    txs
+                })
+                .await
+                .unwrap_or_default();
+                known.extend(txs.into_iter().map(|tx| (tx.hash(), tx)));
+            }
+            Err(e) => tracing::debug!(%peer, error = %e, "transaction fetch failed"),
+        }
+    }
 
-        let rewards = pool.distribute_epoch_rewards();
-        let total_rewards: u64 = rewards.iter().map(|(_, r)| r).sum();
-        tracing::info!(total_rewards, recipients = rewards.len(), "epoch rewards distributed");
+    match compact.reconstruct(|h| known.remove(h)) {
+        Ok(block) => {
+            let msg = NetworkMessage {
+                sender,
+                relayer: Vec::new(),
+                message: ConsensusMessage::ProposeBlock {
+                    proposal,
+                    block: Some(block),
+                },
+            };
+            let _ = rebuilt_tx.send(msg).await;
+        }
+        Err(missing) => tracing::warn!(
+            height = proposal.height.0,
+            round = proposal.round.0,
+            missing = missing.len(),
+            "could not rebuild compact proposal"
+        ),
     }
 }
 
+/// Most gas the transactions in a block may use between them. The fee
+/// market runs on the default `FeeConfig`.
+fn max_block_gas() -> u64 {
+    FeeConfig::default().max_gas_per_block
+}
+
 /// Propose a new block as the round's designated proposer.
 ///
 /// Also feeds the proposal into the local BFT state machine so the proposer
@@ -996,7 +1615,7 @@ async fn propose_block(
     parent_hash: BlockHash,
     rpc_state: &Arc<RpcState>,
 ) {
-    let txs = rpc_state.mempool.read().get_pending_ordered(100);
+    let txs = rpc_state.mempool.read().get_pending_for_block(100, max_block_gas());
     let proposer_id = ValidatorId(signing_key.verifying_key());
     let block = build_block(
         height,
@@ -1019,13 +1638,150 @@ async fn propose_block(
     // Feed into local BFT so it caches the block
     bft.on_proposal(&proposal, Some(&block));
 
-    if let Err(e) = handle
-        .broadcast_message(&ConsensusMessage::ProposeBlock {
-            proposal,
-            block: Some(block),
-        })
-        .await
-    {
+    if let Err(e) = handle.broadcast_proposal(&proposal, &block).await {
         tracing::warn!(error = %e, "failed to broadcast proposal");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trv1_slashing::{EvidenceRecord, SlashingOffense};
+    use trv1_state::treasury_account;
+    use trv1_validator_set::ValidatorStatus;
+
+    fn shared<T>(value: T) -> Arc<std::sync::RwLock<T>> {
+        Arc::new(std::sync::RwLock::new(value))
+    }
+
+    #[test]
+    fn test_apply_commit_slashes_pending_evidence() {
+        let genesis = GenesisConfig::default_testnet();
+        let offender = genesis.validators[0].pubkey;
+        let rpc_state = Arc::new(RpcState::new_mock());
+        genesis_state(&mut rpc_state.state_db.write(), &genesis);
+        let (staking_pool, validator_set) = genesis_modules(&genesis);
+        let (staking_pool, validator_set) = (shared(staking_pool), shared(validator_set));
+        let mut slashing_engine = SlashingEngine::new();
+        slashing_engine
+            .submit_evidence(EvidenceRecord {
+                offense: SlashingOffense::DoubleSign,
+                offender,
+                height: 1,
+                data: b"vote_a:vote_b".to_vec(),
+                processed: false,
+            })
+            .unwrap();
+        let slashing_engine = shared(slashing_engine);
+        let fee_market = shared(FeeMarket::new(FeeConfig::default(), genesis.chain_params.base_fee_floor).unwrap());
+
+        let mut last_block_hash = BlockHash::default();
+        apply_commit(
+            Height(1),
+            BlockHash([1; 32]),
+            None,
+            &rpc_state,
+            &fee_market,
+            &mut last_block_hash,
+            &genesis,
+            &staking_pool,
+            &shared(DeveloperRewards::new()),
+            &shared(ContractRuntime::new()),
+            &validator_set,
+            &slashing_engine,
+        )
+        .unwrap();
+
+        // 5% of the offender's 10,000,000 stake moves to the treasury.
+        let validators = validator_set.read().unwrap();
+        assert_eq!(validators.get_validator(&offender).unwrap().status, ValidatorStatus::Jailed);
+        assert_eq!(validators.get_validator(&offender).unwrap().stake, 9_500_000);
+        assert_eq!(staking_pool.read().unwrap().stake_of(&offender), 9_500_000);
+        assert!(slashing_engine.read().unwrap().evidence_pool().get_pending_evidence().is_empty());
+        let db = rpc_state.state_db.read();
+        assert_eq!(db.get_account(&staking_account()).unwrap().balance, 39_500_000);
+        assert_eq!(db.get_account(&treasury_account()).unwrap().balance, 500_000);
+        assert_eq!(db.total_supply(), 400_000_000);
+
+        // The slash is committed with the block.
+        assert_eq!(db.committed_height(), Some(1));
+        assert_eq!(db.committed_block_hash(), [1; 32]);
+        assert_eq!(last_block_hash, BlockHash([1; 32]));
+        let committed: StakingPool = db.module_state(STAKING_MODULE).unwrap().unwrap();
+        assert_eq!(committed.stake_of(&offender), 9_500_000);
+    }
+
+    /// A backend that stores nothing and fails every commit.
+    #[derive(Debug)]
+    struct FailingBackend;
+
+    impl trv1_state::smt::NodeStore for FailingBackend {
+        fn load_node(&self, _: &[u8; 32]) -> Result<Option<Vec<u8>>, StateError> {
+            Ok(None)
+        }
+    }
+
+    impl trv1_state::StateBackend for FailingBackend {
+        fn committed_height(&self) -> Result<Option<u64>, StateError> {
+            Ok(None)
+        }
+
+        fn load_summary(&self) -> Result<trv1_state::backend::StateSummary, StateError> {
+            Ok(Default::default())
+        }
+
+        fn load_account(&self, _: &[u8; 32]) -> Result<Option<AccountState>, StateError> {
+            Ok(None)
+        }
+
+        fn load_data(&self, _: &trv1_state::backend::DataKey) -> Result<Option<Vec<u8>>, StateError> {
+            Ok(None)
+        }
+
+        fn commit(&self, _: &trv1_state::BlockCommit<'_>) -> Result<(), StateError> {
+            Err(StateError::Storage("disk full".into()))
+        }
+
+        fn history_range(&self) -> Result<Option<std::ops::RangeInclusive<u64>>, StateError> {
+            Ok(None)
+        }
+
+        fn load_undo(&self, _: u64) -> Result<Vec<trv1_state::AccountChange>, StateError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_apply_commit_fails_when_state_is_not_stored() {
+        let genesis = GenesisConfig::default_testnet();
+        let rpc_state = Arc::new(RpcState::new_mock());
+        let mut db = StateDB::open(Arc::new(FailingBackend), HistoryRetention::default()).unwrap();
+        genesis_state(&mut db, &genesis);
+        *rpc_state.state_db.write() = db;
+        let (staking_pool, validator_set) = genesis_modules(&genesis);
+        let fee_market = shared(FeeMarket::new(FeeConfig::default(), genesis.chain_params.base_fee_floor).unwrap());
+
+        let mut last_block_hash = BlockHash::default();
+        let result = apply_commit(
+            Height(1),
+            BlockHash([1; 32]),
+            None,
+            &rpc_state,
+            &fee_market,
+            &mut last_block_hash,
+            &genesis,
+            &shared(staking_pool),
+            &shared(DeveloperRewards::new()),
+            &shared(ContractRuntime::new()),
+            &shared(validator_set),
+            &shared(SlashingEngine::new()),
+        );
+
+        // The node does not move on to the next height.
+        assert!(matches!(result, Err(StateError::Storage(_))));
+        assert_eq!(rpc_state.state_db.read().committed_height(), None);
+        assert_eq!(*rpc_state.current_height.read(), 0);
+        assert_eq!(last_block_hash, BlockHash::default());
+        assert!(rpc_state.block_store.read().is_empty());
+    }
+}
@@ -10,7 +10,7 @@ use rand::rngs::OsRng;
 use trv1_bft::block::Transaction;
 use trv1_genesis::GenesisConfig;
 use trv1_mempool::{MempoolConfig, TransactionPool};
-use trv1_state::{AccountState, StateDB};
+use trv1_state::{staking_account, AccountState, StateDB};
 
 /// Helper: create a signed transaction from the given key.
 fn make_signed_tx(
@@ -27,6 +27,9 @@ fn make_signed_tx(
         nonce,
         signature: vec![],
         data: vec![],
+        gas_limit: 21_000,
+        max_fee_per_gas: 10,
+        max_priority_fee_per_gas: 1,
     };
     tx.sign(signing_key);
     tx
@@ -96,6 +99,25 @@ fn state_db_total_supply_matches_genesis() {
     assert_eq!(state_db.total_supply(), expected_supply);
 }
 
+#[test]
+fn genesis_stakes_are_drawn_from_allocations() {
+    let genesis = GenesisConfig::default_testnet();
+    let mut state_db = StateDB::new();
+
+    for (pubkey, balance) in genesis.account_balances() {
+        state_db.set_account(pubkey, AccountState::new(balance));
+    }
+    let genesis_stake: u64 = genesis.validators.iter().map(|v| v.initial_stake).sum();
+    state_db.set_account(staking_account(), AccountState::new(genesis_stake));
+
+    // Staking moves tokens without minting any.
+    let allocated: u64 = genesis.accounts.iter().map(|a| a.balance).sum();
+    assert_eq!(state_db.total_supply(), allocated);
+    for v in &genesis.validators {
+        assert_eq!(state_db.get_account(&v.pubkey).unwrap().balance, 100_000_000 - v.initial_stake);
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Transaction signing & verification tests
 // ---------------------------------------------------------------------------
@@ -138,13 +160,21 @@ fn transaction_different_keys_produce_different_hashes() {
 // Mempool tests
 // ---------------------------------------------------------------------------
 
+/// Helper: a state where `sk` can pay for a transfer and its fees.
+fn funded_state(sk: &SigningKey) -> StateDB {
+    let mut state = StateDB::new();
+    state.set_account(sk.verifying_key().to_bytes(), AccountState::new(1_000_000));
+    state
+}
+
 #[test]
 fn mempool_accepts_valid_signed_transaction() {
     let mut pool = TransactionPool::new(MempoolConfig::default());
     let sk = SigningKey::generate(&mut OsRng);
+    let state = funded_state(&sk);
     let tx = make_signed_tx(&sk, [2u8; 32], 100, 0);
 
-    pool.add_transaction(tx).expect("valid tx should be accepted");
+    pool.add_transaction(tx, &state).expect("valid tx should be accepted");
     assert_eq!(pool.pending_count(), 1);
 }
 
@@ -152,10 +182,11 @@ fn mempool_accepts_valid_signed_transaction() {
 fn mempool_rejects_duplicate_transaction() {
     let mut pool = TransactionPool::new(MempoolConfig::default());
     let sk = SigningKey::generate(&mut OsRng);
+    let state = funded_state(&sk);
     let tx = make_signed_tx(&sk, [2u8; 32], 100, 0);
 
-    pool.add_transaction(tx.clone()).expect("first should succeed");
-    let result = pool.add_transaction(tx);
+    pool.add_transaction(tx.clone(), &state).expect("first should succeed");
+    let result = pool.add_transaction(tx, &state);
     assert!(result.is_err(), "duplicate should be rejected");
     assert_eq!(pool.pending_count(), 1);
 }
@@ -163,11 +194,13 @@ fn mempool_rejects_duplicate_transaction() {
 #[test]
 fn mempool_multiple_senders() {
     let mut pool = TransactionPool::new(MempoolConfig::default());
+    let mut state = StateDB::new();
 
     for _ in 0..5 {
         let sk = SigningKey::generate(&mut OsRng);
+        state.set_account(sk.verifying_key().to_bytes(), AccountState::new(1_000_000));
         let tx = make_signed_tx(&sk, [2u8; 32], 50, 0);
-        pool.add_transaction(tx).expect("should accept from different senders");
+        pool.add_transaction(tx, &state).expect("should accept from different senders");
     }
 
     assert_eq!(pool.pending_count(), 5);
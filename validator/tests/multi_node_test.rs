@@ -0,0 +1,267 @@
+//! Multi-node tests over the in-memory libp2p transport.
+//!
+//! Each test starts several network nodes inside the test process with
+//! `trv1_net::testing::connected_nodes`, so no ports are bound and runs are
+//! not affected by the host network.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use ed25519_dalek::SigningKey;
+use libp2p::identity::Keypair;
+use rand::rngs::OsRng;
+
+use trv1_bft::block::{Block, BlockHeader, Transaction};
+use trv1_bft::{
+    BftStateMachine, BlockHash, ConsensusMessage, Height, Proposal, Round, TimeoutConfig,
+    ValidatorId, Vote, VoteType,
+};
+use trv1_net::testing::{connected_nodes, TestNode};
+use trv1_net::NetworkHandle;
+
+const WAIT: Duration = Duration::from_secs(10);
+
+/// Helper: `n` validator keys and their ids.
+fn make_validators(n: usize) -> (Vec<SigningKey>, Vec<ValidatorId>) {
+    let keys: Vec<SigningKey> = (0..n).map(|_| SigningKey::generate(&mut OsRng)).collect();
+    let ids = keys.iter().map(|k| ValidatorId(k.verifying_key())).collect();
+    (keys, ids)
+}
+
+/// Helper: start one connected node per validator key, with the network
+/// identity derived from the validator key as the node binary does.
+async fn start_nodes(keys: &[SigningKey], validators: &[ValidatorId]) -> Vec<TestNode> {
+    let keypairs = keys
+        .iter()
+        .map(|k| Keypair::ed25519_from_bytes(k.to_bytes()).unwrap())
+        .collect();
+    let nodes = connected_nodes(keypairs).await.expect("nodes should connect");
+    for node in &nodes {
+        node.handle.set_validators(validators);
+    }
+    nodes
+}
+
+/// Helper: create a signed transaction from the given key.
+fn make_signed_tx(signing_key: &SigningKey, amount: u64, nonce: u64) -> Transaction {
+    let mut tx = Transaction {
+        from: signing_key.verifying_key().to_bytes(),
+        to: [0xBB; 32],
+        amount,
+        nonce,
+        signature: vec![],
+        data: vec![],
+        gas_limit: 21_000,
+        max_fee_per_gas: 10,
+        max_priority_fee_per_gas: 1,
+    };
+    tx.sign(signing_key);
+    tx
+}
+
+fn make_block(height: Height, proposer: &ValidatorId, transactions: Vec<Transaction>) -> Block {
+    Block {
+        header: BlockHeader {
+            height,
+            timestamp: 1_700_000_000,
+            parent_hash: BlockHash::default(),
+            proposer: proposer.clone(),
+            state_root: [0u8; 32],
+            tx_merkle_root: Block::compute_tx_merkle_root(&transactions),
+        },
+        transactions,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Gossip
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn votes_reach_every_node() {
+    let (keys, validators) = make_validators(4);
+    let mut nodes = start_nodes(&keys, &validators).await;
+
+    let vote = Vote::new(VoteType::Prevote, Height(1), Round(0), None, &keys[0]);
+    nodes[0]
+        .handle
+        .broadcast_message(&ConsensusMessage::CastVote(vote))
+        .await
+        .unwrap();
+
+    for node in nodes.iter_mut().skip(1) {
+        let msg = tokio::time::timeout(WAIT, node.handle.next_message())
+            .await
+            .expect("vote should arrive")
+            .unwrap();
+        match msg.message {
+            ConsensusMessage::CastVote(v) => assert_eq!(v.validator, validators[0]),
+            other => panic!("unexpected message: {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn transactions_are_announced_and_pulled() {
+    let (keys, validators) = make_validators(3);
+    let mut nodes = start_nodes(&keys, &validators).await;
+
+    let tx = make_signed_tx(&SigningKey::generate(&mut OsRng), 42, 0);
+    nodes[0].handle.broadcast_transaction(&tx).await.unwrap();
+
+    for node in nodes.iter_mut().skip(1) {
+        let received = tokio::time::timeout(WAIT, node.handle.next_transaction())
+            .await
+            .expect("transaction should arrive")
+            .unwrap();
+        assert_eq!(received.hash(), tx.hash());
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Block sync
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn compact_proposal_missing_transactions_are_fetched() {
+    let (keys, validators) = make_validators(2);
+    let mut nodes = start_nodes(&keys, &validators).await;
+
+    let sender = SigningKey::generate(&mut OsRng);
+    let txs = vec![make_signed_tx(&sender, 1, 0), make_signed_tx(&sender, 2, 1)];
+    let block = make_block(Height(1), &validators[0], txs);
+    let proposal = Proposal::new(Height(1), Round(0), block.hash(), None, &keys[0]);
+    nodes[0]
+        .handle
+        .broadcast_proposal(&proposal, &block)
+        .await
+        .unwrap();
+
+    let msg = tokio::time::timeout(WAIT, nodes[1].handle.next_message())
+        .await
+        .expect("proposal should arrive")
+        .unwrap();
+    let proposer = msg.sender_peer_id().unwrap();
+    let ConsensusMessage::ProposeCompact { block: compact, .. } = msg.message else {
+        panic!("expected a compact proposal");
+    };
+
+    // The receiver has none of the transactions yet.
+    let missing = compact.reconstruct(|_| None).unwrap_err();
+    assert_eq!(missing.len(), 2);
+
+    let fetched = nodes[1]
+        .handle
+        .tx_fetcher()
+        .fetch(proposer, compact.hash(), missing)
+        .await
+        .expect("proposer should serve its block");
+    let by_hash: HashMap<[u8; 32], Transaction> =
+        fetched.into_iter().map(|tx| (tx.hash(), tx)).collect();
+    let rebuilt = compact.reconstruct(|h| by_hash.get(h).cloned()).unwrap();
+    assert_eq!(rebuilt.hash(), block.hash());
+}
+
+// ---------------------------------------------------------------------------
+// Consensus
+// ---------------------------------------------------------------------------
+
+/// Drive one validator's state machine over the network until it commits
+/// a block at its current height. Timeouts are not scheduled: with every
+/// validator online the height must commit in round 0.
+async fn run_until_commit(
+    key: SigningKey,
+    mut bft: BftStateMachine,
+    mut handle: NetworkHandle,
+    transactions: Vec<Transaction>,
+) -> BlockHash {
+    let mut queue: VecDeque<ConsensusMessage> = bft.start_round(Round(0)).into();
+
+    if bft.is_proposer() {
+        let block = make_block(bft.height, &ValidatorId(key.verifying_key()), transactions);
+        let proposal = Proposal::new(bft.height, bft.round, block.hash(), None, &key);
+        queue.extend(bft.on_proposal(&proposal, Some(&block)));
+        handle.broadcast_proposal(&proposal, &block).await.unwrap();
+    }
+
+    loop {
+        while let Some(out) = queue.pop_front() {
+            match out {
+                ConsensusMessage::CastVote(template) => {
+                    let vote = Vote::new(
+                        template.vote_type,
+                        template.height,
+                        template.round,
+                        template.block_hash,
+                        &key,
+                    );
+                    // Gossipsub does not deliver our own messages back to us.
+                    queue.extend(match vote.vote_type {
+                        VoteType::Prevote => bft.on_prevote(&vote),
+                        VoteType::Precommit => bft.on_precommit(&vote),
+                    });
+                    handle
+                        .broadcast_message(&ConsensusMessage::CastVote(vote))
+                        .await
+                        .unwrap();
+                }
+                ConsensusMessage::CommitBlock { block_hash, .. } => return block_hash,
+                _ => {}
+            }
+        }
+
+        let msg = handle.next_message().await.expect("network stopped");
+        let sender = msg.sender_peer_id();
+        let outputs = match msg.message {
+            ConsensusMessage::ProposeCompact { proposal, block } => {
+                // Nothing is in our mempool: fetch the whole body.
+                let fetched = handle
+                    .tx_fetcher()
+                    .fetch(sender.unwrap(), block.hash(), block.tx_hashes.clone())
+                    .await
+                    .unwrap();
+                let by_hash: HashMap<[u8; 32], Transaction> =
+                    fetched.into_iter().map(|tx| (tx.hash(), tx)).collect();
+                let full = block.reconstruct(|h| by_hash.get(h).cloned()).unwrap();
+                handle.cache_block(&full);
+                bft.on_proposal(&proposal, Some(&full))
+            }
+            ConsensusMessage::CastVote(vote) => match vote.vote_type {
+                VoteType::Prevote => bft.on_prevote(&vote),
+                VoteType::Precommit => bft.on_precommit(&vote),
+            },
+            _ => vec![],
+        };
+        queue.extend(outputs);
+    }
+}
+
+#[tokio::test]
+async fn four_validators_commit_a_block() {
+    let (keys, validators) = make_validators(4);
+    let nodes = start_nodes(&keys, &validators).await;
+    let payer = SigningKey::generate(&mut OsRng);
+    let txs = vec![make_signed_tx(&payer, 10, 0), make_signed_tx(&payer, 20, 1)];
+
+    let mut tasks = Vec::new();
+    for (i, (key, node)) in keys.into_iter().zip(nodes).enumerate() {
+        let bft = BftStateMachine::new(
+            Height(0),
+            validators.clone(),
+            Some(i),
+            TimeoutConfig::default(),
+        );
+        tasks.push(tokio::spawn(run_until_commit(key, bft, node.handle, txs.clone())));
+    }
+
+    let mut committed = Vec::new();
+    for task in tasks {
+        let hash = tokio::time::timeout(WAIT, task)
+            .await
+            .expect("validator should commit")
+            .unwrap();
+        committed.push(hash);
+    }
+    assert!(committed.iter().all(|h| *h == committed[0]));
+    assert!(!committed[0].is_zero());
+}